@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- populated on the next scrape of the respective room
+ALTER TABLE calendar ADD COLUMN course_code TEXT;
+ALTER TABLE calendar ADD COLUMN course_semester_hours INTEGER;
+ALTER TABLE calendar ADD COLUMN course_group TEXT;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE de ADD last_calendar_check_at TIMESTAMPTZ DEFAULT NULL;
+COMMENT ON COLUMN de.last_calendar_check_at IS 'the last time TUMonline was asked for the calendar of this room (regardless of whether it changed)';
+UPDATE de SET last_calendar_check_at = last_calendar_scrape_at;
+
+ALTER TABLE de ADD calendar_hash BIGINT DEFAULT NULL;
+COMMENT ON COLUMN de.calendar_hash IS 'hash of the events TUMonline returned on the last scrape, used to skip storing unchanged calendars';
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- populated on the next successful scrape of the respective room
+ALTER TABLE de ADD last_calendar_success_at TIMESTAMPTZ DEFAULT NULL;
+COMMENT ON COLUMN de.last_calendar_success_at IS 'the last time the calendar of this room was downloaded successfully (regardless of whether it changed)';
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE de ADD last_calendar_error TEXT DEFAULT NULL;
+COMMENT ON COLUMN de.last_calendar_error IS 'why the last failed download of the calendar of this room failed';
+ALTER TABLE de ADD last_calendar_error_at TIMESTAMPTZ DEFAULT NULL;
+COMMENT ON COLUMN de.last_calendar_error_at IS 'the last time the calendar of this room could not be downloaded';
+ALTER TABLE de ADD calendar_failures_in_row INTEGER NOT NULL DEFAULT 0;
+COMMENT ON COLUMN de.calendar_failures_in_row IS 'how often downloading the calendar of this room failed since it last succeeded';
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- case- and accent-insensitive form of text for searching
+-- umlauts are spelled out, as "Uebung" is the common way of typing "Übung" without them.
+-- Non-ascii characters are mapped explicitly, as lower() only handles them with a matching collation
+CREATE OR REPLACE FUNCTION calendar_search_normalize(input TEXT) RETURNS TEXT
+    LANGUAGE sql
+    IMMUTABLE
+    PARALLEL SAFE
+    RETURNS NULL ON NULL INPUT
+RETURN translate(
+        replace(replace(replace(replace(replace(replace(replace(lower(input),
+            'ä', 'ae'), 'Ä', 'ae'), 'ö', 'oe'), 'Ö', 'oe'), 'ü', 'ue'), 'Ü', 'ue'), 'ß', 'ss'),
+        'áàâãåéèêëíìîïóòôõúùûçñÁÀÂÃÅÉÈÊËÍÌÎÏÓÒÔÕÚÙÛÇÑ',
+        'aaaaaeeeeiiiioooouuucnaaaaaeeeeiiiioooouuucn'
+       );
+
+ALTER TABLE calendar ADD COLUMN search_text TEXT GENERATED ALWAYS AS (
+    calendar_search_normalize(title_de) || ' ' || calendar_search_normalize(title_en) || ' ' || coalesce(calendar_search_normalize(course_code), '')
+    ) STORED;
+COMMENT ON COLUMN calendar.search_text IS 'normalised titles and course code for the title search';
+CREATE INDEX calendar_search_text_trgm ON calendar USING gin (search_text gin_trgm_ops);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE calendar_feed_tokens
+(
+    id         SERIAL PRIMARY KEY,
+    room_code  TEXT        NOT NULL,
+    token      TEXT        NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ          DEFAULT NULL
+);
+COMMENT ON TABLE calendar_feed_tokens IS 'tokens granting access to the ICS feed of the calendar of a room';
+COMMENT ON COLUMN calendar_feed_tokens.revoked_at IS 'when the token was revoked, revoked tokens are kept to be listable';
+CREATE INDEX IF NOT EXISTS calendar_feed_tokens_room_code_idx ON calendar_feed_tokens (room_code);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE calendar_scrape_cycle
+(
+    id                BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    started_at        TIMESTAMPTZ DEFAULT NULL,
+    last_completed_at TIMESTAMPTZ DEFAULT NULL
+);
+COMMENT ON TABLE calendar_scrape_cycle IS 'progress of the calendar scraper through all rooms, only ever has a single row';
+COMMENT ON COLUMN calendar_scrape_cycle.started_at IS 'when the currently running cycle started, NULL if all rooms are up to date';
+COMMENT ON COLUMN calendar_scrape_cycle.last_completed_at IS 'when all rooms were last up to date';
+INSERT INTO calendar_scrape_cycle DEFAULT VALUES;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE feedback_used_tokens
+(
+    kid        BIGINT PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+COMMENT ON TABLE feedback_used_tokens IS 'feedback tokens which were already used, to reject replays across restarts and instances';
+COMMENT ON COLUMN feedback_used_tokens.kid IS 'the (unsigned) key id of the token, stored bit-for-bit as a signed integer';
+COMMENT ON COLUMN feedback_used_tokens.expires_at IS 'after this, the token is rejected as expired anyway and the row can be pruned';
+CREATE INDEX IF NOT EXISTS feedback_used_tokens_expires_at_idx ON feedback_used_tokens (expires_at);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE feedback_deletion_tokens
+(
+    issue_number BIGINT PRIMARY KEY,
+    token_hash   TEXT        NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    redacted_at  TIMESTAMPTZ          DEFAULT NULL
+);
+COMMENT ON TABLE feedback_deletion_tokens IS 'lets reporters withdraw the feedback they submitted, by proving they know the deletion token handed out on submission';
+COMMENT ON COLUMN feedback_deletion_tokens.issue_number IS 'the GitHub issue the feedback was submitted as';
+COMMENT ON COLUMN feedback_deletion_tokens.token_hash IS 'hex-encoded SHA-256 of the deletion token, the token itself is only known to the reporter';
+COMMENT ON COLUMN feedback_deletion_tokens.redacted_at IS 'when the issue was redacted and closed, NULL if it was not withdrawn';
@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE feedback_delivery_queue
+(
+    receipt         TEXT PRIMARY KEY,
+    payload         JSONB       NOT NULL,
+    attempts        INTEGER     NOT NULL DEFAULT 1,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+COMMENT ON TABLE feedback_delivery_queue IS 'feedback which could not be delivered yet (e.g. because GitHub is down) and is retried in the background';
+COMMENT ON COLUMN feedback_delivery_queue.receipt IS 'random id returned to the reporter, also embedded into the issue to detect deliveries which succeeded unnoticed';
+COMMENT ON COLUMN feedback_delivery_queue.payload IS 'the request to open the issue, exactly as it is sent to GitHub';
+COMMENT ON COLUMN feedback_delivery_queue.attempts IS 'how often delivery was attempted, including the attempt in progress';
+COMMENT ON COLUMN feedback_delivery_queue.next_attempt_at IS 'when delivery is attempted next, pushed back while an attempt is in progress';
+COMMENT ON COLUMN feedback_delivery_queue.created_at IS 'after 24h, delivery is given up';
+CREATE INDEX IF NOT EXISTS feedback_delivery_queue_next_attempt_at_idx ON feedback_delivery_queue (next_attempt_at);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE feedback_contacts
+(
+    issue_number    BIGINT PRIMARY KEY,
+    encrypted_email BYTEA       NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+COMMENT ON TABLE feedback_contacts IS 'how reporters who asked to be contacted about their feedback can be reached, never published in the issue';
+COMMENT ON COLUMN feedback_contacts.issue_number IS 'the GitHub issue the feedback was submitted as';
+COMMENT ON COLUMN feedback_contacts.encrypted_email IS 'the email address, encrypted with AES-256-GCM using FEEDBACK_CONTACT_KEY: 12 byte nonce followed by the ciphertext';
+COMMENT ON COLUMN feedback_contacts.created_at IS 'after 90 days, the contact is purged, even if the issue is still open';
+CREATE INDEX IF NOT EXISTS feedback_contacts_created_at_idx ON feedback_contacts (created_at);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE cdn_imports
+(
+    file          TEXT PRIMARY KEY,
+    last_modified TIMESTAMPTZ NOT NULL,
+    imported_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+COMMENT ON TABLE cdn_imports IS 'which version of the files on the CDN was imported last, to skip imports if nothing changed';
+COMMENT ON COLUMN cdn_imports.file IS 'the file on the CDN, e.g. status_data.parquet';
+COMMENT ON COLUMN cdn_imports.last_modified IS 'the Last-Modified time of the file, as reported by the CDN when it was imported';
+COMMENT ON COLUMN cdn_imports.imported_at IS 'when the import was committed';
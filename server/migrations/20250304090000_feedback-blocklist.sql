@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE feedback_blocklist
+(
+    id         SERIAL PRIMARY KEY,
+    kid_prefix TEXT                 DEFAULT NULL,
+    ip_range   TEXT                 DEFAULT NULL,
+    reason     TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ          DEFAULT NULL,
+    CHECK ((kid_prefix IS NULL) <> (ip_range IS NULL))
+);
+COMMENT ON TABLE feedback_blocklist IS 'tokens and clients, which are not allowed to submit feedback because they abused it';
+COMMENT ON COLUMN feedback_blocklist.kid_prefix IS 'blocks tokens whose kid, as 16 lowercase hex digits, starts with this';
+COMMENT ON COLUMN feedback_blocklist.ip_range IS 'blocks clients in this CIDR range, e.g. 192.0.2.0/24';
+COMMENT ON COLUMN feedback_blocklist.reason IS 'why the entry was added, only shown to admins';
+COMMENT ON COLUMN feedback_blocklist.expires_at IS 'from when on the entry does not block anymore, NULL blocks forever';
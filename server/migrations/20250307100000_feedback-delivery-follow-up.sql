@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE feedback_delivery_queue
+    ADD COLUMN follow_up JSONB NOT NULL DEFAULT '{}';
+COMMENT ON COLUMN feedback_delivery_queue.follow_up IS 'what happens once the issue is opened (e.g. notifying the webhook), as it would have if the feedback was delivered right away';
@@ -1,14 +1,23 @@
 use crate::external::connectum::ConnectumEvent;
 use crate::limited::hash_map::LimitedHashMap;
 use crate::limited::vec::LimitedVec;
-use chrono::{DateTime, Utc};
+use crate::refresh::calendar::SCRAPE_INTERVAL;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use prometheus::{IntCounterVec, Opts, Registry};
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tracing::debug;
 use tracing::error;
 use tracing::warn;
 
+/// Postgres limits the size of a statement, so huge calendars are upserted in chunks
+const UPSERT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Clone)]
 pub struct CalendarLocation {
     pub key: String,
     pub name: String,
@@ -31,9 +40,104 @@ impl CalendarLocation {
     )
             .fetch_all(pool)
             .await?;
-        Ok(LimitedVec(res))
+        Ok(LimitedVec::from(res))
     }
 }
+/// How long a cached location is used before it is looked up again
+///
+/// Only a safety net, as all changes we know of invalidate the cache explicitly
+const LOCATION_CACHE_TTL: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// Read-through cache for [`CalendarLocation::get_locations`]
+///
+/// Locations only change when the data is re-imported or a calendar was scraped.
+/// Both invalidate the affected entries.
+#[derive(Clone, Debug)]
+pub struct CalendarLocationCache {
+    entries: Arc<RwLock<HashMap<String, (Instant, CalendarLocation)>>>,
+    /// Incremented on each invalidation, so that lookups racing an invalidation don't store stale data
+    generation: Arc<AtomicU64>,
+    /// Looked up locations, by whether they were cached
+    lookups: IntCounterVec,
+}
+impl Default for CalendarLocationCache {
+    fn default() -> Self {
+        let lookups = IntCounterVec::new(
+            Opts::new(
+                "calendar_location_cache_lookups_total",
+                "Locations looked up for calendar requests, by whether they were cached",
+            )
+            .namespace("navigatum_api"),
+            &["result"],
+        )
+        .expect("the metric options are valid");
+        Self {
+            entries: Arc::default(),
+            generation: Arc::default(),
+            lookups,
+        }
+    }
+}
+impl CalendarLocationCache {
+    /// Exposes the hit rate on `/api/metrics`
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.lookups.clone()))
+    }
+    #[tracing::instrument(skip(self, pool))]
+    pub(crate) async fn get_locations(
+        &self,
+        pool: &PgPool,
+        ids: &[String],
+    ) -> anyhow::Result<LimitedVec<CalendarLocation>> {
+        let mut locations = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        {
+            let entries = self.entries.read().expect("lock is not poisoned");
+            for id in ids {
+                match entries.get(id) {
+                    Some((cached_at, location)) if cached_at.elapsed() < LOCATION_CACHE_TTL => {
+                        locations.push(location.clone())
+                    }
+                    _ => missing.push(id.clone()),
+                }
+            }
+        }
+        self.lookups
+            .with_label_values(&["hit"])
+            .inc_by(locations.len() as u64);
+        self.lookups
+            .with_label_values(&["miss"])
+            .inc_by(missing.len() as u64);
+        if missing.is_empty() {
+            return Ok(LimitedVec::from(locations));
+        }
+        let generation = self.generation.load(Ordering::Acquire);
+        let fetched = CalendarLocation::get_locations(pool, &missing).await?;
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        let may_store = self.generation.load(Ordering::Acquire) == generation;
+        let now = Instant::now();
+        for location in fetched {
+            if may_store {
+                entries.insert(location.key.clone(), (now, location.clone()));
+            }
+            locations.push(location);
+        }
+        Ok(LimitedVec::from(locations))
+    }
+    /// Forgets the location, e.g. because its calendar was scraped
+    pub fn invalidate(&self, id: &str) {
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.remove(id);
+    }
+    /// Forgets all locations, e.g. because the data was re-imported
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+}
+
 impl Debug for CalendarLocation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut base = f.debug_struct("CalendarLocation");
@@ -45,36 +149,255 @@ impl Debug for CalendarLocation {
     }
 }
 
+/// How up-to-date the calendar of a room is
+#[derive(Debug)]
+pub struct CalendarScrapeStatus {
+    pub key: String,
+    pub calendar_url: Option<String>,
+    pub tumonline_room_nr: Option<i32>,
+    pub last_calendar_success_at: Option<DateTime<Utc>>,
+    pub event_cnt: i64,
+    /// Whether the room still needs to be scraped in the current cycle
+    pub queued: bool,
+}
+impl CalendarScrapeStatus {
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get(pool: &PgPool, id: &str) -> anyhow::Result<Option<Self>> {
+        let res = sqlx::query_as!(
+            CalendarScrapeStatus,
+            r#"SELECT key,
+                      calendar_url,
+                      tumonline_room_nr,
+                      last_calendar_success_at,
+                      (SELECT COUNT(*) FROM calendar WHERE calendar.room_code = de.key) AS "event_cnt!",
+                      (calendar_url IS NOT NULL
+                          AND (last_calendar_check_at IS NULL
+                              OR last_calendar_check_at < NOW() - MAKE_INTERVAL(secs => $2))) AS "queued!"
+               FROM de
+               WHERE key = $1"#,
+            id,
+            SCRAPE_INTERVAL.as_secs_f64()
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(res)
+    }
+}
+
+/// A room whose calendar repeatedly could not be downloaded
+#[derive(Debug, PartialEq)]
+pub struct FailingCalendar {
+    pub key: String,
+    pub failures_in_row: i32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+impl FailingCalendar {
+    /// Rooms which failed at least `min_failures` times in a row, the most failing first
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get_failing(
+        pool: &PgPool,
+        min_failures: i32,
+        limit: i64,
+    ) -> anyhow::Result<Vec<Self>> {
+        let res = sqlx::query_as!(
+            FailingCalendar,
+            r#"SELECT key,
+                      calendar_failures_in_row AS failures_in_row,
+                      last_calendar_error AS last_error,
+                      last_calendar_error_at AS last_error_at,
+                      last_calendar_success_at AS last_success_at
+               FROM de
+               WHERE calendar_url IS NOT NULL AND calendar_failures_in_row >= $1
+               ORDER BY calendar_failures_in_row DESC, key
+               LIMIT $2"#,
+            min_failures,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+}
+
+/// Progress of the scraper through all rooms
+///
+/// Which rooms are left is tracked via their `last_calendar_check_at`.
+/// This only persists when the cycle started/ended, so that restarts don't lose it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrapeCycle {
+    /// `None` if all rooms are up to date
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_completed_at: Option<DateTime<Utc>>,
+}
+impl ScrapeCycle {
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            ScrapeCycle,
+            "SELECT started_at, last_completed_at FROM calendar_scrape_cycle"
+        )
+        .fetch_one(pool)
+        .await
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn start(pool: &PgPool, at: &DateTime<Utc>) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            ScrapeCycle,
+            r#"UPDATE calendar_scrape_cycle
+               SET started_at = $1
+               RETURNING started_at, last_completed_at"#,
+            at
+        )
+        .fetch_one(pool)
+        .await
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn complete(pool: &PgPool, at: &DateTime<Utc>) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            ScrapeCycle,
+            r#"UPDATE calendar_scrape_cycle
+               SET started_at = NULL, last_completed_at = $1
+               RETURNING started_at, last_completed_at"#,
+            at
+        )
+        .fetch_one(pool)
+        .await
+    }
+}
+
+/// A token granting access to the ICS feed of the calendar of a room
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedToken {
+    pub id: i32,
+    pub room_code: String,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+impl FeedToken {
+    #[tracing::instrument(skip(pool, token))]
+    pub(crate) async fn create(
+        pool: &PgPool,
+        room_code: &str,
+        token: &str,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            FeedToken,
+            r#"INSERT INTO calendar_feed_tokens (room_code, token)
+               VALUES ($1, $2)
+               RETURNING id, room_code, token, created_at, revoked_at"#,
+            room_code,
+            token
+        )
+        .fetch_one(pool)
+        .await
+    }
+    /// All tokens of the room, including revoked ones, the newest first
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn list(pool: &PgPool, room_code: &str) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            FeedToken,
+            r#"SELECT id, room_code, token, created_at, revoked_at
+               FROM calendar_feed_tokens
+               WHERE room_code = $1
+               ORDER BY created_at DESC, id DESC"#,
+            room_code
+        )
+        .fetch_all(pool)
+        .await
+    }
+    /// Revokes the token, revoking an already revoked token keeps the original time
+    ///
+    /// Returns `None` if the room does not have such a token.
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn revoke(
+        pool: &PgPool,
+        room_code: &str,
+        id: i32,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            FeedToken,
+            r#"UPDATE calendar_feed_tokens
+               SET revoked_at = COALESCE(revoked_at, NOW())
+               WHERE room_code = $1 AND id = $2
+               RETURNING id, room_code, token, created_at, revoked_at"#,
+            room_code,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+    /// Whether the token grants access to the feed of the room
+    #[tracing::instrument(skip(pool, token))]
+    pub(crate) async fn is_valid(
+        pool: &PgPool,
+        room_code: &str,
+        token: &str,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1
+                             FROM calendar_feed_tokens
+                             WHERE room_code = $1 AND token = $2 AND revoked_at IS NULL) AS "valid!""#,
+            room_code,
+            token
+        )
+        .fetch_one(pool)
+        .await
+    }
+}
+
 pub struct LocationEvents {
     pub events: LimitedVec<Event>,
     pub location: CalendarLocation,
+    /// Set if the events were cut off at `max_events`
+    ///
+    /// All events starting before this time are included.
+    pub complete_until: Option<DateTime<Utc>>,
 }
 impl LocationEvents {
+    /// Events of the locations, ordered by their start
+    ///
+    /// At most `max_events` events are returned per location.
     #[tracing::instrument(skip(pool))]
     pub(crate) async fn get_from_db(
         pool: &PgPool,
         locations: Vec<CalendarLocation>,
         start_after: &DateTime<Utc>,
         end_before: &DateTime<Utc>,
+        max_events: usize,
     ) -> anyhow::Result<LimitedHashMap<String, LocationEvents>> {
         let mut located_events: HashMap<String, LocationEvents> = HashMap::new();
+        // one more than allowed to know if we cut something off
+        let limit = i64::try_from(max_events.saturating_add(1)).unwrap_or(i64::MAX);
         for location in locations.into_iter() {
-            let events = sqlx::query_as!(
+            let mut events = sqlx::query_as!(
             Event,
-            r#"SELECT id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type
+            r#"SELECT id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group
             FROM calendar
-            WHERE room_code = $1 AND start_at >= $2 AND end_at <= $3"#,
+            WHERE room_code = $1 AND start_at >= $2 AND end_at <= $3
+            ORDER BY start_at, id
+            LIMIT $4"#,
             location.key,
             start_after,
-            end_before
+            end_before,
+            limit
         )
                 .fetch_all(pool)
                 .await?;
+            let complete_until = if events.len() > max_events {
+                let cut_off = events.split_off(max_events);
+                Some(cut_off[0].start_at)
+            } else {
+                None
+            };
             located_events.insert(
                 location.key.clone(),
                 LocationEvents {
                     location,
                     events: events.into(),
+                    complete_until,
                 },
             );
         }
@@ -82,6 +405,94 @@ impl LocationEvents {
     }
 }
 
+/// Event of a room, with its times in the local time of the campus (`Europe/Berlin`)
+pub struct LocalEvent {
+    pub id: i32,
+    pub start_at: DateTime<Utc>,
+    pub end_at: DateTime<Utc>,
+    pub start_local: NaiveDateTime,
+    pub end_local: NaiveDateTime,
+    pub title_de: String,
+    pub title_en: String,
+    pub entry_type: String,
+    pub detailed_entry_type: String,
+}
+impl LocalEvent {
+    /// Events touching the local time window `[start, end)`
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get_in_local_window(
+        pool: &PgPool,
+        id: &str,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> anyhow::Result<Vec<LocalEvent>> {
+        let events = sqlx::query_as!(
+            LocalEvent,
+            r#"SELECT id,
+                      start_at,
+                      end_at,
+                      start_at AT TIME ZONE 'Europe/Berlin' AS "start_local!",
+                      end_at AT TIME ZONE 'Europe/Berlin' AS "end_local!",
+                      title_de,
+                      title_en,
+                      entry_type,
+                      detailed_entry_type
+               FROM calendar
+               WHERE room_code = $1
+                 AND start_at < ($3::timestamp AT TIME ZONE 'Europe/Berlin')
+                 AND end_at >= ($2::timestamp AT TIME ZONE 'Europe/Berlin')
+               ORDER BY start_at, id"#,
+            id,
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(events)
+    }
+}
+
+/// Room which does not have any events during a time window
+pub struct FreeRoom {
+    pub key: String,
+    pub name: String,
+    /// When the next event after the window starts
+    pub free_until: Option<DateTime<Utc>>,
+}
+impl FreeRoom {
+    /// Scraped rooms whose key starts with `prefix` and which are free during `[start, end)`
+    ///
+    /// Ordered by how long they stay free, rooms without any further events first.
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get_free(
+        pool: &PgPool,
+        prefix: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> anyhow::Result<Vec<FreeRoom>> {
+        let rooms = sqlx::query_as!(
+            FreeRoom,
+            r#"SELECT key,
+                      name,
+                      (SELECT MIN(start_at) FROM calendar WHERE room_code = de.key AND start_at >= $2) AS free_until
+               FROM de
+               WHERE starts_with(key, $1)
+                 AND calendar_url IS NOT NULL
+                 AND last_calendar_scrape_at IS NOT NULL
+                 AND NOT EXISTS (SELECT 1
+                                 FROM calendar
+                                 WHERE room_code = de.key AND start_at < $3 AND end_at > $2)
+               ORDER BY free_until DESC NULLS FIRST, key"#,
+            prefix,
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rooms)
+    }
+}
+
 pub struct Event {
     pub id: i32,
     pub room_code: String,
@@ -92,6 +503,9 @@ pub struct Event {
     pub stp_type: Option<String>,
     pub entry_type: String,
     pub detailed_entry_type: String,
+    pub course_code: Option<String>,
+    pub course_semester_hours: Option<i32>,
+    pub course_group: Option<String>,
 }
 impl Event {
     #[tracing::instrument(skip(pool))]
@@ -100,60 +514,202 @@ impl Event {
         events: LimitedVec<Event>,
         id: &str,
     ) -> anyhow::Result<()> {
-        // insert into db
         let mut tx = pool.begin().await?;
-        if let Err(e) = Event::delete(&mut tx, id).await {
-            error!(error = ?e, "could not delete existing events");
-            tx.rollback().await?;
-            return Err(e.into());
-        }
-        let mut failed: Option<(usize, sqlx::Error)> = None;
-        for event in events.0.iter() {
-            // conflicts cannot occur because all values for said room were dropped
-            if let Err(e) = event.store(&mut tx).await {
-                failed = match failed {
-                    Some((i, e0)) => Some((i + 1, e0)),
-                    None => Some((1, e)),
-                };
+        // conflicts are events which were updated or moved here from another room
+        for chunk in dedup_by_id(events.as_slice()).chunks(UPSERT_CHUNK_SIZE) {
+            if let Err(e) = Event::upsert_many(&mut tx, chunk).await {
+                error!(error = ?e, total = events.len(), "could not upsert events");
+                tx.rollback().await?;
+                return Err(e.into());
             }
         }
-        if let Some((cnt, e)) = failed {
-            warn!(
-                error = ?e,
-                cnt,
-                total = events.len(),
-                "events could not be inserted because",
-            );
+        if let Err(e) = Event::delete_stale(&mut tx, id, &events).await {
+            error!(error = ?e, "could not delete stale events");
+            tx.rollback().await?;
+            return Err(e.into());
         }
         tx.commit().await?;
         debug!(?id, "finished inserting into the db");
         Ok(())
     }
-    #[tracing::instrument(skip(tx))]
-    async fn delete(
+    /// Events of the rooms which overlap `[start, end)`
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get_overlapping(
+        pool: &PgPool,
+        ids: &[String],
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Event>> {
+        let events = sqlx::query_as!(
+            Event,
+            r#"SELECT id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group
+            FROM calendar
+            WHERE room_code = ANY($1::text[]) AND start_at < $3 AND end_at > $2
+            ORDER BY start_at, id"#,
+            ids,
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(events)
+    }
+    /// Events of a course, in any room, which overlap `[start, end)`
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get_by_course(
+        pool: &PgPool,
+        course_code: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Event>> {
+        let events = sqlx::query_as!(
+            Event,
+            r#"SELECT id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group
+            FROM calendar
+            WHERE course_code = $1 AND start_at < $3 AND end_at > $2
+            ORDER BY start_at, id"#,
+            course_code,
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(events)
+    }
+    /// Events whose title or course code contains `query`, which overlap `[start, end)`
+    ///
+    /// Matching is case- and accent-insensitive, umlauts also match their spelled out form (`ü` <=> `ue`).
+    /// Events whose title or course code starts with `query` come first.
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn search(
+        pool: &PgPool,
+        query: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<Event>> {
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let events = sqlx::query_as!(
+            Event,
+            r#"WITH q AS (SELECT calendar_search_normalize($1) AS pattern)
+            SELECT id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group
+            FROM calendar, q
+            WHERE search_text LIKE '%' || q.pattern || '%' AND start_at < $3 AND end_at > $2
+            ORDER BY (calendar_search_normalize(title_de) LIKE q.pattern || '%'
+                          OR calendar_search_normalize(title_en) LIKE q.pattern || '%'
+                          OR calendar_search_normalize(course_code) LIKE q.pattern || '%') DESC,
+                     start_at, id
+            LIMIT $4"#,
+            escaped,
+            start,
+            end,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(events)
+    }
+    /// Deletes events which TUMonline no longer lists for this room (e.g. cancelled or moved ones)
+    ///
+    /// Only the window spanned by the `fresh` events is touched, as we cannot know anything about events outside of it.
+    #[tracing::instrument(skip(tx, fresh))]
+    async fn delete_stale(
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         id: &str,
+        fresh: &LimitedVec<Event>,
     ) -> Result<(), sqlx::Error> {
-        loop {
-            // deliberately somewhat low to not have too long blocking segments
-            let res = sqlx::query!(
-                r#"
-                    WITH rows_to_delete AS (
-                        SELECT id
-                        FROM calendar WHERE room_code = $1
-                        LIMIT 1000
-                    )
-                    
-                    DELETE FROM calendar
-                    WHERE id IN (SELECT id FROM rows_to_delete);"#,
-                id
-            )
-            .execute(&mut **tx)
+        let window_start = fresh.iter().map(|e| e.start_at).min();
+        let window_end = fresh.iter().map(|e| e.end_at).max();
+        let (Some(window_start), Some(window_end)) = (window_start, window_end) else {
+            debug!("no fresh events => the scraped window is unknown and no events are deleted");
+            return Ok(());
+        };
+        let fresh_ids = fresh.iter().map(|e| e.id).collect::<Vec<i32>>();
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM calendar
+            WHERE room_code = $1
+              AND start_at >= $2
+              AND end_at <= $3
+              AND NOT (id = ANY ($4::int4[]))"#,
+            id,
+            window_start,
+            window_end,
+            &fresh_ids,
+        )
+        .execute(&mut **tx)
+        .await?;
+        debug!(deleted_cnt = res.rows_affected(), "deleted stale events");
+        Ok(())
+    }
+    #[tracing::instrument(skip(pool))]
+    pub async fn update_last_calendar_success_at(
+        pool: &PgPool,
+        id: &str,
+        success_at: &DateTime<Utc>,
+    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE de SET last_calendar_success_at = $1, calendar_failures_in_row = 0 WHERE key=$2",
+            success_at,
+            id
+        )
+        .execute(pool)
+        .await
+    }
+    #[tracing::instrument(skip(pool))]
+    pub async fn record_calendar_failure(
+        pool: &PgPool,
+        id: &str,
+        failed_at: &DateTime<Utc>,
+        error: &str,
+    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE de
+               SET last_calendar_error = $1,
+                   last_calendar_error_at = $2,
+                   calendar_failures_in_row = calendar_failures_in_row + 1
+               WHERE key=$3"#,
+            error,
+            failed_at,
+            id
+        )
+        .execute(pool)
+        .await
+    }
+    #[tracing::instrument(skip(pool))]
+    pub async fn update_last_calendar_check_at(
+        pool: &PgPool,
+        id: &str,
+        check_at: &DateTime<Utc>,
+    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE de SET last_calendar_check_at = $1 WHERE key=$2",
+            check_at,
+            id
+        )
+        .execute(pool)
+        .await
+    }
+    /// Hash of the events stored on the last scrape of this room
+    #[tracing::instrument(skip(pool))]
+    pub async fn calendar_hash(pool: &PgPool, id: &str) -> Result<Option<i64>, sqlx::Error> {
+        let hash = sqlx::query_scalar!("SELECT calendar_hash FROM de WHERE key=$1", id)
+            .fetch_optional(pool)
             .await?;
-            if res.rows_affected() == 0 {
-                return Ok(());
-            }
-        }
+        Ok(hash.flatten())
+    }
+    #[tracing::instrument(skip(pool))]
+    pub async fn update_calendar_hash(
+        pool: &PgPool,
+        id: &str,
+        hash: i64,
+    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query!("UPDATE de SET calendar_hash = $1 WHERE key=$2", hash, id)
+            .execute(pool)
+            .await
     }
     #[tracing::instrument(skip(pool))]
     pub async fn update_last_calendar_scrape_at(
@@ -177,14 +733,53 @@ impl Event {
         .await
     }
 
+    /// Inserts or updates all events in a single statement
+    ///
+    /// The ids have to be unique, as postgres cannot update a row twice in one statement.
+    /// This does not use [`sqlx::query!`], as it cannot check arrays with nullable elements.
+    async fn upsert_many(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        events: &[&Event],
+    ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO calendar (id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group)
+            SELECT * FROM UNNEST($1::int4[], $2::text[], $3::timestamptz[], $4::timestamptz[], $5::text[], $6::text[], $7::text[], $8::text[], $9::text[], $10::text[], $11::int4[], $12::text[])
+            ON CONFLICT (id) DO UPDATE SET
+             room_code = EXCLUDED.room_code,
+             start_at = EXCLUDED.start_at,
+             end_at = EXCLUDED.end_at,
+             title_de = EXCLUDED.title_de,
+             title_en = EXCLUDED.title_en,
+             stp_type = EXCLUDED.stp_type,
+             entry_type = EXCLUDED.entry_type,
+             detailed_entry_type = EXCLUDED.detailed_entry_type,
+             course_code = EXCLUDED.course_code,
+             course_semester_hours = EXCLUDED.course_semester_hours,
+             course_group = EXCLUDED.course_group"#,
+        )
+        .bind(events.iter().map(|e| e.id).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.room_code.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.start_at).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.end_at).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.title_de.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.title_en.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.stp_type.as_deref()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.entry_type.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.detailed_entry_type.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.course_code.as_deref()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.course_semester_hours).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.course_group.as_deref()).collect::<Vec<_>>())
+        .execute(&mut **tx)
+        .await
+    }
     #[tracing::instrument(skip(tx))]
     pub async fn store(
         &self,
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     ) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
         sqlx::query!(
-            r#"INSERT INTO calendar (id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            r#"INSERT INTO calendar (id,room_code,start_at,end_at,title_de,title_en,stp_type,entry_type,detailed_entry_type,course_code,course_semester_hours,course_group)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             ON CONFLICT (id) DO UPDATE SET
              room_code = EXCLUDED.room_code,
              start_at = EXCLUDED.start_at,
@@ -193,7 +788,10 @@ impl Event {
              title_en = EXCLUDED.title_en,
              stp_type = EXCLUDED.stp_type,
              entry_type = EXCLUDED.entry_type,
-             detailed_entry_type = EXCLUDED.detailed_entry_type"#,
+             detailed_entry_type = EXCLUDED.detailed_entry_type,
+             course_code = EXCLUDED.course_code,
+             course_semester_hours = EXCLUDED.course_semester_hours,
+             course_group = EXCLUDED.course_group"#,
             self.id,
             self.room_code,
             self.start_at,
@@ -203,10 +801,29 @@ impl Event {
             self.stp_type,
             self.entry_type,
             self.detailed_entry_type,
+            self.course_code,
+            self.course_semester_hours,
+            self.course_group,
         ).execute(&mut **tx).await
     }
 }
 
+/// The last of the events with the same id, as TUMonline sometimes lists an event twice
+fn dedup_by_id(events: &[Event]) -> Vec<&Event> {
+    let mut index_by_id = HashMap::with_capacity(events.len());
+    let mut unique = Vec::<&Event>::with_capacity(events.len());
+    for event in events {
+        match index_by_id.get(&event.id) {
+            Some(&i) => unique[i] = event,
+            None => {
+                index_by_id.insert(event.id, unique.len());
+                unique.push(event);
+            }
+        }
+    }
+    unique
+}
+
 impl Debug for Event {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let duration = (self.end_at - self.start_at).num_minutes();
@@ -233,6 +850,9 @@ impl From<ConnectumEvent> for Event {
             stp_type: value.stp_type,
             entry_type: value.entry_type,
             detailed_entry_type: value.detailed_entry_type,
+            course_code: value.course_code,
+            course_semester_hours: value.course_semester_hours,
+            course_group: value.course_group,
         }
     }
 }
@@ -245,6 +865,9 @@ pub enum EventType {
     Exercise,
     Exam,
     Barred,
+    Cancelled,
+    Moved,
+    Online,
     Other,
 }
 
@@ -255,6 +878,9 @@ impl Display for EventType {
             EventType::Exercise => write!(f, "exercise"),
             EventType::Exam => write!(f, "exam"),
             EventType::Barred => write!(f, "barred"),
+            EventType::Cancelled => write!(f, "cancelled"),
+            EventType::Moved => write!(f, "moved"),
+            EventType::Online => write!(f, "online"),
             EventType::Other => write!(f, "other"),
         }
     }
@@ -0,0 +1,350 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// A feedback token which was already used
+///
+/// Tokens are identified by their `kid`, which is an `u64` and stored as the `i64` with the same bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsedToken {
+    pub kid: u64,
+    pub expires_at: DateTime<Utc>,
+}
+impl UsedToken {
+    /// Records the token as used and prunes expired tokens
+    ///
+    /// Returns `false` if the token was already used.
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn insert(self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!("DELETE FROM feedback_used_tokens WHERE expires_at < NOW()")
+            .execute(&mut *tx)
+            .await?;
+        let inserted = sqlx::query!(
+            r#"INSERT INTO feedback_used_tokens (kid, expires_at)
+               VALUES ($1, $2)
+               ON CONFLICT (kid) DO NOTHING"#,
+            self.kid as i64,
+            self.expires_at
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(inserted == 1)
+    }
+    /// Whether the token was already used and is not expired yet
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn exists(pool: &PgPool, kid: u64) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1
+                             FROM feedback_used_tokens
+                             WHERE kid = $1 AND expires_at >= NOW()) AS "used!""#,
+            kid as i64
+        )
+        .fetch_one(pool)
+        .await
+    }
+    /// Makes the token usable again
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn delete(pool: &PgPool, kid: u64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM feedback_used_tokens WHERE kid = $1",
+            kid as i64
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Proof that someone submitted the feedback of an issue, which lets them withdraw it
+///
+/// Only the hash of the token is stored, the token itself is only known to the reporter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionToken {
+    pub token_hash: String,
+    pub redacted_at: Option<DateTime<Utc>>,
+}
+impl DeletionToken {
+    #[tracing::instrument(skip(pool, token_hash))]
+    pub(crate) async fn insert(
+        pool: &PgPool,
+        issue_number: u64,
+        token_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO feedback_deletion_tokens (issue_number, token_hash) VALUES ($1, $2)",
+            issue_number as i64,
+            token_hash
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get(
+        pool: &PgPool,
+        issue_number: u64,
+    ) -> Result<Option<DeletionToken>, sqlx::Error> {
+        sqlx::query_as!(
+            DeletionToken,
+            "SELECT token_hash, redacted_at FROM feedback_deletion_tokens WHERE issue_number = $1",
+            issue_number as i64
+        )
+        .fetch_optional(pool)
+        .await
+    }
+    /// Records that the issue was redacted, so that repeated requests don't contact GitHub again
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn mark_redacted(pool: &PgPool, issue_number: u64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE feedback_deletion_tokens SET redacted_at = NOW() WHERE issue_number = $1",
+            issue_number as i64
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Feedback waiting to be delivered, see [`crate::routes::feedback::queue`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedFeedback {
+    pub receipt: String,
+    /// The request to open the issue
+    pub payload: serde_json::Value,
+    /// How often delivery was attempted, including the attempt in progress
+    pub attempts: i32,
+    /// What happens once the issue is opened, see [`crate::routes::feedback::queue`]
+    pub follow_up: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+impl QueuedFeedback {
+    /// Queues the feedback, the first attempt being in progress until `next_attempt_at`
+    #[tracing::instrument(skip(pool, payload, follow_up))]
+    pub(crate) async fn insert(
+        pool: &PgPool,
+        receipt: &str,
+        payload: &serde_json::Value,
+        follow_up: &serde_json::Value,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO feedback_delivery_queue (receipt, payload, follow_up, next_attempt_at) VALUES ($1, $2, $3, $4)",
+            receipt,
+            payload,
+            follow_up,
+            next_attempt_at
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    /// Takes the feedback which is due the longest, if any is due at `now`
+    ///
+    /// Other instances skip it until `lease_until`, while this one attempts to deliver it.
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn claim_due(
+        pool: &PgPool,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+    ) -> Result<Option<QueuedFeedback>, sqlx::Error> {
+        sqlx::query_as!(
+            QueuedFeedback,
+            r#"UPDATE feedback_delivery_queue
+               SET attempts = attempts + 1, next_attempt_at = $2
+               WHERE receipt = (SELECT receipt
+                                FROM feedback_delivery_queue
+                                WHERE next_attempt_at <= $1
+                                ORDER BY next_attempt_at
+                                LIMIT 1 FOR UPDATE SKIP LOCKED)
+               RETURNING receipt, payload, attempts, follow_up, created_at"#,
+            now,
+            lease_until
+        )
+        .fetch_optional(pool)
+        .await
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn reschedule(
+        pool: &PgPool,
+        receipt: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE feedback_delivery_queue SET next_attempt_at = $2 WHERE receipt = $1",
+            receipt,
+            next_attempt_at
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    /// Forgets the feedback, because it was delivered or given up on
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn delete(pool: &PgPool, receipt: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM feedback_delivery_queue WHERE receipt = $1",
+            receipt
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    /// How much feedback is waiting to be delivered
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn count(pool: &PgPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "depth!" FROM feedback_delivery_queue"#)
+            .fetch_one(pool)
+            .await
+    }
+}
+
+/// How to reach the reporter of an issue, see [`crate::routes::feedback::contact`]
+///
+/// The email is only stored encrypted, the key is not in the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedbackContact {
+    pub encrypted_email: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+impl FeedbackContact {
+    #[tracing::instrument(skip(pool, encrypted_email))]
+    pub(crate) async fn insert(
+        pool: &PgPool,
+        issue_number: u64,
+        encrypted_email: &[u8],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO feedback_contacts (issue_number, encrypted_email) VALUES ($1, $2)",
+            issue_number as i64,
+            encrypted_email
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn get(
+        pool: &PgPool,
+        issue_number: u64,
+    ) -> Result<Option<FeedbackContact>, sqlx::Error> {
+        sqlx::query_as!(
+            FeedbackContact,
+            "SELECT encrypted_email, created_at FROM feedback_contacts WHERE issue_number = $1",
+            issue_number as i64
+        )
+        .fetch_optional(pool)
+        .await
+    }
+    /// The issues we have a contact for
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn issue_numbers(pool: &PgPool) -> Result<Vec<u64>, sqlx::Error> {
+        let numbers =
+            sqlx::query_scalar!("SELECT issue_number FROM feedback_contacts ORDER BY issue_number")
+                .fetch_all(pool)
+                .await?;
+        Ok(numbers.into_iter().map(|number| number as u64).collect())
+    }
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn delete(pool: &PgPool, issue_number: u64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM feedback_contacts WHERE issue_number = $1",
+            issue_number as i64
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    /// Deletes the contacts created before `cutoff`, returning how many were deleted
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn delete_created_before(
+        pool: &PgPool,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let deleted = sqlx::query!(
+            "DELETE FROM feedback_contacts WHERE created_at < $1",
+            cutoff
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(deleted)
+    }
+}
+
+/// An entry of the abuse blocklist, see [`crate::routes::feedback::blocklist`]
+///
+/// Either `kid_prefix` or `ip_range` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistEntry {
+    pub id: i32,
+    pub kid_prefix: Option<String>,
+    pub ip_range: Option<String>,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+impl BlocklistEntry {
+    /// Adds the entry and prunes expired ones
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn insert(
+        pool: &PgPool,
+        kid_prefix: Option<&str>,
+        ip_range: Option<&str>,
+        reason: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<BlocklistEntry, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!("DELETE FROM feedback_blocklist WHERE expires_at <= NOW()")
+            .execute(&mut *tx)
+            .await?;
+        let entry = sqlx::query_as!(
+            BlocklistEntry,
+            r#"INSERT INTO feedback_blocklist (kid_prefix, ip_range, reason, expires_at)
+               VALUES ($1, $2, $3, $4)
+               RETURNING id, kid_prefix, ip_range, reason, created_at, expires_at"#,
+            kid_prefix,
+            ip_range,
+            reason,
+            expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+    /// The entries which block at `now`, the oldest first
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn active(
+        pool: &PgPool,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<BlocklistEntry>, sqlx::Error> {
+        sqlx::query_as!(
+            BlocklistEntry,
+            r#"SELECT id, kid_prefix, ip_range, reason, created_at, expires_at
+               FROM feedback_blocklist
+               WHERE expires_at IS NULL OR expires_at > $1
+               ORDER BY id"#,
+            now
+        )
+        .fetch_all(pool)
+        .await
+    }
+    /// Removes the entry, returning it if it existed
+    #[tracing::instrument(skip(pool))]
+    pub(crate) async fn delete(
+        pool: &PgPool,
+        id: i32,
+    ) -> Result<Option<BlocklistEntry>, sqlx::Error> {
+        sqlx::query_as!(
+            BlocklistEntry,
+            r#"DELETE FROM feedback_blocklist
+               WHERE id = $1
+               RETURNING id, kid_prefix, ip_range, reason, created_at, expires_at"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
@@ -1,3 +1,4 @@
 pub mod calendar;
+pub mod feedback;
 pub mod location;
 pub mod public_transport;
@@ -44,6 +44,7 @@ where
 fn add_static_openapi_docs(openapi: &mut utoipa::openapi::OpenApi) {
     use utoipa::openapi::extensions::ExtensionsBuilder;
     use utoipa::openapi::external_docs::ExternalDocsBuilder;
+    use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
     use utoipa::openapi::tag::TagBuilder;
     use utoipa::openapi::{ContactBuilder, InfoBuilder, LicenseBuilder, ServerBuilder};
     let description = r#"Navigating around TUM with excellence – An API to search for rooms,
@@ -109,6 +110,10 @@ If you'd like to help out or join us in this adventure, we would love to talk to
             .name("maps".to_string())
             .description(Some("API to access for map-data"))
             .build(),
+        TagBuilder::new()
+            .name("admin".to_string())
+            .description(Some("APIs to administer the server"))
+            .build(),
     ]);
     openapi.external_docs = Some(
         ExternalDocsBuilder::new()
@@ -119,4 +124,17 @@ If you'd like to help out or join us in this adventure, we would love to talk to
             .into(),
     );
     openapi.schema = "http://json-schema.org/draft-07/schema".to_string();
+    // administrative endpoints
+    openapi
+        .components
+        .get_or_insert_with(Default::default)
+        .add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("`ADMIN_TOKEN` configured on the server"))
+                    .build(),
+            ),
+        );
 }
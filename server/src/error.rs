@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{JsonConfig, QueryConfig};
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// Error body returned by all endpoints
+///
+/// Clients should use `code` to distinguish errors programmatically.
+/// `error` is meant for humans and may change at any time.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct ApiError {
+    /// Human-readable description of what went wrong
+    #[schema(value_type = String, examples("Not found"))]
+    error: Cow<'static, str>,
+    /// Machine-readable error code
+    #[schema(examples("not_found", "internal_error"))]
+    code: &'static str,
+    /// The request parameter which is to blame, if there is a single one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(examples("start", "ids"))]
+    parameter: Option<&'static str>,
+    #[serde(skip)]
+    status: StatusCode,
+}
+
+impl ApiError {
+    pub fn new(
+        status: StatusCode,
+        code: &'static str,
+        error: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            error: error.into(),
+            code,
+            parameter: None,
+            status,
+        }
+    }
+    pub fn with_parameter(mut self, parameter: &'static str) -> Self {
+        self.parameter = Some(parameter);
+        self
+    }
+}
+
+/// Lets invalid JSON bodies be answered with an [`ApiError`] instead of plain text
+pub fn json_config(limit: usize) -> JsonConfig {
+    JsonConfig::default().limit(limit).error_handler(|e, _| {
+        let status = e.status_code();
+        let code = match status {
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            _ => "invalid_body",
+        };
+        ApiError::new(status, code, e.to_string()).into()
+    })
+}
+
+/// Lets invalid query strings be answered with an [`ApiError`] instead of plain text
+pub fn query_config() -> QueryConfig {
+    QueryConfig::default().error_handler(|e, _| {
+        ApiError::new(StatusCode::BAD_REQUEST, "invalid_query", e.to_string()).into()
+    })
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{code}: {error}", code = self.code, error = self.error)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(self)
+    }
+}
+
+impl From<ApiError> for HttpResponse {
+    fn from(value: ApiError) -> Self {
+        value.error_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn serialises_as_json() {
+        let resp = HttpResponse::from(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "Not found",
+        ));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"error":"Not found","code":"not_found"}"#);
+    }
+
+    #[test]
+    fn parameter_is_included() {
+        let err = ApiError::new(StatusCode::BAD_REQUEST, "invalid_window", "too short")
+            .with_parameter("end");
+        insta::assert_snapshot!(
+            serde_json::to_string(&err).unwrap(),
+            @r#"{"error":"too short","code":"invalid_window","parameter":"end"}"#
+        );
+    }
+
+    #[actix_web::test]
+    async fn extractor_errors_are_api_errors() {
+        #[derive(serde::Deserialize)]
+        struct Args {
+            #[expect(dead_code, reason = "only deserialised")]
+            start: u32,
+        }
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(json_config(16))
+                .app_data(query_config())
+                .route(
+                    "/",
+                    actix_web::web::post().to(
+                        |_: actix_web::web::Query<Args>, _: actix_web::web::Json<Args>| async {
+                            HttpResponse::Ok().finish()
+                        },
+                    ),
+                ),
+        )
+        .await;
+        let cases = [
+            ("/?start=1", r#"{"start":"#, 400, "invalid_body"),
+            (
+                "/?start=1",
+                r#"{"start":1,"padding":"over the limit"}"#,
+                413,
+                "payload_too_large",
+            ),
+            ("/?start=soon", r#"{"start":1}"#, 400, "invalid_query"),
+        ];
+        for (uri, body, status, code) in cases {
+            let req = actix_web::test::TestRequest::post()
+                .uri(uri)
+                .insert_header(("content-type", "application/json"))
+                .set_payload(body)
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            assert_eq!(resp.status().as_u16(), status, "{uri} {body}");
+            let actual: serde_json::Value = actix_web::test::read_body_json(resp).await;
+            assert_eq!(actual["code"], code, "{uri} {body}");
+            assert!(actual["error"].is_string(), "{uri} {body}");
+        }
+    }
+}
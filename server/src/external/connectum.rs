@@ -3,9 +3,10 @@ use chrono::{DateTime, Utc};
 use oauth2::basic::{BasicClient, BasicTokenResponse};
 use oauth2::url::Url;
 use oauth2::{AuthUrl, ClientId, ClientSecret, Scope, TokenResponse, TokenUrl};
+use reqwest::header::CONTENT_TYPE;
 use reqwest::redirect;
 use serde::Deserialize;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
@@ -47,19 +48,57 @@ impl APIRequestor {
 
         let url = format!("https://campus.tum.de/tumonline/co/connectum/api/rooms/{id}/calendars");
 
-        let events = self
+        let resp = self
             .client
             .get(&url)
             .bearer_auth(token)
             .send()
             .await?
-            .json::<Vec<ConnectumEvent>>()
-            .await?;
-        Ok(events)
+            .error_for_status()?;
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(ToString::to_string);
+        let body = resp.text().await?;
+        parse_events(content_type.as_deref(), &body)
     }
 }
 
-#[derive(Deserialize)]
+/// TUMonline answered with its maintenance page instead of the calendar
+///
+/// This page is served with `200 OK` => it has to be detected by its content.
+#[derive(Debug)]
+pub struct UnderMaintenance;
+impl Display for UnderMaintenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TUMonline is under maintenance")
+    }
+}
+impl std::error::Error for UnderMaintenance {}
+
+/// Parses the events of a room, detecting the maintenance page of TUMonline
+///
+/// The api only ever answers with json => any html page (detected via the `content_type` or the body) means maintenance.
+pub(crate) fn parse_events(
+    content_type: Option<&str>,
+    body: &str,
+) -> anyhow::Result<Vec<ConnectumEvent>> {
+    let is_html_content_type = content_type.is_some_and(|c| {
+        c.split(';')
+            .next()
+            .is_some_and(|essence| essence.trim().eq_ignore_ascii_case("text/html"))
+    });
+    let body_start = body.trim_start().get(..5).unwrap_or_default();
+    let is_html_body =
+        body_start.eq_ignore_ascii_case("<!doc") || body_start.eq_ignore_ascii_case("<html");
+    if is_html_content_type || is_html_body {
+        return Err(UnderMaintenance.into());
+    }
+    Ok(serde_json::from_str(body)?)
+}
+
+#[derive(Deserialize, Hash)]
 pub struct ConnectumEvent {
     pub id: i32,
     pub room_code: String,
@@ -70,6 +109,12 @@ pub struct ConnectumEvent {
     pub stp_type: Option<String>,
     pub entry_type: String,
     pub detailed_entry_type: String,
+    #[serde(default)]
+    pub course_code: Option<String>,
+    #[serde(default)]
+    pub course_semester_hours: Option<i32>,
+    #[serde(default)]
+    pub course_group: Option<String>,
 }
 #[derive(Clone)]
 struct OauthAccessToken(Arc<RwLock<Option<(Instant, BasicTokenResponse)>>>);
@@ -173,3 +218,37 @@ impl Debug for OauthAccessToken {
         base.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAINTENANCE_PAGE: &str = include_str!("tumonline-maintenance.html");
+
+    #[test]
+    fn detects_maintenance_page() {
+        for content_type in [Some("text/html;charset=UTF-8"), Some("TEXT/HTML"), None] {
+            let err = parse_events(content_type, MAINTENANCE_PAGE).unwrap_err();
+            assert!(err.is::<UnderMaintenance>(), "{content_type:?}: {err:?}");
+        }
+        // the content-type alone is enough
+        let err = parse_events(Some("text/html"), "Wartungsarbeiten").unwrap_err();
+        assert!(err.is::<UnderMaintenance>());
+    }
+
+    #[test]
+    fn parses_events() {
+        let body = r#"[{"id":1,"room_code":"5121.EG.003","start_at":"2012-01-01T00:00:00Z","end_at":"2014-01-01T00:00:00Z","title_de":"Quantenteleportation","title_en":"Quantum teleportation","stp_type":null,"entry_type":"lecture","detailed_entry_type":"Abhaltung"}]"#;
+        let events = parse_events(Some("application/json"), body).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].title_de, "Quantenteleportation");
+        assert!(
+            parse_events(Some("application/json"), "[]")
+                .unwrap()
+                .is_empty()
+        );
+        // broken json is not maintenance
+        let err = parse_events(Some("application/json"), "[{").unwrap_err();
+        assert!(!err.is::<UnderMaintenance>());
+    }
+}
@@ -78,7 +78,7 @@ impl MapImageDownloadTask {
     pub async fn fulfill(self) -> Option<((u32, u32), image::DynamicImage)> {
         let raw_tile = download_map_image(self.location).await;
         match raw_tile {
-            Ok(bytes) => match image::load_from_memory(&bytes.0) {
+            Ok(bytes) => match image::load_from_memory(bytes.as_slice()) {
                 Ok(img) => Some((self.index, img)),
                 Err(e) => {
                     error!(?self, error = ?e, "Error while parsing image");
@@ -112,7 +112,7 @@ async fn download_map_image(location: TileLocation) -> anyhow::Result<LimitedVec
         // wait with exponential backoff
         let size = bytes.len();
         if size > 500 {
-            return Ok(LimitedVec(bytes.into()));
+            return Ok(LimitedVec::from(bytes.to_vec()));
         }
         let wait_time_ms = 1.5_f32.powi(i).round() as u64;
         let wait_time = Duration::from_millis(wait_time_ms);
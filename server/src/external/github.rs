@@ -1,7 +1,375 @@
+use std::time::Duration;
+
 use actix_web::HttpResponse;
+use actix_web::http::StatusCode;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chrono::{DateTime, Utc};
 use octocrab::Octocrab;
+use octocrab::models::IssueState;
 use regex::Regex;
-use tracing::error;
+use serde::Deserialize;
+use tracing::{debug, error, warn};
+use url::Url;
+
+use crate::error::ApiError;
+use crate::routes::feedback::sink::Feedback;
+
+/// Boilerplate wrapped around the description of an issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IssueTemplate {
+    pub heading: &'static str,
+    pub metadata_heading: &'static str,
+    pub location_label: &'static str,
+    pub name_label: &'static str,
+    pub coordinates_label: &'static str,
+    pub client_label: &'static str,
+    pub related_issue_label: &'static str,
+    /// Flags keys which are not in our data
+    pub unknown_location: &'static str,
+    pub attachments_heading: &'static str,
+    pub footer: &'static str,
+}
+#[cfg(test)]
+impl IssueTemplate {
+    /// English labels, tests override what they are about via struct update syntax
+    pub(crate) fn for_test() -> Self {
+        Self {
+            heading: "Description",
+            metadata_heading: "Metadata",
+            location_label: "Location",
+            name_label: "Name",
+            coordinates_label: "Coordinates",
+            client_label: "Client",
+            related_issue_label: "Related issue",
+            unknown_location: "unknown location",
+            attachments_heading: "Attachments",
+            footer: "footer",
+        }
+    }
+}
+/// Optional parts of an issue besides its description
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IssueExtras<'a> {
+    /// Key of the location the issue is about
+    pub location: Option<&'a str>,
+    /// What our data says about the `location`, if it was looked up
+    pub location_lookup: Option<&'a LocationLookup>,
+    /// App version/user agent of the client, if the reporter opted in to share it
+    pub client: Option<&'a str>,
+    /// Issue the reporter wanted to comment on, but which is closed or does not exist
+    pub related_issue: Option<u64>,
+    /// Images embedded into the issue
+    pub attachments: &'a [Url],
+    /// YAML, which our data pipeline parses
+    pub edit_proposal: Option<&'a str>,
+}
+
+/// What our data says about the location of an issue
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationLookup {
+    Known {
+        name: String,
+        lat: f64,
+        lon: f64,
+    },
+    /// The key is not in our data, e.g. because of a typo or an outdated client
+    Unknown,
+}
+
+/// Longer user agents are truncated, they do not help triaging
+const MAX_CLIENT_LEN: usize = 200;
+
+impl IssueTemplate {
+    pub fn render(self, description: &str, extras: IssueExtras) -> String {
+        let mut body = format!("## {heading}\n\n", heading = self.heading);
+        body += &format!("{quoted}\n\n", quoted = quote(&make_inert(description)));
+        if let Some(yaml) = extras.edit_proposal {
+            let yaml = yaml.trim_end();
+            let fence = fence_for(yaml);
+            body += &format!("{fence}yaml\n{yaml}\n{fence}\n\n");
+        }
+        let metadata = self.metadata(extras);
+        if !metadata.is_empty() {
+            body += &format!("## {heading}\n\n", heading = self.metadata_heading);
+            body += &metadata.join("\n");
+            body += "\n\n";
+        }
+        if !extras.attachments.is_empty() {
+            body += &format!("## {heading}\n\n", heading = self.attachments_heading);
+            body += &render_attachments(extras.attachments);
+            body += "\n\n";
+        }
+        body + &format!("---\n\n{footer}", footer = self.footer)
+    }
+
+    /// Feedback added as a comment to an existing issue
+    ///
+    /// Comments have no title, so the subject is put in front of the rendered feedback.
+    pub fn render_comment(self, subject: &str, description: &str, extras: IssueExtras) -> String {
+        format!(
+            "**{subject}**\n\n{body}",
+            subject = make_inert(subject),
+            body = self.render(description, extras)
+        )
+    }
+}
+
+/// Inserted after `@` and `#`, so that GitHub does not turn what follows into a mention or reference
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Neutralises `@mentions` and `#123` references, so that users cannot ping people or link issues
+pub fn neutralise_references(s: &str) -> String {
+    let mut neutralised = String::with_capacity(s.len());
+    for c in s.chars() {
+        neutralised.push(c);
+        if c == '@' || c == '#' {
+            neutralised.push(ZERO_WIDTH_SPACE);
+        }
+    }
+    neutralised
+}
+
+/// User text, which renders as text instead of HTML and does not ping or link anything
+fn make_inert(s: &str) -> String {
+    neutralise_references(s)
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl IssueTemplate {
+    /// Lines of the metadata section, which saves whoever triages the issue from looking the location up
+    fn metadata(self, extras: IssueExtras) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(key) = extras.location {
+            let mut line = format!(
+                "- **{label}:** [`{key}`]({link})",
+                label = self.location_label,
+                link = location_link(key)
+            );
+            if extras.location_lookup == Some(&LocationLookup::Unknown) {
+                line += &format!(" ⚠️ {unknown}", unknown = self.unknown_location);
+            }
+            lines.push(line);
+        }
+        if let Some(LocationLookup::Known { name, lat, lon }) = extras.location_lookup {
+            lines.push(format!(
+                "- **{label}:** {name}",
+                label = self.name_label,
+                name = neutralise_references(name)
+            ));
+            lines.push(format!(
+                "- **{label}:** {lat:.6}, {lon:.6}",
+                label = self.coordinates_label
+            ));
+        }
+        if let Some(client) = extras.client {
+            let client = client
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_CLIENT_LEN)
+                .collect::<String>();
+            lines.push(format!(
+                "- **{label}:** {client}",
+                label = self.client_label,
+                client = make_inert(&client)
+            ));
+        }
+        if let Some(number) = extras.related_issue {
+            lines.push(format!(
+                "- **{label}:** #{number}",
+                label = self.related_issue_label
+            ));
+        }
+        lines
+    }
+}
+
+/// Quotes each line, so that the user text is clearly separated from our boilerplate
+fn quote(s: &str) -> String {
+    s.split('\n')
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A code fence longer than any run of backticks in `content`, so that the content cannot close it
+fn fence_for(content: &str) -> String {
+    let longest_run = content
+        .split(|c: char| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+/// Where the location is shown on our website
+///
+/// As this is part of every issue about the location, it is also used to find them.
+pub fn location_link(key: &str) -> String {
+    format!("https://nav.tum.de/view/{key}")
+}
+
+/// Embeds the images, so that they are visible without leaving the issue
+pub fn render_attachments(attachments: &[Url]) -> String {
+    attachments
+        .iter()
+        .enumerate()
+        .map(|(i, url)| format!("![attachment {n}]({url})", n = i + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Branch of our repository, which images attached to feedback are committed to
+///
+/// GitHub does not offer an API to attach files to issues.
+/// The branch is never merged and has to exist.
+const ATTACHMENT_BRANCH: &str = "feedback-attachments";
+
+/// Comment explaining why an issue was redacted
+const WITHDRAWN_COMMENT: &str = "This feedback was withdrawn by the reporter.";
+
+/// Replaces everything the reporter wrote and closes the issue
+///
+/// GitHub keeps the edit history of the issue => it is labelled for maintainers to delete it after all.
+fn withdrawal_payload() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Withdrawn feedback",
+        "body": "*The content of this issue was removed at the request of the reporter.*",
+        "state": "closed",
+        "state_reason": "not_planned",
+        "labels": ["webform", "delete-after-processing"],
+    })
+}
+
+/// The fields of a created issue we use
+///
+/// octocrabs model of issues requires many fields, which would have to be kept in sync with GitHub for no benefit.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CreatedIssue {
+    pub number: u64,
+    #[serde(rename = "html_url")]
+    pub url: Url,
+}
+
+/// An issue as returned by the [search api](https://docs.github.com/en/rest/search/search#search-issues-and-pull-requests)
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FoundIssue {
+    pub number: u64,
+    pub html_url: Url,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// The fields of an issue we need to decide whether to comment on it
+#[derive(Deserialize, Debug)]
+struct IssueStatus {
+    state: IssueState,
+    /// Pull requests are issues as well, as far as the api is concerned
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedComment {
+    html_url: Url,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResults {
+    items: Vec<FoundIssue>,
+}
+
+/// How many issues are considered when looking for duplicates
+const MAX_SEARCH_RESULTS: u8 = 20;
+
+/// How often creating an issue is attempted before giving up
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// Waiting longer than this in total would hold the request of the user for too long
+const MAX_TOTAL_WAIT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, PartialEq, Eq)]
+enum IssueAttempt {
+    Created(CreatedIssue),
+    /// GitHub is unavailable or rate-limiting us, trying again later might succeed
+    ///
+    /// `retry_after` is set if GitHub told us how long to wait
+    Retryable {
+        retry_after: Option<Duration>,
+    },
+    /// Retrying would not change the outcome (e.g. invalid credentials)
+    Failed,
+    /// The request may have reached GitHub, but we do not know whether the issue was created
+    ///
+    /// Retrying right away could open the issue twice.
+    Unknown,
+}
+
+/// Headers GitHub uses to tell [when to retry](https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api#exceeding-the-rate-limit)
+#[derive(Debug, Default)]
+struct RateLimitHeaders {
+    retry_after: Option<String>,
+    remaining: Option<String>,
+    reset: Option<String>,
+}
+impl RateLimitHeaders {
+    /// How long GitHub wants us to wait before retrying
+    fn wait(&self, now: DateTime<Utc>) -> Option<Duration> {
+        if let Some(seconds) = self.retry_after.as_deref() {
+            return seconds.trim().parse().ok().map(Duration::from_secs);
+        }
+        if self.remaining.as_deref().map(str::trim) != Some("0") {
+            return None;
+        }
+        let reset = self.reset.as_deref()?.trim().parse::<i64>().ok()?;
+        let reset = DateTime::from_timestamp(reset, 0)?;
+        Some((reset - now).to_std().unwrap_or_default())
+    }
+
+    fn classify(&self, status: u16, now: DateTime<Utc>) -> IssueAttempt {
+        let retry_after = self.wait(now);
+        let is_rate_limited = status == 429 || (status == 403 && retry_after.is_some());
+        if is_rate_limited || status >= 500 {
+            IssueAttempt::Retryable { retry_after }
+        } else {
+            IssueAttempt::Failed
+        }
+    }
+}
+
+/// Exponential backoff for the `attempt`th failed attempt
+fn backoff_with_jitter(attempt: u32) -> Duration {
+    let exponential = INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    exponential.mul_f64(rand::random_range(0.5..=1.0))
+}
+
+/// Whether the configured token can be used to open issues
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenValidity {
+    Valid,
+    /// GitHub rejected the token, e.g. because it expired or was revoked
+    Invalid,
+    /// The token works, but is not allowed to open issues
+    MissingScope {
+        scopes: String,
+    },
+}
+
+/// Whether the `scopes` of a classic token are sufficient to open issues and pull requests
+///
+/// As our repository is public, `public_repo` is enough.
+fn has_repo_scope(scopes: &str) -> bool {
+    scopes
+        .split(',')
+        .map(str::trim)
+        .any(|scope| scope == "repo" || scope == "public_repo")
+}
 
 #[derive(Debug)]
 pub struct GitHub {
@@ -22,46 +390,340 @@ impl Default for GitHub {
     }
 }
 impl GitHub {
+    /// Talks to a mock of the GitHub API instead
+    #[cfg(test)]
+    pub(crate) fn with_base_uri(uri: &str) -> Self {
+        let octocrab = Octocrab::builder()
+            .base_uri(uri)
+            .unwrap()
+            .personal_token("test-token".to_string())
+            .build()
+            .unwrap();
+        Self {
+            octocrab: Some(octocrab),
+        }
+    }
+
+    /// Whether a `GITHUB_TOKEN` is configured
+    pub fn has_token(&self) -> bool {
+        self.octocrab.is_some()
+    }
+
+    /// Opens an issue on our repository
+    ///
+    /// If GitHub is unavailable or rate-limiting us, this is retried a few times with exponential backoff,
+    /// respecting the `Retry-After` GitHub sends.
+    /// Only responses proving that no issue was created are retried, and only for up to [`MAX_TOTAL_WAIT`].
+    /// If this does not succeed or we cannot tell whether it did, a `503` is returned.
     #[tracing::instrument]
-    pub async fn open_issue(
-        self,
-        title: &str,
-        description: &str,
-        labels: Vec<String>,
-    ) -> HttpResponse {
-        let title = Self::clean_feedback_data(title, 512);
-        let description = Self::clean_feedback_data(description, 1024 * 1024);
+    pub async fn open_issue(&self, feedback: &Feedback<'_>) -> Result<CreatedIssue, ApiError> {
+        self.open_issue_from(&Self::issue_payload(feedback)).await
+    }
 
-        if title.len() < 3 || description.len() < 10 {
-            return HttpResponse::UnprocessableEntity()
-                .content_type("text/plain")
-                .body("Subject or body missing or too short");
+    /// The request to open an issue for the feedback
+    pub fn issue_payload(feedback: &Feedback<'_>) -> serde_json::Value {
+        serde_json::json!({
+            "title": feedback.title(),
+            "body": feedback.render(),
+            "labels": feedback.triage.labels,
+            "assignees": feedback.triage.assignees,
+        })
+    }
+
+    /// Like [`Self::open_issue`], for a prepared [`Self::issue_payload`]
+    #[tracing::instrument(skip(body))]
+    pub async fn open_issue_from(
+        &self,
+        body: &serde_json::Value,
+    ) -> Result<CreatedIssue, ApiError> {
+        let Some(octocrab) = &self.octocrab else {
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "github_error",
+                "Failed to create issue, please try again later",
+            ));
+        };
+
+        let unavailable = || {
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "github_unavailable",
+                "GitHub is currently unavailable, please try again later",
+            )
+        };
+        let mut attempt = 1;
+        let mut waited = Duration::ZERO;
+        loop {
+            let wait = match Self::try_open_issue(octocrab, body).await {
+                IssueAttempt::Created(issue) => return Ok(issue),
+                IssueAttempt::Failed => {
+                    return Err(ApiError::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "github_error",
+                        "Failed to create issue, please try again later",
+                    ));
+                }
+                // the delivery queue checks whether the issue exists before trying again
+                IssueAttempt::Unknown => return Err(unavailable()),
+                IssueAttempt::Retryable { retry_after } => {
+                    retry_after.unwrap_or_else(|| backoff_with_jitter(attempt))
+                }
+            };
+            if attempt >= MAX_ATTEMPTS || waited + wait > MAX_TOTAL_WAIT {
+                warn!(
+                    attempt,
+                    wait_s = wait.as_secs(),
+                    waited_s = waited.as_secs(),
+                    "giving up creating the issue"
+                );
+                return Err(unavailable());
+            }
+            debug!(
+                attempt,
+                wait_ms = wait.as_millis(),
+                "retrying to create the issue"
+            );
+            tokio::time::sleep(wait).await;
+            waited += wait;
+            attempt += 1;
         }
-        let Some(octocrab) = self.octocrab else {
-            return HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Failed to create issue, please try again later");
+    }
+
+    /// Sends a single request to create an issue
+    ///
+    /// The raw response is used, as the typed api of octocrab does not expose the rate-limit headers.
+    async fn try_open_issue(octocrab: &Octocrab, body: &serde_json::Value) -> IssueAttempt {
+        let resp = match octocrab
+            ._post("/repos/TUM-Dev/navigatum/issues", Some(body))
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!(error = ?e, "Error sending the request to create an issue");
+                return IssueAttempt::Unknown;
+            }
+        };
+        let status = resp.status().as_u16();
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(ToString::to_string)
+        };
+        let rate_limit = RateLimitHeaders {
+            retry_after: header("retry-after"),
+            remaining: header("x-ratelimit-remaining"),
+            reset: header("x-ratelimit-reset"),
+        };
+        let is_success = (200..300).contains(&status);
+        let body = match octocrab.body_to_string(resp).await {
+            Ok(body) => body,
+            Err(e) if is_success => {
+                error!(error = ?e, status, "Error reading the created issue");
+                return IssueAttempt::Unknown;
+            }
+            Err(e) => {
+                error!(error = ?e, status, "Error reading the response of creating an issue");
+                return rate_limit.classify(status, Utc::now());
+            }
         };
+        if is_success {
+            return match serde_json::from_str::<CreatedIssue>(&body) {
+                Ok(issue) => IssueAttempt::Created(issue),
+                Err(e) => {
+                    error!(error = ?e, body, "Could not parse the created issue");
+                    IssueAttempt::Failed
+                }
+            };
+        }
+        error!(status, body, "Error creating issue");
+        rate_limit.classify(status, Utc::now())
+    }
 
+    /// Checks the token via a cheap authenticated request
+    ///
+    /// Only classic tokens report their scopes.
+    /// Fine-grained tokens are assumed to have the required permissions.
+    #[tracing::instrument]
+    pub async fn validate_token(&self) -> anyhow::Result<TokenValidity> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
+        let resp = octocrab._get("/user").await?;
+        let status = resp.status().as_u16();
+        if status == 401 {
+            return Ok(TokenValidity::Invalid);
+        }
+        if !(200..300).contains(&status) {
+            anyhow::bail!("GitHub responded with {status} while validating the token");
+        }
+        let scopes = resp
+            .headers()
+            .get("x-oauth-scopes")
+            .and_then(|v| v.to_str().ok());
+        match scopes {
+            Some(scopes) if !has_repo_scope(scopes) => Ok(TokenValidity::MissingScope {
+                scopes: scopes.to_string(),
+            }),
+            _ => Ok(TokenValidity::Valid),
+        }
+    }
+
+    /// Comments on an issue, if it is an open issue of our repository
+    ///
+    /// Returns the url of the comment, or `None` if the issue is closed, a pull request or does not exist.
+    #[tracing::instrument(skip(comment))]
+    pub async fn comment_if_open(&self, number: u64, comment: &str) -> anyhow::Result<Option<Url>> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
+        if !self.is_open_issue(number).await? {
+            return Ok(None);
+        }
+        let comment: CreatedComment = octocrab
+            .post(
+                format!("/repos/TUM-Dev/navigatum/issues/{number}/comments"),
+                Some(&serde_json::json!({ "body": comment })),
+            )
+            .await?;
+        Ok(Some(comment.html_url))
+    }
+
+    /// Whether the issue is an open issue of our repository
+    ///
+    /// Closed issues, pull requests and issues which do not exist (anymore) are not.
+    #[tracing::instrument]
+    pub async fn is_open_issue(&self, number: u64) -> anyhow::Result<bool> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
+        let resp = octocrab
+            ._get(format!("/repos/TUM-Dev/navigatum/issues/{number}"))
+            .await?;
+        let status = resp.status().as_u16();
+        // GitHub answers with `410 Gone` for deleted issues
+        if matches!(status, 404 | 410) {
+            return Ok(false);
+        }
+        let body = octocrab.body_to_string(resp).await?;
+        anyhow::ensure!(
+            (200..300).contains(&status),
+            "could not get issue {number}: {status} {body}"
+        );
+        let issue = serde_json::from_str::<IssueStatus>(&body)?;
+        Ok(issue.state == IssueState::Open && issue.pull_request.is_none())
+    }
+
+    /// The open issues of our repository mentioning the location, most recently updated first
+    #[tracing::instrument]
+    pub async fn open_issues_about(&self, key: &str) -> anyhow::Result<Vec<FoundIssue>> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
+        let query = format!(
+            "repo:TUM-Dev/navigatum is:issue is:open in:body \"{link}\"",
+            link = location_link(key)
+        );
+        let per_page = MAX_SEARCH_RESULTS.to_string();
+        let results: SearchResults = octocrab
+            .get(
+                "/search/issues",
+                Some(&[
+                    ("q", query.as_str()),
+                    ("sort", "updated"),
+                    ("per_page", per_page.as_str()),
+                ]),
+            )
+            .await?;
+        Ok(results.items)
+    }
+
+    /// Redacts and closes an issue, whose reporter withdrew their feedback
+    #[tracing::instrument]
+    pub async fn withdraw_issue(&self, number: u64) -> anyhow::Result<()> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
         let resp = octocrab
+            ._patch(
+                format!("/repos/TUM-Dev/navigatum/issues/{number}"),
+                Some(&withdrawal_payload()),
+            )
+            .await?;
+        let status = resp.status().as_u16();
+        if !(200..300).contains(&status) {
+            let body = octocrab.body_to_string(resp).await.unwrap_or_default();
+            anyhow::bail!("GitHub responded with {status} while redacting the issue: {body}");
+        }
+        octocrab
             .issues("TUM-Dev", "navigatum")
-            .create(title)
-            .body(description)
-            .labels(labels)
-            .send()
-            .await;
+            .create_comment(number, WITHDRAWN_COMMENT)
+            .await?;
+        Ok(())
+    }
 
-        match resp {
-            Ok(issue) => HttpResponse::Created()
-                .content_type("text/plain")
-                .body(issue.html_url.to_string()),
-            Err(e) => {
-                error!(error = ?e, "Error creating issue");
-                HttpResponse::InternalServerError()
-                    .content_type("text/plain")
-                    .body("Failed to create issue, please try again later")
-            }
+    /// The issue of our repository containing `text` in its body, if there is one
+    ///
+    /// The search index lags behind by up to a minute, so very recent issues might not be found.
+    #[tracing::instrument]
+    pub async fn issue_containing(&self, text: &str) -> anyhow::Result<Option<FoundIssue>> {
+        let Some(octocrab) = &self.octocrab else {
+            anyhow::bail!("GitHub is not configured");
+        };
+        let query = format!("repo:TUM-Dev/navigatum is:issue in:body \"{text}\"");
+        let results: SearchResults = octocrab
+            .get("/search/issues", Some(&[("q", query.as_str())]))
+            .await?;
+        // the search also matches similar words => only exact matches count
+        Ok(results.items.into_iter().find(|issue| {
+            issue
+                .body
+                .as_deref()
+                .is_some_and(|body| body.contains(text))
+        }))
+    }
+
+    /// Commits the file to the [`ATTACHMENT_BRANCH`] and returns where it can be downloaded
+    #[tracing::instrument(skip(content))]
+    pub async fn upload_attachment(&self, path: &str, content: &[u8]) -> Result<Url, ApiError> {
+        let failed = || {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "github_error",
+                "Failed to upload the attachment, please try again later",
+            )
+        };
+        let Some(octocrab) = &self.octocrab else {
+            return Err(failed());
+        };
+        let body = serde_json::json!({
+            "message": format!("attach {path} to feedback"),
+            "content": BASE64_STANDARD.encode(content),
+            "branch": ATTACHMENT_BRANCH,
+        });
+        let resp = octocrab
+            ._put(
+                format!("/repos/TUM-Dev/navigatum/contents/{path}"),
+                Some(&body),
+            )
+            .await
+            .map_err(|e| {
+                error!(error = ?e, "Error sending the request to upload an attachment");
+                failed()
+            })?;
+        let status = resp.status().as_u16();
+        if !(200..300).contains(&status) {
+            let body = octocrab.body_to_string(resp).await.unwrap_or_default();
+            error!(status, body, "Error uploading an attachment");
+            return Err(failed());
         }
+        Url::parse(&format!(
+            "https://raw.githubusercontent.com/TUM-Dev/navigatum/{ATTACHMENT_BRANCH}/{path}"
+        ))
+        .map_err(|e| {
+            error!(error = ?e, path, "the path of the attachment does not form a valid url");
+            failed()
+        })
     }
 
     #[tracing::instrument]
@@ -73,9 +735,12 @@ impl GitHub {
         labels: Vec<String>,
     ) -> HttpResponse {
         let Some(octocrab) = self.octocrab else {
-            return HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Failed to create a pull request, please try again later");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "github_error",
+                "Failed to create a pull request, please try again later",
+            )
+            .into();
         };
 
         // create the PR
@@ -90,9 +755,12 @@ impl GitHub {
             Ok(pr) => pr.number,
             Err(e) => {
                 error!(error = ?e, "Error creating pull request");
-                return HttpResponse::InternalServerError()
-                    .content_type("text/plain")
-                    .body("Failed to create a pull request, please try again later");
+                return ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "github_error",
+                    "Failed to create a pull request, please try again later",
+                )
+                .into();
             }
         };
 
@@ -111,9 +779,12 @@ impl GitHub {
                 .body(issue.html_url.to_string()),
             Err(e) => {
                 error!(error = ?e, "Error updating PR");
-                HttpResponse::InternalServerError()
-                    .content_type("text/plain")
-                    .body("Failed to create a pull request, please try again later")
+                ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "github_error",
+                    "Failed to create a pull request, please try again later",
+                )
+                .into()
             }
         }
     }
@@ -122,7 +793,7 @@ impl GitHub {
     /// - all control characters removed
     /// - is at most len characters long
     /// - can be nicely formatted in markdown (just \n in md is not a linebreak)
-    fn clean_feedback_data(s: &str, len: usize) -> String {
+    pub fn clean_feedback_data(s: &str, len: usize) -> String {
         let s_clean = s
             .chars()
             .filter(|c| !c.is_control() || (c == &'\n'))
@@ -171,9 +842,360 @@ mod tests {
         }
     }
     #[test]
+    fn issue_template() {
+        let template = IssueTemplate::for_test();
+        assert_eq!(
+            template.render("a  \nb", IssueExtras::default()),
+            "## Description\n\n> a  \n> b\n\n---\n\nfooter"
+        );
+        assert_eq!(
+            template.render(
+                "a",
+                IssueExtras {
+                    location: Some("mi"),
+                    ..Default::default()
+                }
+            ),
+            "## Description\n\n> a\n\n## Metadata\n\n- **Location:** [`mi`](https://nav.tum.de/view/mi)\n\n---\n\nfooter"
+        );
+        let image = Url::parse("https://example.com/a.png").unwrap();
+        assert_eq!(
+            template.render(
+                "a",
+                IssueExtras {
+                    attachments: &[image.clone(), image],
+                    ..Default::default()
+                }
+            ),
+            "## Description\n\n> a\n\n## Attachments\n\n![attachment 1](https://example.com/a.png)\n![attachment 2](https://example.com/a.png)\n\n---\n\nfooter"
+        );
+        assert_eq!(
+            template.render(
+                "a",
+                IssueExtras {
+                    edit_proposal: Some("key: mi\n"),
+                    ..Default::default()
+                }
+            ),
+            "## Description\n\n> a\n\n```yaml\nkey: mi\n```\n\n---\n\nfooter"
+        );
+    }
+    #[test]
+    fn issue_metadata() {
+        let template = IssueTemplate::for_test();
+        let known = LocationLookup::Known {
+            name: "Hörsaal 1 (MW 0001)".to_string(),
+            lat: 48.26244490906312,
+            lon: 11.668906258,
+        };
+        let body = template.render(
+            "The door is locked",
+            IssueExtras {
+                location: Some("5602.EG.001"),
+                location_lookup: Some(&known),
+                client: Some("NavigaTUM/1.4.2 (Android 14)"),
+                ..Default::default()
+            },
+        );
+        insta::assert_snapshot!(body, @r"
+        ## Description
+
+        > The door is locked
+
+        ## Metadata
+
+        - **Location:** [`5602.EG.001`](https://nav.tum.de/view/5602.EG.001)
+        - **Name:** Hörsaal 1 (MW 0001)
+        - **Coordinates:** 48.262445, 11.668906
+        - **Client:** NavigaTUM/1.4.2 (Android 14)
+
+        ---
+
+        footer
+        ");
+        // unknown keys are flagged instead of rejected, user agents cannot inject markup
+        let body = template.render(
+            "The door is locked",
+            IssueExtras {
+                location: Some("5602.EG.999"),
+                location_lookup: Some(&LocationLookup::Unknown),
+                client: Some("<script>alert(1)</script>\n## Heading"),
+                ..Default::default()
+            },
+        );
+        insta::assert_snapshot!(body, @r"
+        ## Description
+
+        > The door is locked
+
+        ## Metadata
+
+        - **Location:** [`5602.EG.999`](https://nav.tum.de/view/5602.EG.999) ⚠️ unknown location
+        - **Client:** &lt;script&gt;alert(1)&lt;/script&gt;## Heading
+
+        ---
+
+        footer
+        ");
+        // without opting in and if the lookup failed, only the key is known
+        let body = template.render(
+            "The door is locked",
+            IssueExtras {
+                location: Some("mi"),
+                ..Default::default()
+            },
+        );
+        assert!(
+            body.ends_with(
+                "## Metadata\n\n- **Location:** [`mi`](https://nav.tum.de/view/mi)\n\n---\n\nfooter"
+            ),
+            "{body}"
+        );
+    }
+    #[test]
+    fn adversarial_feedback_is_inert() {
+        let template = IssueTemplate::for_test();
+        let render = |description: &str| {
+            let description = GitHub::clean_feedback_data(description, 1024 * 1024);
+            template.render(&description, IssueExtras::default())
+        };
+        // mention bombs and references
+        let mentions = (0..100)
+            .map(|i| format!("@user{i}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let body = render(&format!(
+            "{mentions} @TUM-Dev/maintainers fixes #1, closes TUM-Dev/navigatum#2"
+        ));
+        assert!(!body.contains("@u"), "{body}");
+        assert!(!body.contains("@T"), "{body}");
+        assert!(!body.contains("#1"), "{body}");
+        assert!(!body.contains("#2"), "{body}");
+        assert!(body.contains("@\u{200B}user99"), "{body}");
+        // html
+        let body = render("</textarea><script>alert(1)</script><img src=x onerror=alert(1)> &lt;");
+        assert!(!body.contains('<'), "{body}");
+        assert!(body.contains("&lt;/textarea&gt;"), "{body}");
+        assert!(body.contains("&amp;lt;"), "{body}");
+        // breaking out of the quote to fake our boilerplate
+        let body = render("a\n\n---\n\n## Attachments\n\u{0}\u{7}footer");
+        assert_eq!(
+            body,
+            "## Description\n\n> a  \n>   \n> ---  \n>   \n> #\u{200B}#\u{200B} Attachments  \n> footer\n\n---\n\nfooter"
+        );
+        // very long lines are truncated before they are formatted
+        let long_line = "@".repeat(2 * 1024 * 1024);
+        let body = render(&long_line);
+        assert_eq!(body.matches('@').count(), 1024 * 1024);
+        assert!(!body.contains("@@"));
+    }
+    #[test]
+    fn edit_proposal_cannot_close_its_fence() {
+        let template = IssueTemplate::for_test();
+        let body = template.render(
+            "a",
+            IssueExtras {
+                edit_proposal: Some("name: |-\n  ```\n  @user\n  ````\n"),
+                ..Default::default()
+            },
+        );
+        assert!(
+            body.contains("`````yaml\nname: |-\n  ```\n  @user\n  ````\n`````\n"),
+            "{body}"
+        );
+        assert_eq!(fence_for(""), "```");
+        assert_eq!(fence_for("``"), "```");
+        assert_eq!(fence_for("a ``` b"), "````");
+    }
+    #[actix_web::test]
+    async fn open_issue_is_triaged() {
+        use actix_web::{ResponseError, web};
+        use std::sync::Mutex;
+
+        use crate::routes::feedback::triage::{FeedbackCategory, Triage};
+        use crate::setup::tests::mock_server;
+
+        type Received = web::Data<Mutex<Option<serde_json::Value>>>;
+        let received: Received = web::Data::new(Mutex::new(None));
+        let mock_data = received.clone();
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(mock_data.clone()).route(
+                "/repos/TUM-Dev/navigatum/issues",
+                web::post().to(
+                    |received: Received, body: web::Json<serde_json::Value>| async move {
+                        *received.lock().unwrap() = Some(body.into_inner());
+                        // not retryable => the request is sent exactly once
+                        HttpResponse::UnprocessableEntity().finish()
+                    },
+                ),
+            );
+        });
+
+        let feedback = Feedback::new(
+            FeedbackCategory::Bug,
+            "A catchy title",
+            "A clear description what happened",
+            IssueExtras {
+                location: Some("mi"),
+                ..Default::default()
+            },
+            Triage {
+                labels: vec!["webform".to_string(), "bug".to_string()],
+                assignees: vec!["octocat".to_string()],
+            },
+            IssueTemplate::for_test(),
+        )
+        .unwrap();
+        let res = GitHub::with_base_uri(&format!("http://{addr}"))
+            .open_issue(&feedback)
+            .await;
+        assert_eq!(
+            res.unwrap_err().status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let sent = received.lock().unwrap().take().expect("the issue was sent");
+        assert_eq!(sent["title"], "A catchy title");
+        assert_eq!(sent["labels"], serde_json::json!(["webform", "bug"]));
+        assert_eq!(sent["assignees"], serde_json::json!(["octocat"]));
+        assert!(sent["body"].as_str().unwrap().contains("[`mi`]"));
+    }
+    #[actix_web::test]
+    async fn open_issue_waits_only_briefly() {
+        use actix_web::{ResponseError, web};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::setup::tests::mock_server;
+
+        let requests = web::Data::new(AtomicUsize::new(0));
+        let mock_data = requests.clone();
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(mock_data.clone()).route(
+                "/repos/TUM-Dev/navigatum/issues",
+                web::post().to(|requests: web::Data<AtomicUsize>| async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    HttpResponse::TooManyRequests()
+                        .insert_header(("Retry-After", "16"))
+                        .finish()
+                }),
+            );
+        });
+
+        let payload = serde_json::json!({"title": "A catchy title"});
+        let res = GitHub::with_base_uri(&format!("http://{addr}"))
+            .open_issue_from(&payload)
+            .await;
+        assert_eq!(
+            res.unwrap_err().status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        // waiting would exceed the MAX_TOTAL_WAIT
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the issue might have been created => left to the delivery queue instead of retrying
+        let unreachable = GitHub::with_base_uri("http://127.0.0.1:1")
+            .open_issue_from(&payload)
+            .await;
+        assert_eq!(
+            unreachable.unwrap_err().status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+    #[test]
+    fn rate_limit_wait() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let headers = |retry_after: Option<&str>, remaining: Option<&str>, reset: Option<&str>| {
+            RateLimitHeaders {
+                retry_after: retry_after.map(ToString::to_string),
+                remaining: remaining.map(ToString::to_string),
+                reset: reset.map(ToString::to_string),
+            }
+        };
+        assert_eq!(headers(None, None, None).wait(now), None);
+        assert_eq!(
+            headers(Some("60"), None, None).wait(now),
+            Some(Duration::from_secs(60))
+        );
+        // retry-after takes precedence over the reset of the rate limit
+        assert_eq!(
+            headers(Some("5"), Some("0"), Some("1700000060")).wait(now),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            headers(None, Some("0"), Some("1700000060")).wait(now),
+            Some(Duration::from_secs(60))
+        );
+        // the reset is only relevant once the rate limit is exhausted
+        assert_eq!(
+            headers(None, Some("12"), Some("1700000060")).wait(now),
+            None
+        );
+        // a reset in the past means we can retry right away
+        assert_eq!(
+            headers(None, Some("0"), Some("1699999990")).wait(now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(headers(Some("soon"), None, None).wait(now), None);
+    }
+    #[test]
+    fn classify_errors() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let none = RateLimitHeaders::default();
+        let retry_after = RateLimitHeaders {
+            retry_after: Some("3".to_string()),
+            ..Default::default()
+        };
+        let retryable = |retry_after| IssueAttempt::Retryable { retry_after };
+        assert_eq!(none.classify(500, now), retryable(None));
+        assert_eq!(none.classify(502, now), retryable(None));
+        assert_eq!(none.classify(429, now), retryable(None));
+        assert_eq!(
+            retry_after.classify(403, now),
+            retryable(Some(Duration::from_secs(3)))
+        );
+        // missing permissions are not going to fix themselves
+        assert_eq!(none.classify(403, now), IssueAttempt::Failed);
+        assert_eq!(none.classify(401, now), IssueAttempt::Failed);
+        assert_eq!(none.classify(422, now), IssueAttempt::Failed);
+    }
+    #[test]
+    fn backoff_grows_exponentially() {
+        for attempt in 1..=3 {
+            let expected = INITIAL_BACKOFF * 2_u32.pow(attempt - 1);
+            let backoff = backoff_with_jitter(attempt);
+            assert!(backoff >= expected / 2, "{backoff:?} < {expected:?}/2");
+            assert!(backoff <= expected, "{backoff:?} > {expected:?}");
+        }
+        assert!(backoff_with_jitter(100) <= MAX_BACKOFF);
+    }
+    #[test]
+    fn repo_scope() {
+        assert!(has_repo_scope("repo"));
+        assert!(has_repo_scope("read:org, repo, workflow"));
+        assert!(has_repo_scope("public_repo"));
+        assert!(!has_repo_scope(""));
+        assert!(!has_repo_scope("read:org, gist"));
+        assert!(!has_repo_scope("repo:status, repo_deployment"));
+    }
+    #[test]
     fn special_cases() {
         assert_eq!(GitHub::clean_feedback_data("", 0), "");
         assert_eq!(GitHub::clean_feedback_data("a\x05bc", 9), "abc");
         assert_eq!(GitHub::clean_feedback_data("ab\x0Dc", 9), "abc");
     }
+
+    #[test]
+    fn withdrawal_redacts_and_closes() {
+        let payload = withdrawal_payload();
+        assert_eq!(payload["state"], "closed");
+        assert_eq!(payload["state_reason"], "not_planned");
+        assert_eq!(
+            payload["labels"],
+            serde_json::json!(["webform", "delete-after-processing"])
+        );
+        // both title and body are replaced, as both were written by the reporter
+        assert_eq!(payload["title"], "Withdrawn feedback");
+        assert!(payload["body"].as_str().unwrap().contains("removed"));
+        assert_eq!(payload.as_object().unwrap().len(), 5);
+    }
 }
@@ -67,7 +67,7 @@ impl Nominatim {
         let Ok(results) = nominatim_results.json::<Vec<Self>>().await else {
             anyhow::bail!("the results from nomnatim is not what we expected {url}");
         };
-        Ok(LimitedVec(results))
+        Ok(LimitedVec::from(results))
     }
 }
 
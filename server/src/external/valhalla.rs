@@ -1,33 +1,220 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use tracing::debug;
+use url::Url;
 use valhalla_client::costing::Costing;
 use valhalla_client::route::Location;
 use valhalla_client::{Units, Valhalla, route};
 
 #[derive(Clone, Debug)]
-pub struct ValhallaWrapper(Valhalla);
+pub struct ValhallaWrapper {
+    client: Valhalla,
+    /// `valhalla_client` does not support the `/locate` endpoint => we have to query it ourselves
+    http: reqwest::Client,
+    base_url: Url,
+}
 
 impl Default for ValhallaWrapper {
     fn default() -> Self {
-        let base_url = "https://nav.tum.de/valhalla".parse().unwrap();
-        ValhallaWrapper(Valhalla::new(base_url))
+        let base_url: Url = "https://nav.tum.de/valhalla".parse().unwrap();
+        ValhallaWrapper {
+            client: Valhalla::new(base_url.clone()),
+            http: reqwest::Client::new(),
+            base_url,
+        }
     }
 }
 
 impl ValhallaWrapper {
     pub async fn route(
         &self,
-        from: valhalla_client::Coordinate,
-        to: valhalla_client::Coordinate,
+        from: Location,
+        to: Location,
         costing: Costing,
         should_use_english: bool,
     ) -> anyhow::Result<route::Trip> {
         debug!(?from, ?to, "routing request");
-        let request = route::Manifest::builder()
-            .locations([Location::from(from), Location::from(to)])
-            .costing(costing)
-            .units(Units::Metric)
-            .language(if should_use_english { "en-US" } else { "de-DE" });
-        Ok(self.0.route(request).await?)
+        let request = route_manifest(from, to, costing, should_use_english);
+        Ok(self.client.route(request).await?)
+    }
+
+    /// The trip exactly as valhalla returned it, for debugging
+    ///
+    /// [`route::Trip`] only contains the fields we use => we have to query valhalla ourselves
+    pub async fn route_raw(
+        &self,
+        from: Location,
+        to: Location,
+        costing: Costing,
+        should_use_english: bool,
+    ) -> anyhow::Result<serde_json::Value> {
+        debug!(?from, ?to, "raw routing request");
+        let request = route_manifest(from, to, costing, should_use_english);
+        let url = format!("{}/route", self.base_url.as_str().trim_end_matches('/'));
+        let mut response = self
+            .http
+            .post(url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        match response.get_mut("trip") {
+            Some(trip) => Ok(trip.take()),
+            None => anyhow::bail!("valhalla did not return a trip"),
+        }
+    }
+
+    /// Where a coordinate snaps onto the routing graph for the given costing
+    pub async fn locate(
+        &self,
+        coordinate: valhalla_client::Coordinate,
+        costing: Costing,
+    ) -> anyhow::Result<LocateResult> {
+        debug!(?coordinate, "locate request");
+        let (lat, lon) = coordinate;
+        let request = LocateManifest {
+            locations: [LocateLocation { lat, lon }],
+            costing,
+            verbose: true,
+        };
+        let url = format!("{}/locate", self.base_url.as_str().trim_end_matches('/'));
+        let mut results = self
+            .http
+            .post(url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<LocateResult>>()
+            .await?;
+        results
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("valhalla did not return a result for our location"))
+    }
+}
+
+/// Error codes of valhalla meaning that the locations cannot be connected with the costing
+///
+/// `171`: no suitable edges near a location, `442`: no path could be found.
+/// See <https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#http-status-codes-and-conditions>
+const NO_ROUTE_ERROR_CODES: [isize; 2] = [171, 442];
+
+/// Whether the [`ValhallaWrapper::route`] failed because there is no route, instead of e.g. valhalla being unavailable
+pub fn is_no_route(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<valhalla_client::Error>(),
+        Some(valhalla_client::Error::RemoteError(remote)) if NO_ROUTE_ERROR_CODES.contains(&remote.error_code)
+    )
+}
+
+/// The request for a route between `from` and `to`
+///
+/// The units are set explicitly, as we convert all lengths valhalla returns from kilometers.
+fn route_manifest(
+    from: Location,
+    to: Location,
+    costing: Costing,
+    should_use_english: bool,
+) -> route::Manifest {
+    route::Manifest::builder()
+        .locations([from, to])
+        .costing(costing)
+        .units(Units::Metric)
+        .language(if should_use_english { "en-US" } else { "de-DE" })
+}
+
+#[derive(Serialize, Debug)]
+struct LocateManifest {
+    locations: [LocateLocation; 1],
+    #[serde(flatten)]
+    costing: Costing,
+    verbose: bool,
+}
+#[derive(Serialize, Debug)]
+struct LocateLocation {
+    lat: f32,
+    lon: f32,
+}
+
+/// Response of valhallas `/locate` endpoint for a single location
+#[derive(Deserialize, Debug, Default)]
+pub struct LocateResult {
+    /// `null` if there are no candidates
+    #[serde(default)]
+    pub edges: Option<Vec<LocatedEdge>>,
+    /// `null` if there are no candidates
+    #[serde(default)]
+    pub nodes: Option<Vec<LocatedNode>>,
+}
+#[derive(Deserialize, Debug)]
+pub struct LocatedEdge {
+    /// Latitude the location was snapped to on this edge
+    pub correlated_lat: f64,
+    /// Longitude the location was snapped to on this edge
+    pub correlated_lon: f64,
+    /// `left`, `right` or `neither`
+    pub side_of_street: Option<String>,
+    #[serde(default)]
+    pub edge_info: Option<EdgeInfo>,
+}
+#[derive(Deserialize, Debug)]
+pub struct EdgeInfo {
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+#[derive(Deserialize, Debug)]
+pub struct LocatedNode {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Distance, Haversine, Point};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// From the MI building to the physics department in Garching, ~600m apart
+    const FROM: (f32, f32) = (48.26244, 11.66794);
+    const TO: (f32, f32) = (48.26712, 11.67135);
+
+    fn pedestrian() -> Costing {
+        Costing::Pedestrian(Default::default())
+    }
+
+    #[test]
+    fn test_lengths_are_requested_in_kilometers() {
+        let manifest = route_manifest(Location::from(FROM), Location::from(TO), pedestrian(), true);
+        let manifest = serde_json::to_value(manifest).unwrap();
+        assert_eq!(manifest["units"], "kilometers");
+    }
+
+    /// Routes via our public valhalla instance => requires network access
+    ///
+    /// ```bash
+    /// cargo test --package navigatum-server test_short_route_has_a_metric_length -- --include-ignored
+    /// ```
+    #[ignore]
+    #[actix_web::test]
+    async fn test_short_route_has_a_metric_length() {
+        let trip = ValhallaWrapper::default()
+            .route(Location::from(FROM), Location::from(TO), pedestrian(), true)
+            .await
+            .unwrap();
+        let straight_line_km = Haversine::distance(
+            Point::new(f64::from(FROM.1), f64::from(FROM.0)),
+            Point::new(f64::from(TO.1), f64::from(TO.0)),
+        ) / 1000.0;
+        // walking is never shorter than the straight line, but not absurdly longer either
+        // in miles, the length would be ~40% shorter than the straight line
+        let detour = trip.summary.length / straight_line_km;
+        assert!(
+            (0.95..=2.5).contains(&detour),
+            "the route is {length}km long, for {straight_line_km}km as the crow flies",
+            length = trip.summary.length
+        );
     }
 }
@@ -0,0 +1,105 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+
+/// Answers `HEAD` requests via the `GET` handler of the same path
+///
+/// Our handlers are only registered for `GET`, which would make `HEAD` requests from CDNs/proxies fail.
+/// Dropping the body is not our job: the http codec remembers the original method and only sends the headers,
+/// so `Content-Length`, `ETag` and `Last-Modified` are the same as for a `GET` request.
+/// Has to wrap the [`actix_middleware_etag::Etag`] middleware, so that it sees a `GET` request as well.
+pub async fn head_as_get(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.method() == Method::HEAD {
+        req.head_mut().method = Method::GET;
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::rt::net::TcpStream;
+    use actix_web::{App, HttpResponse, get, post, test, web};
+    use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::setup::tests::mock_server;
+
+    #[get("/api/example")]
+    async fn example_get() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header(("Last-Modified", "Sat, 01 Jan 2000 00:00:00 GMT"))
+            .body("content")
+    }
+
+    #[post("/api/example_post")]
+    async fn example_post() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn head_is_answered_like_get() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(head_as_get))
+                .service(example_get)
+                .service(example_post),
+        )
+        .await;
+        let get = test::TestRequest::get().uri("/api/example").to_request();
+        let get = test::call_service(&app, get).await;
+        let head = test::TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/api/example")
+            .to_request();
+        let head = test::call_service(&app, head).await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(
+            head.headers().get("Last-Modified"),
+            get.headers().get("Last-Modified")
+        );
+
+        // only GET handlers are reachable this way
+        let head = test::TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/api/example_post")
+            .to_request();
+        let head = test::call_service(&app, head).await;
+        assert!(head.status().is_client_error());
+    }
+
+    #[actix_web::test]
+    async fn head_is_sent_without_body() {
+        let addr = mock_server(|cfg| {
+            cfg.service(
+                web::scope("")
+                    .wrap(from_fn(head_as_get))
+                    .service(example_get),
+            );
+        });
+        // a client would not read the body of a HEAD response => only the raw response shows it
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"HEAD /api/example HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+        assert!(
+            head.to_lowercase()
+                .contains("last-modified: sat, 01 jan 2000 00:00:00 gmt"),
+            "{head}"
+        );
+        assert_eq!(body, "");
+    }
+}
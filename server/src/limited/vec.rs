@@ -1,20 +1,25 @@
 use std::fmt;
 use std::vec::IntoIter;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::warn;
 
 use crate::limited::OrMore;
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
-pub struct LimitedVec<T>(pub Vec<T>);
+/// A [`Vec`] of at most `CAP` items, of which only the first few are shown when debug-printed
+///
+/// [`LimitedVec::push`] rejects items beyond the capacity, collecting or converting truncates.
+/// Without a `CAP`, only the debug output is limited.
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+pub struct LimitedVec<T, const CAP: usize = { usize::MAX }>(Vec<T>);
 
-impl<T> AsRef<[T]> for LimitedVec<T> {
+impl<T, const CAP: usize> AsRef<[T]> for LimitedVec<T, CAP> {
     fn as_ref(&self) -> &[T] {
         &self.0
     }
 }
 
-impl<T> IntoIterator for LimitedVec<T> {
+impl<T, const CAP: usize> IntoIterator for LimitedVec<T, CAP> {
     type Item = T;
     type IntoIter = IntoIter<T>;
 
@@ -23,7 +28,35 @@ impl<T> IntoIterator for LimitedVec<T> {
     }
 }
 
-impl<T> LimitedVec<T> {
+impl<T, const CAP: usize> Default for LimitedVec<T, CAP> {
+    fn default() -> Self {
+        LimitedVec(Vec::new())
+    }
+}
+
+impl<T, const CAP: usize> LimitedVec<T, CAP> {
+    pub const CAPACITY: usize = CAP;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Collects at most `CAP` items and returns how many were dropped
+    pub fn truncating<I: IntoIterator<Item = T>>(iter: I) -> (Self, usize) {
+        let mut iter = iter.into_iter();
+        let values = iter.by_ref().take(CAP).collect();
+        (LimitedVec(values), iter.count())
+    }
+    /// Appends the value, handing it back if the capacity is exhausted
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        self.0.push(value);
+        Ok(())
+    }
+    pub fn is_full(&self) -> bool {
+        self.0.len() >= CAP
+    }
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
@@ -33,16 +66,53 @@ impl<T> LimitedVec<T> {
     pub fn pop(&mut self) -> Option<T> {
         self.0.pop()
     }
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.0.iter()
+    }
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
 }
 
-impl<T> From<Vec<T>> for LimitedVec<T> {
-    fn from(value: Vec<T>) -> Self {
+impl<T, const CAP: usize> From<Vec<T>> for LimitedVec<T, CAP> {
+    fn from(mut value: Vec<T>) -> Self {
+        if value.len() > CAP {
+            warn_dropped::<T>(value.len() - CAP, CAP);
+            value.truncate(CAP);
+        }
         LimitedVec(value)
     }
 }
 
+/// Rejects more than `CAP` items instead of silently dropping some of them
+impl<'de, T: Deserialize<'de>, const CAP: usize> Deserialize<'de> for LimitedVec<T, CAP> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        if values.len() > CAP {
+            let expected = format!("at most {CAP} items");
+            return Err(serde::de::Error::invalid_length(
+                values.len(),
+                &expected.as_str(),
+            ));
+        }
+        Ok(LimitedVec(values))
+    }
+}
+
+fn warn_dropped<T>(dropped: usize, capacity: usize) {
+    warn!(
+        dropped,
+        capacity,
+        item = std::any::type_name::<T>(),
+        "dropped the items exceeding the capacity"
+    );
+}
+
 const LIMIT: usize = 3;
-impl<T: fmt::Debug> fmt::Debug for LimitedVec<T> {
+impl<T: fmt::Debug, const CAP: usize> fmt::Debug for LimitedVec<T, CAP> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.0.len() <= LIMIT {
             f.debug_list().entries(self.0.iter().take(LIMIT)).finish()
@@ -59,15 +129,13 @@ impl<T: fmt::Debug> fmt::Debug for LimitedVec<T> {
         }
     }
 }
-impl<T> FromIterator<T> for LimitedVec<T> {
+impl<T, const CAP: usize> FromIterator<T> for LimitedVec<T, CAP> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut c = Vec::new();
-
-        for i in iter {
-            c.push(i);
+        let (values, dropped) = Self::truncating(iter);
+        if dropped > 0 {
+            warn_dropped::<T>(dropped, CAP);
         }
-
-        LimitedVec(c)
+        values
     }
 }
 
@@ -79,15 +147,59 @@ mod test {
     fn test_limited_output() {
         let w: LimitedVec<u32> = LimitedVec(vec![]);
         assert_eq!(format!("{w:?}"), "[]");
-        let w = LimitedVec(vec![1]);
+        let w: LimitedVec<u32> = LimitedVec(vec![1]);
         assert_eq!(format!("{w:?}"), "[1]");
-        let w = LimitedVec(vec![1, 2]);
+        let w: LimitedVec<u32> = LimitedVec(vec![1, 2]);
         assert_eq!(format!("{w:?}"), "[1, 2]");
-        let w = LimitedVec(vec![1, 2, 3]);
+        let w: LimitedVec<u32> = LimitedVec(vec![1, 2, 3]);
         assert_eq!(format!("{w:?}"), "[1, 2, 3]");
-        let w = LimitedVec(vec![1, 2, 3, 4]);
+        let w: LimitedVec<u32> = LimitedVec(vec![1, 2, 3, 4]);
         assert_eq!(format!("{w:?}"), "[1, 2, 3, ...]");
-        let w = LimitedVec(vec![1, 2, 3, 4, 5]);
+        let w: LimitedVec<u32> = LimitedVec(vec![1, 2, 3, 4, 5]);
         assert_eq!(format!("{w:?}"), "[1, 2, 3, ...]");
     }
+
+    #[test]
+    fn test_push_rejects_overflow() {
+        let mut w: LimitedVec<u32, 2> = LimitedVec::new();
+        assert_eq!(w.push(1), Ok(()));
+        assert!(!w.is_full());
+        assert_eq!(w.push(2), Ok(()));
+        assert!(w.is_full());
+        assert_eq!(w.push(3), Err(3));
+        assert_eq!(w.0, vec![1, 2]);
+        // the default capacity is unbounded
+        let mut w: LimitedVec<u32> = LimitedVec::new();
+        for i in 0..100 {
+            assert_eq!(w.push(i), Ok(()));
+        }
+        assert_eq!(w.len(), 100);
+    }
+
+    #[test]
+    fn test_collect_truncates() {
+        let (w, dropped) = LimitedVec::<u32, 3>::truncating(0..10);
+        assert_eq!(w.0, vec![0, 1, 2]);
+        assert_eq!(dropped, 7);
+        let (w, dropped) = LimitedVec::<u32, 3>::truncating(0..2);
+        assert_eq!(w.0, vec![0, 1]);
+        assert_eq!(dropped, 0);
+        let w = (0..10).collect::<LimitedVec<u32, 3>>();
+        assert_eq!(w.0, vec![0, 1, 2]);
+        let w = LimitedVec::<u32, 3>::from(vec![0, 1, 2, 3]);
+        assert_eq!(w.0, vec![0, 1, 2]);
+        let w = (0..10).collect::<LimitedVec<u32>>();
+        assert_eq!(w.len(), 10);
+        let (w, dropped) = LimitedVec::<u32, 0>::truncating(0..2);
+        assert!(w.is_empty());
+        assert_eq!(dropped, 2);
+    }
+
+    #[test]
+    fn test_deserialize_rejects_overflow() {
+        let w = serde_json::from_str::<LimitedVec<u32, 2>>("[1, 2]").unwrap();
+        assert_eq!(w.0, vec![1, 2]);
+        let err = serde_json::from_str::<LimitedVec<u32, 2>>("[1, 2, 3]").unwrap_err();
+        assert!(err.to_string().contains("at most 2 items"), "{err}");
+    }
 }
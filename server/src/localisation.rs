@@ -28,6 +28,13 @@ pub struct LangQueryArgs {
 }
 
 impl LangQueryArgs {
+    pub const GERMAN: Self = Self {
+        lang: LanguageOptions::De,
+    };
+    pub const ENGLISH: Self = Self {
+        lang: LanguageOptions::En,
+    };
+
     pub fn should_use_english(self) -> bool {
         self.lang == LanguageOptions::En
     }
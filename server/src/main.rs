@@ -1,13 +1,18 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
 use actix_governor::{GlobalKeyExtractor, GovernorConfigBuilder};
 use actix_middleware_etag::Etag;
-use actix_web::{App, HttpResponse, HttpServer, Responder, get, middleware, web};
+use actix_web::{App, HttpResponse, HttpServer, Responder, get, guard, middleware, web};
 use actix_web_prom::{PrometheusMetrics, PrometheusMetricsBuilder};
 use meilisearch_sdk::client::Client;
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use sentry::SessionMode;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::prelude::*;
@@ -17,8 +22,11 @@ use tracing::{debug_span, error, info};
 use tracing_actix_web::TracingLogger;
 
 mod docs;
+mod error;
+mod head;
 mod limited;
 mod localisation;
+mod request_id;
 mod search_executor;
 mod setup;
 use utoipa_actix_web::{AppExt, scope};
@@ -29,7 +37,8 @@ pub mod refresh;
 pub mod routes;
 use routes::*;
 
-const MAX_JSON_PAYLOAD: usize = 1024 * 1024; // 1 MB
+/// Used if `MAX_JSON_PAYLOAD_BYTES` is not set
+const DEFAULT_MAX_JSON_PAYLOAD: usize = 1024 * 1024; // 1 MB
 
 const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
 
@@ -40,6 +49,10 @@ pub struct AppData {
     /// necessary, as otherwise we could return empty results during initialisation
     meilisearch_initialised: Arc<RwLock<()>>,
     valhalla: external::valhalla::ValhallaWrapper,
+    /// locations of calendars, which are looked up for nearly every calendar request
+    calendar_locations: db::calendar::CalendarLocationCache,
+    /// coordinates of our keys, which are looked up for every routing request
+    coordinates: maps::coordinates::CoordinateCache,
 }
 
 impl AppData {
@@ -58,6 +71,8 @@ impl From<PgPool> for AppData {
             pool,
             meilisearch_initialised: Arc::new(Default::default()),
             valhalla: external::valhalla::ValhallaWrapper::default(),
+            calendar_locations: db::calendar::CalendarLocationCache::default(),
+            coordinates: maps::coordinates::CoordinateCache::from_env(),
         }
     }
 }
@@ -112,7 +127,10 @@ fn connection_string() -> String {
     format!("postgres://{username}:{password}@{url}/{db}")
 }
 
-pub fn setup_logging() {
+/// Sets up logging and, if `OTEL_EXPORTER_OTLP_ENDPOINT` is configured, exporting traces via OTLP
+///
+/// The returned provider has to be shut down before exiting to flush the remaining spans.
+pub fn setup_logging() -> Option<SdkTracerProvider> {
     use tracing_subscriber::filter::EnvFilter;
     use tracing_subscriber::fmt::Layer;
     use tracing_subscriber::prelude::*;
@@ -134,16 +152,55 @@ pub fn setup_logging() {
         .init()
         .expect("the global logger to only be set once");
 
+    let tracer_provider = setup_otlp_tracer_provider();
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("navigatum-server"))
+    });
+
     let registry = tracing_subscriber::registry()
         .with(filter)
         .with(sentry::integrations::tracing::layer())
+        .with(otel_layer)
         .with(cfg!(not(any(debug_assertions, test))).then(|| Layer::default().json()))
         .with(cfg!(any(debug_assertions, test)).then(|| Layer::default().pretty()));
     tracing::subscriber::set_global_default(registry).unwrap();
+    tracer_provider
+}
+
+/// Configures span export via OTLP/HTTP
+///
+/// Export is opt-in via `OTEL_EXPORTER_OTLP_ENDPOINT`.
+/// The other `OTEL_EXPORTER_OTLP_*` variables (headers, timeouts, ...) are respected by the exporter.
+fn setup_otlp_tracer_provider() -> Option<SdkTracerProvider> {
+    // logging is not yet set up => reporting errors via eprintln
+    std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("could not set up the OTLP exporter, traces will not be exported: {e:?}");
+            return None;
+        }
+    };
+    let resource = Resource::builder()
+        .with_service_name("navigatum-server")
+        .with_attribute(KeyValue::new(
+            "service.version",
+            option_env!("GIT_COMMIT_SHA").unwrap_or("development"),
+        ))
+        .build();
+    Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build(),
+    )
 }
 
 fn main() -> anyhow::Result<()> {
-    setup_logging();
+    let tracer_provider = setup_logging();
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("no provider was set as default beforehand");
@@ -162,14 +219,29 @@ fn main() -> anyhow::Result<()> {
         },
     ));
 
-    actix_web::rt::System::new().block_on(async { run().await })?;
-    Ok(())
+    let res = actix_web::rt::System::new().block_on(async { run().await });
+    if let Some(Err(e)) = tracer_provider.map(|provider| provider.shutdown()) {
+        error!(error = ?e, "could not flush the remaining spans");
+    }
+    res
 }
-#[tracing::instrument(skip(pool, meilisearch_initialised, initialisation_started))]
+#[tracing::instrument(skip(
+    pool,
+    meilisearch_initialised,
+    initialisation_started,
+    scrape_metrics,
+    scrape_pacing,
+    calendar_locations,
+    coordinates
+))]
 async fn run_maintenance_work(
     pool: Pool<Postgres>,
     meilisearch_initialised: Arc<RwLock<()>>,
     initialisation_started: Arc<Barrier>,
+    scrape_metrics: refresh::metrics::ScrapeMetrics,
+    scrape_pacing: refresh::pacing::ScrapePacing,
+    calendar_locations: db::calendar::CalendarLocationCache,
+    coordinates: maps::coordinates::CoordinateCache,
 ) {
     if std::env::var("SKIP_MS_SETUP") != Ok("true".to_string()) {
         let _ = debug_span!("updating meilisearch data").enter();
@@ -187,8 +259,15 @@ async fn run_maintenance_work(
     if std::env::var("SKIP_DB_SETUP") != Ok("true".to_string()) {
         let _ = debug_span!("updating postgis data").enter();
         setup::database::setup(&pool).await.unwrap();
-        setup::database::load_data(&pool).await.unwrap();
-        setup::transportation::setup(&pool).await.unwrap();
+        let dry_run = std::env::var("DRY_RUN") == Ok("true".to_string());
+        setup::database::load_data(&pool, dry_run).await.unwrap();
+        calendar_locations.invalidate_all();
+        coordinates.invalidate_all();
+        if dry_run {
+            info!("skipping the transportation setup as DRY_RUN=true");
+        } else {
+            setup::transportation::setup(&pool).await.unwrap();
+        }
     } else {
         info!("skipping the database setup as SKIP_DB_SETUP=true");
     }
@@ -196,13 +275,52 @@ async fn run_maintenance_work(
     let map_pool = pool.clone();
     set.spawn(async move { refresh::indoor_maps::all_entries(&map_pool).await });
     let cal_pool = pool.clone();
-    set.spawn(async move { refresh::calendar::all_entries(&cal_pool).await });
+    set.spawn(async move {
+        refresh::calendar::all_entries(&cal_pool, scrape_metrics, scrape_pacing, calendar_locations)
+            .await
+    });
     set.join_all().await;
 }
 
+/// Maximum size of JSON request bodies, configurable via `MAX_JSON_PAYLOAD_BYTES`
+fn max_json_payload() -> usize {
+    let Ok(raw) = std::env::var("MAX_JSON_PAYLOAD_BYTES") else {
+        return DEFAULT_MAX_JSON_PAYLOAD;
+    };
+    match raw.trim().parse() {
+        Ok(limit) => limit,
+        Err(e) => {
+            error!(
+                error = ?e,
+                %raw,
+                "MAX_JSON_PAYLOAD_BYTES is not a valid number of bytes, using the default"
+            );
+            DEFAULT_MAX_JSON_PAYLOAD
+        }
+    }
+}
+
 /// we split main and run because otherwise sentry could not be properly instrumented
 async fn run() -> anyhow::Result<()> {
     let data = AppData::new().await;
+    let prometheus = build_metrics();
+    let route_metrics = web::Data::new(
+        maps::metrics::RouteMetrics::register(&prometheus.registry)
+            .expect("route metrics are only registered once"),
+    );
+    let feedback_metrics = web::Data::new(
+        feedback::metrics::FeedbackMetrics::register(&prometheus.registry)
+            .expect("feedback metrics are only registered once"),
+    );
+    let scrape_metrics = refresh::metrics::ScrapeMetrics::register(&prometheus.registry)
+        .expect("scrape metrics are only registered once");
+    let scrape_pacing = refresh::pacing::ScrapePacing::from_env();
+    data.calendar_locations
+        .register(&prometheus.registry)
+        .expect("calendar location metrics are only registered once");
+    data.coordinates
+        .register(&prometheus.registry)
+        .expect("coordinate cache metrics are only registered once");
 
     // without this barrier an external client might race the RWLock for meilisearch_initialised and gain the read lock before it is allowed
     let initialisation_started = Arc::new(Barrier::new(2));
@@ -210,55 +328,183 @@ async fn run() -> anyhow::Result<()> {
         data.pool.clone(),
         data.meilisearch_initialised.clone(),
         initialisation_started.clone(),
+        scrape_metrics.clone(),
+        scrape_pacing.clone(),
+        data.calendar_locations.clone(),
+        data.coordinates.clone(),
     ));
 
-    let prometheus = build_metrics();
     let shutdown_pool_clone = data.pool.clone();
     initialisation_started.wait().await;
+    // each bulk request calculates many routes
+    let bulk_routing_ratelimit = GovernorConfigBuilder::default()
+        .key_extractor(feedback::ratelimit::ClientIpKeyExtractor::default())
+        .seconds_per_request(60)
+        .burst_size(5)
+        .use_headers()
+        .finish()
+        .expect("Invalid configuration of the governor");
     // feedback specific initialisation
     let feedback_ratelimit = GovernorConfigBuilder::default()
+        .key_extractor(feedback::ratelimit::ClientIpKeyExtractor::default())
+        .seconds_per_request(SECONDS_PER_DAY / 50) // replenish new token every .. seconds
+        .burst_size(10)
+        .use_headers() // tell clients their remaining quota
+        .finish()
+        .expect("Invalid configuration of the governor");
+    // separate from the limit of tokens, as challenges allow posting without a token
+    let feedback_submission_ratelimit = GovernorConfigBuilder::default()
+        .key_extractor(feedback::ratelimit::ClientIpKeyExtractor::default())
+        .seconds_per_request(SECONDS_PER_DAY / 50)
+        .burst_size(10)
+        .use_headers()
+        .finish()
+        .expect("Invalid configuration of the governor");
+    let feedback_global_limit = feedback::ratelimit::global_requests_per_day();
+    let feedback_global_ratelimit = GovernorConfigBuilder::default()
         .key_extractor(GlobalKeyExtractor)
-        .seconds_per_request(SECONDS_PER_DAY / 300) // replenish new token every .. seconds
+        .period(Duration::from_secs(SECONDS_PER_DAY) / feedback_global_limit.unwrap_or(1))
         .burst_size(50)
+        .use_headers()
         .finish()
         .expect("Invalid configuration of the governor");
-    let recorded_tokens = web::Data::new(feedback::tokens::RecordedTokens::default());
+    // initialised on the main runtime, as the clients of the backends may spawn background tasks
+    std::sync::LazyLock::force(&feedback::sink::FEEDBACK_BACKEND);
+    tokio::spawn(feedback::tokens::check_github_token());
+    let recorded_tokens = web::Data::new(feedback::tokens::RecordedTokens::persistent(
+        data.pool.clone(),
+    ));
+    recorded_tokens
+        .register(&prometheus.registry)
+        .expect("token metrics are only registered once");
+    let recorded_issues = web::Data::new(feedback::dedupe::RecordedIssues::default());
+    let delivery_queue = web::Data::new(feedback::queue::DeliveryQueue::new(data.pool.clone()));
+    delivery_queue
+        .register(&prometheus.registry)
+        .expect("delivery queue metrics are only registered once");
+    if let Some(github) = feedback::sink::FEEDBACK_BACKEND.github() {
+        let delivery_queue = delivery_queue.clone();
+        let recorded_issues = recorded_issues.clone();
+        tokio::spawn(async move {
+            delivery_queue
+                .retry_in_background(github, &recorded_issues)
+                .await;
+        });
+        let contact_pool = data.pool.clone();
+        tokio::spawn(
+            async move { feedback::contact::purge_in_background(contact_pool, github).await },
+        );
+    }
+    let recorded_attachments =
+        web::Data::new(feedback::attachments::RecordedAttachments::default());
+    let scrape_metrics_data = web::Data::new(scrape_metrics.clone());
+    let calendar_refresh = web::Data::new(refresh::calendar::OnDemandRefresh::new(
+        scrape_metrics,
+        scrape_pacing,
+        data.calendar_locations.clone(),
+    ));
 
-    info!("running the server");
+    let max_json_payload = max_json_payload();
+    info!(max_json_payload, "running the server");
     HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_header()
-            .allowed_methods(vec!["GET", "POST"])
+            .expose_headers([
+                "X-Request-Id",
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "Retry-After",
+            ])
+            .allowed_methods(vec!["GET", "HEAD", "POST", "DELETE"])
             .max_age(3600)
             .send_wildcard();
 
         docs::add_openapi_docs(
             App::new()
                 .wrap(Etag)
+                .wrap(middleware::from_fn(head::head_as_get))
                 .wrap(prometheus.clone())
                 .wrap(cors)
-                .wrap(TracingLogger::default())
+                .wrap(TracingLogger::<request_id::RequestIdRootSpan>::new())
+                .wrap(middleware::from_fn(request_id::assign_request_id))
                 .wrap(middleware::Compress::default())
                 .wrap(sentry_actix::Sentry::new())
-                .app_data(web::JsonConfig::default().limit(MAX_JSON_PAYLOAD))
+                .app_data(error::json_config(max_json_payload))
+                .app_data(error::query_config())
                 .app_data(web::Data::new(data.clone()))
                 .into_utoipa_app()
                 .app_data(recorded_tokens.clone())
+                .app_data(recorded_issues.clone())
+                .app_data(delivery_queue.clone())
+                .app_data(recorded_attachments.clone())
+                .app_data(route_metrics.clone())
+                .app_data(feedback_metrics.clone())
+                .app_data(calendar_refresh.clone())
+                .app_data(scrape_metrics_data.clone())
                 .service(health_status_handler)
                 .service(calendar::calendar_handler)
+                // has to be registered before the /api/calendar/{id}/... services, which would otherwise match course/status etc.
+                .service(calendar::course::course_handler)
+                .service(calendar::refresh::refresh_handler)
+                .service(calendar::refresh::get_refresh_handler)
+                .service(calendar::sync_status::sync_status_handler)
+                .service(calendar::status::status_handler)
+                .service(calendar::url::url_handler)
+                .service(calendar::week::week_handler)
+                .service(calendar::conflicts::check_conflicts_handler)
+                .service(calendar::free_now::free_now_handler)
+                .service(calendar::search::search_handler)
+                .service(calendar::feed::create_feed_token_handler)
+                .service(calendar::feed::list_feed_tokens_handler)
+                .service(calendar::feed::revoke_feed_token_handler)
+                .service(calendar::feed::ics_handler)
+                .service(admin::reimport_handler)
+                .service(feedback::contact::get_contact)
+                .service(feedback::blocklist::add_blocklist_entry_handler)
+                .service(feedback::blocklist::list_blocklist_handler)
+                .service(feedback::blocklist::remove_blocklist_entry_handler)
                 .service(maps::indoor::list_indoor_maps)
                 .service(maps::indoor::get_indoor_map)
                 .service(maps::route::route_handler)
+                .service(maps::costing_options::costing_options_handler)
+                .service(maps::route::route_debug_handler)
+                .service(
+                    scope("/api/maps/route")
+                        // GET requests are the regular routing requests
+                        .guard(guard::Post())
+                        .wrap(actix_governor::Governor::new(&bulk_routing_ratelimit))
+                        .wrap(middleware::from_fn(feedback::ratelimit::ratelimit_headers))
+                        .service(maps::route::bulk_route_handler),
+                )
+                .service(maps::route::compare_routes_handler)
+                .service(maps::locate::locate_handler)
                 .service(search::search_handler)
                 .service(locations::details::get_handler)
                 .service(locations::nearby::nearby_handler)
                 .service(locations::preview::maps_handler)
-                .service(feedback::post_feedback::send_feedback)
+                .service(
+                    scope("/api/feedback/feedback")
+                        .wrap(actix_governor::Governor::new(
+                            &feedback_submission_ratelimit,
+                        ))
+                        .wrap(middleware::from_fn(feedback::ratelimit::ratelimit_headers))
+                        .service(feedback::post_feedback::send_feedback),
+                )
+                .service(feedback::post_feedback::preview_feedback)
+                .service(feedback::attachments::attach_image)
                 .service(feedback::proposed_edits::propose_edits)
+                .service(feedback::withdraw::withdraw_feedback)
+                .service(feedback::challenge::get_challenge)
                 .service(
                     scope("/api/feedback/get_token")
+                        .wrap(middleware::Condition::new(
+                            feedback_global_limit.is_some(),
+                            actix_governor::Governor::new(&feedback_global_ratelimit),
+                        ))
+                        // per client first, as otherwise rejected requests would count towards the global limit
                         .wrap(actix_governor::Governor::new(&feedback_ratelimit))
+                        .wrap(middleware::from_fn(feedback::ratelimit::ratelimit_headers))
                         .service(feedback::tokens::get_token),
                 )
                 .service(openapi_doc),
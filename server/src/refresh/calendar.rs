@@ -1,17 +1,78 @@
-use crate::db::calendar::Event;
-use crate::external::connectum::APIRequestor;
+use crate::db::calendar::{CalendarLocationCache, Event, ScrapeCycle};
+use crate::external::connectum::{APIRequestor, ConnectumEvent, UnderMaintenance};
 use crate::limited::vec::LimitedVec;
+use crate::refresh::metrics::ScrapeMetrics;
+use crate::refresh::pacing::{ScrapePacing, parse_env};
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use futures::stream::FuturesUnordered;
 use serde::{Deserialize, Serialize, Serializer};
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt::{Debug, Formatter};
-use std::time::Duration;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use tokio::time::sleep;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
-const NUMBER_OF_CONCURRENT_SCRAPES: usize = 3;
+/// How often downloading a calendar is attempted before giving up, if `CALENDAR_SCRAPE_MAX_ATTEMPTS` is not set
+const DEFAULT_MAX_SCRAPE_ATTEMPTS: u32 = 3;
+static MAX_SCRAPE_ATTEMPTS: LazyLock<u32> = LazyLock::new(|| {
+    let Ok(raw) = env::var("CALENDAR_SCRAPE_MAX_ATTEMPTS") else {
+        return DEFAULT_MAX_SCRAPE_ATTEMPTS;
+    };
+    match raw.trim().parse::<u32>() {
+        Ok(attempts) if attempts >= 1 => attempts,
+        _ => {
+            warn!(
+                %raw,
+                default = DEFAULT_MAX_SCRAPE_ATTEMPTS,
+                "CALENDAR_SCRAPE_MAX_ATTEMPTS is not a positive integer, using the default"
+            );
+            DEFAULT_MAX_SCRAPE_ATTEMPTS
+        }
+    }
+});
+/// How long ago a room has to have been checked to be scraped again, configurable via `CALENDAR_SCRAPE_INTERVAL_MINS`
+pub(crate) static SCRAPE_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    let minutes = parse_env("CALENDAR_SCRAPE_INTERVAL_MINS", 60_u64).max(1);
+    Duration::from_secs(minutes * 60)
+});
+/// Backoff before the first retry, doubled for each further retry
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// If this fraction of a batch fails, TUMonline is likely down (e.g. for maintenance) => we pause scraping
+const COOL_DOWN_FAILURE_RATIO: f64 = 0.5;
+/// In smaller batches, single failures would skew the failure ratio too much
+const COOL_DOWN_MIN_BATCH_SIZE: usize = 5;
+const COOL_DOWN: Duration = Duration::from_secs(10 * 60);
+/// How long scraping is paused once TUMonline is under maintenance, if `CALENDAR_MAINTENANCE_PAUSE_SECS` is not set
+const DEFAULT_MAINTENANCE_PAUSE: Duration = Duration::from_secs(15 * 60);
+static MAINTENANCE_PAUSE: LazyLock<Duration> = LazyLock::new(|| {
+    let Ok(raw) = env::var("CALENDAR_MAINTENANCE_PAUSE_SECS") else {
+        return DEFAULT_MAINTENANCE_PAUSE;
+    };
+    match raw.trim().parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            warn!(
+                %raw,
+                default_secs = DEFAULT_MAINTENANCE_PAUSE.as_secs(),
+                "CALENDAR_MAINTENANCE_PAUSE_SECS is not a number of seconds, using the default"
+            );
+            DEFAULT_MAINTENANCE_PAUSE
+        }
+    }
+});
+/// On-demand scrapes are in addition to the regular ones => we keep them to a minimum to not hammer TUMonline
+const NUMBER_OF_CONCURRENT_ON_DEMAND_SCRAPES: usize = 1;
+/// How many rooms may wait for an on-demand scrape at once
+const MAX_QUEUED_ON_DEMAND_SCRAPES: usize = 20;
+/// How many finished on-demand scrapes are remembered for polling
+const MAX_REMEMBERED_ON_DEMAND_SCRAPES: usize = 500;
 
 #[derive(Serialize, Deserialize, sqlx::Type)]
 struct LocationKey {
@@ -26,13 +87,14 @@ impl Debug for LocationKey {
 
 #[tracing::instrument(skip(pool))]
 async fn entries_which_need_scraping(pool: &PgPool) -> anyhow::Result<LimitedVec<LocationKey>> {
+    let interval_secs = SCRAPE_INTERVAL.as_secs_f64();
     let res = sqlx::query_as!(LocationKey,r#"
 WITH ENTRIES_TO_SCRAPE AS (SELECT KEY,
-                                  CASE WHEN last_calendar_scrape_at IS NULL THEN 100 ELSE 1 END          AS boost_if_never_scraped,
+                                  CASE WHEN last_calendar_check_at IS NULL THEN 100 ELSE 1 END           AS boost_if_never_scraped,
                                   CAST(data -> 'ranking_factors' ->> 'rank_combined' AS INTEGER)         AS rank_combined,
-                                  (LAST_CALENDAR_SCRAPE_AT < DATE_SUBTRACT(NOW(), '60 minutes'::INTERVAL, 'Europe/Berlin')
-                                      OR LAST_CALENDAR_SCRAPE_AT IS NULL)                                AS would_need_scraping,
-                                  EXTRACT(EPOCH FROM (NOW() - LAST_CALENDAR_SCRAPE_AT))                  AS seconds_ago,
+                                  (LAST_CALENDAR_CHECK_AT < DATE_SUBTRACT(NOW(), MAKE_INTERVAL(secs => $1), 'Europe/Berlin')
+                                      OR LAST_CALENDAR_CHECK_AT IS NULL)                                 AS would_need_scraping,
+                                  EXTRACT(EPOCH FROM (NOW() - LAST_CALENDAR_CHECK_AT))                   AS seconds_ago,
                                   CALENDAR_URL IS NOT NULL                                               AS can_be_scraped
                            FROM de)
 
@@ -41,14 +103,61 @@ FROM entries_to_scrape
 WHERE would_need_scraping AND can_be_scraped
 -- boost_if_never_scraped: has this ever been scraped? => give a good bonus
 -- rank_combined: "how important is this room?" (range 1..1k)
--- seconds_ago: "how long since we last scraped it?" (range null,30*60/3=600..)
+-- seconds_ago: "how long since we last checked it?" (range null,30*60/3=600..)
 ORDER BY boost_if_never_scraped * rank_combined * coalesce(seconds_ago/6,1) DESC
-LIMIT 30"#)
+LIMIT 30"#, interval_secs)
         .fetch_all(pool)
         .await?;
     Ok(LimitedVec::from(res))
 }
 
+/// How many rooms still need to be scraped in the current cycle
+#[tracing::instrument(skip(pool))]
+async fn rooms_remaining(pool: &PgPool) -> anyhow::Result<u64> {
+    let cnt = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "cnt!"
+        FROM de
+        WHERE calendar_url IS NOT NULL
+          AND (last_calendar_check_at IS NULL OR last_calendar_check_at < NOW() - MAKE_INTERVAL(secs => $1))"#,
+        SCRAPE_INTERVAL.as_secs_f64()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(u64::try_from(cnt)?)
+}
+
+/// How the persisted cycle has to change given the rooms which still need to be scraped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CycleChange {
+    Start,
+    Complete,
+}
+fn cycle_change(cycle: &ScrapeCycle, remaining: u64) -> Option<CycleChange> {
+    match (cycle.started_at, remaining) {
+        (None, 1..) => Some(CycleChange::Start),
+        (Some(_), 0) => Some(CycleChange::Complete),
+        _ => None,
+    }
+}
+
+/// Persists when a cycle through all rooms starts and ends
+///
+/// A cycle started before a restart is continued afterward instead of starting over.
+#[tracing::instrument(skip(pool, metrics))]
+async fn track_cycle(pool: &PgPool, metrics: &ScrapeMetrics, remaining: u64) -> anyhow::Result<()> {
+    let cycle = ScrapeCycle::get(pool).await?;
+    let cycle = match cycle_change(&cycle, remaining) {
+        Some(CycleChange::Start) => ScrapeCycle::start(pool, &Utc::now()).await?,
+        Some(CycleChange::Complete) => {
+            info!(started_at = ?cycle.started_at, "all room-calendars are up to date");
+            ScrapeCycle::complete(pool, &Utc::now()).await?
+        }
+        None => cycle,
+    };
+    metrics.record_cycle(&cycle);
+    Ok(())
+}
+
 fn can_never_succeed() -> bool {
     let client_id_invalid = match env::var("CONNECTUM_OAUTH_CLIENT_ID") {
         Err(_) => true,
@@ -73,12 +182,31 @@ fn can_never_succeed() -> bool {
     false
 }
 
-#[tracing::instrument(skip(pool))]
-pub async fn all_entries(pool: &PgPool) {
-    if can_never_succeed() {
+#[tracing::instrument(skip(pool, metrics, locations))]
+pub async fn all_entries(
+    pool: &PgPool,
+    metrics: ScrapeMetrics,
+    pacing: ScrapePacing,
+    locations: CalendarLocationCache,
+) {
+    if !pacing.dry_run && can_never_succeed() {
         return;
     }
 
+    let initial_delay = Duration::from_secs(parse_env("CALENDAR_SCRAPE_START_DELAY_SECS", 0));
+    info!(
+        interval_mins = SCRAPE_INTERVAL.as_secs() / 60,
+        initial_delay_secs = initial_delay.as_secs(),
+        "configured the calendar scraping interval"
+    );
+    match ScrapeCycle::get(pool).await {
+        Ok(cycle) => {
+            info!(?cycle, "continuing the calendar scraping cycle");
+            metrics.record_cycle(&cycle);
+        }
+        Err(e) => error!(error = ?e, "could not get the calendar scraping cycle"),
+    }
+    sleep(initial_delay).await;
     let api = APIRequestor::default();
     loop {
         let ids = match entries_which_need_scraping(pool).await {
@@ -91,44 +219,168 @@ pub async fn all_entries(pool: &PgPool) {
                 continue;
             }
         };
-        let should_sleep_for_more_results = ids.len() < 20;
+        // a dry-run does not mark rooms as scraped => we would get the same rooms again immediately
+        let should_sleep_for_more_results = ids.len() < 20 || pacing.dry_run;
         if should_sleep_for_more_results {
             sleep(Duration::from_secs(60)).await;
         }
 
-        refresh_events(pool, &api, ids).await;
+        let stats = refresh_events(pool, &api, &metrics, &pacing, &locations, ids).await;
+        match rooms_remaining(pool).await {
+            Ok(remaining) => {
+                metrics.record_rooms_remaining(remaining);
+                if let Err(e) = track_cycle(pool, &metrics, remaining).await {
+                    error!(error = ?e, "could not persist the calendar scraping cycle");
+                }
+            }
+            Err(e) => error!(error = ?e, "could not count the rooms which still need scraping"),
+        }
+        if stats.under_maintenance {
+            let pause = *MAINTENANCE_PAUSE;
+            warn!(
+                succeeded = stats.succeeded,
+                pause_secs = pause.as_secs(),
+                "TUMonline is under maintenance => aborted the batch and pausing scraping",
+            );
+            metrics.cool_downs.inc();
+            sleep(pause).await;
+        } else if stats.should_cool_down() {
+            warn!(
+                succeeded = stats.succeeded,
+                failed = stats.failed,
+                cool_down_secs = COOL_DOWN.as_secs(),
+                "most calendar downloads failed, TUMonline is likely unavailable => pausing scraping",
+            );
+            metrics.cool_downs.inc();
+            sleep(COOL_DOWN).await;
+        }
+    }
+}
+
+/// Outcome of scraping a batch of rooms
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct BatchStats {
+    succeeded: usize,
+    failed: usize,
+    /// TUMonline served its maintenance page => the rest of the batch was not scraped
+    under_maintenance: bool,
+}
+impl BatchStats {
+    fn should_cool_down(&self) -> bool {
+        let total = self.succeeded + self.failed;
+        total >= COOL_DOWN_MIN_BATCH_SIZE
+            && self.failed as f64 >= total as f64 * COOL_DOWN_FAILURE_RATIO
     }
 }
 
-#[tracing::instrument(skip(api, pool))]
-async fn refresh_events(pool: &PgPool, api: &APIRequestor, mut ids: LimitedVec<LocationKey>) {
-    debug!(requested_ids_cnt = ids.len(), "downloading room-calendars");
+#[tracing::instrument(skip(api, pool, metrics, pacing, locations))]
+async fn refresh_events(
+    pool: &PgPool,
+    api: &APIRequestor,
+    metrics: &ScrapeMetrics,
+    pacing: &ScrapePacing,
+    locations: &CalendarLocationCache,
+    mut ids: LimitedVec<LocationKey>,
+) -> BatchStats {
+    let requested = ids.len();
+    debug!(requested_ids_cnt = requested, "downloading room-calendars");
     // we want to scrape all ~2k rooms once per hour
     // 1 thread is 15..20 per minute => we need at least 2 threads
     // this uses a FuturesUnordered which refills itsself to be able to work effectively with lagging tasks
     let mut work_queue = FuturesUnordered::new();
-    for _ in 0..NUMBER_OF_CONCURRENT_SCRAPES {
+    for _ in 0..pacing.concurrency() {
         if let Some(id) = ids.pop() {
-            work_queue.push(refresh_single(pool, api.clone(), id.key));
+            work_queue.push(refresh_single(
+                pool,
+                api.clone(),
+                metrics,
+                pacing,
+                locations,
+                id.key,
+            ));
         }
     }
 
-    while work_queue.next().await.is_some() {
+    let mut stats = BatchStats::default();
+    while let Some(res) = work_queue.next().await {
+        match res {
+            Ok(()) => stats.succeeded += 1,
+            Err(e) if e.is::<UnderMaintenance>() => stats.under_maintenance = true,
+            Err(_) => stats.failed += 1,
+        }
+        // every further request would only get the maintenance page as well
+        if stats.under_maintenance {
+            continue;
+        }
         if let Some(id) = ids.pop() {
-            work_queue.push(refresh_single(pool, api.clone(), id.key));
+            work_queue.push(refresh_single(
+                pool,
+                api.clone(),
+                metrics,
+                pacing,
+                locations,
+                id.key,
+            ));
         }
     }
+    info!(
+        requested,
+        succeeded = stats.succeeded,
+        failed = stats.failed,
+        under_maintenance = stats.under_maintenance,
+        "finished scraping a batch of room-calendars"
+    );
+    stats
 }
 
-#[tracing::instrument(skip(pool, api))]
-async fn refresh_single(pool: &PgPool, mut api: APIRequestor, id: String) -> anyhow::Result<()> {
+#[tracing::instrument(skip(pool, api, metrics, pacing, locations))]
+async fn refresh_single(
+    pool: &PgPool,
+    mut api: APIRequestor,
+    metrics: &ScrapeMetrics,
+    pacing: &ScrapePacing,
+    locations: &CalendarLocationCache,
+    id: String,
+) -> anyhow::Result<()> {
+    if pacing.dry_run {
+        info!(id, "dry-run: would download the calendar");
+        return Ok(());
+    }
+    let started = Instant::now();
     let sync_start = chrono::Utc::now();
-    if let Err(e) = Event::update_last_calendar_scrape_at(pool, &id, &sync_start).await {
-        error!(error = ?e, "could not update last_calendar_scrape_at");
+    let downloaded = list_events_with_retries(&mut api, metrics, pacing, &id).await;
+    store_download(
+        pool, metrics, locations, &id, started, sync_start, downloaded,
+    )
+    .await
+}
+
+/// Stores the outcome of downloading the calendar of a room
+///
+/// If TUMonline is under maintenance, nothing is touched.
+/// Otherwise the room is marked as checked, even if the download failed.
+#[tracing::instrument(skip(pool, metrics, locations, downloaded))]
+async fn store_download(
+    pool: &PgPool,
+    metrics: &ScrapeMetrics,
+    locations: &CalendarLocationCache,
+    id: &str,
+    started: Instant,
+    sync_start: DateTime<Utc>,
+    downloaded: anyhow::Result<Vec<ConnectumEvent>>,
+) -> anyhow::Result<()> {
+    if matches!(&downloaded, Err(e) if e.is::<UnderMaintenance>()) {
+        debug!(
+            id,
+            "TUMonline is under maintenance, keeping the calendar as-is"
+        );
+        return downloaded.map(|_| ());
+    }
+    if let Err(e) = Event::update_last_calendar_check_at(pool, id, &sync_start).await {
+        error!(error = ?e, "could not update last_calendar_check_at");
         return Err(e.into());
     }
-
-    let events = match api.list_events(&id).await {
+    let events = match downloaded {
         Ok(events) => {
             debug!(
                 id,
@@ -138,8 +390,14 @@ async fn refresh_single(pool: &PgPool, mut api: APIRequestor, id: String) -> any
             events
         }
         Err(e) => {
+            metrics.failures.inc();
+            if let Err(db_err) =
+                Event::record_calendar_failure(pool, id, &sync_start, &format!("{e:#}")).await
+            {
+                error!(error = ?db_err, "could not record the failed download");
+            }
             // TODO: this measure is to temporarily make the log usefully again until CO accepts my fix
-            if e.to_string() == *"error decoding response body" {
+            if e.is::<serde_json::Error>() {
                 debug!(
                     error = "https://gitlab.campusonline.community/tum/connectum/-/issues/118",
                     "Cannot download calendar"
@@ -150,15 +408,654 @@ async fn refresh_single(pool: &PgPool, mut api: APIRequestor, id: String) -> any
             return Err(e);
         }
     };
+    metrics.checked.inc();
+    Event::update_last_calendar_success_at(pool, id, &sync_start).await?;
+    let events_cnt = events.len();
 
+    let hash = events_hash(&events);
+    if Event::calendar_hash(pool, id).await? == Some(hash) {
+        debug!(id, "calendar is unchanged, skipping storing it");
+        metrics.record_room(started.elapsed(), events_cnt);
+        return Ok(());
+    }
     let events = events
         .into_iter()
         .map(|mut e| {
-            e.room_code.clone_from(&id);
+            id.clone_into(&mut e.room_code);
             e
         })
         .map(Event::from)
         .collect::<LimitedVec<_>>();
-    Event::store_all(pool, events, &id).await?;
+    Event::store_all(pool, events, id).await?;
+    Event::update_last_calendar_scrape_at(pool, id, &sync_start).await?;
+    locations.invalidate(id);
+    Event::update_calendar_hash(pool, id, hash).await?;
+    metrics.updated.inc();
+    metrics.record_room(started.elapsed(), events_cnt);
     Ok(())
 }
+
+/// Hash of the events, independent of the order TUMonline returns them in
+///
+/// Only used to detect changes between scrapes, hence the hasher not being stable across rust versions is fine:
+/// changing it just means that all rooms are stored once more.
+fn events_hash(events: &[ConnectumEvent]) -> i64 {
+    let mut sorted = events.iter().collect::<Vec<_>>();
+    sorted.sort_unstable_by_key(|e| e.id);
+    let mut hasher = DefaultHasher::new();
+    sorted.hash(&mut hasher);
+    // postgres does not have unsigned integers
+    hasher.finish() as i64
+}
+
+/// Downloads the events of a room, retrying transient failures with exponential backoff
+async fn list_events_with_retries(
+    api: &mut APIRequestor,
+    metrics: &ScrapeMetrics,
+    pacing: &ScrapePacing,
+    id: &str,
+) -> anyhow::Result<Vec<ConnectumEvent>> {
+    let max_attempts = *MAX_SCRAPE_ATTEMPTS;
+    let mut attempt = 1;
+    loop {
+        let permit = pacing.acquire().await;
+        let res = api.list_events(id).await;
+        // we should not block other scrapes while backing off
+        drop(permit);
+        if let Err(e) = &res {
+            metrics.record_upstream_error(&error_label(e));
+        }
+        match res {
+            Ok(events) => return Ok(events),
+            Err(e) if attempt < max_attempts && is_transient(&e) => {
+                let backoff = backoff_with_jitter(attempt);
+                debug!(
+                    error = ?e,
+                    attempt,
+                    max_attempts,
+                    backoff_ms = backoff.as_millis(),
+                    "retrying calendar download"
+                );
+                metrics.retries.inc();
+                metrics.backoff_seconds.inc_by(backoff.as_secs_f64());
+                sleep(backoff).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Exponential backoff for the `attempt`th failed attempt
+///
+/// The jitter prevents concurrent scrapes from retrying in lockstep.
+fn backoff_with_jitter(attempt: u32) -> Duration {
+    let exponential = INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    exponential.mul_f64(rand::random_range(0.5..=1.0))
+}
+
+/// Label of an error for the `status` of the upstream error metrics
+fn error_label(e: &anyhow::Error) -> String {
+    if e.is::<UnderMaintenance>() {
+        return "maintenance".to_string();
+    }
+    let Some(e) = e.downcast_ref::<reqwest::Error>() else {
+        return "other".to_string();
+    };
+    if let Some(status) = e.status() {
+        return status.as_u16().to_string();
+    }
+    let kind = if e.is_timeout() {
+        "timeout"
+    } else if e.is_connect() {
+        "connect"
+    } else if e.is_decode() {
+        "decode"
+    } else {
+        "other"
+    };
+    kind.to_string()
+}
+
+/// Whether retrying might succeed
+///
+/// Client errors or responses we cannot decode would fail again in the same way.
+fn is_transient(e: &anyhow::Error) -> bool {
+    let Some(e) = e.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+    match e.status() {
+        Some(status) => {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => e.is_timeout() || e.is_connect(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshJobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Clone, Debug)]
+pub struct RefreshJob {
+    pub id: u64,
+    pub room: String,
+    pub status: RefreshJobStatus,
+    pub enqueued_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnqueueError {
+    /// Too many rooms are waiting to be scraped already
+    QueueFull,
+}
+
+#[derive(Default)]
+struct RefreshJobs {
+    next_id: u64,
+    /// all remembered jobs by their id
+    jobs: BTreeMap<u64, RefreshJob>,
+    /// jobs which have not yet finished by their room
+    unfinished: HashMap<String, u64>,
+}
+impl RefreshJobs {
+    fn forget_old_jobs(&mut self) {
+        while self.jobs.len() > MAX_REMEMBERED_ON_DEMAND_SCRAPES {
+            let oldest_finished = self
+                .jobs
+                .values()
+                .find(|j| j.finished_at.is_some())
+                .map(|j| j.id);
+            match oldest_finished {
+                Some(id) => self.jobs.remove(&id),
+                None => return,
+            };
+        }
+    }
+}
+
+/// Scrapes single rooms on demand
+///
+/// Requests for a room which is already waiting to be scraped are coalesced into the existing job.
+#[derive(Clone)]
+pub struct OnDemandRefresh {
+    api: APIRequestor,
+    metrics: ScrapeMetrics,
+    pacing: ScrapePacing,
+    locations: CalendarLocationCache,
+    permits: Arc<Semaphore>,
+    jobs: Arc<Mutex<RefreshJobs>>,
+}
+impl OnDemandRefresh {
+    pub fn new(
+        metrics: ScrapeMetrics,
+        pacing: ScrapePacing,
+        locations: CalendarLocationCache,
+    ) -> Self {
+        Self {
+            api: APIRequestor::default(),
+            metrics,
+            pacing,
+            locations,
+            permits: Arc::new(Semaphore::new(NUMBER_OF_CONCURRENT_ON_DEMAND_SCRAPES)),
+            jobs: Arc::new(Mutex::new(RefreshJobs::default())),
+        }
+    }
+}
+impl Debug for OnDemandRefresh {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        //fields purposely omitted
+        f.debug_struct("OnDemandRefresh").finish()
+    }
+}
+impl OnDemandRefresh {
+    /// Enqueues a scrape of `room` and returns the id of the job
+    #[tracing::instrument(skip(self, pool))]
+    pub fn enqueue(&self, pool: &PgPool, room: &str) -> Result<u64, EnqueueError> {
+        let mut jobs = self.jobs.lock().expect("lock is not poisoned");
+        if let Some(id) = jobs.unfinished.get(room) {
+            return Ok(*id);
+        }
+        if jobs.unfinished.len() >= MAX_QUEUED_ON_DEMAND_SCRAPES {
+            return Err(EnqueueError::QueueFull);
+        }
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.jobs.insert(
+            id,
+            RefreshJob {
+                id,
+                room: room.to_string(),
+                status: RefreshJobStatus::Queued,
+                enqueued_at: Utc::now(),
+                finished_at: None,
+                error: None,
+            },
+        );
+        jobs.unfinished.insert(room.to_string(), id);
+        jobs.forget_old_jobs();
+        drop(jobs);
+
+        let this = self.clone();
+        let pool = pool.clone();
+        let room = room.to_string();
+        tokio::spawn(async move { this.run(pool, id, room).await });
+        Ok(id)
+    }
+
+    /// Whether a refresh of `room` is waiting or running
+    pub fn is_pending(&self, room: &str) -> bool {
+        let jobs = self.jobs.lock().expect("lock is not poisoned");
+        jobs.unfinished.contains_key(room)
+    }
+
+    pub fn job(&self, id: u64) -> Option<RefreshJob> {
+        let jobs = self.jobs.lock().expect("lock is not poisoned");
+        jobs.jobs.get(&id).cloned()
+    }
+
+    async fn run(&self, pool: PgPool, id: u64, room: String) {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        self.update(id, |job| job.status = RefreshJobStatus::Running);
+        let res = refresh_single(
+            &pool,
+            self.api.clone(),
+            &self.metrics,
+            &self.pacing,
+            &self.locations,
+            room.clone(),
+        )
+        .await;
+        self.update(id, |job| {
+            job.finished_at = Some(Utc::now());
+            match res {
+                Ok(()) => job.status = RefreshJobStatus::Succeeded,
+                Err(e) => {
+                    job.status = RefreshJobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        });
+        let mut jobs = self.jobs.lock().expect("lock is not poisoned");
+        jobs.unfinished.remove(&room);
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut RefreshJob)) {
+        let mut jobs = self.jobs.lock().expect("lock is not poisoned");
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            f(job);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use prometheus::Registry;
+
+    use super::*;
+
+    fn event(id: i32, title: &str) -> ConnectumEvent {
+        ConnectumEvent {
+            id,
+            room_code: "5602.EG.001".to_string(),
+            start_at: DateTime::UNIX_EPOCH,
+            end_at: DateTime::UNIX_EPOCH,
+            title_de: title.to_string(),
+            title_en: title.to_string(),
+            stp_type: None,
+            entry_type: "lecture".to_string(),
+            detailed_entry_type: "Abhaltung".to_string(),
+            course_code: None,
+            course_semester_hours: None,
+            course_group: None,
+        }
+    }
+
+    #[test]
+    fn test_events_hash_detects_changes() {
+        let unchanged = events_hash(&[event(1, "Analysis"), event(2, "Algebra")]);
+        // the order of the events is irrelevant
+        assert_eq!(
+            unchanged,
+            events_hash(&[event(2, "Algebra"), event(1, "Analysis")])
+        );
+        assert_ne!(
+            unchanged,
+            events_hash(&[event(1, "Analysis"), event(2, "Lineare Algebra")])
+        );
+        // an event was cancelled
+        assert_ne!(unchanged, events_hash(&[event(1, "Analysis")]));
+        assert_ne!(unchanged, events_hash(&[]));
+    }
+
+    #[test]
+    fn test_backoff_grows_exponentially_and_is_capped() {
+        for attempt in 1..=3 {
+            let expected = INITIAL_BACKOFF * 2_u32.pow(attempt - 1);
+            let backoff = backoff_with_jitter(attempt);
+            assert!(backoff >= expected / 2, "{backoff:?} < {expected:?}/2");
+            assert!(backoff <= expected, "{backoff:?} > {expected:?}");
+        }
+        assert!(backoff_with_jitter(100) <= MAX_BACKOFF);
+        assert!(backoff_with_jitter(100) >= MAX_BACKOFF / 2);
+    }
+
+    #[test]
+    fn test_cycle_change() {
+        let idle = ScrapeCycle::default();
+        let running = ScrapeCycle {
+            started_at: Some(DateTime::UNIX_EPOCH),
+            last_completed_at: None,
+        };
+        assert_eq!(cycle_change(&idle, 0), None);
+        assert_eq!(cycle_change(&idle, 3), Some(CycleChange::Start));
+        // a running cycle is continued, even if rooms became due in the meantime
+        assert_eq!(cycle_change(&running, 3), None);
+        assert_eq!(cycle_change(&running, 0), Some(CycleChange::Complete));
+    }
+
+    #[test]
+    fn test_cool_down_only_if_most_of_a_batch_failed() {
+        let stats = |succeeded, failed| BatchStats {
+            succeeded,
+            failed,
+            ..Default::default()
+        };
+        assert!(!stats(0, 0).should_cool_down());
+        // too small to be meaningful
+        assert!(!stats(0, COOL_DOWN_MIN_BATCH_SIZE - 1).should_cool_down());
+        assert!(stats(0, COOL_DOWN_MIN_BATCH_SIZE).should_cool_down());
+        assert!(stats(15, 15).should_cool_down());
+        assert!(!stats(16, 14).should_cool_down());
+    }
+
+    #[test]
+    fn test_only_reqwest_errors_are_transient() {
+        assert!(!is_transient(&anyhow::anyhow!(
+            "error decoding response body"
+        )));
+    }
+
+    #[actix_web::test]
+    async fn test_on_demand_refreshes_are_coalesced_and_capped() {
+        let refresh = OnDemandRefresh::new(
+            ScrapeMetrics::register(&Registry::new()).unwrap(),
+            ScrapePacing::new(1, Duration::ZERO, false),
+            CalendarLocationCache::default(),
+        );
+        // holding all permits ensures that no scrape is actually started
+        let _permits = refresh
+            .permits
+            .acquire_many(NUMBER_OF_CONCURRENT_ON_DEMAND_SCRAPES as u32)
+            .await
+            .unwrap();
+        let pool = PgPool::connect_lazy("postgres://localhost/never_connected").unwrap();
+
+        let first = refresh.enqueue(&pool, "5602.EG.001").unwrap();
+        assert_eq!(refresh.enqueue(&pool, "5602.EG.001"), Ok(first));
+        let job = refresh.job(first).unwrap();
+        assert_eq!(job.room, "5602.EG.001");
+        assert_eq!(job.status, RefreshJobStatus::Queued);
+
+        for i in 1..MAX_QUEUED_ON_DEMAND_SCRAPES {
+            let id = refresh.enqueue(&pool, &format!("room-{i}")).unwrap();
+            assert_ne!(id, first);
+        }
+        assert_eq!(
+            refresh.enqueue(&pool, "one-room-too-many"),
+            Err(EnqueueError::QueueFull)
+        );
+        // already queued rooms are still coalesced
+        assert_eq!(refresh.enqueue(&pool, "5602.EG.001"), Ok(first));
+        assert!(refresh.is_pending("5602.EG.001"));
+        assert!(!refresh.is_pending("one-room-too-many"));
+        assert!(refresh.job(u64::MAX).is_none());
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use chrono::SubsecRound;
+    use pretty_assertions::assert_eq;
+    use prometheus::Registry;
+
+    use super::*;
+    use crate::external::connectum::parse_events;
+    use crate::setup::tests::PostgresTestContainer;
+
+    const MAINTENANCE_PAGE: &str = include_str!("../external/tumonline-maintenance.html");
+
+    /// Events and the scraping timestamps of a room
+    async fn snapshot(
+        pool: &PgPool,
+        id: &str,
+    ) -> (Vec<String>, Option<DateTime<Utc>>, Option<i64>) {
+        let titles = sqlx::query_scalar::<_, String>(
+            "SELECT title_de FROM calendar WHERE room_code = $1 ORDER BY id",
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let (checked_at, hash) = sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<i64>)>(
+            "SELECT last_calendar_check_at, calendar_hash FROM de WHERE key = $1",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (titles, checked_at, hash)
+    }
+
+    async fn insert_room(pool: &PgPool, id: &str) {
+        let data = serde_json::json!({
+            "id": id,
+            "name": id,
+            "type": "room",
+            "type_common_name": "Hörsaal",
+            "props": {"calendar_url": "https://campus.tum.de/1"},
+        });
+        sqlx::query("INSERT INTO de(key,data) VALUES ($1,$2)")
+            .bind(id)
+            .bind(data)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_cycle_is_resumed_after_a_restart() {
+        let pg = PostgresTestContainer::new().await;
+        for id in ["5602.EG.001", "5602.EG.002"] {
+            insert_room(&pg.pool, id).await;
+        }
+        let metrics = ScrapeMetrics::register(&Registry::new()).unwrap();
+        let remaining = rooms_remaining(&pg.pool).await.unwrap();
+        assert_eq!(remaining, 2);
+        track_cycle(&pg.pool, &metrics, remaining).await.unwrap();
+        let started = ScrapeCycle::get(&pg.pool).await.unwrap();
+        assert!(started.started_at.is_some());
+        assert_eq!(started.last_completed_at, None);
+        Event::update_last_calendar_check_at(&pg.pool, "5602.EG.001", &Utc::now())
+            .await
+            .unwrap();
+
+        // crash => nothing in memory survives
+        let metrics = ScrapeMetrics::register(&Registry::new()).unwrap();
+        // rooms scraped before the crash are not scraped again
+        let ids = entries_which_need_scraping(&pg.pool).await.unwrap();
+        assert_eq!(
+            ids.iter().map(|l| l.key.as_str()).collect::<Vec<_>>(),
+            vec!["5602.EG.002"]
+        );
+        let remaining = rooms_remaining(&pg.pool).await.unwrap();
+        track_cycle(&pg.pool, &metrics, remaining).await.unwrap();
+        assert_eq!(ScrapeCycle::get(&pg.pool).await.unwrap(), started);
+        assert_eq!(metrics.sync_status().cycle_started_at, started.started_at);
+
+        // the cycle completes with the last room
+        Event::update_last_calendar_check_at(&pg.pool, "5602.EG.002", &Utc::now())
+            .await
+            .unwrap();
+        let remaining = rooms_remaining(&pg.pool).await.unwrap();
+        assert_eq!(remaining, 0);
+        track_cycle(&pg.pool, &metrics, remaining).await.unwrap();
+        let completed = ScrapeCycle::get(&pg.pool).await.unwrap();
+        assert_eq!(completed.started_at, None);
+        assert!(completed.last_completed_at >= started.started_at);
+        let status = metrics.sync_status();
+        assert_eq!(status.cycle_started_at, None);
+        assert_eq!(status.last_full_cycle_at, completed.last_completed_at);
+    }
+
+    #[actix_web::test]
+    async fn test_cycle_rolls_over() {
+        let pg = PostgresTestContainer::new().await;
+        let metrics = ScrapeMetrics::register(&Registry::new()).unwrap();
+        track_cycle(&pg.pool, &metrics, 2).await.unwrap();
+        track_cycle(&pg.pool, &metrics, 0).await.unwrap();
+        let first = ScrapeCycle::get(&pg.pool).await.unwrap();
+        // staying up to date does not complete further cycles
+        track_cycle(&pg.pool, &metrics, 0).await.unwrap();
+        assert_eq!(ScrapeCycle::get(&pg.pool).await.unwrap(), first);
+
+        // rooms becoming due again start the next cycle
+        track_cycle(&pg.pool, &metrics, 1).await.unwrap();
+        let second = ScrapeCycle::get(&pg.pool).await.unwrap();
+        assert!(second.started_at >= first.last_completed_at);
+        assert_eq!(second.last_completed_at, first.last_completed_at);
+        track_cycle(&pg.pool, &metrics, 0).await.unwrap();
+        let second = ScrapeCycle::get(&pg.pool).await.unwrap();
+        assert_eq!(second.started_at, None);
+        assert!(second.last_completed_at >= first.last_completed_at);
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_page_keeps_the_calendar() {
+        let pg = PostgresTestContainer::new().await;
+        let id = "5602.EG.001";
+        insert_room(&pg.pool, id).await;
+        let metrics = ScrapeMetrics::register(&Registry::new()).unwrap();
+        let first_sync = DateTime::UNIX_EPOCH;
+        let events = vec![ConnectumEvent {
+            id: 1,
+            room_code: id.to_string(),
+            start_at: DateTime::UNIX_EPOCH,
+            end_at: DateTime::UNIX_EPOCH,
+            title_de: "Analysis".to_string(),
+            title_en: "Analysis".to_string(),
+            stp_type: None,
+            entry_type: "lecture".to_string(),
+            detailed_entry_type: "Abhaltung".to_string(),
+            course_code: None,
+            course_semester_hours: None,
+            course_group: None,
+        }];
+        store_download(
+            &pg.pool,
+            &metrics,
+            &CalendarLocationCache::default(),
+            id,
+            Instant::now(),
+            first_sync,
+            Ok(events),
+        )
+        .await
+        .unwrap();
+        let before = snapshot(&pg.pool, id).await;
+        assert_eq!(before.into_inner(), vec!["Analysis".to_string()]);
+        assert_eq!(before.1, Some(first_sync));
+
+        let downloaded = parse_events(Some("text/html;charset=UTF-8"), MAINTENANCE_PAGE);
+        let err = store_download(
+            &pg.pool,
+            &metrics,
+            &CalendarLocationCache::default(),
+            id,
+            Instant::now(),
+            Utc::now(),
+            downloaded,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is::<UnderMaintenance>());
+        assert_eq!(snapshot(&pg.pool, id).await, before);
+    }
+
+    #[actix_web::test]
+    async fn test_scraping_invalidates_the_cached_location() {
+        let pg = PostgresTestContainer::new().await;
+        let id = "5602.EG.001";
+        insert_room(&pg.pool, id).await;
+        let registry = Registry::new();
+        let metrics = ScrapeMetrics::register(&registry).unwrap();
+        let locations = CalendarLocationCache::default();
+        locations.register(&registry).unwrap();
+        let scraped_at = |locations: &CalendarLocationCache| {
+            let pool = &pg.pool;
+            let locations = locations.clone();
+            async move {
+                let found = locations
+                    .get_locations(pool, &[id.to_string()])
+                    .await
+                    .unwrap();
+                assert_eq!(found.len(), 1);
+                found.as_slice()[0].last_calendar_scrape_at
+            }
+        };
+        let lookups = |result: &str| {
+            registry
+                .gather()
+                .into_iter()
+                .find(|m| m.get_name() == "navigatum_api_calendar_location_cache_lookups_total")
+                .and_then(|m| {
+                    m.get_metric()
+                        .iter()
+                        .find(|m| m.get_label()[0].get_value() == result)
+                        .map(|m| m.get_counter().get_value())
+                })
+                .unwrap_or_default()
+        };
+        assert_eq!(scraped_at(&locations).await, None);
+        assert_eq!(scraped_at(&locations).await, None);
+        assert_eq!((lookups("hit"), lookups("miss")), (1.0, 1.0));
+
+        // changes the scraper does not know about are only visible after an explicit invalidation
+        let sync = DateTime::UNIX_EPOCH;
+        Event::update_last_calendar_scrape_at(&pg.pool, id, &sync)
+            .await
+            .unwrap();
+        assert_eq!(scraped_at(&locations).await, None);
+        locations.invalidate_all();
+        assert_eq!(scraped_at(&locations).await, Some(sync));
+
+        // scraping invalidates the room by itself
+        let sync = Utc::now().trunc_subsecs(0);
+        store_download(
+            &pg.pool,
+            &metrics,
+            &locations,
+            id,
+            Instant::now(),
+            sync,
+            Ok(Vec::new()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(scraped_at(&locations).await, Some(sync));
+        assert_eq!((lookups("hit"), lookups("miss")), (2.0, 3.0));
+    }
+}
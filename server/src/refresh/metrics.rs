@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use prometheus::{
+    Counter, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+};
+
+use crate::db::calendar::ScrapeCycle;
+
+/// Metrics of the calendar scraper
+///
+/// Registered against the registry of [`actix_web_prom::PrometheusMetrics`] to be exposed on `/api/metrics`
+#[derive(Clone, Debug)]
+pub struct ScrapeMetrics {
+    /// Downloads which were retried after a transient failure
+    pub(super) retries: IntCounter,
+    /// Total time spent backing off before retrying
+    pub(super) backoff_seconds: Counter,
+    /// Rooms which could not be scraped, even after retrying
+    pub(super) failures: IntCounter,
+    /// How often scraping was paused as most downloads of a batch failed
+    pub(super) cool_downs: IntCounter,
+    /// Rooms whose calendar was downloaded successfully
+    pub(super) checked: IntCounter,
+    /// Rooms whose calendar changed and was stored
+    pub(super) updated: IntCounter,
+    /// Rooms which still need to be scraped in the current cycle
+    rooms_remaining: IntGauge,
+    /// Events downloaded from TUMonline
+    scraped_events: IntCounter,
+    /// Failed requests to TUMonline, by HTTP status (or kind of failure if there is no status)
+    upstream_errors: IntCounterVec,
+    /// How long scraping a single room took
+    room_duration: Histogram,
+    /// When all rooms were last up to date
+    last_full_cycle: IntGauge,
+    /// The same data for `/api/calendar/sync_status`
+    status: Arc<Mutex<SyncStatus>>,
+}
+
+/// Snapshot of the progress of the scraper
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncStatus {
+    /// `None` until the first batch was scraped
+    pub rooms_remaining: Option<u64>,
+    pub rooms_scraped: u64,
+    pub scraped_events: u64,
+    pub upstream_errors: BTreeMap<String, u64>,
+    pub room_duration_total: Duration,
+    /// `None` if all rooms are up to date
+    pub cycle_started_at: Option<DateTime<Utc>>,
+    pub last_full_cycle_at: Option<DateTime<Utc>>,
+}
+
+impl ScrapeMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let retries = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_retries_total",
+                "Calendar downloads retried after a transient failure",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let backoff_seconds = Counter::with_opts(
+            Opts::new(
+                "calendar_scrape_backoff_seconds_total",
+                "Time spent backing off before retrying a calendar download",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let failures = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_failures_total",
+                "Calendars which could not be downloaded, even after retrying",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let cool_downs = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_cool_downs_total",
+                "Pauses of the scraper because most downloads of a batch failed",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let checked = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_checked_rooms_total",
+                "Rooms whose calendar was downloaded successfully",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let updated = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_updated_rooms_total",
+                "Rooms whose calendar changed and was stored",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let rooms_remaining = IntGauge::with_opts(
+            Opts::new(
+                "calendar_scrape_rooms_remaining",
+                "Rooms which still need to be scraped in the current cycle",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let scraped_events = IntCounter::with_opts(
+            Opts::new(
+                "calendar_scrape_events_total",
+                "Events downloaded from TUMonline",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        let upstream_errors = IntCounterVec::new(
+            Opts::new(
+                "calendar_scrape_upstream_errors_total",
+                "Failed requests to TUMonline",
+            )
+            .namespace("navigatum_api"),
+            &["status"],
+        )?;
+        let room_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "calendar_scrape_room_duration_seconds",
+                "Time it took to scrape a single room",
+            )
+            .namespace("navigatum_api")
+            .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+        )?;
+        let last_full_cycle = IntGauge::with_opts(
+            Opts::new(
+                "calendar_scrape_last_full_cycle_timestamp_seconds",
+                "When all rooms were last up to date",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        registry.register(Box::new(retries.clone()))?;
+        registry.register(Box::new(backoff_seconds.clone()))?;
+        registry.register(Box::new(failures.clone()))?;
+        registry.register(Box::new(cool_downs.clone()))?;
+        registry.register(Box::new(checked.clone()))?;
+        registry.register(Box::new(updated.clone()))?;
+        registry.register(Box::new(rooms_remaining.clone()))?;
+        registry.register(Box::new(scraped_events.clone()))?;
+        registry.register(Box::new(upstream_errors.clone()))?;
+        registry.register(Box::new(room_duration.clone()))?;
+        registry.register(Box::new(last_full_cycle.clone()))?;
+        Ok(Self {
+            retries,
+            backoff_seconds,
+            failures,
+            cool_downs,
+            checked,
+            updated,
+            rooms_remaining,
+            scraped_events,
+            upstream_errors,
+            room_duration,
+            last_full_cycle,
+            status: Arc::new(Mutex::new(SyncStatus::default())),
+        })
+    }
+
+    pub fn sync_status(&self) -> SyncStatus {
+        self.status.lock().expect("lock is not poisoned").clone()
+    }
+
+    pub(super) fn record_room(&self, duration: Duration, events_cnt: usize) {
+        self.room_duration.observe(duration.as_secs_f64());
+        self.scraped_events.inc_by(events_cnt as u64);
+        let mut status = self.status.lock().expect("lock is not poisoned");
+        status.rooms_scraped += 1;
+        status.scraped_events += events_cnt as u64;
+        status.room_duration_total += duration;
+    }
+
+    pub(super) fn record_upstream_error(&self, status: &str) {
+        self.upstream_errors.with_label_values(&[status]).inc();
+        let mut sync_status = self.status.lock().expect("lock is not poisoned");
+        *sync_status
+            .upstream_errors
+            .entry(status.to_string())
+            .or_default() += 1;
+    }
+
+    pub(super) fn record_rooms_remaining(&self, remaining: u64) {
+        self.rooms_remaining.set(remaining as i64);
+        let mut status = self.status.lock().expect("lock is not poisoned");
+        status.rooms_remaining = Some(remaining);
+    }
+
+    /// Records the persisted progress through all rooms, which survives restarts
+    pub(super) fn record_cycle(&self, cycle: &ScrapeCycle) {
+        if let Some(completed_at) = cycle.last_completed_at {
+            self.last_full_cycle.set(completed_at.timestamp());
+        }
+        let mut status = self.status.lock().expect("lock is not poisoned");
+        status.cycle_started_at = cycle.started_at;
+        status.last_full_cycle_at = cycle.last_completed_at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn metrics_are_registered() {
+        let registry = Registry::new();
+        let metrics = ScrapeMetrics::register(&registry).unwrap();
+        metrics.retries.inc();
+        metrics.backoff_seconds.inc_by(1.5);
+        metrics.record_upstream_error("503");
+        let names = registry
+            .gather()
+            .into_iter()
+            .map(|m| m.get_name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "navigatum_api_calendar_scrape_backoff_seconds_total",
+                "navigatum_api_calendar_scrape_checked_rooms_total",
+                "navigatum_api_calendar_scrape_cool_downs_total",
+                "navigatum_api_calendar_scrape_events_total",
+                "navigatum_api_calendar_scrape_failures_total",
+                "navigatum_api_calendar_scrape_last_full_cycle_timestamp_seconds",
+                "navigatum_api_calendar_scrape_retries_total",
+                "navigatum_api_calendar_scrape_room_duration_seconds",
+                "navigatum_api_calendar_scrape_rooms_remaining",
+                "navigatum_api_calendar_scrape_updated_rooms_total",
+                "navigatum_api_calendar_scrape_upstream_errors_total",
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_status_tracks_progress() {
+        let metrics = ScrapeMetrics::register(&Registry::new()).unwrap();
+        assert_eq!(metrics.sync_status(), SyncStatus::default());
+
+        metrics.record_rooms_remaining(2);
+        metrics.record_room(Duration::from_secs(1), 10);
+        metrics.record_upstream_error("503");
+        metrics.record_upstream_error("503");
+        metrics.record_upstream_error("timeout");
+        metrics.record_room(Duration::from_secs(3), 5);
+        metrics.record_rooms_remaining(0);
+        metrics.record_cycle(&ScrapeCycle {
+            started_at: None,
+            last_completed_at: Some(Utc::now()),
+        });
+
+        let status = metrics.sync_status();
+        assert_eq!(status.rooms_remaining, Some(0));
+        assert_eq!(status.rooms_scraped, 2);
+        assert_eq!(status.scraped_events, 15);
+        assert_eq!(status.room_duration_total, Duration::from_secs(4));
+        assert_eq!(
+            status.upstream_errors,
+            BTreeMap::from([("503".to_string(), 2), ("timeout".to_string(), 1)])
+        );
+        assert!(status.last_full_cycle_at.is_some());
+        assert_eq!(
+            metrics.last_full_cycle.get(),
+            status.last_full_cycle_at.unwrap().timestamp()
+        );
+    }
+}
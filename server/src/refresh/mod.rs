@@ -1,2 +1,4 @@
 pub mod calendar;
 pub mod indoor_maps;
+pub mod metrics;
+pub mod pacing;
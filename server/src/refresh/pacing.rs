@@ -0,0 +1,174 @@
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::{Instant, sleep_until};
+use tracing::{info, warn};
+
+/// How many rooms are scraped at once, if `CALENDAR_SCRAPE_CONCURRENCY` is not set
+const DEFAULT_CONCURRENCY: usize = 3;
+
+/// Limits how hard we hit TUMonline
+///
+/// Shared between all scrapes, so that on-demand scrapes count against the same limits as the regular ones.
+#[derive(Clone, Debug)]
+pub struct ScrapePacing {
+    concurrency: usize,
+    permits: Arc<Semaphore>,
+    /// Minimum time between starting two requests
+    delay: Duration,
+    /// Upper bound of the random time added to `delay`, so that requests don't hit TUMonline in lockstep
+    jitter: Duration,
+    /// When the next request may be started at the earliest
+    next_request_at: Arc<Mutex<Instant>>,
+    /// Only log what would be scraped instead of hitting TUMonline
+    pub dry_run: bool,
+}
+
+impl ScrapePacing {
+    pub fn new(concurrency: usize, delay: Duration, dry_run: bool) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            concurrency,
+            permits: Arc::new(Semaphore::new(concurrency)),
+            delay,
+            jitter: Duration::ZERO,
+            next_request_at: Arc::new(Mutex::new(Instant::now())),
+            dry_run,
+        }
+    }
+
+    /// Adds a random time of up to `jitter` to the delay between two requests
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Configured via `CALENDAR_SCRAPE_CONCURRENCY`, `CALENDAR_SCRAPE_DELAY_MS`, `CALENDAR_SCRAPE_JITTER_MS` and `CALENDAR_SCRAPE_DRY_RUN`
+    pub fn from_env() -> Self {
+        let concurrency = parse_env("CALENDAR_SCRAPE_CONCURRENCY", DEFAULT_CONCURRENCY);
+        let delay = Duration::from_millis(parse_env("CALENDAR_SCRAPE_DELAY_MS", 0));
+        let jitter = Duration::from_millis(parse_env("CALENDAR_SCRAPE_JITTER_MS", 0));
+        let dry_run = env::var("CALENDAR_SCRAPE_DRY_RUN") == Ok("true".to_string());
+        let pacing = Self::new(concurrency, delay, dry_run).with_jitter(jitter);
+        info!(
+            concurrency = pacing.concurrency,
+            delay_ms = delay.as_millis(),
+            jitter_ms = jitter.as_millis(),
+            dry_run,
+            "configured calendar scraping"
+        );
+        pacing
+    }
+
+    /// How many scrapes may run at once
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Waits until a request to TUMonline may be started
+    ///
+    /// The request may run for as long as the returned permit is held.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        if !self.delay.is_zero() || !self.jitter.is_zero() {
+            // reserving a slot before sleeping spreads waiting requests out by the gap each
+            let start_at = {
+                let mut next_request_at = self.next_request_at.lock().await;
+                let start_at = (*next_request_at).max(Instant::now());
+                *next_request_at = start_at + self.gap();
+                start_at
+            };
+            sleep_until(start_at).await;
+        }
+        permit
+    }
+
+    /// Time between starting two requests
+    fn gap(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.delay;
+        }
+        self.delay + self.jitter.mul_f64(rand::random_range(0.0..=1.0))
+    }
+}
+
+/// Parses the environment variable `key`, falling back to `default` if it is missing or invalid
+pub(super) fn parse_env<T: FromStr + Copy + std::fmt::Display>(key: &str, default: T) -> T {
+    let Ok(raw) = env::var(key) else {
+        return default;
+    };
+    match raw.trim().parse() {
+        Ok(value) => value,
+        Err(_) => {
+            warn!(key, %raw, %default, "could not parse environment variable, using the default");
+            default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_concurrency_is_limited() {
+        let pacing = ScrapePacing::new(2, Duration::ZERO, false);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            let pacing = pacing.clone();
+            let running = running.clone();
+            let max_running = max_running.clone();
+            tasks.spawn(async move {
+                let _permit = pacing.acquire().await;
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        tasks.join_all().await;
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_requests_are_spaced_by_the_delay() {
+        let delay = Duration::from_millis(20);
+        let pacing = ScrapePacing::new(5, delay, false);
+        let start = Instant::now();
+        for _ in 0..4 {
+            drop(pacing.acquire().await);
+        }
+        // the first request may start immediately
+        assert!(start.elapsed() >= delay * 3, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_gap_is_jittered_within_bounds() {
+        let delay = Duration::from_millis(100);
+        let jitter = Duration::from_millis(50);
+        let pacing = ScrapePacing::new(1, delay, false).with_jitter(jitter);
+        for _ in 0..100 {
+            let gap = pacing.gap();
+            assert!(gap >= delay, "{gap:?} < {delay:?}");
+            assert!(gap <= delay + jitter, "{gap:?} > {delay:?} + {jitter:?}");
+        }
+        assert_eq!(ScrapePacing::new(1, delay, false).gap(), delay);
+    }
+
+    #[test]
+    fn test_concurrency_is_at_least_one() {
+        assert_eq!(ScrapePacing::new(0, Duration::ZERO, false).concurrency(), 1);
+    }
+}
@@ -0,0 +1,177 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use tracing::Span;
+use tracing::field::Empty;
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
+
+const REQUEST_ID: &str = "X-Request-Id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Identifies a request in our logs, so that reports of users can be correlated with them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Reuses the id of the client (or a proxy in front of us), if it is sensible
+    fn from_request(req: &ServiceRequest) -> Self {
+        let sent = req
+            .headers()
+            .get(REQUEST_ID)
+            .and_then(|id| id.to_str().ok())
+            .map(str::trim)
+            .filter(|id| is_valid(id));
+        match sent {
+            Some(id) => RequestId(id.to_string()),
+            None => RequestId(format!("{:032x}", rand::random::<u128>())),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Only short ids of "boring" characters are accepted, as they end up in our logs verbatim
+fn is_valid(id: &str) -> bool {
+    (1..=MAX_REQUEST_ID_LEN).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Assigns each request a [`RequestId`] and echoes it in the `X-Request-Id` response header
+///
+/// Has to wrap the [`tracing_actix_web::TracingLogger`], so that [`RequestIdRootSpan`] can attach the id to the span of the request.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let id = RequestId::from_request(&req);
+    req.extensions_mut().insert(id.clone());
+    let http_req = req.request().clone();
+    let mut res = match next.call(req).await {
+        Ok(res) => res.map_into_boxed_body(),
+        Err(e) => ServiceResponse::from_err(e, http_req),
+    };
+    if let Ok(value) = HeaderValue::from_str(&id.0) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+    Ok(res)
+}
+
+/// Like the [`DefaultRootSpanBuilder`], but with our [`RequestId`] instead of a freshly generated one
+///
+/// All logs during the request are within this span and thus carry the id.
+pub struct RequestIdRootSpan;
+
+impl RootSpanBuilder for RequestIdRootSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let method = request.method().clone();
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "default".to_string());
+        let connection_info = request.connection_info();
+        tracing::info_span!(
+            "HTTP request",
+            http.method = %method,
+            http.route = %route,
+            http.target = %request.uri().path_and_query().map(|p| p.as_str()).unwrap_or(""),
+            http.host = %connection_info.host(),
+            http.client_ip = %connection_info.realip_remote_addr().unwrap_or(""),
+            http.user_agent = %request
+                .headers()
+                .get("User-Agent")
+                .and_then(|ua| ua.to_str().ok())
+                .unwrap_or(""),
+            http.status_code = Empty,
+            otel.name = %format!("HTTP {method} {route}"),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            request_id = %request_id,
+            exception.message = Empty,
+            exception.details = Empty,
+        )
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, get, web};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[get("/api/example")]
+    async fn example(id: web::ReqData<RequestId>) -> HttpResponse {
+        HttpResponse::Ok().body(id.to_string())
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("8e03978e-40d5-43e8-bc93-6894a57f9324"));
+        assert!(is_valid("req:1.67891233_abc"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a b"));
+        assert!(!is_valid("a\u{1b}[31m"));
+        assert!(!is_valid(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_is_echoed() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(tracing_actix_web::TracingLogger::<RequestIdRootSpan>::new())
+                .wrap(from_fn(assign_request_id))
+                .service(example),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/example")
+            .insert_header(("X-Request-Id", "8e03978e-40d5-43e8-bc93-6894a57f9324"))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(REQUEST_ID).unwrap(),
+            "8e03978e-40d5-43e8-bc93-6894a57f9324"
+        );
+        assert_eq!(
+            actix_web::test::read_body(res).await,
+            "8e03978e-40d5-43e8-bc93-6894a57f9324"
+        );
+
+        // invalid ids are replaced
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/example")
+            .insert_header(("X-Request-Id", "a b"))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        let id = res.headers().get(REQUEST_ID).unwrap().to_str().unwrap();
+        let id = id.to_string();
+        assert_eq!(id.len(), 32);
+        assert_eq!(actix_web::test::read_body(res).await, id);
+
+        // also for requests no handler exists for
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/missing")
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert!(res.headers().contains_key(REQUEST_ID));
+    }
+}
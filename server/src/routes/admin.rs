@@ -0,0 +1,151 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{HttpRequest, HttpResponse, post, web};
+use serde::Serialize;
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::error::ApiError;
+use crate::setup::database::ImportSummary;
+
+/// Checks that the request carries `Authorization: Bearer <ADMIN_TOKEN>`
+///
+/// Administrative endpoints are disabled if `ADMIN_TOKEN` is not configured.
+pub fn authorise(req: &HttpRequest) -> Result<(), ApiError> {
+    let expected = match std::env::var("ADMIN_TOKEN") {
+        Ok(token) if !token.trim().is_empty() => token,
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "admin_not_configured",
+                "Administrative endpoints are not configured on this server.",
+            ));
+        }
+    };
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    match provided {
+        Some(provided) if constant_time_eq(provided.trim(), expected.trim()) => Ok(()),
+        _ => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "A valid admin token is required for this endpoint",
+        )),
+    }
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct ReimportResponse {
+    /// Entries available on the CDN
+    #[schema(examples(51234))]
+    keys_cnt: usize,
+    /// Entries which were added, changed or removed since the last import
+    #[schema(examples(12))]
+    changed_cnt: usize,
+    /// Entries which were loaded into the database
+    #[schema(examples(11))]
+    updated_cnt: usize,
+    /// Entries which were skipped as they could not be parsed
+    #[schema(examples(1))]
+    invalid_cnt: usize,
+    /// Aliases which were loaded into the database
+    #[schema(examples(81234))]
+    alias_cnt: usize,
+    /// Nothing changed on the CDN since the last import => the import was skipped
+    #[schema(examples(false))]
+    unchanged: bool,
+}
+impl From<ImportSummary> for ReimportResponse {
+    fn from(value: ImportSummary) -> Self {
+        ReimportResponse {
+            keys_cnt: value.keys_cnt,
+            changed_cnt: value.keys_which_need_updating_cnt,
+            updated_cnt: value.updated_cnt,
+            invalid_cnt: value.invalid_cnt,
+            alias_cnt: value.alias_cnt,
+            unchanged: value.unchanged,
+        }
+    }
+}
+
+/// Re-import the data from the CDN
+///
+/// **Requires an admin token.**
+///
+/// Imports the data from the CDN into the database, the same way as on startup.
+/// This allows rolling out a hotfix of the data without restarting the server.
+/// If the data on the CDN has not changed since the last import, nothing is downloaded.
+/// All changes are applied in one transaction.
+/// Only one import runs at a time, so requests made while another import is running are rejected.
+#[utoipa::path(
+    tags=["admin"],
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Data was re-imported**", body = ReimportResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 409, description = "**Conflict.** Another import (e.g. the one on startup) is still running, please try again later", body = ApiError, content_type = "application/json", example = json!({"error": "An import is already running, please try again later", "code": "import_running"})),
+        (status = 500, description = "**Internal Server Error.** The import failed, all changes were rolled back", body = ApiError, content_type = "application/json", example = json!({"error": "The import failed, all changes were rolled back", "code": "import_failed"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[post("/api/admin/reimport")]
+pub async fn reimport_handler(req: HttpRequest, data: web::Data<crate::AppData>) -> HttpResponse {
+    if let Err(e) = authorise(&req) {
+        return e.into();
+    }
+    let Some(result) = crate::setup::database::try_load_data(&data.pool, false).await else {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "import_running",
+            "An import is already running, please try again later",
+        )
+        .into();
+    };
+    match result {
+        Ok(summary) => {
+            data.calendar_locations.invalidate_all();
+            data.coordinates.invalidate_all();
+            info!(?summary, "re-imported the data");
+            HttpResponse::Ok().json(ReimportResponse::from(summary))
+        }
+        Err(e) => {
+            error!(error = ?e, "could not re-import the data");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "import_failed",
+                "The import failed, all changes were rolled back",
+            )
+            .into()
+        }
+    }
+}
+
+/// Compares without leaking the position of the first difference via timing
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(constant_time_eq("", ""));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+        assert!(!constant_time_eq("", "secret"));
+    }
+}
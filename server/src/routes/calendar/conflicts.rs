@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, post, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use super::{EventTypeResponse, validate_ids, validate_locations};
+use crate::db::calendar::Event;
+use crate::error::ApiError;
+use crate::localisation;
+
+#[derive(Serialize, Deserialize, Clone, Debug, utoipa::ToSchema)]
+pub struct ConflictCheckRequest {
+    /// ids of the rooms the slot should be checked for
+    ///
+    /// Limit of max. 10 ids is arbitraryly chosen, if you need this limit increased, please contact us
+    #[schema(max_items=10,min_items=1,example=json!(["5605.EG.011","5510.02.001"]))]
+    keys: Vec<String>,
+    /// Start of the candidate slot
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    start: DateTime<Utc>,
+    /// End of the candidate slot
+    #[schema(examples("2039-01-19T05:14:07+01:00"))]
+    end: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Default, utoipa::ToSchema)]
+struct RoomConflictsResponse {
+    /// Whether the room is free during the whole slot
+    free: bool,
+    /// Entries overlapping with the slot, ordered by their start
+    conflicts: Vec<ConflictResponse>,
+}
+
+#[derive(Serialize, Debug, PartialEq, utoipa::ToSchema)]
+struct ConflictResponse {
+    /// ID of the calendar entry used in TUMonline internally
+    #[schema(examples(6424))]
+    id: i32,
+    /// Title of the entry in the requested language
+    #[schema(examples("Quantenteleportation", "Quantum teleportation"))]
+    title: String,
+    /// What this calendar entry means.
+    ///
+    /// Blocked rooms (`barred`) are conflicts as well.
+    entry_type: EventTypeResponse,
+    /// start of the entry
+    #[schema(examples("2039-01-19T02:00:00Z"))]
+    start_at: DateTime<Utc>,
+    /// end of the entry
+    #[schema(examples("2039-01-19T04:00:00Z"))]
+    end_at: DateTime<Utc>,
+    /// Start of the part of the slot which is taken by this entry
+    #[schema(examples("2039-01-19T03:14:07Z"))]
+    overlap_start: DateTime<Utc>,
+    /// End of the part of the slot which is taken by this entry
+    #[schema(examples("2039-01-19T04:00:00Z"))]
+    overlap_end: DateTime<Utc>,
+}
+
+/// Check a slot for conflicts
+///
+/// Checks whether the candidate slot `[start, end)` is free in each of the requested rooms.
+/// Every entry of the calendar, including rooms being blocked (`Sperre`), is a conflict.
+/// Cancelled entries are removed when the calendar is scraped and thus do not conflict.
+/// Entries merely touching the slot (e.g. ending when the slot starts) do not conflict either.
+#[utoipa::path(
+    tags=["calendar"],
+    params(localisation::LangQueryArgs),
+    request_body = ConflictCheckRequest,
+    responses(
+        (status = 200, description = "**Conflicts of the slot** by room", body = HashMap<String, RoomConflictsResponse>, content_type = "application/json"),
+        (status = 400, description= "**Bad Request.** The slot is empty or too many ids were requested", body = ApiError, content_type = "application/json", example = json!({"error": "The slot has to end after it starts", "code": "invalid_slot"})),
+        (status = 404, description = "**Not found.** The requested location does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002/None does not have a calendar", "code": "no_calendar"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the calendar entries", body = ApiError, content_type = "application/json", example = json!({"error": "could not get calendar entries, please try again later", "code": "internal_error"})),
+        (status = 503, description = "**Not Ready.** please retry later", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.003/None calendar entry is currently in the process of being scraped, please try again later", "code": "not_yet_scraped"})),
+    )
+)]
+#[post("/api/calendar/check_conflicts")]
+pub async fn check_conflicts_handler(
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    web::Json(args): web::Json<ConflictCheckRequest>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let ids = match validate_ids(&args.keys) {
+        Ok(ids) => ids,
+        Err(e) => return e.with_parameter("keys").into(),
+    };
+    if args.start >= args.end {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_slot",
+            "The slot has to end after it starts",
+        )
+        .with_parameter("end")
+        .into();
+    }
+    let locations = match data
+        .calendar_locations
+        .get_locations(&data.pool, &ids)
+        .await
+    {
+        Ok(l) => l.into_inner(),
+        Err(e) => {
+            error!(error = ?e, "could not get locations");
+            return internal_error();
+        }
+    };
+    if let Err(e) = validate_locations(&ids, &locations) {
+        return e.into();
+    }
+    let events = match Event::get_overlapping(&data.pool, &ids, &args.start, &args.end).await {
+        Ok(events) => events,
+        Err(e) => {
+            error!(error = ?e, ids = ?ids, "could not get entries from the db");
+            return internal_error();
+        }
+    };
+    let mut rooms = ids
+        .into_iter()
+        .map(|id| (id, RoomConflictsResponse::default()))
+        .collect::<HashMap<_, _>>();
+    for event in events {
+        let Some((overlap_start, overlap_end)) =
+            overlap((args.start, args.end), (event.start_at, event.end_at))
+        else {
+            continue;
+        };
+        let Some(room) = rooms.get_mut(&event.room_code) else {
+            continue;
+        };
+        room.conflicts.push(ConflictResponse {
+            id: event.id,
+            title: if lang.should_use_english() {
+                event.title_en
+            } else {
+                event.title_de
+            },
+            entry_type: EventTypeResponse::classify(&event.entry_type, &event.detailed_entry_type),
+            start_at: event.start_at,
+            end_at: event.end_at,
+            overlap_start,
+            overlap_end,
+        });
+    }
+    for room in rooms.values_mut() {
+        room.free = room.conflicts.is_empty();
+    }
+    HttpResponse::Ok().json(rooms)
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "could not get calendar entries, please try again later",
+    )
+    .into()
+}
+
+/// Intersection of the half-open intervals `[start, end)`
+///
+/// `None` if they don't share any time, which includes them merely touching.
+fn overlap<T: Ord>(a: (T, T), b: (T, T)) -> Option<(T, T)> {
+    let start = a.0.max(b.0);
+    let end = a.1.min(b.1);
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_overlap() {
+        let slot = (10, 20);
+        let cases = [
+            // partial overlaps at either end
+            ((5, 15), Some((10, 15))),
+            ((15, 25), Some((15, 20))),
+            // containment in both directions
+            ((12, 18), Some((12, 18))),
+            ((0, 30), Some((10, 20))),
+            ((10, 20), Some((10, 20))),
+            // sharing the start or end
+            ((10, 15), Some((10, 15))),
+            ((15, 20), Some((15, 20))),
+            // touching is not overlapping
+            ((0, 10), None),
+            ((20, 30), None),
+            // disjoint
+            ((0, 5), None),
+            ((25, 30), None),
+            // zero-length entries don't take any time
+            ((15, 15), None),
+            ((10, 10), None),
+        ];
+        for (entry, expected) in cases {
+            assert_eq!(overlap(slot, entry), expected, "{slot:?} ∩ {entry:?}");
+            // the intersection is symmetric
+            assert_eq!(overlap(entry, slot), expected, "{entry:?} ∩ {slot:?}");
+        }
+    }
+}
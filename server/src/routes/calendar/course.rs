@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use super::EventResponse;
+use crate::db::calendar::Event;
+use crate::error::ApiError;
+use crate::localisation;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct CoursePathParams {
+    /// Code of the course in the course catalog of TUMonline
+    #[param(example = "IN0007")]
+    course_code: String,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct CourseQueryArgs {
+    /// The first allowed time the calendar would like to display
+    #[param(example = "2039-01-19T03:14:07+01:00")]
+    start: DateTime<Utc>,
+    /// The last allowed time the calendar would like to display
+    #[param(example = "2039-02-19T03:14:07+01:00")]
+    end: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CourseCalendarResponse {
+    /// Code of the course in the course catalog of TUMonline
+    #[schema(examples("IN0007"))]
+    course_code: String,
+    /// Entries of the course, grouped by the group of the course they are for
+    ///
+    /// This distinguishes e.g. the lecture from the different exercise groups.
+    /// Entries which are not assigned to a group come first.
+    groups: Vec<CourseGroupResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CourseGroupResponse {
+    /// Name of the group of the course
+    ///
+    /// `null` for entries which are not assigned to a group
+    #[schema(examples("Gruppe 1", "Zentralübung"))]
+    group: Option<String>,
+    /// Entries of this group in all rooms, ordered by their start
+    events: Vec<CourseEventResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CourseEventResponse {
+    #[serde(flatten)]
+    event: EventResponse,
+    /// Name of the room the entry takes place in
+    #[schema(examples("5602.EG.001 (MI HS 1, Friedrich L. Bauer Hörsaal)"))]
+    room_name: String,
+}
+
+/// Retrieve the calendar entries of a course
+///
+/// Retrieves the entries of a course within the requested time span, regardless of the room they take place in.
+#[utoipa::path(
+    tags=["calendar"],
+    params(CoursePathParams, CourseQueryArgs, localisation::LangQueryArgs),
+    responses(
+        (status = 200, description = "**Entries of the course** in the requested time span", body = CourseCalendarResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The time span is empty", body = ApiError, content_type = "application/json", example = json!({"error": "`end` has to be after `start`", "code": "invalid_window"})),
+        (status = 404, description = "**Not found.** We don't know of any entries of this course in the requested time span", body = ApiError, content_type = "application/json", example = json!({"error": "Course IN0007 does not have any entries in the requested time span", "code": "unknown_course"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the calendar entries", body = ApiError, content_type = "application/json", example = json!({"error": "could not get calendar entries, please try again later", "code": "internal_error"})),
+    )
+)]
+#[get("/api/calendar/course/{course_code}")]
+pub async fn course_handler(
+    params: web::Path<CoursePathParams>,
+    web::Query(args): web::Query<CourseQueryArgs>,
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let course_code = params.course_code.trim();
+    if args.start >= args.end {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_window",
+            "`end` has to be after `start`",
+        )
+        .with_parameter("end")
+        .into();
+    }
+    let events = match Event::get_by_course(&data.pool, course_code, &args.start, &args.end).await {
+        Ok(events) => events,
+        Err(e) => {
+            error!(error = ?e, course_code, "could not get entries from the db");
+            return internal_error();
+        }
+    };
+    if events.is_empty() {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_course",
+            format!("Course {course_code} does not have any entries in the requested time span"),
+        )
+        .into();
+    }
+    let mut rooms = events
+        .iter()
+        .map(|e| e.room_code.clone())
+        .collect::<Vec<_>>();
+    rooms.sort_unstable();
+    rooms.dedup();
+    let room_names = match data
+        .calendar_locations
+        .get_locations(&data.pool, &rooms)
+        .await
+    {
+        Ok(locations) => locations
+            .into_iter()
+            .map(|l| (l.key, l.name))
+            .collect::<HashMap<_, _>>(),
+        Err(e) => {
+            error!(error = ?e, "could not get locations");
+            return internal_error();
+        }
+    };
+    HttpResponse::Ok().json(CourseCalendarResponse {
+        course_code: course_code.to_string(),
+        groups: group_events(events, &room_names, lang),
+    })
+}
+
+/// Groups the events by their course group, keeping them ordered by their start
+fn group_events(
+    events: Vec<Event>,
+    room_names: &HashMap<String, String>,
+    lang: localisation::LangQueryArgs,
+) -> Vec<CourseGroupResponse> {
+    let mut groups = BTreeMap::<Option<String>, Vec<CourseEventResponse>>::new();
+    for event in events {
+        let group = event.course_group.clone();
+        let room_name = room_names
+            .get(&event.room_code)
+            .cloned()
+            .unwrap_or_else(|| event.room_code.clone());
+        let mut event = EventResponse::from(event);
+        if lang.should_use_english() {
+            event.translate_to_english();
+        }
+        groups
+            .entry(group)
+            .or_default()
+            .push(CourseEventResponse { event, room_name });
+    }
+    groups
+        .into_iter()
+        .map(|(group, events)| CourseGroupResponse { group, events })
+        .collect()
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "could not get calendar entries, please try again later",
+    )
+    .into()
+}
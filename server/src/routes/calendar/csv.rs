@@ -0,0 +1,242 @@
+use std::convert::Infallible;
+
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use actix_web::http::header::Accept;
+use actix_web::web::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use super::{EventResponse, EventTypeResponse};
+use crate::localisation;
+
+/// Column names of the exported rows
+const HEADER_DE: [&str; 8] = [
+    "id",
+    "titel",
+    "beginn",
+    "ende",
+    "typ",
+    "detaillierter_typ",
+    "kurs_code",
+    "kurs_sws",
+];
+const HEADER_EN: [&str; 8] = [
+    "id",
+    "title",
+    "start",
+    "end",
+    "type",
+    "detailed_type",
+    "course_code",
+    "course_semester_hours",
+];
+/// Without a BOM, Excel assumes a legacy encoding and garbles umlauts
+const UTF8_BOM: &str = "\u{feff}";
+
+#[derive(
+    Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    /// One row per entry, for importing into spreadsheets
+    ///
+    /// Entries are not grouped into series, even if `group_series` is requested.
+    Csv,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, utoipa::IntoParams)]
+#[serde(default)]
+pub struct FormatQueryArgs {
+    /// Format of the response
+    ///
+    /// Alternatively, `text/csv` can be requested via the `Accept` header.
+    format: Option<ResponseFormat>,
+}
+
+impl FormatQueryArgs {
+    /// The `format` parameter takes precedence over the `Accept` header
+    pub fn negotiate(self, req: &HttpRequest) -> ResponseFormat {
+        if let Some(format) = self.format {
+            return format;
+        }
+        let prefers_csv = req
+            .get_header::<Accept>()
+            .and_then(|accept| accept.ranked().into_iter().next())
+            .is_some_and(|preferred| preferred.essence_str() == "text/csv");
+        if prefers_csv {
+            ResponseFormat::Csv
+        } else {
+            ResponseFormat::Json
+        }
+    }
+}
+
+/// Streams the entries as [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180) rows, ordered by their start
+pub(super) fn event_rows(
+    mut events: Vec<EventResponse>,
+    lang: localisation::LangQueryArgs,
+) -> impl Stream<Item = Result<Bytes, Infallible>> + 'static {
+    events.sort_by_key(|e| (e.start_at, e.id));
+    let english = lang.should_use_english();
+    let header = if english { HEADER_EN } else { HEADER_DE };
+    let header = format!("{UTF8_BOM}{}", row(&header));
+    let rows = events.into_iter().map(move |e| event_row(&e, english));
+    futures::stream::iter(
+        std::iter::once(header)
+            .chain(rows)
+            .map(|row| Ok(Bytes::from(row))),
+    )
+}
+
+fn event_row(event: &EventResponse, english: bool) -> String {
+    let title = if english {
+        &event.title_en
+    } else {
+        &event.title_de
+    };
+    row(&[
+        &event.id.to_string(),
+        title,
+        &timestamp(&event.start_at),
+        &timestamp(&event.end_at),
+        entry_type_name(event.entry_type),
+        &event.detailed_entry_type,
+        event.course.as_ref().map_or("", |c| c.code.as_str()),
+        &event
+            .course
+            .as_ref()
+            .and_then(|c| c.semester_hours)
+            .map(|h| h.to_string())
+            .unwrap_or_default(),
+    ])
+}
+
+/// Formatted the same way as in the json response
+fn timestamp(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn entry_type_name(entry_type: EventTypeResponse) -> &'static str {
+    match entry_type {
+        EventTypeResponse::Lecture => "lecture",
+        EventTypeResponse::Exercise => "exercise",
+        EventTypeResponse::Exam => "exam",
+        EventTypeResponse::Barred => "barred",
+        EventTypeResponse::Cancelled => "cancelled",
+        EventTypeResponse::Moved => "moved",
+        EventTypeResponse::Online => "online",
+        EventTypeResponse::Other => "other",
+    }
+}
+
+fn row(fields: &[&str]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|f| escape(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+/// Quotes fields which would otherwise be split
+///
+/// Semicolons are quoted as well, as Excel uses them as the separator in german locales.
+fn escape(field: &str) -> String {
+    if field.contains([',', ';', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use futures::StreamExt;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::routes::calendar::CourseResponse;
+
+    #[test]
+    fn test_escape() {
+        let cases = [
+            ("Quantenteleportation", "Quantenteleportation"),
+            ("", ""),
+            ("Vorlesung, Teil 2", "\"Vorlesung, Teil 2\""),
+            ("Vorlesung; Teil 2", "\"Vorlesung; Teil 2\""),
+            ("Vorlesung\nTeil 2", "\"Vorlesung\nTeil 2\""),
+            ("Vorlesung\r\nTeil 2", "\"Vorlesung\r\nTeil 2\""),
+            ("Die \"beste\" Vorlesung", "\"Die \"\"beste\"\" Vorlesung\""),
+        ];
+        for (field, expected) in cases {
+            assert_eq!(escape(field), expected, "escaping {field:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_event_rows() {
+        let event = |id, title_de: &str, start_at: &str, semester_hours| EventResponse {
+            id,
+            room_code: "5121.EG.003".into(),
+            start_at: start_at.parse().unwrap(),
+            end_at: "2014-01-01T00:00:00Z".parse().unwrap(),
+            title_de: title_de.into(),
+            title_en: "Quantum teleportation".into(),
+            stp_type: None,
+            entry_type: EventTypeResponse::Lecture,
+            detailed_entry_type: "Abhaltung".into(),
+            course: Some(CourseResponse {
+                code: "PH1001".into(),
+                semester_hours,
+                group: None,
+            }),
+        };
+        let events = vec![
+            event(2, "Quanten, Teil 2", "2013-01-01T00:00:00Z", None),
+            event(1, "Quanten; \"Teil 1\"", "2012-01-01T00:00:00.5Z", Some(4)),
+        ];
+        let body = event_rows(events, localisation::LangQueryArgs::default())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "\u{feff}id,titel,beginn,ende,typ,detaillierter_typ,kurs_code,kurs_sws\r\n\
+             1,\"Quanten; \"\"Teil 1\"\"\",2012-01-01T00:00:00.500Z,2014-01-01T00:00:00Z,lecture,Abhaltung,PH1001,4\r\n\
+             2,\"Quanten, Teil 2\",2013-01-01T00:00:00Z,2014-01-01T00:00:00Z,lecture,Abhaltung,PH1001,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let negotiate = |format, accept: Option<&str>| {
+            let mut req = TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header(("Accept", accept));
+            }
+            FormatQueryArgs { format }.negotiate(&req.to_http_request())
+        };
+        assert_eq!(negotiate(None, None), ResponseFormat::Json);
+        assert_eq!(negotiate(None, Some("*/*")), ResponseFormat::Json);
+        assert_eq!(negotiate(None, Some("text/csv")), ResponseFormat::Csv);
+        assert_eq!(
+            negotiate(None, Some("application/json;q=0.5, text/csv")),
+            ResponseFormat::Csv
+        );
+        assert_eq!(
+            negotiate(Some(ResponseFormat::Json), Some("text/csv")),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            negotiate(Some(ResponseFormat::Csv), None),
+            ResponseFormat::Csv
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ContentDisposition, DispositionParam, DispositionType,
+    LastModified,
+};
+use actix_web::{HttpRequest, HttpResponse, delete, get, post, web};
+use chrono::{DateTime, SubsecRound, TimeDelta, Utc};
+use rand::Rng;
+use rand::distr::Alphanumeric;
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::{error, info, warn};
+
+use super::{EventResponse, MAX_EVENTS, ics};
+use crate::db::calendar::{CalendarLocation, FeedToken, LocationEvents};
+use crate::error::ApiError;
+use crate::localisation;
+use crate::routes::admin;
+
+/// How often subscribed clients should refresh the feed, if `CALENDAR_FEED_REFRESH_MINS` is not set
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+static REFRESH_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    let Ok(raw) = std::env::var("CALENDAR_FEED_REFRESH_MINS") else {
+        return DEFAULT_REFRESH_INTERVAL;
+    };
+    match raw.trim().parse::<u64>() {
+        Ok(mins) if mins >= 1 => Duration::from_secs(mins * 60),
+        _ => {
+            warn!(
+                %raw,
+                default_mins = DEFAULT_REFRESH_INTERVAL.as_secs() / 60,
+                "CALENDAR_FEED_REFRESH_MINS is not a positive integer, using the default"
+            );
+            DEFAULT_REFRESH_INTERVAL
+        }
+    }
+});
+/// Entries this long ago are still part of the feed
+const FEED_PAST: TimeDelta = TimeDelta::weeks(4);
+/// Entries up to this far in the future are part of the feed
+const FEED_FUTURE: TimeDelta = TimeDelta::weeks(26);
+/// Long enough not to be guessable
+const TOKEN_LEN: usize = 32;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct FeedPathParams {
+    /// ID of the room
+    #[param(example = "5602.EG.001")]
+    id: String,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct FeedTokenPathParams {
+    /// ID of the room
+    #[param(example = "5602.EG.001")]
+    id: String,
+    /// ID of the token as returned when creating it
+    #[param(example = 42)]
+    token_id: i32,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct FeedQueryArgs {
+    /// Token granting access to the feed of this room
+    #[param(example = "R4nd0mT0k3nR4nd0mT0k3nR4nd0mT0k3")]
+    token: String,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct FeedTokenResponse {
+    /// ID of the token, used to revoke it
+    #[schema(examples(42))]
+    id: i32,
+    /// Room whose feed the token grants access to
+    #[schema(examples("5602.EG.001"))]
+    room: String,
+    /// The token to pass as `token` to the feed
+    #[schema(examples("R4nd0mT0k3nR4nd0mT0k3nR4nd0mT0k3"))]
+    token: String,
+    /// When the token was created
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    created_at: DateTime<Utc>,
+    /// When the token was revoked
+    ///
+    /// Revoked tokens do not grant access anymore
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    revoked_at: Option<DateTime<Utc>>,
+}
+impl From<FeedToken> for FeedTokenResponse {
+    fn from(value: FeedToken) -> Self {
+        FeedTokenResponse {
+            id: value.id,
+            room: value.room_code,
+            token: value.token,
+            created_at: value.created_at,
+            revoked_at: value.revoked_at,
+        }
+    }
+}
+
+/// Create a feed token for a room
+///
+/// **Requires an admin token.**
+///
+/// The token grants access to the ICS feed of the room via [`/api/calendar/{id}/ics`](#tag/calendar/operation/ics_handler) until it is revoked.
+#[utoipa::path(
+    tags=["calendar"],
+    params(FeedPathParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "**Token was created**", body = FeedTokenResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The room does not exist or does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002 does not have a calendar", "code": "no_calendar"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[post("/api/calendar/{id}/feed_token")]
+pub async fn create_feed_token_handler(
+    req: HttpRequest,
+    params: web::Path<FeedPathParams>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let id = params.id.trim();
+    if let Err(e) = calendar_location(&data, id).await {
+        return e.into();
+    }
+    let token = rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect::<String>();
+    match FeedToken::create(&data.pool, id, &token).await {
+        Ok(token) => {
+            info!(id, token_id = token.id, "created a feed token");
+            HttpResponse::Created().json(FeedTokenResponse::from(token))
+        }
+        Err(e) => {
+            error!(error = ?e, id, "could not create feed token");
+            internal_error()
+        }
+    }
+}
+
+/// List the feed tokens of a room
+///
+/// **Requires an admin token.**
+///
+/// Revoked tokens are included, the newest token comes first.
+#[utoipa::path(
+    tags=["calendar"],
+    params(FeedPathParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Tokens of the room**", body = Vec<FeedTokenResponse>, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[get("/api/calendar/{id}/feed_token")]
+pub async fn list_feed_tokens_handler(
+    req: HttpRequest,
+    params: web::Path<FeedPathParams>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let id = params.id.trim();
+    match FeedToken::list(&data.pool, id).await {
+        Ok(tokens) => HttpResponse::Ok().json(
+            tokens
+                .into_iter()
+                .map(FeedTokenResponse::from)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            error!(error = ?e, id, "could not list feed tokens");
+            internal_error()
+        }
+    }
+}
+
+/// Revoke a feed token
+///
+/// **Requires an admin token.**
+///
+/// The feed cannot be accessed with this token anymore.
+/// Revoking an already revoked token is a no-op.
+#[utoipa::path(
+    tags=["calendar"],
+    params(FeedTokenPathParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Token was revoked**", body = FeedTokenResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The room does not have such a token", body = ApiError, content_type = "application/json", example = json!({"error": "Feed token 42 for 5602.EG.001 does not exist", "code": "not_found"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[delete("/api/calendar/{id}/feed_token/{token_id}")]
+pub async fn revoke_feed_token_handler(
+    req: HttpRequest,
+    params: web::Path<FeedTokenPathParams>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let id = params.id.trim();
+    match FeedToken::revoke(&data.pool, id, params.token_id).await {
+        Ok(Some(token)) => {
+            info!(id, token_id = token.id, "revoked a feed token");
+            HttpResponse::Ok().json(FeedTokenResponse::from(token))
+        }
+        Ok(None) => ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!(
+                "Feed token {token_id} for {id} does not exist",
+                token_id = params.token_id
+            ),
+        )
+        .into(),
+        Err(e) => {
+            error!(error = ?e, id, "could not revoke feed token");
+            internal_error()
+        }
+    }
+}
+
+/// Subscribe to the calendar of a room
+///
+/// **Requires a feed token** for this room, which can be created by an admin.
+///
+/// Returns the entries from four weeks ago up to half a year ahead as an [iCalendar](https://www.rfc-editor.org/rfc/rfc5545) feed, which calendar apps can subscribe to.
+/// The feed asks clients to refresh it every hour (by default) via `REFRESH-INTERVAL` and `X-PUBLISHED-TTL`.
+#[utoipa::path(
+    tags=["calendar"],
+    params(FeedPathParams, FeedQueryArgs, localisation::LangQueryArgs),
+    responses(
+        (status = 200, description = "**Entries of the calendar**", body = String, content_type = "text/calendar"),
+        (status = 403, description = "**Forbidden.** The token is unknown, revoked or for a different room", body = ApiError, content_type = "application/json", example = json!({"error": "The token does not grant access to this feed", "code": "invalid_token", "parameter": "token"})),
+        (status = 404, description = "**Not found.** The room does not have a calendar (anymore)", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002 does not have a calendar", "code": "no_calendar"})),
+        (status = 503, description = "**Not Ready.** please retry later", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.003 calendar entry is currently in the process of being scraped, please try again later", "code": "not_yet_scraped"})),
+    )
+)]
+#[get("/api/calendar/{id}/ics")]
+pub async fn ics_handler(
+    params: web::Path<FeedPathParams>,
+    web::Query(args): web::Query<FeedQueryArgs>,
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let id = params.id.trim();
+    match FeedToken::is_valid(&data.pool, id, args.token.trim()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::new(
+                StatusCode::FORBIDDEN,
+                "invalid_token",
+                "The token does not grant access to this feed",
+            )
+            .with_parameter("token")
+            .into();
+        }
+        Err(e) => {
+            error!(error = ?e, id, "could not check feed token");
+            return internal_error();
+        }
+    }
+    let location = match calendar_location(&data, id).await {
+        Ok(location) => location,
+        Err(e) => return e.into(),
+    };
+    let Some(last_modified) = location.last_calendar_scrape_at else {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_yet_scraped",
+            format!(
+                "Room {id} calendar entry is currently in the process of being scraped, please try again later"
+            ),
+        )
+        .into();
+    };
+    let name = location.name.clone();
+    let now = Utc::now();
+    let events = match LocationEvents::get_from_db(
+        &data.pool,
+        vec![location],
+        &(now - FEED_PAST),
+        &(now + FEED_FUTURE),
+        *MAX_EVENTS,
+    )
+    .await
+    {
+        Ok(mut events) => events
+            .0
+            .remove(id)
+            .map(|l| l.events.into_inner())
+            .unwrap_or_default(),
+        Err(e) => {
+            error!(error = ?e, id, "could not get entries from the db");
+            return internal_error();
+        }
+    };
+    let events = events
+        .into_iter()
+        .map(|e| {
+            let mut event = EventResponse::from(e);
+            if lang.should_use_english() {
+                event.translate_to_english();
+            }
+            event
+        })
+        .collect::<Vec<_>>();
+    let feed = ics::Feed {
+        name: &name,
+        refresh_interval: *REFRESH_INTERVAL,
+        last_modified,
+    };
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::MaxAge(REFRESH_INTERVAL.as_secs() as u32),
+            // the feed is only meant for holders of the token
+            CacheDirective::Private,
+        ]))
+        .insert_header(LastModified(
+            SystemTime::from(last_modified.trunc_subsecs(0)).into(),
+        ))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(format!("{id}.ics"))],
+        })
+        .content_type("text/calendar; charset=utf-8")
+        .body(ics::calendar(&feed, &events, lang))
+}
+
+/// The room, if it exists and has a calendar
+async fn calendar_location(data: &crate::AppData, id: &str) -> Result<CalendarLocation, ApiError> {
+    let locations = data
+        .calendar_locations
+        .get_locations(&data.pool, &[id.to_string()])
+        .await
+        .map_err(|e| {
+            error!(error = ?e, id, "could not get location");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get the location, please try again later",
+            )
+        })?;
+    match locations.into_iter().next() {
+        None => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("Room {id} does not exist"),
+        )),
+        Some(location) if location.calendar_url.is_none() => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "no_calendar",
+            format!("Room {id} does not have a calendar"),
+        )),
+        Some(location) => Ok(location),
+    }
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "could not get the calendar feed, please try again later",
+    )
+    .into()
+}
@@ -0,0 +1,131 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use crate::db::calendar::FreeRoom;
+use crate::error::ApiError;
+
+/// Shorter prefixes would match large parts of the campus
+const MIN_PREFIX_LEN: usize = 4;
+const MAX_DURATION_MIN: u32 = 24 * 60;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct FreeNowQueryArgs {
+    /// Prefix of the ids of the rooms, usually the id of a building
+    #[param(example = "5602", min_length = 4)]
+    prefix: String,
+    /// For how many minutes the rooms need to be free
+    #[param(example = 60, minimum = 1, maximum = 1440)]
+    #[serde(default = "default_duration_min")]
+    duration_min: u32,
+}
+
+fn default_duration_min() -> u32 {
+    60
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct FreeNowResponse {
+    /// The time the rooms are free from
+    ///
+    /// As responses are cached, this can be up to a minute in the past.
+    #[schema(examples("2039-01-19T03:14:07Z"))]
+    as_of: DateTime<Utc>,
+    /// Free rooms, ordered by how long they stay free
+    rooms: Vec<FreeRoomResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct FreeRoomResponse {
+    /// ID of the room
+    #[schema(examples("5602.EG.001"))]
+    id: String,
+    /// Name of the room
+    #[schema(examples("5602.EG.001 (MI HS 1, Friedrich L. Bauer Hörsaal)"))]
+    name: String,
+    /// When the next entry in the calendar of the room starts
+    ///
+    /// `null` if we don't know of any further entries
+    #[schema(examples("2039-01-19T05:00:00Z"))]
+    free_until: Option<DateTime<Utc>>,
+}
+
+impl From<FreeRoom> for FreeRoomResponse {
+    fn from(room: FreeRoom) -> Self {
+        Self {
+            id: room.key,
+            name: room.name,
+            free_until: room.free_until,
+        }
+    }
+}
+
+/// Get rooms which are free right now
+///
+/// Lists the rooms whose id starts with `prefix` that do not have any calendar entry during the next `duration_min` minutes.
+/// Only rooms whose calendar we have scraped are considered.
+#[utoipa::path(
+    tags=["calendar"],
+    params(FreeNowQueryArgs),
+    responses(
+        (status = 200, description = "**Free rooms** with the time they are free from", body = FreeNowResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The prefix is too short or the duration is out of range", body = ApiError, content_type = "application/json", example = json!({"error": "The prefix has to be at least 4 characters long", "code": "prefix_too_short"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the rooms", body = ApiError, content_type = "application/json", example = json!({"error": "could not get free rooms, please try again later", "code": "internal_error"})),
+    )
+)]
+#[get("/api/calendar/free_now")]
+pub async fn free_now_handler(
+    web::Query(args): web::Query<FreeNowQueryArgs>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let prefix = args.prefix.trim();
+    if prefix.chars().count() < MIN_PREFIX_LEN {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "prefix_too_short",
+            format!("The prefix has to be at least {MIN_PREFIX_LEN} characters long"),
+        )
+        .with_parameter("prefix")
+        .into();
+    }
+    if !(1..=MAX_DURATION_MIN).contains(&args.duration_min) {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_duration",
+            format!("The duration has to be between 1 and {MAX_DURATION_MIN} minutes"),
+        )
+        .with_parameter("duration_min")
+        .into();
+    }
+    let as_of = Utc::now();
+    let until = as_of + TimeDelta::minutes(i64::from(args.duration_min));
+    let rooms = match FreeRoom::get_free(&data.pool, prefix, &as_of, &until).await {
+        Ok(rooms) => rooms,
+        Err(e) => {
+            error!(error = ?e, prefix, "could not get free rooms");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get free rooms, please try again later",
+            )
+            .into();
+        }
+    };
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::MaxAge(60), // rooms only become (un)available on a minute basis
+            CacheDirective::Public,
+        ]))
+        .json(FreeNowResponse {
+            as_of,
+            rooms: rooms.into_iter().map(FreeRoomResponse::from).collect(),
+        })
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::EventResponse;
+use crate::localisation;
+
+/// Lines longer than this have to be folded
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Metadata of a calendar feed
+pub(super) struct Feed<'a> {
+    /// Room the feed is for, used as the name and location
+    pub name: &'a str,
+    /// How often subscribed clients should check for updates
+    pub refresh_interval: Duration,
+    /// Last time the calendar was scraped
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Renders the entries as an [RFC 5545](https://www.rfc-editor.org/rfc/rfc5545) calendar
+///
+/// Besides the standard `REFRESH-INTERVAL` of [RFC 7986](https://www.rfc-editor.org/rfc/rfc7986), `X-PUBLISHED-TTL` is set, as Outlook only understands the latter.
+pub(super) fn calendar(
+    feed: &Feed,
+    events: &[EventResponse],
+    lang: localisation::LangQueryArgs,
+) -> String {
+    let interval = format!("PT{}M", (feed.refresh_interval.as_secs() / 60).max(1));
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//NavigaTUM//Calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("NAME:{}", escape(feed.name)),
+        format!("X-WR-CALNAME:{}", escape(feed.name)),
+        format!("REFRESH-INTERVAL;VALUE=DURATION:{interval}"),
+        format!("X-PUBLISHED-TTL:{interval}"),
+    ];
+    let english = lang.should_use_english();
+    for event in events {
+        let title = if english {
+            &event.title_en
+        } else {
+            &event.title_de
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@nav.tum.de", event.id),
+            format!("DTSTAMP:{}", timestamp(&feed.last_modified)),
+            format!("DTSTART:{}", timestamp(&event.start_at)),
+            format!("DTEND:{}", timestamp(&event.end_at)),
+            format!("SUMMARY:{}", escape(title)),
+            format!("LOCATION:{}", escape(feed.name)),
+            format!("CATEGORIES:{}", escape(&event.detailed_entry_type)),
+        ]);
+        let description = [
+            event.stp_type.as_deref(),
+            event.course.as_ref().map(|c| c.code.as_str()),
+            event.course.as_ref().and_then(|c| c.group.as_deref()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if !description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&description.join("\n"))));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+/// Times are always sent in UTC, so that we don't need to ship a `VTIMEZONE`
+fn timestamp(t: &DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters which have a meaning in `TEXT` values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Terminates the line and splits it into lines of at most 75 octets
+///
+/// Continuation lines start with a space, which is not counted as part of the content.
+/// Lines are only split between characters, never within a multibyte character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::routes::calendar::{CourseResponse, EventTypeResponse};
+
+    #[test]
+    fn test_escape() {
+        let cases = [
+            ("Quantenteleportation", "Quantenteleportation"),
+            ("Vorlesung, Teil 2", "Vorlesung\\, Teil 2"),
+            ("Vorlesung; Teil 2", "Vorlesung\\; Teil 2"),
+            ("Vorlesung\r\nTeil 2", "Vorlesung\\nTeil 2"),
+            ("C:\\Windows", "C:\\\\Windows"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(escape(text), expected, "escaping {text:?}");
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short\r\n");
+        let folded = fold(&format!("SUMMARY:{}", "ä".repeat(40)));
+        for line in folded.split_terminator("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS, "{line:?} is too long");
+        }
+        assert_eq!(
+            folded.replace("\r\n ", ""),
+            format!("SUMMARY:{}\r\n", "ä".repeat(40))
+        );
+    }
+
+    #[test]
+    fn test_calendar() {
+        let event = EventResponse {
+            id: 1,
+            room_code: "5121.EG.003".into(),
+            start_at: "2012-01-01T08:00:00Z".parse().unwrap(),
+            end_at: "2012-01-01T09:30:00Z".parse().unwrap(),
+            title_de: "Quanten, Teil 1".into(),
+            title_en: "Quantum teleportation".into(),
+            stp_type: Some("Vorlesung".into()),
+            entry_type: EventTypeResponse::Lecture,
+            detailed_entry_type: "Abhaltung".into(),
+            course: Some(CourseResponse {
+                code: "PH1001".into(),
+                semester_hours: None,
+                group: Some("Gruppe 1".into()),
+            }),
+        };
+        let feed = Feed {
+            name: "5121.EG.003 (Computerraum)",
+            refresh_interval: Duration::from_secs(60 * 60),
+            last_modified: "2012-01-01T00:00:00Z".parse().unwrap(),
+        };
+        let body = calendar(&feed, &[event], localisation::LangQueryArgs::default());
+        assert!(body.split_terminator("\r\n").all(|l| !l.contains('\n')));
+        insta::assert_snapshot!(body.replace("\r\n", "\n"), @r"
+        BEGIN:VCALENDAR
+        VERSION:2.0
+        PRODID:-//NavigaTUM//Calendar//EN
+        CALSCALE:GREGORIAN
+        METHOD:PUBLISH
+        NAME:5121.EG.003 (Computerraum)
+        X-WR-CALNAME:5121.EG.003 (Computerraum)
+        REFRESH-INTERVAL;VALUE=DURATION:PT60M
+        X-PUBLISHED-TTL:PT60M
+        BEGIN:VEVENT
+        UID:1@nav.tum.de
+        DTSTAMP:20120101T000000Z
+        DTSTART:20120101T080000Z
+        DTEND:20120101T093000Z
+        SUMMARY:Quanten\, Teil 1
+        LOCATION:5121.EG.003 (Computerraum)
+        CATEGORIES:Abhaltung
+        DESCRIPTION:Vorlesung\nPH1001\nGruppe 1
+        END:VEVENT
+        END:VCALENDAR
+        ");
+    }
+}
@@ -0,0 +1,1860 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, post, web};
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::LazyLock;
+use std::time::SystemTime;
+use tracing::{error, warn};
+
+use crate::db::calendar::{CalendarLocation, Event, LocationEvents};
+use crate::error::ApiError;
+use crate::localisation;
+
+pub mod conflicts;
+pub mod course;
+mod csv;
+pub mod feed;
+pub mod free_now;
+mod ics;
+pub mod refresh;
+pub mod search;
+mod series;
+pub mod status;
+pub mod sync_status;
+pub mod url;
+pub mod week;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ContentDisposition, DispositionParam, DispositionType, ETag,
+    EntityTag, IfModifiedSince, IfNoneMatch, LastModified,
+};
+
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+
+/// How many entries are returned per location, if `CALENDAR_MAX_EVENTS` is not set
+const DEFAULT_MAX_EVENTS: usize = 3000;
+static MAX_EVENTS: LazyLock<usize> = LazyLock::new(|| {
+    let Ok(raw) = std::env::var("CALENDAR_MAX_EVENTS") else {
+        return DEFAULT_MAX_EVENTS;
+    };
+    match raw.trim().parse::<usize>() {
+        Ok(max) if max >= 1 => max,
+        _ => {
+            warn!(
+                %raw,
+                default = DEFAULT_MAX_EVENTS,
+                "CALENDAR_MAX_EVENTS is not a positive integer, using the default"
+            );
+            DEFAULT_MAX_EVENTS
+        }
+    }
+});
+
+#[derive(Serialize, Deserialize, Clone, Debug, utoipa::IntoParams, utoipa::ToSchema)]
+pub struct Arguments {
+    /// ids you want the calendars for
+    ///
+    /// Limit of max. 10 ids is arbitraryly chosen, if you need this limit increased, please contact us
+    #[schema(max_items=10,min_items=1,example=json!(["5605.EG.011","5510.02.001","5606.EG.036","5304"]))]
+    ids: Vec<String>,
+    /// The first allowed time the calendar would like to display
+    #[schema(examples("2039-01-19T03:14:07+01:00", "2042-01-07T00:00:00 UTC"))]
+    start_after: DateTime<Utc>,
+    /// The last allowed time the calendar would like to display
+    #[schema(examples("2039-01-19T03:14:07+01:00", "2042-01-07T00:00:00 UTC"))]
+    end_before: DateTime<Utc>,
+    /// Group recurring entries into series
+    ///
+    /// If set, `series` is returned for each location instead of `events`.
+    #[serde(default)]
+    group_series: bool,
+}
+
+impl Arguments {
+    fn validate_ids(&self) -> Result<Vec<String>, ApiError> {
+        validate_ids(&self.ids).map_err(|e| e.with_parameter("ids"))
+    }
+}
+
+/// Strips whitespace from the requested ids and enforces that 1..=10 are requested
+fn validate_ids(ids: &[String]) -> Result<Vec<String>, ApiError> {
+    let ids = ids
+        .iter()
+        .map(|s| s.replace(|c: char| c.is_whitespace() || c.is_control(), ""))
+        .collect::<Vec<String>>();
+    if ids.len() > 10 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "too_many_ids",
+            "Too many ids to query. We suspect that users don't need this. If you need this limit increased, please send us a message",
+        ));
+    };
+    if ids.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "no_ids",
+            "No id requested",
+        ));
+    };
+    Ok(ids)
+}
+
+/// Retrieve Calendar Entries
+///
+/// Retrieves calendar entries for specific `ids` within the requested time span.
+/// The time span is defined by the `start_after` and `end_before` query parameters.
+/// Ensure to provide valid date-time formats for these parameters.
+///
+/// If successful, returns additional entries in the requested time span.
+///
+/// Responses carry a weak `ETag` and a `Last-Modified` header.
+/// Calendars are only re-scraped every few hours, so clients polling this endpoint should send `If-None-Match` (or `If-Modified-Since`) and will get a `304 Not Modified` without a body if nothing changed.
+///
+/// For importing into spreadsheets, the entries can be requested as CSV via `format=csv` or `Accept: text/csv`.
+/// The header row is localised via `lang`.
+///
+/// To keep responses reasonably sized, at most 3000 entries (by default) are returned per location.
+/// If a calendar has more entries in the requested time span, its response is marked as `truncated` and only contains the earliest entries.
+/// `complete_until` then says up to which time the entries are complete, so the rest can be requested with `start_after` set to it.
+#[utoipa::path(
+    tags=["calendar"],
+    params(localisation::LangQueryArgs, csv::FormatQueryArgs),
+    responses(
+        (status = 200, description = "**Entries of the calendar** in the requested time span", content(
+            (HashMap<String, LocationEventsResponse> = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 304, description = "**Not Modified.** The calendar did not change since the version identified by `If-None-Match`/`If-Modified-Since`"),
+        (status = 400, description= "**Bad Request.** Not all fields in the body are present as defined above", body = ApiError, content_type = "application/json", example = json!({"error": "Too many ids to query. We suspect that users don't need this. If you need this limit increased, please send us a message", "code": "too_many_ids"})),
+        (status = 404, description = "**Not found.** The requested location does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002/None does not have a calendar", "code": "no_calendar"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the calendar entries", body = ApiError, content_type = "application/json", example = json!({"error": "could not get calendar entries, please try again later", "code": "internal_error"})),
+        (status = 503, description = "**Not Ready.** please retry later", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.003/None calendar entry is currently in the process of being scraped, please try again later", "code": "not_yet_scraped"})),
+    )
+)]
+#[post("/api/calendar")]
+pub async fn calendar_handler(
+    req: HttpRequest,
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    web::Query(format): web::Query<csv::FormatQueryArgs>,
+    web::Json(args): web::Json<Arguments>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let format = format.negotiate(&req);
+    let ids = match args.validate_ids() {
+        Ok(ids) => ids,
+        Err(e) => return e.into(),
+    };
+    let locations = match data
+        .calendar_locations
+        .get_locations(&data.pool, &ids)
+        .await
+    {
+        Ok(l) => l.into_inner(),
+        Err(e) => {
+            error!(error = ?e, "could not refetch");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get calendar entries, please try again later",
+            )
+            .into();
+        }
+    };
+    if let Err(e) = validate_locations(&ids, &locations) {
+        return e.into();
+    }
+    let last_modified = locations
+        .iter()
+        .filter_map(|l| l.last_calendar_scrape_at)
+        .max()
+        .expect("validate_locations ensures that at least one scraped location exists");
+    let events = match LocationEvents::get_from_db(
+        &data.pool,
+        locations,
+        &args.start_after,
+        &args.end_before,
+        *MAX_EVENTS,
+    )
+    .await
+    {
+        Ok(events) => events.0,
+        Err(e) => {
+            error!(error = ?e,ids = ?ids,"could not get entries from the db");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get calendar entries, please try again later",
+            )
+            .into();
+        }
+    };
+    let entry_count = events.values().map(|e| e.events.len()).sum();
+    let etag = calendar_etag(&ids, &args, lang, format, last_modified, entry_count);
+    let cache_control = CacheControl(vec![
+        CacheDirective::MaxAge(60 * 60), // valid for 1h
+        CacheDirective::Public,
+    ]);
+    // HTTP dates only have second precision
+    let last_modified = LastModified(SystemTime::from(last_modified.trunc_subsecs(0)).into());
+    if is_not_modified(&req, &etag, &last_modified) {
+        return HttpResponse::NotModified()
+            .insert_header(cache_control)
+            .insert_header(ETag(etag))
+            .insert_header(last_modified)
+            .finish();
+    }
+    let events = events
+        .into_iter()
+        .map(|(id, events)| {
+            let mut events = LocationEventsResponse::from(events);
+            if lang.should_use_english() {
+                events.translate_to_english();
+            }
+            if args.group_series && format == csv::ResponseFormat::Json {
+                events.group_into_series();
+            }
+            (id, events)
+        })
+        .collect::<HashMap<_, _>>();
+    if format == csv::ResponseFormat::Csv {
+        let events = events
+            .into_values()
+            .flat_map(|l| l.events.unwrap_or_default())
+            .collect();
+        return HttpResponse::Ok()
+            .insert_header(cache_control)
+            .insert_header(ETag(etag))
+            .insert_header(last_modified)
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename("calendar.csv".to_string())],
+            })
+            .content_type("text/csv; charset=utf-8")
+            .streaming(csv::event_rows(events, lang));
+    }
+    HttpResponse::Ok()
+        .insert_header(cache_control)
+        .insert_header(ETag(etag))
+        .insert_header(last_modified)
+        .json(events)
+}
+
+/// Weak validator for a calendar response
+///
+/// Calendars only change when they are re-scraped.
+/// The newest scrape time and the number of entries (to notice deletions) thus identify a response for a given query.
+fn calendar_etag(
+    ids: &[String],
+    args: &Arguments,
+    lang: localisation::LangQueryArgs,
+    format: csv::ResponseFormat,
+    last_modified: DateTime<Utc>,
+    entry_count: usize,
+) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    ids.hash(&mut hasher);
+    args.start_after.hash(&mut hasher);
+    args.end_before.hash(&mut hasher);
+    lang.should_use_english().hash(&mut hasher);
+    args.group_series.hash(&mut hasher);
+    format.hash(&mut hasher);
+    last_modified.hash(&mut hasher);
+    entry_count.hash(&mut hasher);
+    EntityTag::new_weak(format!("{:016x}", hasher.finish()))
+}
+
+/// Evaluates the conditional request headers as described in [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2)
+///
+/// `If-Modified-Since` is only considered if no `If-None-Match` is present.
+fn is_not_modified(req: &HttpRequest, etag: &EntityTag, last_modified: &LastModified) -> bool {
+    if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+        return match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(tags) => tags.iter().any(|t| t.weak_eq(etag)),
+        };
+    }
+    match req.get_header::<IfModifiedSince>() {
+        Some(IfModifiedSince(since)) => last_modified.0 <= since,
+        None => false,
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct LocationEventsResponse {
+    /// Entries of the calendar
+    ///
+    /// Not present if `group_series` was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Option<Vec<EventResponse>>,
+    /// Entries of the calendar, grouped into series of recurring entries
+    ///
+    /// Only present if `group_series` was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    series: Option<Vec<series::EventSeriesResponse>>,
+    location: CalendarLocationResponse,
+    /// If the calendar has more entries in the requested time span than we return at once
+    ///
+    /// Not present if all entries are included
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[schema(examples(true))]
+    truncated: bool,
+    /// Up to when the entries are complete if `truncated`
+    ///
+    /// All entries starting before this time are included.
+    /// To get the remaining ones, request again with `start_after` set to this time.
+    /// Entries starting exactly at this time may be returned twice => please deduplicate them by their `id`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(examples("2039-01-19T03:14:07Z"))]
+    complete_until: Option<DateTime<Utc>>,
+}
+impl From<LocationEvents> for LocationEventsResponse {
+    fn from(value: LocationEvents) -> Self {
+        LocationEventsResponse {
+            events: Some(value.events.into_iter().map(EventResponse::from).collect()),
+            series: None,
+            location: CalendarLocationResponse::from(value.location),
+            truncated: value.complete_until.is_some(),
+            complete_until: value.complete_until,
+        }
+    }
+}
+impl LocationEventsResponse {
+    fn translate_to_english(&mut self) {
+        for event in self.events.iter_mut().flatten() {
+            event.translate_to_english();
+        }
+    }
+    fn group_into_series(&mut self) {
+        if let Some(events) = self.events.take() {
+            self.series = Some(series::group_into_series(events));
+        }
+    }
+}
+fn validate_locations(ids: &[String], locations: &[CalendarLocation]) -> Result<(), ApiError> {
+    for id in ids {
+        if !locations.iter().any(|l| &l.key == id) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "unknown_id",
+                format!("Requested id {id} does not exist"),
+            )
+            .with_parameter("ids"));
+        }
+    }
+    assert_eq!(locations.len(), ids.len());
+    for loc in locations {
+        if loc.last_calendar_scrape_at.is_none() {
+            return Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "not_yet_scraped",
+                format!(
+                    "Room {key}/{url:?} calendar entry is currently in the process of being scraped, please try again later",
+                    key = loc.key,
+                    url = loc.calendar_url
+                ),
+            ));
+        };
+    }
+    for loc in locations {
+        if loc.calendar_url.is_none() {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "no_calendar",
+                format!(
+                    "Room {key}/{url:?} does not have a calendar",
+                    key = loc.key,
+                    url = loc.calendar_url
+                ),
+            ));
+        };
+    }
+    Ok(())
+}
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CalendarLocationResponse {
+    /// Structured, globaly unique room code
+    ///
+    /// Included to enable multi-room calendars.
+    /// Format: BUILDING.LEVEL.NUMBER
+    #[schema(examples("5602.EG.001", "5121.EG.003"))]
+    key: String,
+    /// name of the entry in a human-readable form
+    #[schema(examples(
+        "5602.EG.001 (MI HS 1, Friedrich L. Bauer Hörsaal)",
+        "5121.EG.003 (Computerraum)"
+    ))]
+    name: String,
+    /// last time the calendar was scraped for this room
+    #[schema(examples("2039-01-19T03:14:07+01:00", "2042-01-07T00:00:00 UTC"))]
+    last_calendar_scrape_at: DateTime<Utc>,
+    /// Link to the calendar of the room
+    #[schema(examples(
+        "https://campus.tum.de/tumonline/tvKalender.wSicht?cOrg=19691&cRes=12543&cReadonly=J",
+        "https://campus.tum.de/tumonline/tvKalender.wSicht?cOrg=19691&cRes=12559&cReadonly=J"
+    ))]
+    calendar_url: Option<String>,
+    /// Type of the entry in a human-readable form
+    #[schema(examples("Serverraum", "Büro"))]
+    type_common_name: String,
+    /// type of the entry
+    ///
+    /// TODO document as a n enum with the following choices:
+    /// - `room`
+    /// - `building`
+    /// - `joined_building`
+    /// - `area`
+    /// - `site`
+    /// - `campus`
+    /// - `poi`
+    #[schema(examples("room", "building", "joined_building", "area", "site", "campus", "poi"))]
+    r#type: String,
+}
+impl From<CalendarLocation> for CalendarLocationResponse {
+    fn from(value: CalendarLocation) -> Self {
+        CalendarLocationResponse {
+            key: value.key,
+            name: value.name,
+            last_calendar_scrape_at: value
+                .last_calendar_scrape_at
+                .expect("we filterd for last_calendar_scrape_at in the step beforehand"),
+            calendar_url: value.calendar_url,
+            type_common_name: value.type_common_name,
+            r#type: value.r#type,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, utoipa::ToSchema)]
+struct EventResponse {
+    /// ID of the calendar entry used in TUMonline internally
+    #[schema(examples(6424))]
+    id: i32,
+    /// Structured, globaly unique room code
+    ///
+    /// Included to enable multi-room calendars.
+    /// Format: BUILDING.LEVEL.NUMBER
+    #[schema(examples("5602.EG.001", "5121.EG.003"))]
+    room_code: String,
+    /// start of the entry
+    #[schema(examples("2018-01-01T00:00:00"))]
+    start_at: DateTime<Utc>,
+    /// end of the entry
+    #[schema(examples("2019-01-01T00:00:00"))]
+    end_at: DateTime<Utc>,
+    /// German title of the Entry
+    #[schema(examples("Quantenteleportation"))]
+    title_de: String,
+    /// English title of the Entry
+    #[schema(examples("Quantum teleportation"))]
+    title_en: String,
+    /// Lecture-type
+    ///
+    /// Translated to english if `lang=en` is requested and we know the translation.
+    #[schema(examples("Vorlesung mit Zentralübung", "Lecture with central exercise"))]
+    stp_type: Option<String>,
+    /// What this calendar entry means.
+    ///
+    /// Each of these should be displayed in a different color.
+    /// `cancelled` and `moved` entries no longer take place here and should be struck through.
+    entry_type: EventTypeResponse,
+    /// For some Entrys, we do have more information (what kind of a `lecture` is it? What kind of an other `entry` is it?)
+    ///
+    /// Translated to english if `lang=en` is requested and we know the translation.
+    #[schema(examples("Abhaltung", "Course session"))]
+    detailed_entry_type: String,
+    /// The course this entry belongs to
+    ///
+    /// Only present for entries which are part of a course
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    course: Option<CourseResponse>,
+}
+impl EventResponse {
+    fn translate_to_english(&mut self) {
+        self.stp_type = self
+            .stp_type
+            .as_deref()
+            .map(|t| translate_type_name(t).to_string());
+        self.detailed_entry_type = translate_type_name(&self.detailed_entry_type).to_string();
+    }
+}
+
+/// Known course- and event type names used by TUMonline and their english translation
+const TYPE_NAME_TRANSLATIONS: &[(&str, &str)] = &[
+    // course types
+    ("Vorlesung", "Lecture"),
+    ("Übung", "Exercise"),
+    (
+        "Vorlesung mit Zentralübung",
+        "Lecture with central exercise",
+    ),
+    (
+        "Vorlesung mit integrierten Übungen",
+        "Lecture with integrated exercises",
+    ),
+    ("Seminar", "Seminar"),
+    ("Hauptseminar", "Advanced seminar"),
+    ("Proseminar", "Proseminar"),
+    ("Praktikum", "Lab course"),
+    ("Projekt", "Project"),
+    ("Kolloquium", "Colloquium"),
+    ("Tutorium", "Tutorial"),
+    ("Repetitorium", "Revision course"),
+    ("Exkursion", "Excursion"),
+    ("Workshop", "Workshop"),
+    // event types
+    ("Abhaltung", "Course session"),
+    ("Prüfung", "Exam"),
+    ("Prüfungstermin", "Exam date"),
+    ("Klausur", "Written exam"),
+    ("Sperre", "Blocked"),
+    ("Veranstaltung", "Event"),
+    ("Besprechung", "Meeting"),
+    ("Vortrag", "Talk"),
+    ("Sitzung", "Session"),
+    ("Reservierung", "Reservation"),
+    ("Wartung", "Maintenance"),
+    ("Reinigung", "Cleaning"),
+    ("Feiertag", "Public holiday"),
+    ("Abgesagt", "Cancelled"),
+    ("Verschoben", "Moved"),
+    ("Online", "Online"),
+    ("Sonstiges", "Other"),
+];
+
+/// Translates a course- or event type name from TUMonline to english
+///
+/// Falls back to the german name for names we don't know (yet)
+fn translate_type_name(german: &str) -> &str {
+    match TYPE_NAME_TRANSLATIONS.iter().find(|(de, _)| *de == german) {
+        Some(&(_, en)) => en,
+        None => {
+            warn!(german, "no english translation for this type name known");
+            german
+        }
+    }
+}
+impl From<Event> for EventResponse {
+    fn from(value: Event) -> Self {
+        EventResponse {
+            id: value.id,
+            room_code: value.room_code,
+            start_at: value.start_at,
+            end_at: value.end_at,
+            title_de: value.title_de,
+            title_en: value.title_en,
+            stp_type: value.stp_type,
+            entry_type: EventTypeResponse::classify(&value.entry_type, &value.detailed_entry_type),
+            detailed_entry_type: value.detailed_entry_type,
+            course: value.course_code.map(|code| CourseResponse {
+                code,
+                semester_hours: value.course_semester_hours,
+                group: value.course_group,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, utoipa::ToSchema)]
+struct CourseResponse {
+    /// Code of the course in the course catalog of TUMonline
+    #[schema(examples("PH1001", "IN0001"))]
+    code: String,
+    /// How many hours per week the course takes during the semester
+    #[schema(examples(4, 2))]
+    semester_hours: Option<i32>,
+    /// Name of the group of the course this entry is for
+    #[schema(examples("Gruppe 1", "Zentralübung"))]
+    group: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTypeResponse {
+    Lecture,
+    Exercise,
+    Exam,
+    Barred,
+    /// The entry does not take place, but is still listed by TUMonline
+    Cancelled,
+    /// The entry was moved to a different time or room
+    Moved,
+    /// The entry takes place online instead of in the room
+    Online,
+    Other,
+}
+/// Parts of the detailed entry type with which TUMonline marks entries which don't take place as usual
+///
+/// Checked in order, case-insensitively
+const STATUS_KEYWORDS: &[(&str, EventTypeResponse)] = &[
+    ("abgesagt", EventTypeResponse::Cancelled),
+    ("absage", EventTypeResponse::Cancelled),
+    ("storniert", EventTypeResponse::Cancelled),
+    ("entfällt", EventTypeResponse::Cancelled),
+    ("verschoben", EventTypeResponse::Moved),
+    ("verlegt", EventTypeResponse::Moved),
+    ("online", EventTypeResponse::Online),
+];
+impl EventTypeResponse {
+    /// Classifies an entry by its `entry_type`, unless the `detailed_entry_type` says that it does not take place as usual
+    fn classify(entry_type: &str, detailed_entry_type: &str) -> Self {
+        let detailed = detailed_entry_type.to_lowercase();
+        if let Some(&(_, status)) = STATUS_KEYWORDS.iter().find(|(k, _)| detailed.contains(k)) {
+            return status;
+        }
+        match entry_type {
+            "lecture" => EventTypeResponse::Lecture,
+            "exercise" => EventTypeResponse::Exercise,
+            "exam" => EventTypeResponse::Exam,
+            "barred" => EventTypeResponse::Barred,
+            "cancelled" => EventTypeResponse::Cancelled,
+            "moved" => EventTypeResponse::Moved,
+            "online" => EventTypeResponse::Online,
+            _ => EventTypeResponse::Other,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_translate_type_name() {
+        let cases = [
+            ("Vorlesung", "Lecture"),
+            (
+                "Vorlesung mit Zentralübung",
+                "Lecture with central exercise",
+            ),
+            ("Übung", "Exercise"),
+            ("Abhaltung", "Course session"),
+            ("Prüfung", "Exam"),
+            ("Sperre", "Blocked"),
+            // unknown names fall back to german
+            ("Quantenverschränkung", "Quantenverschränkung"),
+            ("", ""),
+        ];
+        for (german, english) in cases {
+            assert_eq!(
+                translate_type_name(german),
+                english,
+                "translating {german:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_classify_entry_type() {
+        let cases = [
+            ("lecture", "Abhaltung", EventTypeResponse::Lecture),
+            ("exercise", "Abhaltung", EventTypeResponse::Exercise),
+            ("exam", "Prüfungstermin", EventTypeResponse::Exam),
+            ("barred", "Sperre", EventTypeResponse::Barred),
+            ("other", "Sonstiges", EventTypeResponse::Other),
+            ("something new", "Abhaltung", EventTypeResponse::Other),
+            ("lecture", "Abgesagt", EventTypeResponse::Cancelled),
+            (
+                "lecture",
+                "Abhaltung abgesagt",
+                EventTypeResponse::Cancelled,
+            ),
+            ("exam", "Termin entfällt", EventTypeResponse::Cancelled),
+            ("lecture", "Verschoben", EventTypeResponse::Moved),
+            ("exercise", "Online", EventTypeResponse::Online),
+            ("cancelled", "Abhaltung", EventTypeResponse::Cancelled),
+        ];
+        for (entry_type, detailed_entry_type, expected) in cases {
+            assert_eq!(
+                EventTypeResponse::classify(entry_type, detailed_entry_type),
+                expected,
+                "classifying {entry_type:?}/{detailed_entry_type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_translation_table_is_unambiguous() {
+        for (i, (de, _)) in TYPE_NAME_TRANSLATIONS.iter().enumerate() {
+            assert!(
+                !TYPE_NAME_TRANSLATIONS[i + 1..].iter().any(|(d, _)| d == de),
+                "{de} is translated twice"
+            );
+        }
+    }
+
+    #[test]
+    fn test_translate_event() {
+        let mut event = EventResponse {
+            id: 1,
+            room_code: "5121.EG.003".into(),
+            start_at: Utc::now(),
+            end_at: Utc::now(),
+            title_de: "Quantenteleportation".into(),
+            title_en: "Quantum teleportation".into(),
+            stp_type: Some("Vorlesung mit Zentralübung".into()),
+            entry_type: EventTypeResponse::Lecture,
+            detailed_entry_type: "Abhaltung".into(),
+            course: None,
+        };
+        event.translate_to_english();
+        assert_eq!(
+            event.stp_type.as_deref(),
+            Some("Lecture with central exercise")
+        );
+        assert_eq!(event.detailed_entry_type, "Course session");
+        // titles are already localised by TUMonline
+        assert_eq!(event.title_de, "Quantenteleportation");
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use actix_web::App;
+    use actix_web::http::header::{
+        CACHE_CONTROL, ContentType, ETAG, HeaderName, HeaderValue, IF_MODIFIED_SINCE,
+        IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use actix_web::test;
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::AppData;
+    use crate::db::calendar::{EventType, FreeRoom};
+    use crate::limited::vec::LimitedVec;
+    use crate::setup::tests::PostgresTestContainer;
+
+    /// Workaround because [`Option::unwrap()`] is not (yet) available in const context.
+    /// See https://github.com/rust-lang/rust/issues/67441 for further context
+    const fn unwrap<T: Copy>(opt: Option<T>) -> T {
+        match opt {
+            Some(val) => val,
+            None => panic!("unwrapped None"),
+        }
+    }
+    const fn datetime_from_ymd(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        let date = unwrap(NaiveDate::from_ymd_opt(year, month, day));
+        let time = unwrap(NaiveTime::from_num_seconds_from_midnight_opt(0, 0));
+        let naive_datetime = NaiveDateTime::new(date, time);
+        DateTime::from_naive_utc_and_offset(naive_datetime, Utc)
+    }
+    const TIME_Y2K: DateTime<Utc> = datetime_from_ymd(2000, 1, 1);
+    const TIME_2010: DateTime<Utc> = datetime_from_ymd(2010, 1, 1);
+    const TIME_2012: DateTime<Utc> = datetime_from_ymd(2012, 1, 1);
+    const TIME_2014: DateTime<Utc> = datetime_from_ymd(2014, 1, 1);
+    const TIME_2016: DateTime<Utc> = datetime_from_ymd(2016, 1, 1);
+    const TIME_2020: DateTime<Utc> = datetime_from_ymd(2020, 1, 1);
+
+    fn sample_data() -> (Vec<(String, Value)>, Vec<Event>) {
+        (
+            vec![
+                (
+                    "5121.EG.003".into(),
+                    serde_json::json!({"aliases":["003@5121"],"coords":{"accuracy":"building","lat":48.26842603718826,"lon":11.677995005953209,"source":"inferred"},"id":"5121.EG.003","maps":{"default":"interactive"},"name":"5121.EG.003 (Computerraum)","parent_names":["Standorte","Garching Forschungszentrum","Physik","Maier-Leibnitz-Laboratorium (MLL), TUM & LMU","Atlashalle"],"parents":["root","garching","physik","mll","5121"],"poi":{"nearby_public_transport":{"mvg":[]}},"props":{"calendar_url":"https://campus.tum.de/3","computed":[{"name":"Raumkennung","text":"5121.EG.003"},{"name":"Architekten-Name","text":"003"},{"name":"Stockwerk","text":"Erdgeschoss"},{"name":"Adresse","text":"Am Coulombwall 6, 85748 Garching b. München"}],"operator":{"code":"TUPELMU","id":39536,"name":"Ludwig-Maximilians-Universität München (LMU)","url":"https://campus.tum.de/tumonline/webnav.navigate_to?corg=39536"},"tumonline_room_nr":45064},"ranking_factors":{"rank_combined":10,"rank_type":100,"rank_usage":10},"sources":{"base":[{"name":"TUMonline","url":"https://campus.tum.de/tumonline/ee/ui/ca2/app/desktop/#/pl/ui/$ctx/45064"}]},"type":"room","type_common_name":"Serverraum","usage":{"din_277":"TF8.9","din_277_desc":"Sonstige betriebstechnische Anlagen","name":"Serverraum"},"redirect_url":"/room/5121.EG.003"}),
+                ),
+                (
+                    "5121.EG.002".into(),
+                    serde_json::json!({"aliases":["002@5121"],"coords":{"accuracy":"building","lat":48.26842603718826,"lon":11.677995005953209,"source":"inferred"},"id":"5121.EG.002","maps":{"default":"interactive"},"name":"5121.EG.002 (Testroom)","parent_names":["Standorte","Garching Forschungszentrum","Physik","Maier-Leibnitz-Laboratorium (MLL),TUM & LMU","Atlashalle"],"parents":["root","garching","physik","mll","5121"],"poi":{"nearby_public_transport":{"mvg":[]}},"props":{"computed":[{"name":"Raumkennung","text":"5121.EG.002"},{"name":"Architekten-Name","text":"002"},{"name":"Stockwerk","text":"Erdgeschoss"},{"name":"Adresse","text":"Am Coulombwall 6,85748 Garching b. München"}  ],"operator":{"code":"TUPELMU","id":39536,"name":"Ludwig-Maximilians-Universität München (LMU)","url":"https://campus.tum.de/tumonline/webnav.navigate_to?corg=39536"},"tumonline_room_nr":44904},"ranking_factors":{"rank_combined":10,"rank_type":100,"rank_usage":10},"sources":{"base":[{"name":"TUMonline","url":"https://campus.tum.de/tumonline/ee/ui/ca2/app/desktop/#/pl/ui/$ctx/44904"}  ]},"type":"room","type_common_name":"Versuchshalle","usage":{"din_277":"NF3.3","din_277_desc":"Technologische Labors","name":"Versuchshalle"},"redirect_url":"/room/5121.EG.002"}),
+                ),
+                (
+                    "5121.EG.001".into(),
+                    serde_json::json!({"aliases":["001@5121"],"coords":{"accuracy":"building","lat":48.26842603718826,"lon":11.677995005953209,"source":"inferred"},"id":"5121.EG.001","maps":{"default":"interactive"},"name":"5121.EG.001 (Montage- und Versuchshalle)","parent_names":["Standorte","Garching Forschungszentrum","Physik","Maier-Leibnitz-Laboratorium (MLL),TUM & LMU","Atlashalle"],"parents":["root","garching","physik","mll","5121"],"poi":{"nearby_public_transport":{"mvg":[]}},"props":{"calendar_url":"https://campus.tum.de/1","computed":[{"name":"Raumkennung","text":"5121.EG.001"},{"name":"Architekten-Name","text":"001"},{"name":"Stockwerk","text":"Erdgeschoss"},{"name":"Adresse","text":"Am Coulombwall 6,85748 Garching b. München"}  ],"operator":{"code":"TUPELMU","id":39536,"name":"Ludwig-Maximilians-Universität München (LMU)","url":"https://campus.tum.de/tumonline/webnav.navigate_to?corg=39536"},"tumonline_room_nr":44904},"ranking_factors":{"rank_combined":10,"rank_type":100,"rank_usage":10},"sources":{"base":[{"name":"TUMonline","url":"https://campus.tum.de/tumonline/ee/ui/ca2/app/desktop/#/pl/ui/$ctx/44904"}  ]},"type":"room","type_common_name":"Versuchshalle","usage":{"din_277":"NF3.3","din_277_desc":"Technologische Labors","name":"Versuchshalle"},"redirect_url":"/room/5121.EG.001"}),
+                ),
+            ],
+            vec![
+                Event {
+                    id: 1,
+                    room_code: "5121.EG.003".into(),
+                    start_at: TIME_2012,
+                    end_at: TIME_2014,
+                    title_de: "Quantenteleportation".into(),
+                    title_en: "Quantum teleportation".into(),
+                    stp_type: Some("Vorlesung mit Zentralübung".into()),
+                    entry_type: EventType::Lecture.to_string(),
+                    detailed_entry_type: "Abhaltung".into(),
+                    course_code: Some("PH1001".into()),
+                    course_semester_hours: Some(4),
+                    course_group: Some("Gruppe 1".into()),
+                },
+                Event {
+                    id: 2,
+                    room_code: "5121.EG.003".into(),
+                    start_at: TIME_2014,
+                    end_at: TIME_2016,
+                    title_de: "Quantenteleportation 2".into(),
+                    title_en: "Quantum teleportation 2".into(),
+                    stp_type: Some("Vorlesung mit Zentralübung".into()),
+                    entry_type: EventType::Lecture.to_string(),
+                    detailed_entry_type: "Abhaltung".into(),
+                    course_code: None,
+                    course_semester_hours: None,
+                    course_group: None,
+                },
+                Event {
+                    id: 3,
+                    room_code: "5121.EG.001".into(),
+                    start_at: TIME_2014,
+                    end_at: TIME_2016,
+                    title_de: "Wartung".into(),
+                    title_en: "maintenance".into(),
+                    stp_type: Some("Vorlesung mit Zentralübung".into()),
+                    entry_type: EventType::Barred.to_string(),
+                    detailed_entry_type: "Abhaltung".into(),
+                    course_code: None,
+                    course_semester_hours: None,
+                    course_group: None,
+                },
+                Event {
+                    id: 4,
+                    room_code: "5121.EG.001".into(),
+                    start_at: TIME_Y2K,
+                    end_at: TIME_2020,
+                    title_de: "Quantenteleportation 3".into(),
+                    title_en: "Quantum teleportation 3".into(),
+                    stp_type: Some("Vorlesung".into()),
+                    entry_type: EventType::Other.to_string(),
+                    detailed_entry_type: "Abhaltung".into(),
+                    course_code: None,
+                    course_semester_hours: None,
+                    course_group: None,
+                },
+                Event {
+                    id: 5,
+                    room_code: "5121.EG.001".into(),
+                    start_at: TIME_Y2K,
+                    end_at: TIME_2010,
+                    title_de: "Quantenteleportation 3".into(),
+                    title_en: "Quantum teleportation 3".into(),
+                    stp_type: Some("Vorlesung".into()),
+                    entry_type: EventType::Exam.to_string(),
+                    detailed_entry_type: "Abhaltung".into(),
+                    course_code: None,
+                    course_semester_hours: None,
+                    course_group: None,
+                },
+            ],
+        )
+    }
+
+    async fn load_sample_data(pool: &sqlx::PgPool, now_rfc3339: &str) {
+        let mut tx = pool.begin().await.unwrap();
+        let (locations, events) = sample_data();
+        for (key, data) in locations {
+            for lang in ["de", "en"] {
+                let query = format!(
+                    "INSERT INTO {lang}(key,data,last_calendar_scrape_at) VALUES ('{key}','{data}','{now_rfc3339}')"
+                );
+                sqlx::query(&query).execute(&mut *tx).await.unwrap();
+            }
+        }
+
+        for event in events {
+            event.store(&mut tx).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_index_get() {
+        // setup + load data into postgis
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now();
+        let now = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true); // throwing away accuracy for simpler testing
+        load_sample_data(&pg.pool, &now).await;
+        // set up the http service/api/calendar
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .app_data(crate::error::json_config(1024 * 1024))
+                .service(calendar_handler),
+        )
+        .await;
+        // -- send requests and assert response --
+        {
+            // missing required query parameters
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 400);
+            insta::assert_snapshot!(actual, @r###"{"code":"invalid_body","error":"Json deserialize error: EOF while parsing a value at line 1 column 0"}"###);
+        }
+        {
+            // missing required query parameters
+            let args = Arguments {
+                end_before: Utc::now(),
+                start_after: Utc::now(),
+                ids: vec![],
+                group_series: false,
+            };
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .set_json(args)
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 400);
+            insta::assert_snapshot!(actual, @r###"{"code":"no_ids","error":"No id requested","parameter":"ids"}"###);
+        }
+        {
+            // way too many parameters
+            let args = Arguments {
+                end_before: Utc::now(),
+                start_after: Utc::now(),
+                ids: (0..10_000).map(|i| i.to_string()).collect(),
+                group_series: false,
+            };
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .set_json(args)
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 400);
+            insta::assert_snapshot!(actual, @r###"{"code":"too_many_ids","error":"Too many ids to query. We suspect that users don't need this. If you need this limit increased, please send us a message","parameter":"ids"}"###);
+        }
+        {
+            // room without a calendar
+            let args = Arguments {
+                end_before: Utc::now(),
+                start_after: Utc::now(),
+                ids: vec!["5121.EG.002".into()],
+                group_series: false,
+            };
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .set_json(args)
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 404);
+            insta::assert_snapshot!(actual, @r###"{"code":"no_calendar","error":"Room 5121.EG.002/None does not have a calendar"}"###);
+        }
+        {
+            // show all entries of 5121.EG.003
+            let args = Arguments {
+                start_after: TIME_Y2K,
+                end_before: TIME_2020,
+                ids: vec!["5121.EG.003".into()],
+                group_series: false,
+            };
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .set_json(args)
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 200);
+            insta::assert_yaml_snapshot!(actual, {".**.last_calendar_scrape_at" => "[last_calendar_scrape_at]"});
+        }
+        {
+            // show both rooms, but a limited timeframe
+            let args = Arguments {
+                start_after: TIME_2012,
+                end_before: TIME_2014,
+                ids: vec!["5121.EG.003".into(), "5121.EG.001".into()],
+                group_series: false,
+            };
+            let req = test::TestRequest::post()
+                .uri("/api/calendar")
+                .set_json(args)
+                .insert_header(ContentType::json())
+                .to_request();
+            let (_, resp) = test::call_service(&app, req).await.into_parts();
+
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 200);
+            insta::assert_yaml_snapshot!(actual, {".**.last_calendar_scrape_at" => "[last_calendar_scrape_at]"});
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_empty_window() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(calendar_handler),
+        )
+        .await;
+        // e.g. a semester break => none of the rooms has an entry
+        let args = Arguments {
+            start_after: TIME_2016,
+            end_before: TIME_2020,
+            ids: vec!["5121.EG.003".into(), "5121.EG.001".into()],
+            group_series: false,
+        };
+        let req = test::TestRequest::post()
+            .uri("/api/calendar")
+            .set_json(args)
+            .to_request();
+        let (_, resp) = test::call_service(&app, req).await.into_parts();
+        assert!(resp.headers().contains_key(LAST_MODIFIED));
+        let (status, actual) = run_testcase(resp).await;
+        assert_eq!(status, 200);
+        for id in ["5121.EG.003", "5121.EG.001"] {
+            assert_eq!(actual[id]["events"], serde_json::json!([]), "{id}");
+            assert_eq!(
+                actual[id]["location"]["last_calendar_scrape_at"], now,
+                "{id}"
+            );
+        }
+    }
+
+    fn conditional_request(
+        args: &Arguments,
+        header: Option<(HeaderName, HeaderValue)>,
+    ) -> test::TestRequest {
+        let mut req = test::TestRequest::post()
+            .uri("/api/calendar")
+            .set_json(args)
+            .insert_header(ContentType::json());
+        if let Some(header) = header {
+            req = req.insert_header(header);
+        }
+        req
+    }
+
+    #[actix_web::test]
+    async fn test_conditional_requests() {
+        // setup + load data into postgis
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now();
+        let now_rfc3339 = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now_rfc3339).await;
+        let data = AppData::from(pg.pool.clone());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(data.clone()))
+                .service(calendar_handler),
+        )
+        .await;
+        let args = Arguments {
+            start_after: TIME_Y2K,
+            end_before: TIME_2020,
+            ids: vec!["5121.EG.003".into()],
+            group_series: false,
+        };
+        // -- initial request --
+        let resp = test::call_service(&app, conditional_request(&args, None).to_request()).await;
+        assert_eq!(resp.status(), 200);
+        let etag = resp.headers().get(ETAG).unwrap().clone();
+        assert!(etag.to_str().unwrap().starts_with("W/"));
+        let last_modified = resp.headers().get(LAST_MODIFIED).unwrap().clone();
+        // -- nothing changed --
+        {
+            let resp = test::call_service(
+                &app,
+                conditional_request(&args, Some((IF_NONE_MATCH, etag.clone()))).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 304);
+            assert_eq!(resp.headers().get(ETAG), Some(&etag));
+            let body = test::read_body(resp).await;
+            assert!(body.is_empty());
+        }
+        {
+            let resp = test::call_service(
+                &app,
+                conditional_request(&args, Some((IF_MODIFIED_SINCE, last_modified.clone())))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 304);
+        }
+        {
+            // a different query is a different resource
+            let args = Arguments {
+                start_after: TIME_2012,
+                ..args.clone()
+            };
+            let resp = test::call_service(
+                &app,
+                conditional_request(&args, Some((IF_NONE_MATCH, etag.clone()))).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 200);
+        }
+        // -- the scraper updates the room --
+        let (_, mut events) = sample_data();
+        events.retain(|e| e.room_code == "5121.EG.003");
+        events.pop();
+        Event::store_all(&pg.pool, events.into(), "5121.EG.003")
+            .await
+            .unwrap();
+        Event::update_last_calendar_scrape_at(
+            &pg.pool,
+            "5121.EG.003",
+            &(now + chrono::Duration::hours(1)),
+        )
+        .await
+        .unwrap();
+        data.calendar_locations.invalidate("5121.EG.003");
+        {
+            let resp = test::call_service(
+                &app,
+                conditional_request(&args, Some((IF_NONE_MATCH, etag.clone()))).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 200);
+            assert_ne!(resp.headers().get(ETAG), Some(&etag));
+            let (_, actual) = run_testcase(resp.into_parts().1).await;
+            assert_eq!(actual["5121.EG.003"]["events"].as_array().unwrap().len(), 1);
+        }
+        {
+            let resp = test::call_service(
+                &app,
+                conditional_request(&args, Some((IF_MODIFIED_SINCE, last_modified.clone())))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 200);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_stale_events_are_deleted() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(calendar_handler),
+        )
+        .await;
+        let event_ids = || {
+            let app = &app;
+            async move {
+                let args = Arguments {
+                    start_after: TIME_Y2K,
+                    end_before: TIME_2020,
+                    ids: vec!["5121.EG.001".into()],
+                    group_series: false,
+                };
+                let req = test::TestRequest::post()
+                    .uri("/api/calendar")
+                    .set_json(args)
+                    .to_request();
+                let (_, resp) = test::call_service(&app, req).await.into_parts();
+                let (status, actual) = run_testcase(resp).await;
+                assert_eq!(status, 200);
+                let mut ids = actual["5121.EG.001"]["events"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|e| e["id"].as_i64().unwrap())
+                    .collect::<Vec<_>>();
+                ids.sort_unstable();
+                ids
+            }
+        };
+        assert_eq!(event_ids().await, vec![3, 4, 5]);
+
+        // upstream replaced event 3 (2014..2016) with event 6 (2012..2016)
+        let rescheduled = Event {
+            id: 6,
+            room_code: "5121.EG.001".into(),
+            start_at: TIME_2012,
+            end_at: TIME_2016,
+            title_de: "Wartung".into(),
+            title_en: "maintenance".into(),
+            stp_type: None,
+            entry_type: EventType::Barred.to_string(),
+            detailed_entry_type: "Abhaltung".into(),
+            course_code: None,
+            course_semester_hours: None,
+            course_group: None,
+        };
+        Event::store_all(&pg.pool, LimitedVec::from(vec![rescheduled]), "5121.EG.001")
+            .await
+            .unwrap();
+        // events 4 and 5 are not within the scraped window => untouched
+        assert_eq!(event_ids().await, vec![4, 5, 6]);
+
+        // without fresh events, we don't know the scraped window => nothing is deleted
+        Event::store_all(&pg.pool, LimitedVec::new(), "5121.EG.001")
+            .await
+            .unwrap();
+        assert_eq!(event_ids().await, vec![4, 5, 6]);
+    }
+
+    #[actix_web::test]
+    async fn test_conflicting_ids_are_updated() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let event = |id: i32, title: &str| Event {
+            id,
+            room_code: "5121.EG.001".into(),
+            start_at: TIME_2012,
+            end_at: TIME_2014,
+            title_de: title.into(),
+            title_en: title.into(),
+            stp_type: None,
+            entry_type: EventType::Other.to_string(),
+            detailed_entry_type: "Abhaltung".into(),
+            course_code: None,
+            course_semester_hours: None,
+            course_group: None,
+        };
+        // more than one chunk, event 1 moved here from 5121.EG.003 and is listed twice
+        let mut events = (1_000..3_500)
+            .map(|id| event(id, "filler"))
+            .collect::<Vec<_>>();
+        events.push(event(1, "moved"));
+        events.push(event(1, "moved again"));
+        Event::store_all(&pg.pool, LimitedVec::from(events), "5121.EG.001")
+            .await
+            .unwrap();
+
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT room_code, title_de FROM calendar WHERE id = 1",
+        )
+        .fetch_all(&pg.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![("5121.EG.001".to_string(), "moved again".to_string())]
+        );
+        let (filler,) =
+            sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM calendar WHERE title_de = 'filler'")
+                .fetch_one(&pg.pool)
+                .await
+                .unwrap();
+        assert_eq!(filler, 2_500);
+    }
+
+    #[actix_web::test]
+    async fn test_truncation_at_max_events() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let events_with_max = |max_events: usize| {
+            let pool = &pg.pool;
+            async move {
+                let locations = CalendarLocation::get_locations(pool, &["5121.EG.001".into()])
+                    .await
+                    .unwrap()
+                    .into_inner();
+                let mut events =
+                    LocationEvents::get_from_db(pool, locations, &TIME_Y2K, &TIME_2020, max_events)
+                        .await
+                        .unwrap()
+                        .0;
+                let events = events.remove("5121.EG.001").unwrap();
+                let ids = events.events.iter().map(|e| e.id).collect::<Vec<_>>();
+                let response = serde_json::to_value(LocationEventsResponse::from(events)).unwrap();
+                (ids, response)
+            }
+        };
+        // exactly at the cap => nothing is missing
+        let (ids, response) = events_with_max(3).await;
+        assert_eq!(ids, vec![4, 5, 3]);
+        assert_eq!(response.get("truncated"), None);
+        assert_eq!(response.get("complete_until"), None);
+        // one over the cap => the last entry is cut off
+        let (ids, response) = events_with_max(2).await;
+        assert_eq!(ids, vec![4, 5]);
+        assert_eq!(response["truncated"], Value::Bool(true));
+        assert_eq!(
+            response["complete_until"],
+            serde_json::to_value(TIME_2014).unwrap()
+        );
+        // cut between entries starting at the same time => complete_until may not skip the second one
+        let (ids, response) = events_with_max(1).await;
+        assert_eq!(ids, vec![4]);
+        assert_eq!(response["truncated"], Value::Bool(true));
+        assert_eq!(
+            response["complete_until"],
+            serde_json::to_value(TIME_Y2K).unwrap()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_csv_matches_json() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(calendar_handler),
+        )
+        .await;
+        let args = Arguments {
+            start_after: TIME_Y2K,
+            end_before: TIME_2020,
+            ids: vec!["5121.EG.003".into(), "5121.EG.001".into()],
+            group_series: false,
+        };
+        let req = test::TestRequest::post()
+            .uri("/api/calendar?lang=en")
+            .set_json(&args)
+            .to_request();
+        let (_, resp) = test::call_service(&app, req).await.into_parts();
+        let (status, json) = run_testcase(resp).await;
+        assert_eq!(status, 200);
+        let mut events = args
+            .ids
+            .iter()
+            .flat_map(|id| json[id]["events"].as_array().unwrap().clone())
+            .collect::<Vec<_>>();
+        events.sort_by_key(|e| {
+            (
+                e["start_at"].as_str().unwrap().to_string(),
+                e["id"].as_i64(),
+            )
+        });
+        let expected_rows = events
+            .iter()
+            .map(|e| {
+                [
+                    e["id"].to_string(),
+                    e["title_en"].as_str().unwrap().to_string(),
+                    e["start_at"].as_str().unwrap().to_string(),
+                    e["end_at"].as_str().unwrap().to_string(),
+                    e["entry_type"].as_str().unwrap().to_string(),
+                    e["detailed_entry_type"].as_str().unwrap().to_string(),
+                    e["course"]["code"].as_str().unwrap_or_default().to_string(),
+                    e["course"]["semester_hours"]
+                        .as_i64()
+                        .map(|h| h.to_string())
+                        .unwrap_or_default(),
+                ]
+                .join(",")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected_rows.len(), 5);
+
+        // csv is never grouped into series
+        let args = Arguments {
+            group_series: true,
+            ..args
+        };
+        let csv_requests = [
+            test::TestRequest::post().uri("/api/calendar?lang=en&format=csv"),
+            test::TestRequest::post()
+                .uri("/api/calendar?lang=en")
+                .insert_header(("Accept", "text/csv")),
+        ];
+        for req in csv_requests {
+            let resp = test::call_service(&app, req.set_json(&args).to_request()).await;
+            assert_eq!(resp.status(), 200);
+            assert_eq!(
+                resp.headers().get("Content-Type").unwrap(),
+                "text/csv; charset=utf-8"
+            );
+            let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            let mut lines = body.split_terminator("\r\n");
+            assert_eq!(
+                lines.next(),
+                Some(
+                    "\u{feff}id,title,start,end,type,detailed_type,course_code,course_semester_hours"
+                )
+            );
+            assert_eq!(lines.collect::<Vec<_>>(), expected_rows);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_check_conflicts() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(conflicts::check_conflicts_handler),
+        )
+        .await;
+        let check = |keys: &[&str], start: DateTime<Utc>, end: DateTime<Utc>| {
+            let app = &app;
+            let body = serde_json::json!({"keys": keys, "start": start, "end": end});
+            async move {
+                let req = test::TestRequest::post()
+                    .uri("/api/calendar/check_conflicts?lang=en")
+                    .set_json(body)
+                    .to_request();
+                let (_, resp) = test::call_service(app, req).await.into_parts();
+                run_testcase(resp).await
+            }
+        };
+        let (status, actual) = check(&["5121.EG.003", "5121.EG.001"], TIME_2012, TIME_2014).await;
+        assert_eq!(status, 200);
+        insta::assert_json_snapshot!(actual, @r###"
+        {
+          "5121.EG.001": {
+            "conflicts": [
+              {
+                "end_at": "2020-01-01T00:00:00Z",
+                "entry_type": "other",
+                "id": 4,
+                "overlap_end": "2014-01-01T00:00:00Z",
+                "overlap_start": "2012-01-01T00:00:00Z",
+                "start_at": "2000-01-01T00:00:00Z",
+                "title": "Quantum teleportation 3"
+              }
+            ],
+            "free": false
+          },
+          "5121.EG.003": {
+            "conflicts": [
+              {
+                "end_at": "2014-01-01T00:00:00Z",
+                "entry_type": "lecture",
+                "id": 1,
+                "overlap_end": "2014-01-01T00:00:00Z",
+                "overlap_start": "2012-01-01T00:00:00Z",
+                "start_at": "2012-01-01T00:00:00Z",
+                "title": "Quantum teleportation"
+              }
+            ],
+            "free": false
+          }
+        }
+        "###);
+        // blocked rooms are conflicts
+        let (status, actual) = check(&["5121.EG.001"], TIME_2014, TIME_2016).await;
+        assert_eq!(status, 200);
+        let ids = actual["5121.EG.001"]["conflicts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["id"].as_i64().unwrap(), c["entry_type"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![(4, "other"), (3, "barred")]);
+        // touching the end of the last entry is fine
+        let (status, actual) = check(&["5121.EG.003"], TIME_2016, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            actual,
+            serde_json::json!({"5121.EG.003": {"free": true, "conflicts": []}})
+        );
+        // empty slots are rejected
+        let (status, actual) = check(&["5121.EG.003"], TIME_2016, TIME_2016).await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "invalid_slot");
+        // rooms without a calendar can't be checked
+        let (status, actual) = check(&["5121.EG.002"], TIME_2016, TIME_2020).await;
+        assert_eq!(status, 404);
+        assert_eq!(actual["code"], "no_calendar");
+    }
+
+    #[actix_web::test]
+    async fn test_free_rooms() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let free = |start: DateTime<Utc>, minutes| {
+            let pool = &pg.pool;
+            async move {
+                let end = start + chrono::TimeDelta::minutes(minutes);
+                FreeRoom::get_free(pool, "5121", &start, &end)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| (r.key, r.free_until))
+                    .collect::<Vec<_>>()
+            }
+        };
+        // ordered by how long the rooms stay free
+        let before_y2k = TIME_Y2K - chrono::TimeDelta::days(2);
+        assert_eq!(
+            free(before_y2k, 60).await,
+            vec![
+                ("5121.EG.003".to_string(), Some(TIME_2012)),
+                ("5121.EG.001".to_string(), Some(TIME_Y2K)),
+            ]
+        );
+        // busy rooms are excluded, even if the entry starts after the window started
+        assert_eq!(
+            free(before_y2k, 3 * 24 * 60).await,
+            vec![("5121.EG.003".to_string(), Some(TIME_2012))]
+        );
+        // entries ending when the window starts don't block the room
+        assert_eq!(
+            free(TIME_2020, 60).await,
+            vec![
+                ("5121.EG.001".to_string(), None),
+                ("5121.EG.003".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            free(TIME_2010, 60).await,
+            vec![("5121.EG.003".to_string(), Some(TIME_2012))]
+        );
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(free_now::free_now_handler),
+        )
+        .await;
+        let get = |query: &str| {
+            let app = &app;
+            let req = test::TestRequest::get()
+                .uri(&format!("/api/calendar/free_now?{query}"))
+                .to_request();
+            async move {
+                let (_, resp) = test::call_service(app, req).await.into_parts();
+                let cache_control = resp.headers().get(CACHE_CONTROL).cloned();
+                (run_testcase(resp).await, cache_control)
+            }
+        };
+        let before = Utc::now();
+        let ((status, actual), cache_control) = get("prefix=5121").await;
+        assert_eq!(status, 200);
+        assert_eq!(cache_control.unwrap(), "max-age=60, public");
+        let as_of = actual["as_of"].as_str().unwrap().parse::<DateTime<Utc>>();
+        assert!(as_of.unwrap() >= before);
+        assert_eq!(
+            actual["rooms"],
+            serde_json::json!([
+                {"id": "5121.EG.001", "name": "5121.EG.001 (Montage- und Versuchshalle)", "free_until": null},
+                {"id": "5121.EG.003", "name": "5121.EG.003 (Computerraum)", "free_until": null},
+            ])
+        );
+        let ((status, actual), _) = get("prefix=512").await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "prefix_too_short");
+        let ((status, actual), _) = get("prefix=5121&duration_min=0").await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "invalid_duration");
+    }
+
+    #[actix_web::test]
+    async fn test_course_calendar() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let course_event = |id, room_code: &str, group: Option<&str>| Event {
+            id,
+            room_code: room_code.into(),
+            start_at: TIME_2012,
+            end_at: TIME_2014,
+            title_de: "Quantenteleportation".into(),
+            title_en: "Quantum teleportation".into(),
+            stp_type: Some("Übung".into()),
+            entry_type: EventType::Exercise.to_string(),
+            detailed_entry_type: "Abhaltung".into(),
+            course_code: Some("PH1001".into()),
+            course_semester_hours: Some(4),
+            course_group: group.map(Into::into),
+        };
+        let mut tx = pg.pool.begin().await.unwrap();
+        for event in [
+            course_event(6, "5121.EG.001", None),
+            course_event(7, "5121.EG.003", Some("Gruppe 2")),
+            course_event(8, "5121.EG.001", Some("Gruppe 1")),
+        ] {
+            event.store(&mut tx).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(course::course_handler),
+        )
+        .await;
+        let get = |course: &str, start: DateTime<Utc>, end: DateTime<Utc>| {
+            let app = &app;
+            let start = start.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let end = end.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/api/calendar/course/{course}?start={start}&end={end}&lang=en"
+                ))
+                .to_request();
+            async move {
+                let (_, resp) = test::call_service(app, req).await.into_parts();
+                run_testcase(resp).await
+            }
+        };
+        let (status, actual) = get("PH1001", TIME_2010, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(actual["course_code"], "PH1001");
+        let groups = actual["groups"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|g| {
+                let events = g["events"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|e| (e["id"].as_i64().unwrap(), e["room_name"].as_str().unwrap()))
+                    .collect::<Vec<_>>();
+                (g["group"].as_str(), events)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (None, vec![(6, "5121.EG.001 (Montage- und Versuchshalle)")]),
+                (
+                    Some("Gruppe 1"),
+                    vec![
+                        (1, "5121.EG.003 (Computerraum)"),
+                        (8, "5121.EG.001 (Montage- und Versuchshalle)")
+                    ]
+                ),
+                (Some("Gruppe 2"), vec![(7, "5121.EG.003 (Computerraum)")]),
+            ]
+        );
+        // the events are translated and annotated with their room
+        let event = &actual["groups"][0]["events"][0];
+        assert_eq!(event["room_code"], "5121.EG.001");
+        assert_eq!(event["stp_type"], "Exercise");
+        assert_eq!(event["course"]["code"], "PH1001");
+
+        // no entries in the window
+        let (status, actual) = get("PH1001", TIME_2016, TIME_2020).await;
+        assert_eq!(status, 404);
+        assert_eq!(actual["code"], "unknown_course");
+        let (status, actual) = get("IN0007", TIME_2010, TIME_2020).await;
+        assert_eq!(status, 404);
+        assert_eq!(actual["code"], "unknown_course");
+        let (status, actual) = get("PH1001", TIME_2020, TIME_2010).await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "invalid_window");
+    }
+
+    #[actix_web::test]
+    async fn test_title_search() {
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        load_sample_data(&pg.pool, &now).await;
+        let event = |id, room_code: &str, title_de: &str, course_code: Option<&str>| Event {
+            id,
+            room_code: room_code.into(),
+            start_at: TIME_2012,
+            end_at: TIME_2014,
+            title_de: title_de.into(),
+            title_en: title_de.into(),
+            stp_type: Some("Übung".into()),
+            entry_type: EventType::Exercise.to_string(),
+            detailed_entry_type: "Abhaltung".into(),
+            course_code: course_code.map(Into::into),
+            course_semester_hours: None,
+            course_group: None,
+        };
+        let mut tx = pg.pool.begin().await.unwrap();
+        for event in [
+            event(6, "5121.EG.003", "Tutorium zum Übungsblatt", None),
+            event(
+                7,
+                "5121.EG.001",
+                "Übung zu Analysis für Informatik",
+                Some("MA0902"),
+            ),
+        ] {
+            event.store(&mut tx).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(search::search_handler),
+        )
+        .await;
+        let get = |q: &str, start: DateTime<Utc>, end: DateTime<Utc>| {
+            let app = &app;
+            let start = start.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let end = end.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/api/calendar/search?q={q}&start={start}&end={end}"
+                ))
+                .to_request();
+            async move {
+                let (_, resp) = test::call_service(app, req).await.into_parts();
+                run_testcase(resp).await
+            }
+        };
+        let found = |actual: &Value| {
+            actual["rooms"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| {
+                    let ids = r["events"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|e| e["id"].as_i64().unwrap())
+                        .collect::<Vec<_>>();
+                    (r["id"].as_str().unwrap().to_string(), ids)
+                })
+                .collect::<Vec<_>>()
+        };
+        // umlauts match their spelled out form, prefix matches come first
+        for q in ["Uebung", "%C3%9Cbung", "UEBUNG"] {
+            let (status, actual) = get(q, TIME_Y2K, TIME_2020).await;
+            assert_eq!(status, 200, "{q}");
+            assert_eq!(
+                found(&actual),
+                vec![
+                    ("5121.EG.001".to_string(), vec![7]),
+                    ("5121.EG.003".to_string(), vec![6]),
+                ],
+                "{q}"
+            );
+            assert_eq!(actual["truncated"], Value::Bool(false));
+        }
+        let (status, actual) = get("analysis%20f%C3%BCr", TIME_Y2K, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            actual["rooms"][0]["name"],
+            "5121.EG.001 (Montage- und Versuchshalle)"
+        );
+        assert_eq!(found(&actual), vec![("5121.EG.001".to_string(), vec![7])]);
+        let (status, actual) = get("ma0902", TIME_Y2K, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(found(&actual), vec![("5121.EG.001".to_string(), vec![7])]);
+        // wildcards are matched literally
+        let (status, actual) = get("%25%25%25", TIME_Y2K, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(found(&actual), vec![]);
+        // outside of the window
+        let (status, actual) = get("Uebung", TIME_2016, TIME_2020).await;
+        assert_eq!(status, 200);
+        assert_eq!(found(&actual), vec![]);
+
+        let (status, actual) = get("Ue", TIME_Y2K, TIME_2020).await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "query_too_short");
+        let (status, actual) = get(&"a".repeat(101), TIME_Y2K, TIME_2020).await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "query_too_long");
+        let (status, actual) = get("Uebung", TIME_2020, TIME_2010).await;
+        assert_eq!(status, 400);
+        assert_eq!(actual["code"], "invalid_window");
+    }
+
+    #[actix_web::test]
+    async fn test_ics_feed() {
+        use crate::db::calendar::FeedToken;
+
+        let pg = PostgresTestContainer::new().await;
+        let now = Utc::now().trunc_subsecs(0);
+        load_sample_data(&pg.pool, &now.to_rfc3339()).await;
+        let mut tx = pg.pool.begin().await.unwrap();
+        Event {
+            id: 6,
+            room_code: "5121.EG.003".into(),
+            start_at: now + chrono::TimeDelta::days(1),
+            end_at: now + chrono::TimeDelta::days(1) + chrono::TimeDelta::hours(2),
+            title_de: "Quantenteleportation, Teil 2".into(),
+            title_en: "Quantum teleportation, part 2".into(),
+            stp_type: Some("Vorlesung".into()),
+            entry_type: EventType::Lecture.to_string(),
+            detailed_entry_type: "Abhaltung".into(),
+            course_code: Some("PH1001".into()),
+            course_semester_hours: None,
+            course_group: None,
+        }
+        .store(&mut tx)
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+        let valid = FeedToken::create(&pg.pool, "5121.EG.003", "valid")
+            .await
+            .unwrap();
+        let revoked = FeedToken::create(&pg.pool, "5121.EG.003", "revoked")
+            .await
+            .unwrap();
+        FeedToken::create(&pg.pool, "5121.EG.001", "other_room")
+            .await
+            .unwrap();
+        let revoked = FeedToken::revoke(&pg.pool, "5121.EG.003", revoked.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(revoked.revoked_at.is_some());
+        // tokens of other rooms cannot be revoked via this room
+        let other = FeedToken::revoke(&pg.pool, "5121.EG.003", valid.id + 2)
+            .await
+            .unwrap();
+        assert_eq!(other, None);
+        assert_eq!(
+            FeedToken::list(&pg.pool, "5121.EG.003").await.unwrap(),
+            vec![revoked.clone(), valid.clone()]
+        );
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .service(feed::ics_handler),
+        )
+        .await;
+        let get = |id: &str, token: &str| {
+            let req = test::TestRequest::get()
+                .uri(&format!("/api/calendar/{id}/ics?token={token}"))
+                .to_request();
+            test::call_service(&app, req)
+        };
+        let resp = get("5121.EG.003", "valid").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/calendar; charset=utf-8"
+        );
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.starts_with("BEGIN:VCALENDAR\r\n"), "{body}");
+        assert!(body.contains("\r\nREFRESH-INTERVAL;VALUE=DURATION:PT60M\r\n"));
+        assert!(body.contains("\r\nX-PUBLISHED-TTL:PT60M\r\n"));
+        assert!(body.contains("\r\nSUMMARY:Quantenteleportation\\, Teil 2\r\n"));
+        // the sample entries are too long ago to be part of the feed
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 1, "{body}");
+
+        for (id, token) in [
+            ("5121.EG.003", "revoked"),
+            ("5121.EG.003", "other_room"),
+            ("5121.EG.003", "unknown"),
+            ("5121.EG.001", "valid"),
+        ] {
+            let resp = get(id, token).await;
+            let (_, resp) = resp.into_parts();
+            let (status, actual) = run_testcase(resp).await;
+            assert_eq!(status, 403, "{id} {token}");
+            assert_eq!(actual["code"], "invalid_token", "{id} {token}");
+            assert_eq!(actual["parameter"], "token", "{id} {token}");
+        }
+    }
+
+    async fn run_testcase(resp: HttpResponse) -> (u16, Value) {
+        let actual_status = resp.status().as_u16();
+        let body_box = resp.into_body();
+        let body_bytes = actix_web::body::to_bytes(body_box).await.unwrap();
+        let body_text = String::from_utf8(body_bytes.into_iter().collect()).unwrap();
+        // if the expected value cleanly deserializes into json, we should compare using this
+        let body = if let Ok(actual) = serde_json::from_str::<Value>(&body_text) {
+            actual
+        } else {
+            Value::String(body_text)
+        };
+        (actual_status, body)
+    }
+}
@@ -0,0 +1,209 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use crate::error::ApiError;
+use crate::refresh::calendar::{EnqueueError, OnDemandRefresh, RefreshJob, RefreshJobStatus};
+use crate::routes::admin;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct RefreshPathParams {
+    /// ID of the room whose calendar should be refreshed
+    #[param(example = "5602.EG.001")]
+    id: String,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct RefreshJobPathParams {
+    /// ID of the room whose calendar should be refreshed
+    #[param(example = "5602.EG.001")]
+    id: String,
+    /// ID of the refresh job as returned when requesting the refresh
+    #[param(example = 42)]
+    job_id: u64,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum RefreshJobStatusResponse {
+    /// Waiting for other refreshes to finish
+    Queued,
+    /// Currently being scraped
+    Running,
+    /// The calendar was refreshed
+    Succeeded,
+    /// The calendar could not be refreshed
+    Failed,
+}
+impl From<RefreshJobStatus> for RefreshJobStatusResponse {
+    fn from(value: RefreshJobStatus) -> Self {
+        match value {
+            RefreshJobStatus::Queued => RefreshJobStatusResponse::Queued,
+            RefreshJobStatus::Running => RefreshJobStatusResponse::Running,
+            RefreshJobStatus::Succeeded => RefreshJobStatusResponse::Succeeded,
+            RefreshJobStatus::Failed => RefreshJobStatusResponse::Failed,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct RefreshJobResponse {
+    /// ID of the refresh job
+    #[schema(examples(42))]
+    job_id: u64,
+    /// Room whose calendar is refreshed
+    #[schema(examples("5602.EG.001"))]
+    id: String,
+    status: RefreshJobStatusResponse,
+    /// When the refresh was requested
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    enqueued_at: DateTime<Utc>,
+    /// When the refresh finished (successfully or not)
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    finished_at: Option<DateTime<Utc>>,
+    /// Why the refresh failed
+    #[schema(examples("error decoding response body"))]
+    error: Option<String>,
+}
+impl From<RefreshJob> for RefreshJobResponse {
+    fn from(value: RefreshJob) -> Self {
+        RefreshJobResponse {
+            job_id: value.id,
+            id: value.room,
+            status: RefreshJobStatusResponse::from(value.status),
+            enqueued_at: value.enqueued_at,
+            finished_at: value.finished_at,
+            error: value.error,
+        }
+    }
+}
+
+/// Refresh the calendar of a room
+///
+/// **Requires an admin token.**
+///
+/// Enqueues an immediate scrape of the calendar of the room instead of waiting for the next scraping cycle.
+/// Refreshes for a room which is already waiting to be refreshed are coalesced into the existing job.
+/// The progress can be polled via [`/api/calendar/{id}/refresh/{job_id}`](#tag/calendar/operation/get_refresh_handler).
+#[utoipa::path(
+    tags=["calendar"],
+    params(RefreshPathParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "**Refresh was enqueued**", body = RefreshJobResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The room does not exist or does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002 does not have a calendar", "code": "no_calendar"})),
+        (status = 429, description = "**Too many requests.** Too many refreshes are already waiting", body = ApiError, content_type = "application/json", example = json!({"error": "Too many refreshes are waiting already, please try again later", "code": "too_many_refreshes"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[post("/api/calendar/{id}/refresh")]
+pub async fn refresh_handler(
+    req: HttpRequest,
+    params: web::Path<RefreshPathParams>,
+    data: web::Data<crate::AppData>,
+    refresh: web::Data<OnDemandRefresh>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let id = params.id.trim();
+    let locations = match data
+        .calendar_locations
+        .get_locations(&data.pool, &[id.to_string()])
+        .await
+    {
+        Ok(locations) => locations.into_inner(),
+        Err(e) => {
+            error!(error = ?e, id, "could not get location");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get the location, please try again later",
+            )
+            .into();
+        }
+    };
+    match locations.first() {
+        None => {
+            return ApiError::new(
+                StatusCode::NOT_FOUND,
+                "not_found",
+                format!("Room {id} does not exist"),
+            )
+            .into();
+        }
+        Some(location) if location.calendar_url.is_none() => {
+            return ApiError::new(
+                StatusCode::NOT_FOUND,
+                "no_calendar",
+                format!("Room {id} does not have a calendar"),
+            )
+            .into();
+        }
+        Some(_) => {}
+    }
+    match refresh.enqueue(&data.pool, id) {
+        Ok(job_id) => {
+            let job = refresh
+                .job(job_id)
+                .expect("unfinished jobs are never forgotten");
+            HttpResponse::Accepted().json(RefreshJobResponse::from(job))
+        }
+        Err(EnqueueError::QueueFull) => ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_refreshes",
+            "Too many refreshes are waiting already, please try again later",
+        )
+        .into(),
+    }
+}
+
+/// Get the status of a calendar refresh
+///
+/// **Requires an admin token.**
+///
+/// Finished refreshes are only remembered for a limited time.
+#[utoipa::path(
+    tags=["calendar"],
+    params(RefreshJobPathParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Status of the refresh**", body = RefreshJobResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The refresh job does not exist (anymore)", body = ApiError, content_type = "application/json", example = json!({"error": "Refresh job 42 for 5602.EG.001 does not exist", "code": "not_found"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[get("/api/calendar/{id}/refresh/{job_id}")]
+pub async fn get_refresh_handler(
+    req: HttpRequest,
+    params: web::Path<RefreshJobPathParams>,
+    refresh: web::Data<OnDemandRefresh>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    match refresh.job(params.job_id) {
+        Some(job) if job.room == params.id.trim() => {
+            HttpResponse::Ok().json(RefreshJobResponse::from(job))
+        }
+        _ => ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!(
+                "Refresh job {job_id} for {id} does not exist",
+                job_id = params.job_id,
+                id = params.id
+            ),
+        )
+        .into(),
+    }
+}
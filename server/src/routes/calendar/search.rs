@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use super::EventResponse;
+use crate::db::calendar::Event;
+use crate::error::ApiError;
+use crate::localisation;
+
+/// Shorter queries match too much to be useful and cannot use the trigram index
+const MIN_QUERY_LEN: usize = 3;
+const MAX_QUERY_LEN: usize = 100;
+const MAX_RESULTS: usize = 200;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct SearchQueryArgs {
+    /// Part of the title or the course code of the entries
+    ///
+    /// Case and accents are ignored, umlauts may be spelled out (`Uebung` finds `Übung`).
+    #[param(example = "Analysis für Informatik", min_length = 3, max_length = 100)]
+    q: String,
+    /// The first allowed time the calendar would like to display
+    #[param(example = "2039-01-19T03:14:07+01:00")]
+    start: DateTime<Utc>,
+    /// The last allowed time the calendar would like to display
+    #[param(example = "2039-01-26T03:14:07+01:00")]
+    end: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CalendarSearchResponse {
+    /// Rooms with matching entries, the best matching room first
+    rooms: Vec<SearchRoomResponse>,
+    /// If there are more than the 200 returned matches
+    ///
+    /// Please refine the query or the time span in this case.
+    truncated: bool,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct SearchRoomResponse {
+    /// ID of the room
+    #[schema(examples("5602.EG.001"))]
+    id: String,
+    /// Name of the room
+    #[schema(examples("5602.EG.001 (MI HS 1, Friedrich L. Bauer Hörsaal)"))]
+    name: String,
+    /// Matching entries in this room
+    ///
+    /// Entries whose title or course code start with the query come first, then they are ordered by their start.
+    events: Vec<EventResponse>,
+}
+
+/// Search calendar entries by their title
+///
+/// Answers questions like "where does `Analysis für Informatik` take place this week".
+/// Searches the german and english titles and the course codes of the entries within the requested time span.
+#[utoipa::path(
+    tags=["calendar"],
+    params(SearchQueryArgs, localisation::LangQueryArgs),
+    responses(
+        (status = 200, description = "**Matching entries**, grouped by room", body = CalendarSearchResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The query is too short/long or the time span is empty", body = ApiError, content_type = "application/json", example = json!({"error": "The query has to be at least 3 characters long", "code": "query_too_short"})),
+        (status = 500, description = "**Internal Server Error.** We could not search the calendar entries", body = ApiError, content_type = "application/json", example = json!({"error": "could not search calendar entries, please try again later", "code": "internal_error"})),
+    )
+)]
+#[get("/api/calendar/search")]
+pub async fn search_handler(
+    web::Query(args): web::Query<SearchQueryArgs>,
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let query = args.q.trim();
+    let query_len = query.chars().count();
+    if query_len < MIN_QUERY_LEN {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "query_too_short",
+            format!("The query has to be at least {MIN_QUERY_LEN} characters long"),
+        )
+        .with_parameter("q")
+        .into();
+    }
+    if query_len > MAX_QUERY_LEN {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "query_too_long",
+            format!("The query may be at most {MAX_QUERY_LEN} characters long"),
+        )
+        .with_parameter("q")
+        .into();
+    }
+    if args.start >= args.end {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_window",
+            "`end` has to be after `start`",
+        )
+        .with_parameter("end")
+        .into();
+    }
+    // one more than allowed to know if we cut something off
+    let limit = (MAX_RESULTS + 1) as i64;
+    let mut events = match Event::search(&data.pool, query, &args.start, &args.end, limit).await {
+        Ok(events) => events,
+        Err(e) => {
+            error!(error = ?e, query, "could not search entries in the db");
+            return internal_error();
+        }
+    };
+    let truncated = events.len() > MAX_RESULTS;
+    events.truncate(MAX_RESULTS);
+    let mut rooms = events
+        .iter()
+        .map(|e| e.room_code.clone())
+        .collect::<Vec<_>>();
+    rooms.sort_unstable();
+    rooms.dedup();
+    let room_names = match data
+        .calendar_locations
+        .get_locations(&data.pool, &rooms)
+        .await
+    {
+        Ok(locations) => locations
+            .into_iter()
+            .map(|l| (l.key, l.name))
+            .collect::<HashMap<_, _>>(),
+        Err(e) => {
+            error!(error = ?e, "could not get locations");
+            return internal_error();
+        }
+    };
+    HttpResponse::Ok().json(CalendarSearchResponse {
+        rooms: group_by_room(events, &room_names, lang),
+        truncated,
+    })
+}
+
+/// Groups the events by their room
+///
+/// Rooms are ordered by their best match, the order of the events within a room is kept.
+fn group_by_room(
+    events: Vec<Event>,
+    room_names: &HashMap<String, String>,
+    lang: localisation::LangQueryArgs,
+) -> Vec<SearchRoomResponse> {
+    let mut rooms = Vec::<SearchRoomResponse>::new();
+    let mut room_index = HashMap::<String, usize>::new();
+    for event in events {
+        let index = *room_index
+            .entry(event.room_code.clone())
+            .or_insert_with(|| {
+                rooms.push(SearchRoomResponse {
+                    id: event.room_code.clone(),
+                    name: room_names
+                        .get(&event.room_code)
+                        .cloned()
+                        .unwrap_or_else(|| event.room_code.clone()),
+                    events: Vec::new(),
+                });
+                rooms.len() - 1
+            });
+        let mut event = EventResponse::from(event);
+        if lang.should_use_english() {
+            event.translate_to_english();
+        }
+        rooms[index].events.push(event);
+    }
+    rooms
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "could not search calendar entries, please try again later",
+    )
+    .into()
+}
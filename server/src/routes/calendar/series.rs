@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::{CourseResponse, EventResponse, EventTypeResponse};
+
+/// Recurring calendar entries, grouped into one series
+#[derive(Serialize, Debug, PartialEq, utoipa::ToSchema)]
+pub(super) struct EventSeriesResponse {
+    /// German title shared by the occurrences
+    #[schema(examples("Quantenteleportation"))]
+    title_de: String,
+    /// English title shared by the occurrences
+    #[schema(examples("Quantum teleportation"))]
+    title_en: String,
+    /// Lecture-type shared by the occurrences
+    #[schema(examples("Vorlesung mit Zentralübung"))]
+    stp_type: Option<String>,
+    /// What this calendar entry means.
+    entry_type: EventTypeResponse,
+    /// More information what kind of entry the occurrences are
+    #[schema(examples("Abhaltung"))]
+    detailed_entry_type: String,
+    /// The course this series belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    course: Option<CourseResponse>,
+    /// Occurrences sharing the data of the series, ordered by their start
+    occurrences: Vec<OccurrenceResponse>,
+    /// Entries of this series deviating from the shared data (e.g. renamed or cancelled occurrences)
+    ///
+    /// Listed in full, ordered by their start
+    exceptions: Vec<EventResponse>,
+}
+
+/// Single occurrence of an [`EventSeriesResponse`]
+#[derive(Serialize, Debug, PartialEq, Eq, utoipa::ToSchema)]
+pub(super) struct OccurrenceResponse {
+    /// ID of the calendar entry used in TUMonline internally
+    #[schema(examples(6424))]
+    id: i32,
+    /// start of the entry
+    #[schema(examples("2018-01-01T00:00:00"))]
+    start_at: DateTime<Utc>,
+    /// end of the entry
+    #[schema(examples("2019-01-01T00:00:00"))]
+    end_at: DateTime<Utc>,
+}
+
+/// What makes entries part of the same series
+#[derive(Hash, PartialEq, Eq)]
+enum SeriesKey {
+    /// TUMonline does not tell us which entries belong together.
+    /// Entries of the same course are the closest thing to this.
+    Course(String),
+    /// Fallback for entries without a course
+    Title(String, EventTypeResponse),
+}
+impl From<&EventResponse> for SeriesKey {
+    fn from(event: &EventResponse) -> Self {
+        match &event.course {
+            Some(course) => SeriesKey::Course(course.code.clone()),
+            None => SeriesKey::Title(event.title_de.clone(), event.entry_type),
+        }
+    }
+}
+
+/// Data an occurrence shares with its series
+#[derive(Hash, PartialEq, Eq, Clone)]
+struct SharedData {
+    title_de: String,
+    title_en: String,
+    stp_type: Option<String>,
+    detailed_entry_type: String,
+}
+impl From<&EventResponse> for SharedData {
+    fn from(event: &EventResponse) -> Self {
+        SharedData {
+            title_de: event.title_de.clone(),
+            title_en: event.title_en.clone(),
+            stp_type: event.stp_type.clone(),
+            detailed_entry_type: event.detailed_entry_type.clone(),
+        }
+    }
+}
+
+/// Groups the events into series
+///
+/// The data shared by a series is what most of its entries agree on.
+/// Series are ordered by their first entry.
+pub(super) fn group_into_series(mut events: Vec<EventResponse>) -> Vec<EventSeriesResponse> {
+    events.sort_by_key(|e| (e.start_at, e.id));
+    let mut series_order = Vec::<SeriesKey>::new();
+    let mut grouped = HashMap::<SeriesKey, Vec<EventResponse>>::new();
+    for event in events {
+        let key = SeriesKey::from(&event);
+        if !grouped.contains_key(&key) {
+            series_order.push(SeriesKey::from(&event));
+        }
+        grouped.entry(key).or_default().push(event);
+    }
+    series_order
+        .into_iter()
+        .map(|key| {
+            let events = grouped
+                .remove(&key)
+                .expect("every key in the order was inserted");
+            EventSeriesResponse::from(events)
+        })
+        .collect()
+}
+
+impl From<Vec<EventResponse>> for EventSeriesResponse {
+    fn from(events: Vec<EventResponse>) -> Self {
+        let shared = most_common_shared_data(&events);
+        let first = events.first().expect("series are never empty");
+        let entry_type = first.entry_type;
+        let course = first.course.clone();
+        let (regular, exceptions): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|e| SharedData::from(e) == shared);
+        EventSeriesResponse {
+            title_de: shared.title_de,
+            title_en: shared.title_en,
+            stp_type: shared.stp_type,
+            entry_type,
+            detailed_entry_type: shared.detailed_entry_type,
+            course,
+            occurrences: regular
+                .into_iter()
+                .map(|e| OccurrenceResponse {
+                    id: e.id,
+                    start_at: e.start_at,
+                    end_at: e.end_at,
+                })
+                .collect(),
+            exceptions,
+        }
+    }
+}
+
+/// The [`SharedData`] most of the events agree on
+///
+/// Ties are broken in favour of the earlier event
+fn most_common_shared_data(events: &[EventResponse]) -> SharedData {
+    let mut counts = HashMap::<SharedData, (usize, usize)>::new();
+    for (position, event) in events.iter().enumerate() {
+        counts
+            .entry(SharedData::from(event))
+            .or_insert((0, position))
+            .0 += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, (count, first_position))| (*count, std::cmp::Reverse(*first_position)))
+        .map(|(shared, _)| shared)
+        .expect("series are never empty")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn lecture(id: i32, week: u32, title_de: &str, detailed_entry_type: &str) -> EventResponse {
+        let start_at = Utc.with_ymd_and_hms(2024, 10, 14, 10, 0, 0).unwrap()
+            + chrono::Duration::weeks(week.into());
+        EventResponse {
+            id,
+            room_code: "5602.EG.001".into(),
+            start_at,
+            end_at: start_at + chrono::Duration::minutes(90),
+            title_de: title_de.into(),
+            title_en: "Quantum teleportation".into(),
+            stp_type: Some("Vorlesung".into()),
+            entry_type: EventTypeResponse::Lecture,
+            detailed_entry_type: detailed_entry_type.into(),
+            course: Some(CourseResponse {
+                code: "PH1001".into(),
+                semester_hours: Some(4),
+                group: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_weekly_series_with_exceptions() {
+        let mut moved = lecture(3, 2, "Quantenteleportation", "Abhaltung");
+        moved.start_at += chrono::Duration::days(1);
+        moved.end_at += chrono::Duration::days(1);
+        let events = vec![
+            lecture(4, 3, "Quantenteleportation", "Abhaltung"),
+            lecture(1, 0, "Quantenteleportation", "Abhaltung"),
+            lecture(2, 1, "Quantenteleportation", "Abhaltung entfällt"),
+            moved,
+            lecture(
+                5,
+                4,
+                "Quantenteleportation - Klausurvorbereitung",
+                "Abhaltung",
+            ),
+        ];
+        let series = group_into_series(events);
+        assert_eq!(series.len(), 1);
+        let series = &series[0];
+        assert_eq!(series.title_de, "Quantenteleportation");
+        assert_eq!(series.detailed_entry_type, "Abhaltung");
+        // moved occurrences still share the data of the series
+        let occurrence_ids = series.occurrences.iter().map(|o| o.id).collect::<Vec<_>>();
+        assert_eq!(occurrence_ids, vec![1, 3, 4]);
+        assert_eq!(
+            series.occurrences[1].start_at,
+            Utc.with_ymd_and_hms(2024, 10, 29, 10, 0, 0).unwrap()
+        );
+        // cancelled and renamed occurrences are listed in full
+        let exception_ids = series.exceptions.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(exception_ids, vec![2, 5]);
+        assert_eq!(
+            series.exceptions[0].detailed_entry_type,
+            "Abhaltung entfällt"
+        );
+    }
+
+    #[test]
+    fn test_series_are_separated() {
+        let mut exam = lecture(3, 5, "Quantenteleportation", "Prüfung");
+        exam.entry_type = EventTypeResponse::Exam;
+        exam.course = None;
+        let mut other_course = lecture(4, 0, "Quantenverschränkung", "Abhaltung");
+        other_course.course = Some(CourseResponse {
+            code: "PH1002".into(),
+            semester_hours: None,
+            group: Some("Gruppe 1".into()),
+        });
+        let events = vec![
+            lecture(1, 0, "Quantenteleportation", "Abhaltung"),
+            lecture(2, 1, "Quantenteleportation", "Abhaltung"),
+            exam,
+            other_course,
+        ];
+        let series = group_into_series(events);
+        let summary = series
+            .iter()
+            .map(|s| {
+                (
+                    s.course.as_ref().map(|c| c.code.as_str()),
+                    s.entry_type,
+                    s.occurrences.len(),
+                    s.exceptions.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("PH1001"), EventTypeResponse::Lecture, 2, 0),
+                (Some("PH1002"), EventTypeResponse::Lecture, 1, 0),
+                (None, EventTypeResponse::Exam, 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tie_is_broken_by_the_earlier_event() {
+        let events = vec![
+            lecture(2, 1, "Quantenteleportation", "Abhaltung entfällt"),
+            lecture(1, 0, "Quantenteleportation", "Abhaltung"),
+        ];
+        let series = group_into_series(events);
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].detailed_entry_type, "Abhaltung");
+        assert_eq!(series[0].exceptions.len(), 1);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(group_into_series(vec![]), vec![]);
+    }
+}
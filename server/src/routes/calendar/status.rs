@@ -0,0 +1,192 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use crate::db::calendar::CalendarScrapeStatus;
+use crate::error::ApiError;
+use crate::refresh::calendar::OnDemandRefresh;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct StatusPathParams {
+    /// ID of the room
+    #[param(example = "5602.EG.001")]
+    id: String,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CalendarStatusResponse {
+    /// ID of the room
+    #[schema(examples("5602.EG.001"))]
+    id: String,
+    /// Where the calendar of the room can be found in TUMonline
+    #[schema(examples(
+        "https://campus.tum.de/tumonline/tvKalender.wSicht?cOrg=19691&cRes=12543&cReadonly=J"
+    ))]
+    calendar_url: String,
+    /// ID of the room in TUMonline
+    #[schema(examples(12543))]
+    tumonline_room_nr: Option<i32>,
+    /// When the calendar was last downloaded successfully
+    ///
+    /// `null` if this never happened
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    last_successful_scrape_at: Option<DateTime<Utc>>,
+    /// How many events are stored for this room
+    #[schema(examples(42))]
+    event_count: i64,
+    /// Whether the room is waiting to be scraped (either in the current scraping cycle or on demand)
+    queued: bool,
+}
+
+/// Get the scraping status of a room
+///
+/// Shows how up-to-date the calendar of a room is.
+/// Useful to verify reports that a calendar looks outdated.
+#[utoipa::path(
+    tags=["calendar"],
+    params(StatusPathParams),
+    responses(
+        (status = 200, description = "**Scraping status of the room**", body = CalendarStatusResponse, content_type = "application/json"),
+        (status = 404, description = "**Not found.** The room does not exist or does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002 does not have a calendar", "code": "no_calendar"})),
+    )
+)]
+#[get("/api/calendar/{id}/status")]
+pub async fn status_handler(
+    params: web::Path<StatusPathParams>,
+    data: web::Data<crate::AppData>,
+    refresh: web::Data<OnDemandRefresh>,
+) -> HttpResponse {
+    let id = params.id.trim();
+    let status = match CalendarScrapeStatus::get(&data.pool, id).await {
+        Ok(status) => status,
+        Err(e) => {
+            error!(error = ?e, id, "could not get the scraping status");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get the scraping status, please try again later",
+            )
+            .into();
+        }
+    };
+    let Some(status) = status else {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("Room {id} does not exist"),
+        )
+        .into();
+    };
+    let Some(calendar_url) = status.calendar_url else {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "no_calendar",
+            format!("Room {id} does not have a calendar"),
+        )
+        .into();
+    };
+    HttpResponse::Ok().json(CalendarStatusResponse {
+        queued: status.queued || refresh.is_pending(&status.key),
+        id: status.key,
+        calendar_url,
+        tumonline_room_nr: status.tumonline_room_nr,
+        last_successful_scrape_at: status.last_calendar_success_at,
+        event_count: status.event_cnt,
+    })
+}
+
+#[cfg(test)]
+mod db_tests {
+    use actix_web::{App, test};
+    use pretty_assertions::assert_eq;
+    use prometheus::Registry;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::AppData;
+    use crate::db::calendar::CalendarLocationCache;
+    use crate::refresh::metrics::ScrapeMetrics;
+    use crate::refresh::pacing::ScrapePacing;
+    use crate::setup::tests::PostgresTestContainer;
+
+    async fn insert_room(pool: &sqlx::PgPool, key: &str, props: Value) {
+        let data = serde_json::json!({
+            "id": key,
+            "name": key,
+            "type": "room",
+            "type_common_name": "Hörsaal",
+            "coords": {"lat": 48.26, "lon": 11.67, "source": "inferred"},
+            "props": props,
+        });
+        sqlx::query("INSERT INTO de(key,data) VALUES ($1,$2)")
+            .bind(key)
+            .bind(data)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn get_status(pool: &sqlx::PgPool, id: &str) -> (u16, Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pool.clone())))
+                .app_data(web::Data::new(OnDemandRefresh::new(
+                    ScrapeMetrics::register(&Registry::new()).unwrap(),
+                    ScrapePacing::new(1, Duration::ZERO, false),
+                    CalendarLocationCache::default(),
+                )))
+                .service(status_handler),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/calendar/{id}/status"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let status = resp.status().as_u16();
+        (status, test::read_body_json(resp).await)
+    }
+
+    #[actix_web::test]
+    async fn test_never_scraped_room() {
+        let pg = PostgresTestContainer::new().await;
+        insert_room(
+            &pg.pool,
+            "5602.EG.001",
+            serde_json::json!({"calendar_url": "https://campus.tum.de/1", "tumonline_room_nr": 1}),
+        )
+        .await;
+        let (status, body) = get_status(&pg.pool, "5602.EG.001").await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "id": "5602.EG.001",
+                "calendar_url": "https://campus.tum.de/1",
+                "tumonline_room_nr": 1,
+                "last_successful_scrape_at": null,
+                "event_count": 0,
+                "queued": true,
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_rooms_without_status() {
+        let pg = PostgresTestContainer::new().await;
+        insert_room(&pg.pool, "5602.EG.002", serde_json::json!({})).await;
+        let (status, body) = get_status(&pg.pool, "5602.EG.002").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "no_calendar");
+        let (status, body) = get_status(&pg.pool, "does-not-exist").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "not_found");
+    }
+}
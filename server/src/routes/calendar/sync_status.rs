@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use crate::db::calendar::FailingCalendar;
+use crate::error::ApiError;
+use crate::refresh::metrics::{ScrapeMetrics, SyncStatus};
+
+/// Single failures happen (e.g. timeouts) and are retried in the next cycle anyway
+const MIN_FAILURES_IN_ROW: i32 = 3;
+const MAX_FAILING_ROOMS: i64 = 100;
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct SyncStatusResponse {
+    /// Rooms which still need to be scraped in the current cycle
+    ///
+    /// `null` until the first batch of rooms was scraped after startup
+    #[schema(examples(42))]
+    rooms_remaining: Option<u64>,
+    /// Rooms scraped since startup
+    #[schema(examples(1337))]
+    rooms_scraped: u64,
+    /// Events downloaded from TUMonline since startup
+    #[schema(examples(31337))]
+    scraped_events: u64,
+    /// Failed requests to TUMonline since startup, by HTTP status (or kind of failure if there is no status)
+    #[schema(examples(json!({"503": 3, "timeout": 1})))]
+    upstream_errors: BTreeMap<String, u64>,
+    /// Mean time it took to scrape a single room
+    ///
+    /// `null` if no room was scraped since startup
+    #[schema(examples(1.5))]
+    mean_room_duration_seconds: Option<f64>,
+    /// When the scraper started the current cycle through all rooms
+    ///
+    /// `null` if all rooms are up to date.
+    /// Cycles are continued after restarts.
+    #[schema(examples("2039-01-19T02:14:07+01:00"))]
+    cycle_started_at: Option<DateTime<Utc>>,
+    /// When all rooms were last up to date
+    ///
+    /// `null` if this never happened
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    last_full_cycle_at: Option<DateTime<Utc>>,
+    /// Rooms whose calendar could not be downloaded at least 3 times in a row, the most failing first
+    ///
+    /// Limited to 100 rooms.
+    /// In contrast to the counters, this survives restarts.
+    failing_rooms: Vec<FailingRoomResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct FailingRoomResponse {
+    /// ID of the room
+    #[schema(examples("5602.EG.001"))]
+    id: String,
+    /// How often downloading the calendar failed since it last succeeded
+    #[schema(examples(5))]
+    failures_in_row: i32,
+    /// Why the last download failed
+    #[schema(examples("TUMonline returned 404 Not Found"))]
+    last_error: Option<String>,
+    /// When the last download failed
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    last_error_at: Option<DateTime<Utc>>,
+    /// When the calendar was last downloaded successfully
+    ///
+    /// `null` if this never happened
+    #[schema(examples("2039-01-18T03:14:07+01:00"))]
+    last_success_at: Option<DateTime<Utc>>,
+}
+impl From<FailingCalendar> for FailingRoomResponse {
+    fn from(value: FailingCalendar) -> Self {
+        FailingRoomResponse {
+            id: value.key,
+            failures_in_row: value.failures_in_row,
+            last_error: value.last_error,
+            last_error_at: value.last_error_at,
+            last_success_at: value.last_success_at,
+        }
+    }
+}
+
+impl From<SyncStatus> for SyncStatusResponse {
+    fn from(value: SyncStatus) -> Self {
+        let mean_room_duration_seconds = (value.rooms_scraped > 0)
+            .then(|| value.room_duration_total.as_secs_f64() / value.rooms_scraped as f64);
+        SyncStatusResponse {
+            rooms_remaining: value.rooms_remaining,
+            rooms_scraped: value.rooms_scraped,
+            scraped_events: value.scraped_events,
+            upstream_errors: value.upstream_errors,
+            mean_room_duration_seconds,
+            cycle_started_at: value.cycle_started_at,
+            last_full_cycle_at: value.last_full_cycle_at,
+            failing_rooms: Vec::new(),
+        }
+    }
+}
+
+/// Get the progress of the calendar scraper
+///
+/// Reports the same data as the `navigatum_api_calendar_scrape_*` metrics.
+/// Counters are reset when the server restarts.
+/// Additionally lists rooms whose calendars are chronically failing to update.
+#[utoipa::path(
+    tags=["calendar"],
+    responses(
+        (status = 200, description = "**Progress of the calendar scraper**", body = SyncStatusResponse, content_type = "application/json"),
+        (status = 500, description = "**Internal Server Error.** We could not load the failing rooms", body = ApiError, content_type = "application/json", example = json!({"error": "could not get the sync status, please try again later", "code": "internal_error"})),
+    )
+)]
+#[get("/api/calendar/sync_status")]
+pub async fn sync_status_handler(
+    metrics: web::Data<ScrapeMetrics>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let failing = match FailingCalendar::get_failing(
+        &data.pool,
+        MIN_FAILURES_IN_ROW,
+        MAX_FAILING_ROOMS,
+    )
+    .await
+    {
+        Ok(failing) => failing,
+        Err(e) => {
+            error!(error = ?e, "could not get the failing rooms");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get the sync status, please try again later",
+            )
+            .into();
+        }
+    };
+    let mut response = SyncStatusResponse::from(metrics.sync_status());
+    response.failing_rooms = failing.into_iter().map(FailingRoomResponse::from).collect();
+    HttpResponse::Ok().json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_mean_duration() {
+        let response = SyncStatusResponse::from(SyncStatus::default());
+        assert_eq!(response.mean_room_duration_seconds, None);
+        let response = SyncStatusResponse::from(SyncStatus {
+            rooms_scraped: 4,
+            room_duration_total: Duration::from_secs(6),
+            ..SyncStatus::default()
+        });
+        assert_eq!(response.mean_room_duration_seconds, Some(1.5));
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use actix_web::{App, test};
+    use pretty_assertions::assert_eq;
+    use prometheus::Registry;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::AppData;
+    use crate::db::calendar::Event;
+    use crate::setup::tests::PostgresTestContainer;
+
+    #[actix_web::test]
+    async fn test_failing_rooms() {
+        let pg = PostgresTestContainer::new().await;
+        for (key, calendar_url) in [
+            ("5602.EG.001", Some("https://campus.tum.de/1")),
+            ("5602.EG.002", Some("https://campus.tum.de/2")),
+            ("5602.EG.003", None),
+        ] {
+            let data = serde_json::json!({
+                "id": key,
+                "name": key,
+                "type": "room",
+                "type_common_name": "Hörsaal",
+                "props": {"calendar_url": calendar_url},
+            });
+            sqlx::query("INSERT INTO de(key,data) VALUES ($1,$2)")
+                .bind(key)
+                .bind(data)
+                .execute(&pg.pool)
+                .await
+                .unwrap();
+        }
+        let failed_at = DateTime::UNIX_EPOCH;
+        for _ in 0..MIN_FAILURES_IN_ROW {
+            for key in ["5602.EG.001", "5602.EG.002"] {
+                Event::record_calendar_failure(&pg.pool, key, &failed_at, "404 Not Found")
+                    .await
+                    .unwrap();
+            }
+        }
+        // recovered => no longer failing
+        Event::update_last_calendar_success_at(&pg.pool, "5602.EG.002", &Utc::now())
+            .await
+            .unwrap();
+        Event::record_calendar_failure(&pg.pool, "5602.EG.002", &failed_at, "timeout")
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pg.pool.clone())))
+                .app_data(web::Data::new(
+                    ScrapeMetrics::register(&Registry::new()).unwrap(),
+                ))
+                .service(sync_status_handler),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/api/calendar/sync_status")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body["failing_rooms"],
+            serde_json::json!([{
+                "id": "5602.EG.001",
+                "failures_in_row": 3,
+                "last_error": "404 Not Found",
+                "last_error_at": "1970-01-01T00:00:00Z",
+                "last_success_at": null,
+            }])
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use crate::error::ApiError;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct UrlPathParams {
+    /// ID of the room
+    #[param(example = "5602.EG.001")]
+    id: String,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CalendarUrlResponse {
+    /// Where the calendar of the room can be found in TUMonline
+    #[schema(examples(
+        "https://campus.tum.de/tumonline/tvKalender.wSicht?cOrg=19691&cRes=12543&cReadonly=J"
+    ))]
+    calendar_url: String,
+}
+
+/// Get the TUMonline calendar link of a room
+///
+/// Returns only the link, which [`/api/calendar`](#tag/calendar/operation/calendar_handler) returns along with the events.
+/// Use this if you just want to link to TUMonline, as it does not have to look up any events.
+#[utoipa::path(
+    tags=["calendar"],
+    params(UrlPathParams),
+    responses(
+        (status = 200, description = "**Link to the calendar of the room** in TUMonline", body = CalendarUrlResponse, content_type = "application/json"),
+        (status = 404, description = r#"**Not found.** Causes are (delivered via the `code` in the body):
+
+- `not_found`: The room does not exist.
+- `no_calendar`: The room does not have a calendar."#, body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002 does not have a calendar", "code": "no_calendar"})),
+    )
+)]
+#[get("/api/calendar/{id}/url")]
+pub async fn url_handler(
+    params: web::Path<UrlPathParams>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let id = params.id.trim();
+    let locations = match data
+        .calendar_locations
+        .get_locations(&data.pool, &[id.to_string()])
+        .await
+    {
+        Ok(locations) => locations.into_inner(),
+        Err(e) => {
+            error!(error = ?e, id, "could not get location");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get the location, please try again later",
+            )
+            .into();
+        }
+    };
+    let Some(location) = locations.into_iter().next() else {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("Room {id} does not exist"),
+        )
+        .into();
+    };
+    let Some(calendar_url) = location.calendar_url else {
+        return ApiError::new(
+            StatusCode::NOT_FOUND,
+            "no_calendar",
+            format!("Room {id} does not have a calendar"),
+        )
+        .into();
+    };
+    HttpResponse::Ok().json(CalendarUrlResponse { calendar_url })
+}
+
+#[cfg(test)]
+mod db_tests {
+    use actix_web::{App, test};
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::AppData;
+    use crate::setup::tests::PostgresTestContainer;
+
+    async fn insert_room(pool: &sqlx::PgPool, key: &str, props: Value) {
+        let data = serde_json::json!({
+            "id": key,
+            "name": key,
+            "type": "room",
+            "type_common_name": "Hörsaal",
+            "coords": {"lat": 48.26, "lon": 11.67, "source": "inferred"},
+            "props": props,
+        });
+        sqlx::query("INSERT INTO de(key,data) VALUES ($1,$2)")
+            .bind(key)
+            .bind(data)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn get_url(pool: &sqlx::PgPool, id: &str) -> (u16, Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::from(pool.clone())))
+                .service(url_handler),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/calendar/{id}/url"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let status = resp.status().as_u16();
+        (status, test::read_body_json(resp).await)
+    }
+
+    #[actix_web::test]
+    async fn test_calendar_url() {
+        let pg = PostgresTestContainer::new().await;
+        insert_room(
+            &pg.pool,
+            "5602.EG.001",
+            serde_json::json!({"calendar_url": "https://campus.tum.de/1", "tumonline_room_nr": 1}),
+        )
+        .await;
+        insert_room(&pg.pool, "5602.EG.002", serde_json::json!({})).await;
+
+        let (status, body) = get_url(&pg.pool, "5602.EG.001").await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            serde_json::json!({"calendar_url": "https://campus.tum.de/1"})
+        );
+        let (status, body) = get_url(&pg.pool, "5602.EG.002").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "no_calendar");
+        let (status, body) = get_url(&pg.pool, "does-not-exist").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "not_found");
+    }
+}
@@ -0,0 +1,416 @@
+use std::cmp::Reverse;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+
+use super::{EventTypeResponse, translate_type_name, validate_locations};
+use crate::db::calendar::LocalEvent;
+use crate::error::ApiError;
+use crate::localisation;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct WeekPathParams {
+    /// ID of the room
+    #[param(example = "5602.EG.001")]
+    id: String,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct WeekQueryArgs {
+    /// Any day of the requested week
+    #[param(example = "2024-05-13")]
+    date: NaiveDate,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct WeekResponse {
+    /// Monday of the requested week
+    #[schema(examples("2024-05-13"))]
+    week_start: NaiveDate,
+    /// Entries of the week, from monday to sunday
+    #[schema(min_items = 7, max_items = 7)]
+    days: Vec<DayResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct DayResponse {
+    #[schema(examples("2024-05-13"))]
+    date: NaiveDate,
+    /// Entries of this day, ordered by their start
+    ///
+    /// Entries spanning midnight are listed on each day they touch.
+    slots: Vec<SlotResponse>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct SlotResponse {
+    /// ID of the calendar entry used in TUMonline internally
+    #[schema(examples(6424))]
+    id: i32,
+    /// Title of the entry in the requested language
+    #[schema(examples("Quantenteleportation", "Quantum teleportation"))]
+    title: String,
+    entry_type: EventTypeResponse,
+    /// Translated to english if `lang=en` is requested and we know the translation.
+    #[schema(examples("Abhaltung", "Course session"))]
+    detailed_entry_type: String,
+    /// start of the whole entry
+    #[schema(examples("2024-05-13T08:15:00Z"))]
+    start_at: DateTime<Utc>,
+    /// end of the whole entry
+    #[schema(examples("2024-05-13T09:45:00Z"))]
+    end_at: DateTime<Utc>,
+    /// Minute of the (local) day at which the entry starts on this day
+    #[schema(examples(615), minimum = 0, maximum = 1440)]
+    start_minute: u16,
+    /// How many minutes of this day the entry takes
+    #[schema(examples(90), minimum = 0, maximum = 1440)]
+    duration_minutes: u16,
+    /// Lane the entry is displayed in, counted from the left
+    ///
+    /// Overlapping entries are displayed side by side.
+    #[schema(examples(0))]
+    lane: usize,
+    /// Number of lanes shared by the entries overlapping (transitively) with this one
+    #[schema(examples(1), minimum = 1)]
+    lane_count: usize,
+}
+
+/// Get the calendar of a room as a week grid
+///
+/// Groups the entries of the week containing `date` by weekday (in the local time of the campus) and precomputes their layout.
+/// Overlapping entries are assigned to lanes, so that they can be displayed next to each other.
+#[utoipa::path(
+    tags=["calendar"],
+    params(WeekPathParams, WeekQueryArgs, localisation::LangQueryArgs),
+    responses(
+        (status = 200, description = "**Entries of the week** grouped by weekday", body = WeekResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The room does not exist or the date is invalid", body = ApiError, content_type = "application/json", example = json!({"error": "Requested id 5121.EG.004 does not exist", "code": "unknown_id"})),
+        (status = 404, description = "**Not found.** The room does not have a calendar", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.002/None does not have a calendar", "code": "no_calendar"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the calendar entries", body = ApiError, content_type = "application/json", example = json!({"error": "could not get calendar entries, please try again later", "code": "internal_error"})),
+        (status = 503, description = "**Not Ready.** please retry later", body = ApiError, content_type = "application/json", example = json!({"error": "Room 5121.EG.003/None calendar entry is currently in the process of being scraped, please try again later", "code": "not_yet_scraped"})),
+    )
+)]
+#[get("/api/calendar/{id}/week")]
+pub async fn week_handler(
+    params: web::Path<WeekPathParams>,
+    web::Query(week): web::Query<WeekQueryArgs>,
+    web::Query(lang): web::Query<localisation::LangQueryArgs>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    let ids = [params.id.trim().to_string()];
+    let locations = match data
+        .calendar_locations
+        .get_locations(&data.pool, &ids)
+        .await
+    {
+        Ok(l) => l.into_inner(),
+        Err(e) => {
+            error!(error = ?e, "could not get location");
+            return internal_error();
+        }
+    };
+    if let Err(e) = validate_locations(&ids, &locations) {
+        return e.into();
+    }
+    let week_start = week
+        .date
+        .checked_sub_days(Days::new(week.date.weekday().num_days_from_monday().into()))
+        .and_then(|monday| Some((monday, monday.checked_add_days(Days::new(7))?)));
+    let Some((week_start, week_end)) = week_start else {
+        return ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_date",
+            "The requested week is out of range",
+        )
+        .with_parameter("date")
+        .into();
+    };
+    let events = match LocalEvent::get_in_local_window(
+        &data.pool,
+        &ids[0],
+        week_start.and_time(NaiveTime::MIN),
+        week_end.and_time(NaiveTime::MIN),
+    )
+    .await
+    {
+        Ok(events) => events,
+        Err(e) => {
+            error!(error = ?e, ids = ?ids, "could not get entries from the db");
+            return internal_error();
+        }
+    };
+    let days = week_start
+        .iter_days()
+        .take(7)
+        .map(|date| DayResponse {
+            date,
+            slots: day_slots(&events, date, lang),
+        })
+        .collect();
+    HttpResponse::Ok().json(WeekResponse { week_start, days })
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "could not get calendar entries, please try again later",
+    )
+    .into()
+}
+
+fn day_slots(
+    events: &[LocalEvent],
+    date: NaiveDate,
+    lang: localisation::LangQueryArgs,
+) -> Vec<SlotResponse> {
+    let clipped = events
+        .iter()
+        .filter_map(|e| Some((e, clip_to_day(e.start_local, e.end_local, date)?)))
+        .collect::<Vec<_>>();
+    let minutes = clipped.iter().map(|(_, m)| *m).collect::<Vec<_>>();
+    let lanes = assign_lanes(&minutes);
+    let mut slots = clipped
+        .into_iter()
+        .zip(lanes)
+        .map(|((e, (start, end)), lane)| {
+            let (title, detailed_entry_type) = if lang.should_use_english() {
+                (&e.title_en, translate_type_name(&e.detailed_entry_type))
+            } else {
+                (&e.title_de, e.detailed_entry_type.as_str())
+            };
+            SlotResponse {
+                id: e.id,
+                title: title.clone(),
+                entry_type: EventTypeResponse::classify(&e.entry_type, &e.detailed_entry_type),
+                detailed_entry_type: detailed_entry_type.to_string(),
+                start_at: e.start_at,
+                end_at: e.end_at,
+                start_minute: start,
+                duration_minutes: end - start,
+                lane: lane.index,
+                lane_count: lane.count,
+            }
+        })
+        .collect::<Vec<_>>();
+    slots.sort_by_key(|s| (s.start_minute, s.lane));
+    slots
+}
+
+/// The part of `[start, end)` which is on `date`, in minutes of that day
+///
+/// Zero-length entries belong to the day they happen on.
+fn clip_to_day(start: NaiveDateTime, end: NaiveDateTime, date: NaiveDate) -> Option<(u16, u16)> {
+    let day_start = date.and_time(NaiveTime::MIN);
+    let day_end = day_start.checked_add_days(Days::new(1))?;
+    let touches_day = if start == end {
+        start >= day_start && start < day_end
+    } else {
+        start < day_end && end > day_start
+    };
+    if !touches_day {
+        return None;
+    }
+    // local wall-clock times => every day is 24h long, even when daylight saving time changes
+    let minute_of_day =
+        |t: NaiveDateTime| (t.clamp(day_start, day_end) - day_start).num_minutes() as u16;
+    Some((minute_of_day(start), minute_of_day(end)))
+}
+
+/// Position of an entry among the entries it overlaps with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lane {
+    index: usize,
+    count: usize,
+}
+
+/// Assigns overlapping `[start, end)` intervals to side-by-side lanes
+///
+/// Intervals are placed in the leftmost free lane in the order of their start, longer ones first.
+/// This makes the assignment independent of the order the intervals are passed in (except for exact duplicates).
+/// All intervals of a group of (transitively) overlapping intervals share the same lane count, so they can be rendered with equal widths.
+/// Zero-length intervals are treated as one minute long, as they would not be visible otherwise.
+fn assign_lanes(intervals: &[(u16, u16)]) -> Vec<Lane> {
+    let mut order = (0..intervals.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| (intervals[i].0, Reverse(intervals[i].1), i));
+    let mut lanes = vec![Lane { index: 0, count: 1 }; intervals.len()];
+    // end of the last interval in each lane of the current group
+    let mut lane_ends = Vec::<u16>::new();
+    let mut group = Vec::<usize>::new();
+    for i in order {
+        let (start, end) = intervals[i];
+        let end = end.max(start + 1);
+        if lane_ends.iter().all(|&lane_end| lane_end <= start) {
+            // nothing is running anymore => a new group starts
+            for member in group.drain(..) {
+                lanes[member].count = lane_ends.len();
+            }
+            lane_ends.clear();
+        }
+        let index = match lane_ends.iter().position(|&lane_end| lane_end <= start) {
+            Some(index) => {
+                lane_ends[index] = end;
+                index
+            }
+            None => {
+                lane_ends.push(end);
+                lane_ends.len() - 1
+            }
+        };
+        lanes[i].index = index;
+        group.push(i);
+    }
+    for member in group {
+        lanes[member].count = lane_ends.len();
+    }
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn lanes(intervals: &[(u16, u16)]) -> Vec<(usize, usize)> {
+        assign_lanes(intervals)
+            .into_iter()
+            .map(|l| (l.index, l.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_lanes_without_overlaps() {
+        assert_eq!(lanes(&[]), vec![]);
+        assert_eq!(lanes(&[(0, 60)]), vec![(0, 1)]);
+        // touching is not overlapping
+        assert_eq!(
+            lanes(&[(0, 60), (60, 120), (120, 180)]),
+            vec![(0, 1), (0, 1), (0, 1)]
+        );
+        // order of the input does not matter
+        assert_eq!(lanes(&[(60, 120), (0, 60)]), vec![(0, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn test_lanes_identical() {
+        assert_eq!(
+            lanes(&[(600, 690), (600, 690), (600, 690)]),
+            vec![(0, 3), (1, 3), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_containment() {
+        // the longer entry gets the leftmost lane, even if it is passed in last
+        assert_eq!(
+            lanes(&[(600, 630), (630, 660), (600, 720)]),
+            vec![(1, 2), (1, 2), (0, 2)]
+        );
+        // an entry nested in a nested entry
+        assert_eq!(
+            lanes(&[(0, 600), (60, 300), (120, 180)]),
+            vec![(0, 3), (1, 3), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_chain() {
+        // a overlaps b, b overlaps c, but a does not overlap c => c reuses the lane of a
+        // all of them form one group and thus share the same lane count
+        assert_eq!(
+            lanes(&[(0, 60), (30, 90), (60, 120)]),
+            vec![(0, 2), (1, 2), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_staircase() {
+        // each entry overlaps the next two => three lanes are needed and reused in turn
+        assert_eq!(
+            lanes(&[(0, 90), (30, 120), (60, 150), (90, 180), (120, 210)]),
+            vec![(0, 3), (1, 3), (2, 3), (0, 3), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_reuse_leftmost_free_lane() {
+        // the third lane frees up first, but once both are free again the leftmost one is taken
+        assert_eq!(
+            lanes(&[(0, 120), (0, 30), (0, 60), (45, 90), (100, 110)]),
+            vec![(0, 3), (2, 3), (1, 3), (2, 3), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_groups_are_independent() {
+        // a busy morning does not make the afternoon narrower
+        assert_eq!(
+            lanes(&[(0, 60), (0, 60), (0, 60), (600, 660), (630, 690)]),
+            vec![(0, 3), (1, 3), (2, 3), (0, 2), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_lanes_zero_length() {
+        // zero-length entries take up a minute
+        assert_eq!(lanes(&[(60, 60), (60, 60)]), vec![(0, 2), (1, 2)]);
+        assert_eq!(lanes(&[(0, 60), (60, 60)]), vec![(0, 1), (0, 1)]);
+        assert_eq!(lanes(&[(0, 60), (59, 59)]), vec![(0, 2), (1, 2)]);
+        assert_eq!(lanes(&[(1439, 1439), (1439, 1440)]), vec![(1, 2), (0, 2)]);
+    }
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        format!("{date}T{time}").parse().unwrap()
+    }
+
+    #[test]
+    fn test_clip_to_day() {
+        let day: NaiveDate = "2024-05-13".parse().unwrap();
+        let clip = |start, end| clip_to_day(start, end, day);
+        assert_eq!(
+            clip(at("2024-05-13", "10:15:00"), at("2024-05-13", "11:45:00")),
+            Some((615, 705))
+        );
+        // spanning midnight => clipped on both days
+        assert_eq!(
+            clip(at("2024-05-12", "22:00:00"), at("2024-05-13", "02:00:00")),
+            Some((0, 120))
+        );
+        assert_eq!(
+            clip(at("2024-05-13", "22:00:00"), at("2024-05-14", "02:00:00")),
+            Some((1320, 1440))
+        );
+        assert_eq!(
+            clip(at("2024-05-12", "00:00:00"), at("2024-05-15", "00:00:00")),
+            Some((0, 1440))
+        );
+        // ending/starting exactly at midnight does not touch the other day
+        assert_eq!(
+            clip(at("2024-05-12", "22:00:00"), at("2024-05-13", "00:00:00")),
+            None
+        );
+        assert_eq!(
+            clip(at("2024-05-14", "00:00:00"), at("2024-05-14", "02:00:00")),
+            None
+        );
+        // zero-length entries
+        assert_eq!(
+            clip(at("2024-05-13", "00:00:00"), at("2024-05-13", "00:00:00")),
+            Some((0, 0))
+        );
+        assert_eq!(
+            clip(at("2024-05-14", "00:00:00"), at("2024-05-14", "00:00:00")),
+            None
+        );
+    }
+}
@@ -0,0 +1,825 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, delete, get, post, web};
+use chrono::{DateTime, Utc};
+use prometheus::{IntCounterVec, Opts, Registry};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use super::ratelimit::ClientIpKeyExtractor;
+use super::tokens::RecordedTokens;
+use crate::db::feedback::BlocklistEntry;
+use crate::error::ApiError;
+use crate::routes::admin;
+
+/// How long the entries are used before they are loaded again
+///
+/// Changes via the admin endpoints of this instance apply right away, other instances pick them up after this.
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Tokens and clients, which are not allowed to submit feedback
+///
+/// Entries are managed via the admin endpoints and stored in the database.
+/// Without a database, nothing is blocked.
+#[derive(Clone)]
+pub struct Blocklist {
+    store: Option<PgPool>,
+    /// The entries and when they were loaded, as every request is checked
+    cached: Arc<RwLock<Option<(Instant, Arc<[BlocklistEntry]>)>>>,
+    /// Rejected requests, by whether the token or the client was blocked
+    blocked: IntCounterVec,
+}
+impl Default for Blocklist {
+    fn default() -> Self {
+        let blocked = IntCounterVec::new(
+            Opts::new(
+                "feedback_blocked_total",
+                "Requests rejected by the abuse blocklist, by whether their token or their client was blocked",
+            )
+            .namespace("navigatum_api"),
+            &["by"],
+        )
+        .expect("the metric options are valid");
+        Self {
+            store: None,
+            cached: Arc::default(),
+            blocked,
+        }
+    }
+}
+
+impl fmt::Debug for Blocklist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //fields purposely omitted
+        f.debug_struct("Blocklist").finish()
+    }
+}
+
+/// What a request is checked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Subject {
+    /// The `kid` of the token
+    Kid(u64),
+    /// The address of the client
+    Ip(IpAddr),
+}
+impl Subject {
+    fn as_label(self) -> &'static str {
+        match self {
+            Subject::Kid(_) => "token",
+            Subject::Ip(_) => "client",
+        }
+    }
+}
+
+impl Blocklist {
+    pub fn persistent(pool: PgPool) -> Self {
+        Self {
+            store: Some(pool),
+            ..Self::default()
+        }
+    }
+
+    /// Exposes on `/api/metrics` how many requests were blocked
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.blocked.clone()))
+    }
+
+    /// Whether the token with this `kid` is blocked
+    pub async fn blocks_kid(&self, kid: u64) -> bool {
+        self.blocks(Subject::Kid(kid)).await
+    }
+
+    /// Rejects the request, if the client sending it is blocked
+    pub async fn check_request(&self, req: &HttpRequest) -> Result<(), ApiError> {
+        let Some(ip) = ClientIpKeyExtractor::default().client_ip_of(req) else {
+            return Ok(());
+        };
+        if self.blocks(Subject::Ip(ip)).await {
+            return Err(blocked());
+        }
+        Ok(())
+    }
+
+    /// Forgets the cached entries, e.g. because they were changed
+    pub fn invalidate(&self) {
+        *self.cached.write().expect("lock is not poisoned") = None;
+    }
+
+    async fn blocks(&self, subject: Subject) -> bool {
+        self.blocks_at(subject, Utc::now()).await
+    }
+
+    async fn blocks_at(&self, subject: Subject, now: DateTime<Utc>) -> bool {
+        let Some(entries) = self.entries(now).await else {
+            return false;
+        };
+        let Some(entry) = find_match(&entries, subject, now) else {
+            return false;
+        };
+        info!(entry = entry.id, ?subject, "blocked a request");
+        self.blocked.with_label_values(&[subject.as_label()]).inc();
+        true
+    }
+
+    /// The cached entries, loaded again once they are older than the [`CACHE_TTL`]
+    ///
+    /// Entries expiring in between are skipped by [`find_match`].
+    async fn entries(&self, now: DateTime<Utc>) -> Option<Arc<[BlocklistEntry]>> {
+        let pool = self.store.as_ref()?;
+        if let Some((loaded_at, entries)) = &*self.cached.read().expect("lock is not poisoned") {
+            if loaded_at.elapsed() < CACHE_TTL {
+                return Some(entries.clone());
+            }
+        }
+        match BlocklistEntry::active(pool, now).await {
+            Ok(entries) => {
+                let entries = Arc::<[BlocklistEntry]>::from(entries);
+                *self.cached.write().expect("lock is not poisoned") =
+                    Some((Instant::now(), entries.clone()));
+                Some(entries)
+            }
+            Err(e) => {
+                // we would rather let abuse through than block everyone
+                error!(error = ?e, "could not get the blocklist, not blocking anything");
+                None
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn blocked_cnt(&self, by: &str) -> u64 {
+        self.blocked.with_label_values(&[by]).get()
+    }
+}
+
+/// The response to blocked requests
+///
+/// Purposely does not tell why, so that abusers do not learn what to change.
+pub(super) fn blocked() -> ApiError {
+    ApiError::new(
+        StatusCode::FORBIDDEN,
+        "forbidden",
+        "This request is not permitted",
+    )
+}
+
+/// The first entry blocking the `subject` at `now`
+fn find_match(
+    entries: &[BlocklistEntry],
+    subject: Subject,
+    now: DateTime<Utc>,
+) -> Option<&BlocklistEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.expires_at.is_none_or(|expires_at| expires_at > now))
+        .find(|entry| match subject {
+            Subject::Kid(kid) => entry
+                .kid_prefix
+                .as_deref()
+                .is_some_and(|prefix| format!("{kid:016x}").starts_with(prefix)),
+            Subject::Ip(ip) => entry
+                .ip_range
+                .as_deref()
+                .and_then(|range| range.parse::<IpRange>().ok())
+                .is_some_and(|range| range.contains(ip)),
+        })
+}
+
+/// The normalised prefix, if it can be the start of a `kid` written as 16 hex digits
+fn parse_kid_prefix(prefix: &str) -> Option<String> {
+    let prefix = prefix.trim().to_lowercase();
+    let valid = (1..=16).contains(&prefix.len()) && prefix.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then_some(prefix)
+}
+
+/// A range of addresses in CIDR notation, e.g. `192.0.2.0/24` or `2001:db8::/32`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpRange {
+    /// The first address of the range
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients may connect to dual-stack sockets as `::ffff:192.0.2.1`
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                u32::from(ip) & mask_v4(self.prefix_len) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                u128::from(ip) & mask_v6(self.prefix_len) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mask_v4(prefix_len: u8) -> u32 {
+    u32::MAX
+        .checked_shl(32 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+fn mask_v6(prefix_len: u8) -> u128 {
+    u128::MAX
+        .checked_shl(128 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+impl FromStr for IpRange {
+    type Err = &'static str;
+
+    /// A single address is a range of just this address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s.trim(), None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| "not a valid IP address")?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            None => max_prefix_len,
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or("not a valid prefix length")?,
+        };
+        // host bits are ignored, as e.g. 192.0.2.1/24 is commonly meant as 192.0.2.0/24
+        let network = match addr {
+            IpAddr::V4(addr) => IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask_v4(prefix_len))),
+            IpAddr::V6(addr) => IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask_v6(prefix_len))),
+        };
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[derive(Deserialize, Debug, utoipa::ToSchema)]
+struct BlocklistRequest {
+    /// Blocks tokens whose `kid` (written as 16 hex digits) starts with this
+    ///
+    /// Exactly one of `kid_prefix` and `ip_range` has to be set.
+    #[schema(examples("3f2a"))]
+    kid_prefix: Option<String>,
+    /// Blocks clients in this range, a single address blocks just this address
+    ///
+    /// Exactly one of `kid_prefix` and `ip_range` has to be set.
+    #[schema(examples("192.0.2.0/24", "2001:db8::/32"))]
+    ip_range: Option<String>,
+    /// Why the entry is added, only shown to admins
+    #[schema(examples("scripted spam, see #1234"))]
+    reason: String,
+    /// From when on the entry does not block anymore, if ever
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct BlocklistEntryResponse {
+    /// ID of the entry, used to remove it
+    #[schema(examples(42))]
+    id: i32,
+    #[schema(examples("3f2a"))]
+    kid_prefix: Option<String>,
+    #[schema(examples("192.0.2.0/24"))]
+    ip_range: Option<String>,
+    #[schema(examples("scripted spam, see #1234"))]
+    reason: String,
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    created_at: DateTime<Utc>,
+    /// From when on the entry does not block anymore, `null` if it blocks forever
+    #[schema(examples("2039-01-19T03:14:07+01:00"))]
+    expires_at: Option<DateTime<Utc>>,
+}
+impl From<BlocklistEntry> for BlocklistEntryResponse {
+    fn from(value: BlocklistEntry) -> Self {
+        BlocklistEntryResponse {
+            id: value.id,
+            kid_prefix: value.kid_prefix,
+            ip_range: value.ip_range,
+            reason: value.reason,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct BlocklistPathParams {
+    /// ID of the entry as returned when adding it
+    #[param(example = 42)]
+    id: i32,
+}
+
+fn invalid(parameter: &'static str, message: impl Into<Cow<'static, str>>) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, "invalid_blocklist_entry", message)
+        .with_parameter(parameter)
+}
+
+/// The normalised `kid_prefix` and `ip_range` of the request
+fn validate(
+    request: &BlocklistRequest,
+    now: DateTime<Utc>,
+) -> Result<(Option<String>, Option<String>), ApiError> {
+    let subject = match (&request.kid_prefix, &request.ip_range) {
+        (Some(prefix), None) => match parse_kid_prefix(prefix) {
+            Some(prefix) => (Some(prefix), None),
+            None => {
+                return Err(invalid(
+                    "kid_prefix",
+                    "kid_prefix has to be 1 to 16 hex digits",
+                ));
+            }
+        },
+        (None, Some(range)) => match range.parse::<IpRange>() {
+            Ok(range) => (None, Some(range.to_string())),
+            Err(e) => return Err(invalid("ip_range", format!("ip_range is {e}"))),
+        },
+        _ => {
+            return Err(invalid(
+                "kid_prefix",
+                "Exactly one of kid_prefix and ip_range has to be set",
+            ));
+        }
+    };
+    if request.reason.trim().is_empty() {
+        return Err(invalid("reason", "A reason is required"));
+    }
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(invalid("expires_at", "expires_at has to be in the future"));
+    }
+    Ok(subject)
+}
+
+fn internal_error() -> HttpResponse {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "Failed to access the blocklist, please try again later",
+    )
+    .into()
+}
+
+/// Block tokens or clients from submitting feedback
+///
+/// **Requires an admin token.**
+///
+/// Blocked requests to submit feedback, attach images or propose edits are rejected with a `403 forbidden`, which does not tell why.
+/// Entries either block tokens by a prefix of their `kid`, or clients by their address.
+/// Other instances of the server pick up new entries within 30 seconds.
+#[utoipa::path(
+    tags=["admin"],
+    security(("bearer" = [])),
+    request_body = BlocklistRequest,
+    responses(
+        (status = 201, description = "**Entry was added**", body = BlocklistEntryResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad request.** The entry is invalid, see the `parameter`", body = ApiError, content_type = "application/json", example = json!({"error": "ip_range is not a valid prefix length", "code": "invalid_blocklist_entry", "parameter": "ip_range"})),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[post("/api/admin/feedback/blocklist")]
+pub async fn add_blocklist_entry_handler(
+    req: HttpRequest,
+    data: web::Data<crate::AppData>,
+    recorded_tokens: web::Data<RecordedTokens>,
+    request: web::Json<BlocklistRequest>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let (kid_prefix, ip_range) = match validate(&request, Utc::now()) {
+        Ok(subject) => subject,
+        Err(e) => return e.into(),
+    };
+    let entry = BlocklistEntry::insert(
+        &data.pool,
+        kid_prefix.as_deref(),
+        ip_range.as_deref(),
+        request.reason.trim(),
+        request.expires_at,
+    )
+    .await;
+    match entry {
+        Ok(entry) => {
+            info!(entry = entry.id, "added an entry to the blocklist");
+            recorded_tokens.blocklist().invalidate();
+            HttpResponse::Created().json(BlocklistEntryResponse::from(entry))
+        }
+        Err(e) => {
+            error!(error = ?e, "could not add to the blocklist");
+            internal_error()
+        }
+    }
+}
+
+/// List the blocklist
+///
+/// **Requires an admin token.**
+///
+/// Expired entries are not included, the oldest entry comes first.
+#[utoipa::path(
+    tags=["admin"],
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Entries which currently block**", body = Vec<BlocklistEntryResponse>, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[get("/api/admin/feedback/blocklist")]
+pub async fn list_blocklist_handler(
+    req: HttpRequest,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    match BlocklistEntry::active(&data.pool, Utc::now()).await {
+        Ok(entries) => HttpResponse::Ok().json(
+            entries
+                .into_iter()
+                .map(BlocklistEntryResponse::from)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            error!(error = ?e, "could not list the blocklist");
+            internal_error()
+        }
+    }
+}
+
+/// Remove an entry from the blocklist
+///
+/// **Requires an admin token.**
+///
+/// The tokens or clients it blocked can submit feedback again (on other instances of the server within 30 seconds).
+#[utoipa::path(
+    tags=["admin"],
+    security(("bearer" = [])),
+    params(BlocklistPathParams),
+    responses(
+        (status = 200, description = "**Entry was removed**", body = BlocklistEntryResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** There is no such entry", body = ApiError, content_type = "application/json", example = json!({"error": "Blocklist entry 42 does not exist", "code": "not_found"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[delete("/api/admin/feedback/blocklist/{id}")]
+pub async fn remove_blocklist_entry_handler(
+    req: HttpRequest,
+    params: web::Path<BlocklistPathParams>,
+    data: web::Data<crate::AppData>,
+    recorded_tokens: web::Data<RecordedTokens>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    match BlocklistEntry::delete(&data.pool, params.id).await {
+        Ok(Some(entry)) => {
+            info!(entry = entry.id, "removed an entry from the blocklist");
+            recorded_tokens.blocklist().invalidate();
+            HttpResponse::Ok().json(BlocklistEntryResponse::from(entry))
+        }
+        Ok(None) => ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("Blocklist entry {id} does not exist", id = params.id),
+        )
+        .into(),
+        Err(e) => {
+            error!(error = ?e, id = params.id, "could not remove from the blocklist");
+            internal_error()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn range(range: &str) -> IpRange {
+        range.parse().unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr_parsing() {
+        assert_eq!(range("192.0.2.0/24").to_string(), "192.0.2.0/24");
+        // host bits are dropped
+        assert_eq!(range("192.0.2.77/24").to_string(), "192.0.2.0/24");
+        assert_eq!(range(" 2001:db8::1/32 ").to_string(), "2001:db8::/32");
+        // single addresses
+        assert_eq!(range("192.0.2.1").to_string(), "192.0.2.1/32");
+        assert_eq!(range("2001:db8::1").to_string(), "2001:db8::1/128");
+        assert_eq!(range("10.1.2.3/0").to_string(), "0.0.0.0/0");
+
+        for invalid in [
+            "",
+            "192.0.2.0/33",
+            "2001:db8::/129",
+            "192.0.2.0/",
+            "192.0.2/24",
+            "localhost/8",
+            "192.0.2.0/-1",
+        ] {
+            assert!(invalid.parse::<IpRange>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_cidr_matching() {
+        let v4 = range("192.0.2.0/24");
+        assert!(v4.contains(ip("192.0.2.0")));
+        assert!(v4.contains(ip("192.0.2.255")));
+        assert!(!v4.contains(ip("192.0.3.0")));
+        assert!(!v4.contains(ip("192.0.1.255")));
+        // IPv4 clients of dual-stack sockets
+        assert!(v4.contains(ip("::ffff:192.0.2.1")));
+        assert!(!v4.contains(ip("2001:db8::1")));
+
+        let odd = range("10.0.0.0/13");
+        assert!(odd.contains(ip("10.7.255.255")));
+        assert!(!odd.contains(ip("10.8.0.0")));
+
+        let v6 = range("2001:db8::/32");
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+        assert!(!v6.contains(ip("192.0.2.1")));
+
+        assert!(range("192.0.2.1").contains(ip("192.0.2.1")));
+        assert!(!range("192.0.2.1").contains(ip("192.0.2.2")));
+        assert!(range("0.0.0.0/0").contains(ip("203.0.113.9")));
+        assert!(range("::/0").contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_kid_prefixes() {
+        assert_eq!(parse_kid_prefix(" 3F2a "), Some("3f2a".to_string()));
+        assert_eq!(parse_kid_prefix(&"f".repeat(16)), Some("f".repeat(16)));
+        assert_eq!(parse_kid_prefix(&"f".repeat(17)), None);
+        assert_eq!(parse_kid_prefix(""), None);
+        assert_eq!(parse_kid_prefix("xyz"), None);
+    }
+
+    fn entry(
+        id: i32,
+        kid_prefix: Option<&str>,
+        ip_range: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> BlocklistEntry {
+        BlocklistEntry {
+            id,
+            kid_prefix: kid_prefix.map(str::to_string),
+            ip_range: ip_range.map(str::to_string),
+            reason: "abuse".to_string(),
+            created_at: DateTime::from_timestamp(0, 0).unwrap(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let entries = [
+            entry(1, Some("00ff"), None, None),
+            entry(2, None, Some("192.0.2.0/24"), None),
+        ];
+        let id = |subject| find_match(&entries, subject, now).map(|entry| entry.id);
+        assert_eq!(id(Subject::Kid(0x00ff_0000_0000_0001)), Some(1));
+        // kids are zero-padded to 16 digits
+        assert_eq!(id(Subject::Kid(0xff)), None);
+        assert_eq!(id(Subject::Kid(0x0100_0000_0000_0000)), None);
+        assert_eq!(id(Subject::Ip(ip("192.0.2.9"))), Some(2));
+        assert_eq!(id(Subject::Ip(ip("198.51.100.1"))), None);
+    }
+
+    #[test]
+    fn test_expired_entries_do_not_block() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let second = chrono::Duration::seconds(1);
+        let subject = Subject::Ip(ip("192.0.2.9"));
+        let expiring = |expires_at| [entry(1, None, Some("192.0.2.0/24"), expires_at)];
+        assert!(find_match(&expiring(Some(now + second)), subject, now).is_some());
+        assert!(find_match(&expiring(Some(now)), subject, now).is_none());
+        assert!(find_match(&expiring(Some(now - second)), subject, now).is_none());
+        assert!(find_match(&expiring(None), subject, now).is_some());
+    }
+
+    #[test]
+    fn test_validation() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let request = |kid_prefix: Option<&str>, ip_range: Option<&str>| BlocklistRequest {
+            kid_prefix: kid_prefix.map(str::to_string),
+            ip_range: ip_range.map(str::to_string),
+            reason: "abuse".to_string(),
+            expires_at: None,
+        };
+        assert_eq!(
+            validate(&request(Some("AB"), None), now).unwrap(),
+            (Some("ab".to_string()), None)
+        );
+        assert_eq!(
+            validate(&request(None, Some("192.0.2.7/24")), now).unwrap(),
+            (None, Some("192.0.2.0/24".to_string()))
+        );
+        let parameter = |request: BlocklistRequest| {
+            let error = serde_json::to_value(validate(&request, now).unwrap_err()).unwrap();
+            assert_eq!(error["code"], "invalid_blocklist_entry");
+            error["parameter"].as_str().unwrap().to_string()
+        };
+        assert_eq!(parameter(request(None, None)), "kid_prefix");
+        assert_eq!(
+            parameter(request(Some("ab"), Some("192.0.2.0/24"))),
+            "kid_prefix"
+        );
+        assert_eq!(parameter(request(Some("xyz"), None)), "kid_prefix");
+        assert_eq!(parameter(request(None, Some("192.0.2.0/33"))), "ip_range");
+        assert_eq!(
+            parameter(BlocklistRequest {
+                reason: " ".to_string(),
+                ..request(Some("ab"), None)
+            }),
+            "reason"
+        );
+        assert_eq!(
+            parameter(BlocklistRequest {
+                expires_at: Some(now),
+                ..request(Some("ab"), None)
+            }),
+            "expires_at"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_without_a_database_nothing_is_blocked() {
+        let blocklist = Blocklist::default();
+        assert!(!blocklist.blocks_kid(1).await);
+        let req = actix_web::test::TestRequest::default()
+            .peer_addr("192.0.2.1:1234".parse().unwrap())
+            .to_http_request();
+        assert!(blocklist.check_request(&req).await.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use actix_web::test;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::setup::tests::PostgresTestContainer;
+
+    #[actix_web::test]
+    async fn test_blocked_requests_are_counted() {
+        let pg = PostgresTestContainer::new().await;
+        let blocklist = Blocklist::persistent(pg.pool.clone());
+        BlocklistEntry::insert(&pg.pool, Some("00ff"), None, "abuse", None)
+            .await
+            .unwrap();
+        BlocklistEntry::insert(&pg.pool, None, Some("192.0.2.0/24"), "abuse", None)
+            .await
+            .unwrap();
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        BlocklistEntry::insert(
+            &pg.pool,
+            None,
+            Some("198.51.100.0/24"),
+            "abuse",
+            Some(expires_at),
+        )
+        .await
+        .unwrap();
+
+        assert!(blocklist.blocks_kid(0x00ff_0000_0000_0001).await);
+        assert!(!blocklist.blocks_kid(1).await);
+        let request_from = |ip: &str| {
+            test::TestRequest::default()
+                .peer_addr(format!("{ip}:1234").parse().unwrap())
+                .to_http_request()
+        };
+        let err = blocklist
+            .check_request(&request_from("192.0.2.1"))
+            .await
+            .unwrap_err();
+        assert_eq!(err, blocked());
+        assert!(
+            blocklist
+                .check_request(&request_from("198.51.100.1"))
+                .await
+                .is_err()
+        );
+        let expired = Subject::Ip("198.51.100.1".parse().unwrap());
+        assert!(!blocklist.blocks_at(expired, expires_at).await);
+        assert!(
+            blocklist
+                .check_request(&request_from("203.0.113.1"))
+                .await
+                .is_ok()
+        );
+
+        assert_eq!(blocklist.blocked_cnt("token"), 1);
+        assert_eq!(blocklist.blocked_cnt("client"), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_entries_are_stored() {
+        let pg = PostgresTestContainer::new().await;
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let first = BlocklistEntry::insert(
+            &pg.pool,
+            None,
+            Some("192.0.2.0/24"),
+            "abuse",
+            Some(expires_at),
+        )
+        .await
+        .unwrap();
+        let second = BlocklistEntry::insert(&pg.pool, Some("00ff"), None, "abuse", None)
+            .await
+            .unwrap();
+        let ids =
+            |entries: Vec<BlocklistEntry>| entries.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        let active = BlocklistEntry::active(&pg.pool, Utc::now()).await.unwrap();
+        assert_eq!(ids(active), vec![first.id, second.id]);
+
+        let active = BlocklistEntry::active(&pg.pool, expires_at).await.unwrap();
+        assert_eq!(ids(active), vec![second.id]);
+        // expired entries are pruned when adding new ones
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        let pruned = BlocklistEntry::insert(&pg.pool, Some("cd"), None, "abuse", Some(expired))
+            .await
+            .unwrap();
+        let third = BlocklistEntry::insert(&pg.pool, Some("ab"), None, "abuse", None)
+            .await
+            .unwrap();
+        assert!(
+            BlocklistEntry::delete(&pg.pool, pruned.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let removed = BlocklistEntry::delete(&pg.pool, second.id).await.unwrap();
+        assert_eq!(
+            removed.map(|e| e.kid_prefix),
+            Some(Some("00ff".to_string()))
+        );
+        assert!(
+            BlocklistEntry::delete(&pg.pool, second.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let active = BlocklistEntry::active(&pg.pool, expires_at).await.unwrap();
+        assert_eq!(ids(active), vec![third.id]);
+    }
+
+    #[actix_web::test]
+    async fn test_entries_are_cached() {
+        let pg = PostgresTestContainer::new().await;
+        let blocklist = Blocklist::persistent(pg.pool.clone());
+        let entry = BlocklistEntry::insert(&pg.pool, Some("00ff"), None, "abuse", None)
+            .await
+            .unwrap();
+        assert!(blocklist.blocks_kid(0x00ff_0000_0000_0001).await);
+
+        BlocklistEntry::delete(&pg.pool, entry.id).await.unwrap();
+        assert!(blocklist.blocks_kid(0x00ff_0000_0000_0001).await);
+        blocklist.invalidate();
+        assert!(!blocklist.blocks_kid(0x00ff_0000_0000_0001).await);
+    }
+}
@@ -0,0 +1,303 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::tokens::{JwtKeys, configured_keys};
+use crate::error::ApiError;
+use crate::routes::admin::constant_time_eq;
+
+/// The only algorithm we offer, named as [ALTCHA](https://altcha.org/docs/server-integration) does
+const ALGORITHM: &str = "SHA-256";
+/// How many numbers clients have to try at most, if `FEEDBACK_CHALLENGE_MAX_NUMBER` is not set
+///
+/// Clients try half of them on average, which takes well below a second in a browser.
+const DEFAULT_MAX_NUMBER: u64 = 100_000;
+/// How long a challenge can be solved and used for
+///
+/// Has to be shorter than [`super::tokens::TOKEN_MAX_AGE`], as this is how long used challenges are remembered.
+const CHALLENGE_MAX_AGE: i64 = 3600;
+
+/// A proof-of-work challenge, which has to be solved to post feedback
+///
+/// The client has to find the `number` between `0` and `maxnumber`, for which the hex-encoded SHA-256 of `salt` followed by the `number` is the `challenge`.
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Challenge {
+    #[schema(example = "SHA-256")]
+    algorithm: String,
+    #[schema(example = "4e3ba4c1f5cdfc2c3a5e4b0a1b7f6c1c5a96b3e8f3d8a2c76cc1a4cd6c0d1d2a")]
+    challenge: String,
+    /// Upper bound (inclusive) of the `number` the client is looking for
+    #[schema(example = 100_000)]
+    maxnumber: u64,
+    /// Contains when the challenge expires, so that it does not have to be stored
+    #[schema(example = "9b1c3e7f5a0d2c4e6f8a1b3d?expires=1669637381")]
+    salt: String,
+    /// Proves that we created the challenge
+    #[schema(example = "0f4c2e5d7b9a1c3e5f7a9b1d3f5a7c9e1b3d5f7a9c1e3b5d7f9a1c3e5b7d9f1a")]
+    signature: String,
+}
+
+/// A [`Challenge`] and the `number` solving it
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ChallengeSolution {
+    #[schema(example = "SHA-256")]
+    algorithm: String,
+    #[schema(example = "4e3ba4c1f5cdfc2c3a5e4b0a1b7f6c1c5a96b3e8f3d8a2c76cc1a4cd6c0d1d2a")]
+    challenge: String,
+    /// The number the client found
+    #[schema(example = 42_137)]
+    number: u64,
+    #[schema(example = "9b1c3e7f5a0d2c4e6f8a1b3d?expires=1669637381")]
+    salt: String,
+    #[schema(example = "0f4c2e5d7b9a1c3e5f7a9b1d3f5a7c9e1b3d5f7a9c1e3b5d7f9a1c3e5b7d9f1a")]
+    signature: String,
+}
+
+/// How many numbers clients have to try at most, configurable via `FEEDBACK_CHALLENGE_MAX_NUMBER`
+fn max_number() -> u64 {
+    let Ok(raw) = std::env::var("FEEDBACK_CHALLENGE_MAX_NUMBER") else {
+        return DEFAULT_MAX_NUMBER;
+    };
+    match raw.trim().parse() {
+        Ok(max_number) if max_number > 0 => max_number,
+        _ => {
+            warn!(
+                raw,
+                "FEEDBACK_CHALLENGE_MAX_NUMBER is not a positive number, using the default"
+            );
+            DEFAULT_MAX_NUMBER
+        }
+    }
+}
+
+fn hash(salt: &str, number: u64) -> String {
+    format!("{:x}", Sha256::digest(format!("{salt}{number}").as_bytes()))
+}
+
+fn sign(secret: &[u8], challenge: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(challenge.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+impl Challenge {
+    /// A new challenge, signed with the primary key
+    fn new(keys: &JwtKeys, max_number: u64, now: i64) -> Self {
+        let salt = format!(
+            "{:024x}?expires={}",
+            rand::random::<u128>() >> 32,
+            now + CHALLENGE_MAX_AGE
+        );
+        let challenge = hash(&salt, rand::random_range(0..=max_number));
+        Self {
+            algorithm: ALGORITHM.to_string(),
+            signature: sign(keys.primary_secret(), &challenge),
+            challenge,
+            maxnumber: max_number,
+            salt,
+        }
+    }
+}
+
+impl ChallengeSolution {
+    /// Identifies the challenge, so that it can be recorded as used like a token
+    pub(super) fn id(&self) -> u64 {
+        let digest = Sha256::digest(self.challenge.as_bytes());
+        u64::from_be_bytes(
+            digest[..8]
+                .try_into()
+                .expect("SHA-256 digests are 32 bytes"),
+        )
+    }
+
+    /// Whether we created the challenge, it has not expired and the `number` solves it
+    ///
+    /// Costs two hashes and one HMAC per key, regardless of the difficulty.
+    /// Whether the challenge was already used is up to the caller.
+    /// Returns when the challenge expires.
+    pub(super) fn verify(&self, keys: &JwtKeys, now: i64) -> Result<i64, ApiError> {
+        let invalid = |message| ApiError::new(StatusCode::FORBIDDEN, "invalid_challenge", message);
+        if self.algorithm != ALGORITHM {
+            return Err(invalid("Unsupported algorithm"));
+        }
+        let expires = self
+            .salt
+            .split_once("?expires=")
+            .and_then(|(_, expires)| expires.parse::<i64>().ok());
+        let Some(expires) = expires else {
+            return Err(invalid("Invalid challenge"));
+        };
+        // in constant time, so that valid signatures cannot be found byte by byte
+        let signed_by_us = keys
+            .secrets()
+            .any(|secret| constant_time_eq(&sign(secret, &self.challenge), &self.signature));
+        if !signed_by_us {
+            return Err(invalid("Invalid challenge"));
+        }
+        if expires < now {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "challenge_expired",
+                "Challenge expired",
+            ));
+        }
+        if hash(&self.salt, self.number) != self.challenge {
+            return Err(invalid("The number does not solve the challenge"));
+        }
+        Ok(expires)
+    }
+}
+
+/// Get a proof-of-work challenge
+///
+/// ***Do not abuse this endpoint.***
+///
+/// Instead of (or in addition to) a [token](#tag/feedback/operation/get_token), feedback can be posted with a solved challenge.
+/// Unlike tokens, challenges can be used right away, once solved.
+///
+/// The challenge follows the format of [ALTCHA](https://altcha.org), so their widget can solve it.
+/// To solve it by hand, find the `number` between `0` and `maxnumber` for which `hex(sha256(salt + number))` equals `challenge`.
+/// Then send all fields of the challenge and the `number` along with the feedback.
+///
+/// Challenges are valid for 1h and can only be used once.
+#[utoipa::path(
+    tags=["feedback"],
+    responses(
+        (status = 200, description = "**A new challenge**", body = Challenge, content_type = "application/json"),
+        (status = 503, description= "**Service unavailable.** We have not configured where feedback is delivered to (e.g. a GitHub Access Token). This could be because we are experiencing technical difficulties or intentional. Please try again later.", body = ApiError, content_type = "application/json", example = json!({"error": "Feedback is currently not configured on this server.", "code": "feedback_not_configured"})),
+    )
+)]
+#[get("/api/feedback/challenge")]
+pub async fn get_challenge() -> HttpResponse {
+    let keys = match configured_keys() {
+        Ok(keys) => keys,
+        Err(e) => return e.into(),
+    };
+    let challenge = Challenge::new(&keys, max_number(), chrono::Utc::now().timestamp());
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn keys() -> JwtKeys {
+        JwtKeys::parse("new-secret,old-secret").unwrap()
+    }
+
+    /// What clients do
+    fn solve(challenge: Challenge) -> ChallengeSolution {
+        let number = (0..=challenge.maxnumber)
+            .find(|n| hash(&challenge.salt, *n) == challenge.challenge)
+            .expect("the challenge is solvable");
+        ChallengeSolution {
+            algorithm: challenge.algorithm,
+            challenge: challenge.challenge,
+            number,
+            salt: challenge.salt,
+            signature: challenge.signature,
+        }
+    }
+
+    fn code(err: ApiError) -> String {
+        serde_json::to_value(err).unwrap()["code"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_solved_challenges_verify() {
+        let solution = solve(Challenge::new(&keys(), 1_000, NOW));
+        assert!(solution.verify(&keys(), NOW).is_ok());
+        assert!(solution.verify(&keys(), NOW + CHALLENGE_MAX_AGE).is_ok());
+        // previous keys still accept the challenges they signed
+        let rotated = JwtKeys::parse("newer-secret,new-secret").unwrap();
+        assert!(solution.verify(&rotated, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let solution = solve(Challenge::new(&keys(), 1_000, NOW));
+        let wrong_number = ChallengeSolution {
+            number: solution.number + 1,
+            ..solution.clone()
+        };
+        assert_eq!(
+            code(wrong_number.verify(&keys(), NOW).unwrap_err()),
+            "invalid_challenge"
+        );
+        // extending the expiry changes the hash => only works with a forged signature
+        let extended = ChallengeSolution {
+            salt: solution.salt.replace("?expires=", "?expires=9"),
+            ..solution.clone()
+        };
+        assert_eq!(
+            code(extended.verify(&keys(), NOW).unwrap_err()),
+            "invalid_challenge"
+        );
+        // a challenge of our own, which the client made up
+        let made_up_salt = "0?expires=9999999999".to_string();
+        let made_up = ChallengeSolution {
+            challenge: hash(&made_up_salt, 0),
+            number: 0,
+            salt: made_up_salt,
+            ..solution.clone()
+        };
+        assert_eq!(
+            code(made_up.verify(&keys(), NOW).unwrap_err()),
+            "invalid_challenge"
+        );
+        let other_server = JwtKeys::parse("someone-elses-secret").unwrap();
+        assert_eq!(
+            code(solution.verify(&other_server, NOW).unwrap_err()),
+            "invalid_challenge"
+        );
+        assert_eq!(
+            code(
+                solution
+                    .verify(&keys(), NOW + CHALLENGE_MAX_AGE + 1)
+                    .unwrap_err()
+            ),
+            "challenge_expired"
+        );
+    }
+
+    #[test]
+    fn test_ids_differ() {
+        let a = solve(Challenge::new(&keys(), 10, NOW));
+        let b = solve(Challenge::new(&keys(), 10, NOW));
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.id(), a.clone().id());
+    }
+
+    #[test]
+    fn test_work_factor() {
+        // solving takes up to `maxnumber` hashes, verifying a single one
+        let challenge = Challenge::new(&keys(), 1_000, NOW);
+        assert_eq!(challenge.maxnumber, 1_000);
+        let solution = solve(challenge);
+        assert!(solution.number <= 1_000);
+        // the number has to be the secret one, not just any number within the work factor
+        let other = ChallengeSolution {
+            number: (solution.number + 1) % 1_001,
+            ..solution.clone()
+        };
+        assert!(solution.verify(&keys(), NOW).is_ok());
+        assert!(other.verify(&keys(), NOW).is_err());
+    }
+}
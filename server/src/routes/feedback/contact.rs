@@ -0,0 +1,380 @@
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+use crate::AppData;
+use crate::db::feedback::FeedbackContact;
+use crate::error::ApiError;
+use crate::external::github::GitHub;
+use crate::routes::admin::authorise;
+
+/// After this, contacts are purged, even if the issue is still open
+const MAX_AGE: Duration = Duration::from_secs(90 * 24 * 3600);
+/// How often the background task purges contacts
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Email addresses cannot be longer than this, see RFC 5321
+const MAX_EMAIL_LEN: usize = 254;
+const NONCE_LEN: usize = 12;
+
+/// The key contacts are encrypted with, configured via `FEEDBACK_CONTACT_KEY`
+///
+/// Without a key, feedback with a `contact_email` is rejected.
+static CONTACT_KEY: LazyLock<Option<ContactKey>> = LazyLock::new(|| {
+    let raw = std::env::var("FEEDBACK_CONTACT_KEY").ok()?;
+    match ContactKey::parse(&raw) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            error!(error = ?e, "FEEDBACK_CONTACT_KEY is not a valid key, contact emails are not accepted");
+            None
+        }
+    }
+});
+
+/// AES-256-GCM key for the email addresses of reporters who want to be contacted
+///
+/// The issue number is authenticated along with the email, so that a contact cannot be moved to another issue.
+pub struct ContactKey(Aes256Gcm);
+
+impl fmt::Debug for ContactKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //fields purposely omitted
+        f.debug_struct("ContactKey").finish()
+    }
+}
+
+impl ContactKey {
+    /// Parses a base64-encoded 32 byte key, e.g. generated via `openssl rand -base64 32`
+    fn parse(raw: &str) -> anyhow::Result<Self> {
+        let key = BASE64_STANDARD.decode(raw.trim())?;
+        anyhow::ensure!(
+            key.len() == 32,
+            "the key has to be 32 bytes, not {len}",
+            len = key.len()
+        );
+        let cipher = Aes256Gcm::new_from_slice(&key).expect("the key has the right length");
+        Ok(Self(cipher))
+    }
+
+    /// The random nonce followed by the ciphertext
+    fn encrypt(&self, issue_number: u64, email: &str) -> Vec<u8> {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let payload = Payload {
+            msg: email.as_bytes(),
+            aad: &issue_number.to_be_bytes(),
+        };
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("emails are far shorter than what AES-GCM can encrypt");
+        [nonce.as_slice(), &ciphertext].concat()
+    }
+
+    fn decrypt(&self, issue_number: u64, encrypted: &[u8]) -> anyhow::Result<String> {
+        anyhow::ensure!(encrypted.len() > NONCE_LEN, "the contact is truncated");
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &issue_number.to_be_bytes(),
+        };
+        let email = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                anyhow::anyhow!("the contact was encrypted with another key or for another issue")
+            })?;
+        Ok(String::from_utf8(email)?)
+    }
+}
+
+/// Checks that the `contact_email` of feedback is an email address we can store
+pub(super) fn validate_email(email: &str) -> Result<String, ApiError> {
+    if CONTACT_KEY.is_none() {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "contact_not_configured",
+            "Contact emails are currently not accepted on this server, please submit the feedback without one",
+        ));
+    }
+    let email = email.trim();
+    let valid = email.len() <= MAX_EMAIL_LEN && email.parse::<lettre::Address>().is_ok();
+    if !valid {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_contact_email",
+            "The contact_email is not a valid email address",
+        )
+        .with_parameter("contact_email"));
+    }
+    Ok(email.to_string())
+}
+
+/// Stores how to reach the reporter of the issue
+///
+/// If it cannot be stored, the feedback was still delivered => this is logged instead of failing.
+pub(super) async fn store(pool: &PgPool, issue_number: u64, email: &str) {
+    let Some(key) = &*CONTACT_KEY else {
+        error!(
+            issue_number,
+            "the contact key vanished, cannot store the contact"
+        );
+        return;
+    };
+    let encrypted = key.encrypt(issue_number, email);
+    if let Err(e) = FeedbackContact::insert(pool, issue_number, &encrypted).await {
+        error!(error = ?e, issue_number, "could not store the contact");
+    }
+}
+
+/// Purges contacts of closed issues and those older than 90 days, periodically
+pub async fn purge_in_background(pool: PgPool, github: &GitHub) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        purge(&pool, github, Utc::now()).await;
+    }
+}
+
+/// Purges the contacts which were created 90 days before `now` or whose issue is not open anymore
+///
+/// Returns how many contacts were purged.
+/// If GitHub cannot tell us whether an issue is open, its contact is kept until the next attempt.
+async fn purge(pool: &PgPool, github: &GitHub, now: DateTime<Utc>) -> u64 {
+    let cutoff = now - chrono::Duration::from_std(MAX_AGE).expect("90 days fit into a duration");
+    let mut purged = match FeedbackContact::delete_created_before(pool, cutoff).await {
+        Ok(purged) => purged,
+        Err(e) => {
+            error!(error = ?e, "could not purge old contacts");
+            0
+        }
+    };
+    let issue_numbers = match FeedbackContact::issue_numbers(pool).await {
+        Ok(issue_numbers) => issue_numbers,
+        Err(e) => {
+            error!(error = ?e, "could not get the issues we have contacts for");
+            return purged;
+        }
+    };
+    for issue_number in issue_numbers {
+        match github.is_open_issue(issue_number).await {
+            Ok(true) => {}
+            Ok(false) => match FeedbackContact::delete(pool, issue_number).await {
+                Ok(()) => purged += 1,
+                Err(e) => error!(error = ?e, issue_number, "could not purge the contact"),
+            },
+            Err(e) => {
+                warn!(error = ?e, issue_number, "could not check whether the issue is still open");
+            }
+        }
+    }
+    if purged > 0 {
+        info!(purged, "purged contacts of closed or old feedback");
+    }
+    purged
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct ContactPathParams {
+    /// Number of the GitHub issue the feedback was submitted as
+    #[param(example = 9)]
+    issue_number: u64,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct ContactResponse {
+    /// The address the reporter asked to be contacted at
+    #[schema(examples("reporter@example.com"))]
+    email: String,
+    /// When the feedback was submitted, the contact is purged 90 days later
+    #[schema(examples("2025-02-25T11:00:00Z"))]
+    created_at: DateTime<Utc>,
+}
+
+/// Get the contact of the reporter of an issue
+///
+/// **Requires an admin token.**
+///
+/// Reporters can leave a `contact_email` when [submitting feedback](#tag/feedback/operation/send_feedback), to be told once their report is resolved.
+/// It is never written into the issue, but stored encrypted.
+/// Contacts are purged once the issue is closed or after 90 days, whichever happens first.
+#[utoipa::path(
+    tags=["admin"],
+    security(("bearer" = [])),
+    params(ContactPathParams),
+    responses(
+        (status = 200, description = "**The contact** of the reporter", body = ContactResponse, content_type = "application/json"),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The reporter did not leave a contact, or it was already purged", body = ApiError, content_type = "application/json", example = json!({"error": "There is no contact for this issue", "code": "contact_not_found"})),
+        (status = 500, description = "**Internal Server Error.** The contact could not be read (e.g. because `FEEDBACK_CONTACT_KEY` changed)", body = ApiError, content_type = "application/json", example = json!({"error": "Failed to read the contact", "code": "internal_error"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints or contacts are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[get("/api/admin/feedback/{issue_number}/contact")]
+pub async fn get_contact(
+    req: HttpRequest,
+    params: web::Path<ContactPathParams>,
+    data: web::Data<AppData>,
+) -> HttpResponse {
+    if let Err(e) = authorise(&req) {
+        return e.into();
+    }
+    let issue_number = params.issue_number;
+    let Some(key) = &*CONTACT_KEY else {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "contact_not_configured",
+            "Contacts are not configured on this server",
+        )
+        .into();
+    };
+    let internal_error = || {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Failed to read the contact",
+        )
+    };
+    let contact = match FeedbackContact::get(&data.pool, issue_number).await {
+        Ok(Some(contact)) => contact,
+        Ok(None) => {
+            return ApiError::new(
+                StatusCode::NOT_FOUND,
+                "contact_not_found",
+                "There is no contact for this issue",
+            )
+            .into();
+        }
+        Err(e) => {
+            error!(error = ?e, issue_number, "could not get the contact");
+            return internal_error().into();
+        }
+    };
+    match key.decrypt(issue_number, &contact.encrypted_email) {
+        Ok(email) => {
+            info!(issue_number, "the contact of the reporter was read");
+            HttpResponse::Ok()
+                .insert_header(("Cache-Control", "no-store"))
+                .json(ContactResponse {
+                    email,
+                    created_at: contact.created_at,
+                })
+        }
+        Err(e) => {
+            error!(error = ?e, issue_number, "could not decrypt the contact");
+            internal_error().into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    pub(super) fn key() -> ContactKey {
+        ContactKey::parse(&BASE64_STANDARD.encode([7; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_encryption_round_trip() {
+        let key = key();
+        let encrypted = key.encrypt(9, "reporter@example.com");
+        // the email is not stored in the clear
+        assert!(!String::from_utf8_lossy(&encrypted).contains("reporter"));
+        assert_eq!(key.decrypt(9, &encrypted).unwrap(), "reporter@example.com");
+        // random nonces => the same email is encrypted differently each time
+        assert_ne!(encrypted, key.encrypt(9, "reporter@example.com"));
+    }
+
+    #[test]
+    fn test_decryption_is_authenticated() {
+        let key = key();
+        let encrypted = key.encrypt(9, "reporter@example.com");
+        // a contact cannot be moved to another issue
+        assert!(key.decrypt(10, &encrypted).is_err());
+        let other_key = ContactKey::parse(&BASE64_STANDARD.encode([8; 32])).unwrap();
+        assert!(other_key.decrypt(9, &encrypted).is_err());
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.decrypt(9, &tampered).is_err());
+        assert!(key.decrypt(9, &encrypted[..NONCE_LEN]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(ContactKey::parse("not base64!").is_err());
+        assert!(ContactKey::parse(&BASE64_STANDARD.encode([7; 16])).is_err());
+        assert!(ContactKey::parse(&format!(" {} ", BASE64_STANDARD.encode([7; 32]))).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::tests::key;
+    use super::*;
+    use crate::routes::feedback::queue::db_tests::mock_github;
+    use crate::setup::tests::PostgresTestContainer;
+
+    async fn insert(pool: &PgPool, issue_number: u64, created_at: DateTime<Utc>) {
+        let encrypted = key().encrypt(issue_number, "reporter@example.com");
+        FeedbackContact::insert(pool, issue_number, &encrypted)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE feedback_contacts SET created_at = $2 WHERE issue_number = $1")
+            .bind(issue_number as i64)
+            .bind(created_at)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_purge() {
+        let pg = PostgresTestContainer::new().await;
+        let (github, mock) = mock_github(0).await;
+        // issues 1 and 2 are open, all others do not exist
+        mock.created
+            .lock()
+            .unwrap()
+            .extend([serde_json::json!({}), serde_json::json!({})]);
+        let now = Utc::now();
+        insert(&pg.pool, 1, now).await;
+        insert(&pg.pool, 2, now - chrono::Duration::days(91)).await;
+        insert(&pg.pool, 3, now).await;
+
+        assert_eq!(purge(&pg.pool, &github, now).await, 2);
+        assert_eq!(
+            FeedbackContact::issue_numbers(&pg.pool).await.unwrap(),
+            vec![1]
+        );
+        let stored = FeedbackContact::get(&pg.pool, 1).await.unwrap().unwrap();
+        assert_eq!(
+            key().decrypt(1, &stored.encrypted_email).unwrap(),
+            "reporter@example.com"
+        );
+
+        // nothing left to purge
+        assert_eq!(purge(&pg.pool, &github, now).await, 0);
+        let in_90_days = now + chrono::Duration::days(90) + chrono::Duration::minutes(1);
+        assert_eq!(purge(&pg.pool, &github, in_90_days).await, 1);
+        assert!(FeedbackContact::get(&pg.pool, 1).await.unwrap().is_none());
+    }
+}
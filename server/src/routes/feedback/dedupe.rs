@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use tokio::sync::Mutex;
+use url::Url;
+
+/// How long feedback is considered a duplicate of an earlier issue
+const DUPLICATE_WINDOW: i64 = 3600 * 24 * 7; // 7d
+/// How many issues are remembered at most
+const MAX_RECORDED_ISSUES: usize = 1000;
+/// Share of the words two subjects need to have in common to be about the same problem
+///
+/// Kept high, as merging unrelated feedback into one issue is worse than a duplicate issue.
+const MIN_SUBJECT_SIMILARITY: f64 = 0.6;
+
+/// Issues recently opened via feedback, by the hash of their content
+///
+/// Unlike the [`super::tokens::RecordedTokens`], these are neither synced across instances, nor persisted between reboots.
+#[derive(Default)]
+pub struct RecordedIssues(Mutex<Vec<IssueRecord>>);
+
+impl fmt::Debug for RecordedIssues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //fields purposely omitted
+        f.debug_struct("RecordedIssues").finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssueRecord {
+    hash: u64,
+    pub number: u64,
+    pub url: Url,
+    created_at: i64,
+}
+
+/// Hash of the feedback, which ignores differences in casing, punctuation and whitespace
+///
+/// The same report about different locations is not a duplicate, so the `location` key is hashed as is.
+pub fn feedback_hash(location: Option<&str>, subject: &str, body: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    location.hash(&mut hasher);
+    normalise(subject).hash(&mut hasher);
+    normalise(body).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn normalise(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether two subjects likely describe the same problem
+///
+/// Compares the sets of words (ignoring casing, punctuation and words shorter than 3 characters) via their [Jaccard index](https://en.wikipedia.org/wiki/Jaccard_index).
+pub fn similar_subjects(a: &str, b: &str) -> bool {
+    let words = |s: &str| {
+        normalise(s)
+            .split(' ')
+            .filter(|word| word.chars().count() >= 3)
+            .map(ToString::to_string)
+            .collect::<HashSet<_>>()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let common = a.intersection(&b).count() as f64;
+    let all = a.union(&b).count() as f64;
+    common / all >= MIN_SUBJECT_SIMILARITY
+}
+
+impl RecordedIssues {
+    /// The issue recently opened for feedback with this hash
+    pub async fn find(&self, hash: u64) -> Option<IssueRecord> {
+        let now = chrono::Utc::now().timestamp();
+        let mut issues = self.0.lock().await;
+        issues.retain(|i| i.created_at + DUPLICATE_WINDOW > now);
+        issues.iter().find(|i| i.hash == hash).cloned()
+    }
+
+    pub async fn record(&self, hash: u64, number: u64, url: Url) {
+        let mut issues = self.0.lock().await;
+        issues.retain(|i| i.hash != hash);
+        if issues.len() >= MAX_RECORDED_ISSUES {
+            issues.remove(0);
+        }
+        issues.push(IssueRecord {
+            hash,
+            number,
+            url,
+            created_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    /// Forgets an issue, e.g. because it was closed
+    pub async fn forget(&self, hash: u64) {
+        self.0.lock().await.retain(|i| i.hash != hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_hash_ignores_formatting() {
+        assert_eq!(
+            feedback_hash(
+                None,
+                "Room is wrong",
+                "The room 5602.EG.001 is\non the wrong floor!"
+            ),
+            feedback_hash(
+                None,
+                "room is wrong ",
+                "the Room 5602 EG 001 is on the wrong floor"
+            )
+        );
+        assert_ne!(
+            feedback_hash(
+                None,
+                "Room is wrong",
+                "The room 5602.EG.001 is on the wrong floor"
+            ),
+            feedback_hash(
+                None,
+                "Room is wrong",
+                "The room 5602.EG.002 is on the wrong floor"
+            )
+        );
+        // subject and body are hashed separately
+        assert_ne!(
+            feedback_hash(None, "a b", "c"),
+            feedback_hash(None, "a", "b c")
+        );
+        // the same report about another location is a different issue
+        assert_ne!(
+            feedback_hash(Some("5602.EG.001"), "Room is wrong", "on the wrong floor"),
+            feedback_hash(Some("5602.EG.002"), "Room is wrong", "on the wrong floor")
+        );
+        assert_ne!(
+            feedback_hash(Some("mi"), "Room is wrong", "on the wrong floor"),
+            feedback_hash(None, "Room is wrong", "on the wrong floor")
+        );
+    }
+
+    #[test]
+    fn test_similar_subjects() {
+        assert!(similar_subjects(
+            "Elevator in MI is broken",
+            "elevator broken!"
+        ));
+        assert!(similar_subjects(
+            "The elevator is broken",
+            "elevator is broken"
+        ));
+        assert!(!similar_subjects(
+            "Elevator in MI is broken",
+            "Coffee machine is broken"
+        ));
+        assert!(!similar_subjects("Elevator is broken", "Elevator is slow"));
+        // only short words => nothing to compare
+        assert!(!similar_subjects("Is in", "is in"));
+        assert!(!similar_subjects("", ""));
+    }
+
+    #[actix_web::test]
+    async fn test_record_find_forget() {
+        let issues = RecordedIssues::default();
+        let url: Url = "https://github.com/TUM-Dev/navigatum/issues/9"
+            .parse()
+            .unwrap();
+        assert_eq!(issues.find(42).await, None);
+        issues.record(42, 9, url.clone()).await;
+        let record = issues.find(42).await.unwrap();
+        assert_eq!((record.number, record.url), (9, url));
+        assert_eq!(issues.find(43).await, None);
+        issues.forget(42).await;
+        assert_eq!(issues.find(42).await, None);
+    }
+}
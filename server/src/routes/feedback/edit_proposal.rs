@@ -0,0 +1,291 @@
+use actix_web::http::StatusCode;
+use geo::{Distance, Haversine, Point};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::error::ApiError;
+
+/// Proposed coordinates further away than this from the current ones are most likely a mistake
+///
+/// Locations on the wrong campus have to be reported as regular feedback.
+const MAX_COORDINATE_CORRECTION_M: f64 = 5_000.0;
+/// Names and types are single line, short strings
+const MAX_TEXT_LEN: usize = 200;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, utoipa::ToSchema)]
+pub struct Coordinate {
+    /// Latitude
+    #[schema(example = 48.26244490906312)]
+    lat: f64,
+    /// Longitude
+    #[schema(example = 11.668906258)]
+    lon: f64,
+}
+
+/// The corrected value of one of the fields of a location
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, utoipa::ToSchema)]
+#[serde(tag = "field", content = "proposed", rename_all = "snake_case")]
+pub enum ProposedValue {
+    Name(String),
+    Coordinates(Coordinate),
+    /// One of the types our locations have, e.g. `room` or `building`
+    Type(String),
+}
+
+/// A correction of our data, which our data pipeline can apply without a human rewriting it
+#[derive(Deserialize, Clone, Debug, PartialEq, utoipa::ToSchema)]
+pub struct EditProposalRequest {
+    /// The key of the location which is wrong
+    #[schema(example = "5602.EG.001")]
+    key: String,
+    #[serde(flatten)]
+    value: ProposedValue,
+}
+
+/// Machine-readable part of the issue, with the current value as we know it
+#[derive(Serialize, Debug, PartialEq)]
+struct EditProposal<'a> {
+    key: &'a str,
+    field: &'static str,
+    current: ProposedValueWithoutTag,
+    proposed: ProposedValueWithoutTag,
+}
+
+/// The value without saying which field it is for, as that is already listed separately
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum ProposedValueWithoutTag {
+    Text(String),
+    Coordinates(Coordinate),
+}
+impl From<ProposedValue> for ProposedValueWithoutTag {
+    fn from(value: ProposedValue) -> Self {
+        match value {
+            ProposedValue::Name(text) | ProposedValue::Type(text) => Self::Text(text),
+            ProposedValue::Coordinates(coordinate) => Self::Coordinates(coordinate),
+        }
+    }
+}
+
+impl ProposedValue {
+    fn field(&self) -> &'static str {
+        match self {
+            ProposedValue::Name(_) => "name",
+            ProposedValue::Coordinates(_) => "coordinates",
+            ProposedValue::Type(_) => "type",
+        }
+    }
+    /// The value of the same field we currently have
+    fn current(&self, location: &CurrentLocation) -> ProposedValue {
+        match self {
+            ProposedValue::Name(_) => ProposedValue::Name(location.name.clone()),
+            ProposedValue::Coordinates(_) => ProposedValue::Coordinates(Coordinate {
+                lat: location.lat,
+                lon: location.lon,
+            }),
+            ProposedValue::Type(_) => ProposedValue::Type(location.r#type.clone()),
+        }
+    }
+}
+
+struct CurrentLocation {
+    name: String,
+    r#type: String,
+    lat: f64,
+    lon: f64,
+}
+
+impl EditProposalRequest {
+    /// Validates the proposal against our data and renders it as YAML
+    pub async fn render(&self, pool: &PgPool) -> Result<String, ApiError> {
+        validate_value(&self.value)?;
+        let current = sqlx::query_as!(
+            CurrentLocation,
+            "SELECT name, type, lat, lon FROM de WHERE key = $1",
+            self.key
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, key = self.key, "could not look up the location of an edit proposal");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Could not look up the location, please try again later",
+            )
+        })?;
+        let Some(current) = current else {
+            return Err(invalid(format!(
+                "{key} is not a known location",
+                key = self.key
+            )));
+        };
+        match &self.value {
+            ProposedValue::Coordinates(proposed) => {
+                let distance = Haversine::distance(
+                    Point::new(current.lon, current.lat),
+                    Point::new(proposed.lon, proposed.lat),
+                );
+                if distance > MAX_COORDINATE_CORRECTION_M {
+                    return Err(invalid(format!(
+                        "the proposed coordinates are {distance:.0}m away from the location, please submit regular feedback instead"
+                    )));
+                }
+            }
+            ProposedValue::Type(proposed) => {
+                let known = sqlx::query_scalar!(
+                    r#"SELECT EXISTS(SELECT 1 FROM de WHERE type = $1) AS "known!""#,
+                    proposed
+                )
+                .fetch_one(pool)
+                .await
+                .unwrap_or(true);
+                if !known {
+                    return Err(invalid(format!("{proposed} is not a type of location")));
+                }
+            }
+            ProposedValue::Name(_) => {}
+        }
+        let proposal = EditProposal {
+            key: &self.key,
+            field: self.value.field(),
+            current: self.value.current(&current).into(),
+            proposed: self.value.clone().into(),
+        };
+        serde_yaml::to_string(&proposal).map_err(|e| {
+            error!(error = ?e, ?proposal, "could not render the edit proposal");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Could not render the edit proposal, please try again later",
+            )
+        })
+    }
+}
+
+/// Checks what can be checked without our data
+///
+/// Text is restricted to a single line, so that it cannot break out of the YAML block in the issue.
+fn validate_value(value: &ProposedValue) -> Result<(), ApiError> {
+    match value {
+        ProposedValue::Name(text) | ProposedValue::Type(text) => {
+            let text = text.trim();
+            if text.is_empty() || text.chars().count() > MAX_TEXT_LEN {
+                return Err(invalid(format!(
+                    "the proposed {field} has to be between 1 and {MAX_TEXT_LEN} characters long",
+                    field = value.field()
+                )));
+            }
+            if text.chars().any(char::is_control) || text.contains("```") {
+                return Err(invalid(format!(
+                    "the proposed {field} has to be a single line of text",
+                    field = value.field()
+                )));
+            }
+        }
+        ProposedValue::Coordinates(Coordinate { lat, lon }) => {
+            if !(-90.0..=90.0).contains(lat) || !(-180.0..=180.0).contains(lon) {
+                return Err(invalid(
+                    "the proposed coordinates are not valid".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_edit_proposal",
+        message,
+    )
+    .with_parameter("edit_proposal")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = serde_json::from_str::<EditProposalRequest>(
+            r#"{"key": "5602.EG.001", "field": "coordinates", "proposed": {"lat": 48.26, "lon": 11.67}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.value,
+            ProposedValue::Coordinates(Coordinate {
+                lat: 48.26,
+                lon: 11.67
+            })
+        );
+        let request = serde_json::from_str::<EditProposalRequest>(
+            r#"{"key": "5602.EG.001", "field": "name", "proposed": "Hörsaal 1"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.value, ProposedValue::Name("Hörsaal 1".into()));
+        assert!(
+            serde_json::from_str::<EditProposalRequest>(
+                r#"{"key": "5602.EG.001", "field": "floor", "proposed": "EG"}"#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_value() {
+        let name = |n: &str| validate_value(&ProposedValue::Name(n.into())).is_ok();
+        assert!(name("Hörsaal 1"));
+        assert!(!name(" "));
+        assert!(!name("Hörsaal\n1"));
+        assert!(!name("```"));
+        assert!(!name(&"a".repeat(MAX_TEXT_LEN + 1)));
+        let coordinate =
+            |lat, lon| validate_value(&ProposedValue::Coordinates(Coordinate { lat, lon })).is_ok();
+        assert!(coordinate(48.26, 11.67));
+        assert!(!coordinate(91.0, 11.67));
+        assert!(!coordinate(48.26, f64::NAN));
+    }
+
+    #[test]
+    fn test_yaml_is_machine_readable() {
+        let proposal = EditProposal {
+            key: "5602.EG.001",
+            field: "coordinates",
+            current: ProposedValueWithoutTag::Coordinates(Coordinate {
+                lat: 48.1,
+                lon: 11.5,
+            }),
+            proposed: ProposedValueWithoutTag::Coordinates(Coordinate {
+                lat: 48.2,
+                lon: 11.5,
+            }),
+        };
+        insta::assert_snapshot!(serde_yaml::to_string(&proposal).unwrap(), @r"
+        key: 5602.EG.001
+        field: coordinates
+        current:
+          lat: 48.1
+          lon: 11.5
+        proposed:
+          lat: 48.2
+          lon: 11.5
+        ");
+        let proposal = EditProposal {
+            key: "5602.EG.001",
+            field: "name",
+            current: ProposedValueWithoutTag::Text("Hörsaal 1".into()),
+            proposed: ProposedValueWithoutTag::Text("yes: no # really".into()),
+        };
+        insta::assert_snapshot!(serde_yaml::to_string(&proposal).unwrap(), @r"
+        key: 5602.EG.001
+        field: name
+        current: Hörsaal 1
+        proposed: 'yes: no # really'
+        ");
+    }
+}
@@ -0,0 +1,164 @@
+use std::future::Future;
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+use super::spam::SpamMetrics;
+
+/// What happened to a feedback submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmissionOutcome {
+    /// An issue was opened or the feedback was added to the `related_issue`
+    Created,
+    /// The feedback was added as a `+1` to an issue reporting the same problem
+    Deduplicated,
+    /// GitHub was unavailable, the feedback is delivered later
+    Queued,
+    RejectedSpam,
+    /// The content of the feedback was invalid (e.g. too short or an unknown category)
+    RejectedValidation,
+    /// The backend failed to take the feedback
+    UpstreamError,
+}
+impl SubmissionOutcome {
+    fn as_label(self) -> &'static str {
+        match self {
+            SubmissionOutcome::Created => "created",
+            SubmissionOutcome::Deduplicated => "deduplicated",
+            SubmissionOutcome::Queued => "queued",
+            SubmissionOutcome::RejectedSpam => "rejected_spam",
+            SubmissionOutcome::RejectedValidation => "rejected_validation",
+            SubmissionOutcome::UpstreamError => "upstream_error",
+        }
+    }
+}
+
+/// Metrics of submitting feedback
+///
+/// Registered against the registry of [`actix_web_prom::PrometheusMetrics`] to be exposed on `/api/metrics`
+#[derive(Clone, Debug)]
+pub struct FeedbackMetrics {
+    pub spam: SpamMetrics,
+    /// Feedback submissions, by `category` and [`SubmissionOutcome`]
+    submissions: IntCounterVec,
+    /// How long requests to GitHub took, by `operation`
+    github_duration: HistogramVec,
+}
+
+impl FeedbackMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let spam = SpamMetrics::register(registry)?;
+        let submissions = IntCounterVec::new(
+            Opts::new(
+                "feedback_submissions_total",
+                "Feedback submissions, by category and outcome",
+            )
+            .namespace("navigatum_api"),
+            &["category", "outcome"],
+        )?;
+        let github_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "feedback_github_duration_seconds",
+                "Time GitHub took to answer requests about feedback, including retries",
+            )
+            .namespace("navigatum_api")
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["operation"],
+        )?;
+        registry.register(Box::new(submissions.clone()))?;
+        registry.register(Box::new(github_duration.clone()))?;
+        Ok(Self {
+            spam,
+            submissions,
+            github_duration,
+        })
+    }
+
+    pub fn record(&self, category: &str, outcome: SubmissionOutcome) {
+        self.submissions
+            .with_label_values(&[category, outcome.as_label()])
+            .inc();
+    }
+
+    /// Records the `outcome`, if the `result` is an error
+    pub fn record_err<T, E>(
+        &self,
+        category: &str,
+        outcome: SubmissionOutcome,
+        result: Result<T, E>,
+    ) -> Result<T, E> {
+        if result.is_err() {
+            self.record(category, outcome);
+        }
+        result
+    }
+
+    /// Awaits the `request` to GitHub, recording how long it took
+    pub async fn time_github<T>(
+        &self,
+        operation: &'static str,
+        request: impl Future<Output = T>,
+    ) -> T {
+        let timer = self
+            .github_duration
+            .with_label_values(&[operation])
+            .start_timer();
+        let result = request.await;
+        timer.observe_duration();
+        result
+    }
+
+    #[cfg(test)]
+    pub(super) fn submissions(&self, category: &str, outcome: SubmissionOutcome) -> u64 {
+        self.submissions
+            .with_label_values(&[category, outcome.as_label()])
+            .get()
+    }
+
+    #[cfg(test)]
+    pub(super) fn github_requests(&self, operation: &str) -> u64 {
+        self.github_duration
+            .with_label_values(&[operation])
+            .get_sample_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn metrics_are_registered() {
+        let registry = Registry::new();
+        let metrics = FeedbackMetrics::register(&registry).unwrap();
+        metrics.record("bug", SubmissionOutcome::Created);
+        let result: Result<(), ()> =
+            metrics.record_err("bug", SubmissionOutcome::UpstreamError, Err(()));
+        assert!(result.is_err());
+        let _ = metrics.record_err::<_, ()>("bug", SubmissionOutcome::UpstreamError, Ok(()));
+        assert_eq!(metrics.time_github("open_issue", async { 42 }).await, 42);
+
+        assert_eq!(metrics.submissions("bug", SubmissionOutcome::Created), 1);
+        assert_eq!(
+            metrics.submissions("bug", SubmissionOutcome::UpstreamError),
+            1
+        );
+        assert_eq!(metrics.github_requests("open_issue"), 1);
+        let names = registry
+            .gather()
+            .into_iter()
+            .map(|m| m.get_name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "navigatum_api_feedback_github_duration_seconds",
+                "navigatum_api_feedback_quarantined_total",
+                "navigatum_api_feedback_submissions_total",
+            ]
+        );
+        // registering twice would produce duplicate timeseries
+        assert!(FeedbackMetrics::register(&registry).is_err());
+    }
+}
@@ -1,3 +1,17 @@
+pub mod attachments;
+pub mod blocklist;
+pub mod challenge;
+pub mod contact;
+pub mod dedupe;
+pub mod edit_proposal;
+pub mod metrics;
 pub mod post_feedback;
 pub mod proposed_edits;
+pub mod queue;
+pub mod ratelimit;
+pub mod sink;
+pub mod spam;
 pub mod tokens;
+pub mod triage;
+pub mod webhook;
+pub mod withdraw;
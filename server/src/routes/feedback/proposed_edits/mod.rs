@@ -1,9 +1,15 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use actix_web::http::StatusCode;
 use actix_web::web::{Data, Json};
-use actix_web::{HttpResponse, post};
+use actix_web::{HttpRequest, HttpResponse, post};
 use serde::Deserialize;
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
 use tracing::error;
 #[expect(
     unused_imports,
@@ -11,12 +17,13 @@ use tracing::error;
 )]
 use url::Url;
 
+use crate::error::ApiError;
 use crate::limited::hash_map::LimitedHashMap;
 
 use super::proposed_edits::coordinate::Coordinate;
 use super::proposed_edits::image::Image;
 use super::proposed_edits::tmp_repo::TempRepo;
-use super::tokens::RecordedTokens;
+use super::tokens::{RecordedTokens, TokenScope};
 use crate::external::github::GitHub;
 
 mod coordinate;
@@ -117,7 +124,7 @@ impl EditRequest {
 ///
 /// This posts the actual feedback to GitHub and returns the github link.
 /// This API will create pull-requests instead of issues => only a subset of feedback is allowed.
-/// For this Endpoint to work, you need to generate a token via the [`/api/feedback/get_token`](#tag/feedback/operation/get_token) endpoint.
+/// For this Endpoint to work, you need to generate a token via the [`/api/feedback/get_token`](#tag/feedback/operation/get_token) endpoint with the scope `edit_proposal`.
 ///
 /// # Note:
 ///
@@ -127,43 +134,62 @@ impl EditRequest {
     responses(
         (status = 201, description= "The edit request feedback has been **successfully posted to GitHub**. We return the link to the GitHub issue.", body= Url, content_type="text/plain", example="https://github.com/TUM-Dev/navigatum/issues/9"),
         (status = 400, description= "**Bad Request.** Not all fields in the body are present as defined above"),
-        (status = 403, description= r#"**Forbidden.** Causes are (delivered via the body):
+        (status = 403, description= r#"**Forbidden.** Causes are (delivered via the `code` in the body):
 
-- `Invalid token`: You have not supplied a token generated via the `gen_token`-Endpoint.
-- `Token not old enough, please wait`: Tokens are only valid after 10s.
-- `Token expired`: Tokens are only valid for 12h.
-- `Token already used`: Tokens are non reusable/refreshable single-use items."#),
-        (status = 422, description= "**Unprocessable Entity.** Subject or body missing or too short."),
-        (status = 451, description= "**Unavailable for legal reasons.** Using this endpoint without accepting the privacy policy is not allowed. For us to post to GitHub, this has to be true"),
-        (status = 500, description= "**Internal Server Error.** We have a problem communicating with GitHubs servers. Please try again later."),
-        (status = 503, description= "Service unavailable. We have not configured a GitHub Access Token. This could be because we are experiencing technical difficulties or intentional. Please try again later."),
+- `invalid_token`: You have not supplied a token generated via the `gen_token`-Endpoint.
+- `token_not_yet_valid`: Tokens are only valid after 5s.
+- `token_expired`: Tokens are only valid for 12h.
+- `token_already_used`: Tokens are non reusable/refreshable single-use items.
+- `wrong_token_scope`: The token was not requested with the scope `edit_proposal`.
+- `forbidden`: Feedback from this token or client is not accepted."#, body = ApiError, content_type = "application/json", example = json!({"error": "Token already used.", "code": "token_already_used"})),
+        (status = 422, description= "**Unprocessable Entity.** Not enough edits provided.", body = ApiError, content_type = "application/json", example = json!({"error": "Not enough edits provided", "code": "no_edits"})),
+        (status = 451, description= "**Unavailable for legal reasons.** Using this endpoint without accepting the privacy policy is not allowed. For us to post to GitHub, this has to be true", body = ApiError, content_type = "application/json", example = json!({"error": "Using this endpoint without accepting the privacy policy is not allowed", "code": "privacy_not_accepted"})),
+        (status = 500, description= "**Internal Server Error.** We have a problem communicating with GitHubs servers. Please try again later.", body = ApiError, content_type = "application/json", example = json!({"error": "Failed to create a pull request, please try again later", "code": "github_error"})),
+        (status = 503, description= "Service unavailable. We have not configured a GitHub Access Token. This could be because we are experiencing technical difficulties or intentional. Please try again later.", body = ApiError, content_type = "application/json", example = json!({"error": "Feedback is currently not configured on this server.", "code": "feedback_not_configured"})),
+        (status = 507, description= "**Insufficient Storage.** Too many edits provided.", body = ApiError, content_type = "application/json", example = json!({"error": "Too many edits provided", "code": "too_many_edits"})),
     )
 )]
 #[post("/api/feedback/propose_edits")]
 pub async fn propose_edits(
+    req: HttpRequest,
     recorded_tokens: Data<RecordedTokens>,
     req_data: Json<EditRequest>,
 ) -> HttpResponse {
     // auth
-    if let Some(e) = recorded_tokens.validate(&req_data.token).await {
-        return e;
+    if let Err(e) = recorded_tokens.blocklist().check_request(&req).await {
+        return e.into();
+    }
+    if let Err(e) = recorded_tokens
+        .validate(&req_data.token, TokenScope::EditProposal)
+        .await
+    {
+        return e.into();
     }
 
     // validate request
     if !req_data.privacy_checked {
-        return HttpResponse::UnavailableForLegalReasons()
-            .content_type("text/plain")
-            .body("Using this endpoint without accepting the privacy policy is not allowed");
+        return ApiError::new(
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            "privacy_not_accepted",
+            "Using this endpoint without accepting the privacy policy is not allowed",
+        )
+        .into();
     };
     if req_data.edits.0.is_empty() {
-        return HttpResponse::UnprocessableEntity()
-            .content_type("text/plain")
-            .body("Not enough edits provided");
+        return ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "no_edits",
+            "Not enough edits provided",
+        )
+        .into();
     };
     if req_data.edits.0.len() > 500 {
-        return HttpResponse::InsufficientStorage()
-            .content_type("text/plain")
-            .body("Too many edits provided");
+        return ApiError::new(
+            StatusCode::INSUFFICIENT_STORAGE,
+            "too_many_edits",
+            "Too many edits provided",
+        )
+        .into();
     };
 
     let branch_name = format!("usergenerated/request-{}", rand::random::<u16>());
@@ -186,9 +212,12 @@ pub async fn propose_edits(
         }
         Err(error) => {
             error!(?error, "could not apply changes");
-            HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Could apply changes, please try again later")
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Could apply changes, please try again later",
+            )
+            .into()
         }
     }
 }
@@ -0,0 +1,593 @@
+use std::time::Duration;
+
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Utc};
+use prometheus::{IntGauge, Opts, Registry};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use url::Url;
+
+use super::dedupe::RecordedIssues;
+use super::sink::{Feedback, Submitted};
+use super::webhook::{self, WebhookPayload};
+use crate::db::feedback::QueuedFeedback;
+use crate::error::ApiError;
+use crate::external::github::GitHub;
+
+/// How long an instance may take to attempt a delivery, before another instance retries it
+const LEASE: Duration = Duration::from_secs(5 * 60);
+/// Delay before the first retry, doubled for each further attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2 * 3600);
+/// After this, delivery is given up
+const MAX_AGE: Duration = Duration::from_secs(24 * 3600);
+/// How often the background task looks for feedback which is due
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of submitting feedback via the [`DeliveryQueue`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    Delivered(Submitted),
+    /// GitHub is unavailable, the feedback is delivered in the background
+    Queued {
+        /// Lets the reporter and maintainers refer to the feedback, before it has an issue
+        receipt: Url,
+    },
+}
+
+/// What happens once queued feedback was delivered, as it would have if it was delivered right away
+///
+/// Deletion tokens and contacts need the issue number, before which the reporter got their response
+/// => feedback with a contact is not queued, queued feedback comes without a deletion token.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct FollowUp {
+    /// Recorded, so that duplicates are added to the issue (see [`RecordedIssues`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dedupe_hash: Option<u64>,
+    /// Sent via the webhook, linking to the issue instead of the receipt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notification: Option<WebhookPayload>,
+}
+
+impl FollowUp {
+    fn of(queued: &QueuedFeedback) -> Self {
+        serde_json::from_value(queued.follow_up.clone()).unwrap_or_else(|e| {
+            error!(error = ?e, receipt = queued.receipt.as_str(), "could not read what follows the delivery");
+            Self::default()
+        })
+    }
+
+    async fn run(self, recorded_issues: &RecordedIssues, number: u64, url: Url) {
+        if let Some(mut notification) = self.notification {
+            notification.url = url.clone();
+            webhook::send(notification);
+        }
+        if let Some(hash) = self.dedupe_hash {
+            recorded_issues.record(hash, number, url).await;
+        }
+    }
+}
+
+/// Feedback which could not be delivered to GitHub right away, persisted to be retried in the background
+///
+/// Each submission gets a receipt, which is hidden in the issue.
+/// Before retrying, we search for it, so that an attempt which succeeded unnoticed (e.g. a timeout after GitHub opened the issue) is not repeated.
+#[derive(Debug)]
+pub struct DeliveryQueue {
+    pool: PgPool,
+    /// Feedback waiting to be delivered, across all instances
+    depth: IntGauge,
+}
+
+impl DeliveryQueue {
+    pub fn new(pool: PgPool) -> Self {
+        let depth = IntGauge::with_opts(
+            Opts::new(
+                "feedback_delivery_queue_depth",
+                "Feedback waiting to be delivered, as GitHub was unavailable",
+            )
+            .namespace("navigatum_api"),
+        )
+        .expect("the metric options are valid");
+        Self { pool, depth }
+    }
+
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.depth.clone()))
+    }
+
+    /// Opens an issue for the feedback, queueing it if GitHub is unavailable
+    ///
+    /// Unless the feedback is quarantined (i.e. has no `dedupe_hash`), the issue is announced via the webhook and recorded for deduplication.
+    /// For feedback delivered right away, this is up to the caller, otherwise it happens once the feedback is delivered in the background.
+    ///
+    /// If the feedback cannot be persisted, it is delivered without the safety net of the queue.
+    pub async fn submit(
+        &self,
+        github: &GitHub,
+        feedback: &Feedback<'_>,
+        dedupe_hash: Option<u64>,
+    ) -> Result<Delivery, ApiError> {
+        let receipt = format!("{:032x}", rand::random::<u128>());
+        let payload = with_receipt(GitHub::issue_payload(feedback), &receipt);
+        let follow_up = FollowUp {
+            dedupe_hash,
+            notification: dedupe_hash
+                .map(|_| WebhookPayload::new(feedback, &receipt_url(&receipt))),
+        };
+        let follow_up = serde_json::to_value(follow_up).expect("the follow-up is serializable");
+        let now = Utc::now();
+        if let Err(e) = QueuedFeedback::insert(
+            &self.pool,
+            &receipt,
+            &payload,
+            &follow_up,
+            after(now, LEASE),
+        )
+        .await
+        {
+            error!(error = ?e, "could not queue the feedback, delivering it without retries");
+            let issue = github.open_issue_from(&payload).await?;
+            return Ok(Delivery::Delivered(submitted(issue)));
+        }
+        match github.open_issue_from(&payload).await {
+            Ok(issue) => {
+                self.forget(&receipt).await;
+                Ok(Delivery::Delivered(submitted(issue)))
+            }
+            Err(e) if is_retryable(&e) => {
+                self.reschedule(&receipt, 1, now).await;
+                self.update_depth().await;
+                info!(receipt, "GitHub is unavailable, queued the feedback");
+                Ok(Delivery::Queued {
+                    receipt: receipt_url(&receipt),
+                })
+            }
+            Err(e) => {
+                self.forget(&receipt).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Retries delivering the queued feedback, forever
+    pub async fn retry_in_background(&self, github: &GitHub, recorded_issues: &RecordedIssues) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.retry_due(github, recorded_issues, Utc::now()).await;
+        }
+    }
+
+    /// Attempts to deliver all feedback which is due at `now`
+    ///
+    /// Returns how many attempts were made.
+    pub async fn retry_due(
+        &self,
+        github: &GitHub,
+        recorded_issues: &RecordedIssues,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let mut attempts = 0;
+        loop {
+            let queued = match QueuedFeedback::claim_due(&self.pool, now, after(now, LEASE)).await {
+                Ok(Some(queued)) => queued,
+                Ok(None) => break,
+                Err(e) => {
+                    error!(error = ?e, "could not get the queued feedback");
+                    break;
+                }
+            };
+            self.retry(github, recorded_issues, queued, now).await;
+            attempts += 1;
+        }
+        self.update_depth().await;
+        attempts
+    }
+
+    async fn retry(
+        &self,
+        github: &GitHub,
+        recorded_issues: &RecordedIssues,
+        queued: QueuedFeedback,
+        now: DateTime<Utc>,
+    ) {
+        let receipt = queued.receipt.as_str();
+        match github.issue_containing(receipt).await {
+            Ok(Some(issue)) => {
+                info!(
+                    receipt,
+                    number = issue.number,
+                    "an earlier attempt delivered the feedback"
+                );
+                self.forget(receipt).await;
+                FollowUp::of(&queued)
+                    .run(recorded_issues, issue.number, issue.html_url)
+                    .await;
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(error = ?e, receipt, "could not check whether the feedback was delivered");
+                self.retry_later(&queued, now).await;
+                return;
+            }
+        }
+        match github.open_issue_from(&queued.payload).await {
+            Ok(issue) => {
+                info!(
+                    receipt,
+                    number = issue.number,
+                    attempts = queued.attempts,
+                    "delivered the queued feedback"
+                );
+                self.forget(receipt).await;
+                FollowUp::of(&queued)
+                    .run(recorded_issues, issue.number, issue.url)
+                    .await;
+            }
+            Err(e) if is_retryable(&e) => self.retry_later(&queued, now).await,
+            Err(e) => {
+                error!(error = ?e, receipt, "GitHub rejected the queued feedback, giving up");
+                self.forget(receipt).await;
+            }
+        }
+    }
+
+    /// Schedules the next attempt, unless the feedback is queued for too long already
+    async fn retry_later(&self, queued: &QueuedFeedback, now: DateTime<Utc>) {
+        let receipt = queued.receipt.as_str();
+        let age = (now - queued.created_at).to_std().unwrap_or_default();
+        if age >= MAX_AGE {
+            error!(
+                receipt,
+                attempts = queued.attempts,
+                "could not deliver the feedback within 24h, giving up"
+            );
+            self.forget(receipt).await;
+            return;
+        }
+        self.reschedule(receipt, queued.attempts, now).await;
+    }
+
+    async fn reschedule(&self, receipt: &str, attempts: i32, now: DateTime<Utc>) {
+        let next_attempt_at = after(now, retry_delay(attempts));
+        if let Err(e) = QueuedFeedback::reschedule(&self.pool, receipt, next_attempt_at).await {
+            // retried once the lease expires instead
+            error!(error = ?e, receipt, "could not reschedule the queued feedback");
+        }
+    }
+
+    async fn forget(&self, receipt: &str) {
+        if let Err(e) = QueuedFeedback::delete(&self.pool, receipt).await {
+            // when retrying, the search for the receipt prevents delivering it twice
+            error!(error = ?e, receipt, "could not remove the feedback from the queue");
+        }
+    }
+
+    async fn update_depth(&self) {
+        match QueuedFeedback::count(&self.pool).await {
+            Ok(depth) => self.depth.set(depth),
+            Err(e) => error!(error = ?e, "could not count the queued feedback"),
+        }
+    }
+}
+
+/// Hides the receipt in the body of the issue, so that the issue can be found by it
+fn with_receipt(mut payload: serde_json::Value, receipt: &str) -> serde_json::Value {
+    if let Some(body) = payload.get_mut("body") {
+        let rendered = body.as_str().unwrap_or_default();
+        *body = format!("{rendered}\n\n<!-- feedback-receipt: {receipt} -->").into();
+    }
+    payload
+}
+
+fn receipt_url(receipt: &str) -> Url {
+    Url::parse(&format!("urn:navigatum:feedback:{receipt}")).expect("the receipt is a valid urn")
+}
+
+fn submitted(issue: crate::external::github::CreatedIssue) -> Submitted {
+    Submitted {
+        url: issue.url,
+        issue_number: Some(issue.number),
+    }
+}
+
+/// Whether GitHub was unavailable or rate-limiting us, i.e. whether retrying later may succeed
+fn is_retryable(error: &ApiError) -> bool {
+    error.status_code() == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// How long to wait after the `attempts`th failed attempt
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = u32::try_from(attempts.saturating_sub(1)).unwrap_or_default();
+    INITIAL_RETRY_DELAY
+        .saturating_mul(2_u32.saturating_pow(exponent))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn after(time: DateTime<Utc>, delay: Duration) -> DateTime<Utc> {
+    time + chrono::Duration::from_std(delay).expect("our delays are at most a few hours")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_receipt_is_hidden_in_the_body() {
+        let payload = serde_json::json!({"title": "A catchy title", "body": "A clear description"});
+        let payload = with_receipt(payload, "0123abcd");
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "title": "A catchy title",
+                "body": "A clear description\n\n<!-- feedback-receipt: 0123abcd -->",
+            })
+        );
+        assert_eq!(
+            receipt_url("0123abcd").as_str(),
+            "urn:navigatum:feedback:0123abcd"
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(60));
+        assert_eq!(retry_delay(2), Duration::from_secs(120));
+        assert_eq!(retry_delay(4), Duration::from_secs(480));
+        assert_eq!(retry_delay(8), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(i32::MAX), MAX_RETRY_DELAY);
+        // attempts are counted from one, but a broken row must not panic
+        assert_eq!(retry_delay(0), INITIAL_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_follow_up_survives_the_queue() {
+        let follow_up = FollowUp {
+            dedupe_hash: Some(u64::MAX),
+            notification: None,
+        };
+        let stored = serde_json::to_value(&follow_up).unwrap();
+        assert_eq!(stored, serde_json::json!({"dedupe_hash": u64::MAX}));
+        assert_eq!(
+            serde_json::from_value::<FollowUp>(stored).unwrap(),
+            follow_up
+        );
+        // rows queued before follow-ups were stored
+        let legacy = serde_json::from_value::<FollowUp>(serde_json::json!({})).unwrap();
+        assert_eq!(legacy, FollowUp::default());
+    }
+
+    #[test]
+    fn test_only_unavailability_is_retried() {
+        let error = |status| ApiError::new(status, "github_error", "");
+        assert!(is_retryable(&error(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(!is_retryable(&error(StatusCode::INTERNAL_SERVER_ERROR)));
+        assert!(!is_retryable(&error(StatusCode::UNPROCESSABLE_ENTITY)));
+    }
+}
+
+#[cfg(test)]
+pub(super) mod db_tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{HttpResponse, web};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::routes::feedback::sink::tests::feedback;
+    use crate::setup::tests::{PostgresTestContainer, mock_server};
+
+    /// Mocks the parts of GitHub feedback is delivered via
+    #[derive(Default)]
+    pub(in crate::routes::feedback) struct MockGitHub {
+        /// Requests to create an issue which fail with a `503` before GitHub recovers
+        outage: AtomicUsize,
+        pub(in crate::routes::feedback) created: Mutex<Vec<serde_json::Value>>,
+        /// Comments, by the number of the issue they were added to
+        pub(in crate::routes::feedback) comments: Mutex<Vec<(u64, String)>>,
+    }
+
+    async fn create_issue(
+        mock: web::Data<MockGitHub>,
+        body: web::Json<serde_json::Value>,
+    ) -> HttpResponse {
+        let in_outage = mock
+            .outage
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if in_outage {
+            return HttpResponse::ServiceUnavailable()
+                .insert_header(("Retry-After", "0"))
+                .finish();
+        }
+        let mut created = mock.created.lock().unwrap();
+        created.push(body.into_inner());
+        let number = created.len();
+        HttpResponse::Created().json(serde_json::json!({
+            "number": number,
+            "html_url": format!("https://github.com/TUM-Dev/navigatum/issues/{number}"),
+        }))
+    }
+
+    async fn search_issues(mock: web::Data<MockGitHub>) -> HttpResponse {
+        let items = mock
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, issue)| {
+                serde_json::json!({
+                    "number": i + 1,
+                    "html_url": format!("https://github.com/TUM-Dev/navigatum/issues/{}", i + 1),
+                    "title": issue["title"],
+                    "body": issue["body"],
+                })
+            })
+            .collect::<Vec<_>>();
+        HttpResponse::Ok().json(serde_json::json!({"total_count": items.len(), "items": items}))
+    }
+
+    /// All issues we created are open
+    async fn get_issue(mock: web::Data<MockGitHub>, number: web::Path<u64>) -> HttpResponse {
+        let created = mock.created.lock().unwrap().len() as u64;
+        if (1..=created).contains(&number.into_inner()) {
+            HttpResponse::Ok().json(serde_json::json!({"state": "open"}))
+        } else {
+            HttpResponse::NotFound().finish()
+        }
+    }
+
+    async fn create_comment(
+        mock: web::Data<MockGitHub>,
+        number: web::Path<u64>,
+        body: web::Json<serde_json::Value>,
+    ) -> HttpResponse {
+        let number = number.into_inner();
+        let mut comments = mock.comments.lock().unwrap();
+        comments.push((number, body["body"].as_str().unwrap().to_string()));
+        HttpResponse::Created().json(serde_json::json!({
+            "html_url": format!("https://github.com/TUM-Dev/navigatum/issues/{number}#issuecomment-{}", comments.len()),
+        }))
+    }
+
+    pub(in crate::routes::feedback) async fn mock_github(
+        outage: usize,
+    ) -> (GitHub, web::Data<MockGitHub>) {
+        let mock = web::Data::new(MockGitHub {
+            outage: AtomicUsize::new(outage),
+            ..Default::default()
+        });
+        let mock_data = mock.clone();
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(mock_data.clone())
+                .route(
+                    "/repos/TUM-Dev/navigatum/issues",
+                    web::post().to(create_issue),
+                )
+                .route(
+                    "/repos/TUM-Dev/navigatum/issues/{number}",
+                    web::get().to(get_issue),
+                )
+                .route(
+                    "/repos/TUM-Dev/navigatum/issues/{number}/comments",
+                    web::post().to(create_comment),
+                )
+                .route("/search/issues", web::get().to(search_issues));
+        });
+        (GitHub::with_base_uri(&format!("http://{addr}")), mock)
+    }
+
+    fn as_issue(issue_number: u64) -> Delivery {
+        Delivery::Delivered(Submitted {
+            url: Url::parse(&format!(
+                "https://github.com/TUM-Dev/navigatum/issues/{issue_number}"
+            ))
+            .unwrap(),
+            issue_number: Some(issue_number),
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_delivered_right_away() {
+        let pg = PostgresTestContainer::new().await;
+        let queue = DeliveryQueue::new(pg.pool.clone());
+        let (github, mock) = mock_github(0).await;
+        assert_eq!(
+            queue.submit(&github, &feedback(), Some(1)).await.unwrap(),
+            as_issue(1)
+        );
+        assert_eq!(QueuedFeedback::count(&pg.pool).await.unwrap(), 0);
+        assert_eq!(mock.created.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_queued_during_an_outage() {
+        let pg = PostgresTestContainer::new().await;
+        let queue = DeliveryQueue::new(pg.pool.clone());
+        // outlasts the retries of a single request and the first background retry
+        let (github, mock) = mock_github(8).await;
+        let recorded_issues = RecordedIssues::default();
+        let queued = queue.submit(&github, &feedback(), Some(42)).await;
+        let Delivery::Queued { receipt } = queued.unwrap() else {
+            panic!("feedback should be queued during the outage");
+        };
+        assert_eq!(queue.depth.get(), 1);
+        let receipt = receipt
+            .as_str()
+            .trim_start_matches("urn:navigatum:feedback:");
+
+        // not yet due
+        assert_eq!(
+            queue.retry_due(&github, &recorded_issues, Utc::now()).await,
+            0
+        );
+        let later = Utc::now() + chrono::Duration::hours(1);
+        assert_eq!(queue.retry_due(&github, &recorded_issues, later).await, 1);
+        assert_eq!(queue.depth.get(), 1);
+        assert!(mock.created.lock().unwrap().is_empty());
+        assert_eq!(recorded_issues.find(42).await, None);
+
+        let even_later = later + chrono::Duration::hours(1);
+        assert_eq!(
+            queue.retry_due(&github, &recorded_issues, even_later).await,
+            1
+        );
+        assert_eq!(queue.depth.get(), 0);
+        let created = mock.created.lock().unwrap().clone();
+        assert_eq!(created.len(), 1);
+        assert!(created[0]["body"].as_str().unwrap().contains(receipt));
+        // duplicates are added to the issue, as for feedback delivered right away
+        assert_eq!(recorded_issues.find(42).await.unwrap().number, 1);
+    }
+
+    #[actix_web::test]
+    async fn test_delivered_feedback_is_not_repeated() {
+        let pg = PostgresTestContainer::new().await;
+        let queue = DeliveryQueue::new(pg.pool.clone());
+        let (github, mock) = mock_github(0).await;
+        // an attempt which succeeded, but whose response got lost
+        let payload = with_receipt(GitHub::issue_payload(&feedback()), "0123abcd");
+        github.open_issue_from(&payload).await.unwrap();
+        let follow_up = serde_json::json!({"dedupe_hash": 42});
+        QueuedFeedback::insert(&pg.pool, "0123abcd", &payload, &follow_up, Utc::now())
+            .await
+            .unwrap();
+
+        let recorded_issues = RecordedIssues::default();
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(queue.retry_due(&github, &recorded_issues, later).await, 1);
+        assert_eq!(mock.created.lock().unwrap().len(), 1);
+        assert_eq!(QueuedFeedback::count(&pg.pool).await.unwrap(), 0);
+        assert_eq!(recorded_issues.find(42).await.unwrap().number, 1);
+    }
+
+    #[actix_web::test]
+    async fn test_given_up_after_a_day() {
+        let pg = PostgresTestContainer::new().await;
+        let queue = DeliveryQueue::new(pg.pool.clone());
+        let (github, mock) = mock_github(usize::MAX).await;
+        let payload = with_receipt(GitHub::issue_payload(&feedback()), "0123abcd");
+        let follow_up = serde_json::json!({"dedupe_hash": 42});
+        QueuedFeedback::insert(&pg.pool, "0123abcd", &payload, &follow_up, Utc::now())
+            .await
+            .unwrap();
+
+        let tomorrow = Utc::now() + chrono::Duration::hours(25);
+        let recorded_issues = RecordedIssues::default();
+        assert_eq!(
+            queue.retry_due(&github, &recorded_issues, tomorrow).await,
+            1
+        );
+        assert_eq!(QueuedFeedback::count(&pg.pool).await.unwrap(), 0);
+        assert!(mock.created.lock().unwrap().is_empty());
+        assert_eq!(queue.depth.get(), 0);
+    }
+}
@@ -0,0 +1,328 @@
+use std::net::IpAddr;
+use std::sync::LazyLock;
+
+use actix_governor::KeyExtractor;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, HttpResponse};
+use tracing::warn;
+
+use crate::error::ApiError;
+
+/// Used if `FEEDBACK_GLOBAL_LIMIT_PER_DAY` is not set
+const DEFAULT_GLOBAL_REQUESTS_PER_DAY: u32 = 300;
+
+/// How many reverse proxies (each appending to `X-Forwarded-For`) are in front of us
+///
+/// Only the entries these proxies appended can be trusted, everything left of them is sent by the client.
+/// If this is `0`, `X-Forwarded-For` is ignored and the address of the peer is used.
+static TRUSTED_PROXY_HOPS: LazyLock<usize> = LazyLock::new(|| {
+    let Ok(raw) = std::env::var("FEEDBACK_TRUSTED_PROXY_HOPS") else {
+        return 0;
+    };
+    raw.trim().parse().unwrap_or_else(|e| {
+        warn!(error = ?e, %raw, "FEEDBACK_TRUSTED_PROXY_HOPS is not a valid number of proxies, ignoring X-Forwarded-For");
+        0
+    })
+});
+
+/// How many feedback tokens can be given out per day across all clients, if limited at all
+pub fn global_requests_per_day() -> Option<u32> {
+    let Ok(raw) = std::env::var("FEEDBACK_GLOBAL_LIMIT_PER_DAY") else {
+        return Some(DEFAULT_GLOBAL_REQUESTS_PER_DAY);
+    };
+    match raw.trim().parse() {
+        Ok(0) => None,
+        Ok(limit) => Some(limit),
+        Err(e) => {
+            warn!(error = ?e, %raw, "FEEDBACK_GLOBAL_LIMIT_PER_DAY is not a valid number of requests, using the default");
+            Some(DEFAULT_GLOBAL_REQUESTS_PER_DAY)
+        }
+    }
+}
+
+/// Translates the `x-ratelimit-*` headers of [`actix_governor`] into the standardised `RateLimit-*` ones
+///
+/// The governor rejects requests with a plain-text body, which is replaced with an [`ApiError`].
+/// Has to wrap all governors of a scope, so that it sees their rejections as well.
+pub async fn ratelimit_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let http_req = req.request().clone();
+    let mut res = match next.call(req).await {
+        Ok(res) if res.status() != StatusCode::TOO_MANY_REQUESTS => res.map_into_left_body(),
+        Ok(res) => {
+            let (req, res) = res.into_parts();
+            ServiceResponse::new(req, rejection(res.headers())).map_into_right_body()
+        }
+        // the governor may reject via an error instead of a response
+        Err(e) if e.as_response_error().status_code() == StatusCode::TOO_MANY_REQUESTS => {
+            let res = e.error_response();
+            ServiceResponse::new(http_req, rejection(res.headers())).map_into_right_body()
+        }
+        Err(e) => return Err(e),
+    };
+    let headers = res.headers_mut();
+    for (governor, standard) in [
+        ("x-ratelimit-limit", "ratelimit-limit"),
+        ("x-ratelimit-remaining", "ratelimit-remaining"),
+    ] {
+        if let Some(value) = headers.get(governor).cloned() {
+            headers.insert(HeaderName::from_static(standard), value);
+        }
+    }
+    if res.status() == StatusCode::TOO_MANY_REQUESTS {
+        // the limit which rejected the request might not be the one which set the headers
+        res.headers_mut().insert(
+            HeaderName::from_static("ratelimit-remaining"),
+            HeaderValue::from(0),
+        );
+    }
+    Ok(res)
+}
+
+/// The response to a request the governor rejected, keeping its headers
+fn rejection(governor_headers: &HeaderMap) -> HttpResponse {
+    let retry_after = governor_headers
+        .get(RETRY_AFTER)
+        .or_else(|| governor_headers.get("x-ratelimit-after"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let message = match retry_after {
+        Some(seconds) => format!("Too many requests, please try again in {seconds}s"),
+        None => "Too many requests, please try again later".to_string(),
+    };
+    let mut res = HttpResponse::from(ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        message,
+    ));
+    for (name, value) in governor_headers.iter() {
+        if name.as_str().starts_with("x-ratelimit-") {
+            res.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    if let Some(seconds) = retry_after {
+        res.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(seconds));
+    }
+    res
+}
+
+/// Rate-limits by the IP of the client, so that one client cannot exhaust the limit for everyone
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIpKeyExtractor {
+    trusted_proxy_hops: usize,
+}
+
+impl Default for ClientIpKeyExtractor {
+    fn default() -> Self {
+        Self {
+            trusted_proxy_hops: *TRUSTED_PROXY_HOPS,
+        }
+    }
+}
+
+impl ClientIpKeyExtractor {
+    /// The address the first trusted proxy received the request from
+    ///
+    /// If there are fewer entries than trusted proxies, the request did not pass through all of them and the peer is used instead.
+    fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        if self.trusted_proxy_hops == 0 {
+            return peer;
+        }
+        let forwarded = forwarded_for
+            .into_iter()
+            .flat_map(|header| header.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let Some(index) = forwarded.len().checked_sub(self.trusted_proxy_hops) else {
+            return peer;
+        };
+        forwarded[index].parse().ok().or(peer)
+    }
+
+    /// The address of the client, which sent the request
+    pub fn client_ip_of(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        // several headers are treated as one comma separated list, as required by RFC 9110
+        let forwarded_for = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let forwarded_for = Some(forwarded_for.as_str()).filter(|f| !f.is_empty());
+        self.client_ip(peer, forwarded_for)
+    }
+}
+
+impl KeyExtractor for ClientIpKeyExtractor {
+    type Key = IpAddr;
+    type KeyExtractionError = ApiError;
+
+    fn name(&self) -> &'static str {
+        "client ip"
+    }
+
+    fn extract(&self, req: &ServiceRequest) -> Result<Self::Key, Self::KeyExtractionError> {
+        self.client_ip_of(req.request()).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "unknown_client",
+                "Could not determine the address of the client",
+            )
+        })
+    }
+
+    fn key_name(&self, key: &Self::Key) -> Option<String> {
+        Some(key.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use actix_governor::{Governor, GovernorConfigBuilder};
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, web};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_clients_are_limited_independently() {
+        let config = GovernorConfigBuilder::default()
+            .key_extractor(ClientIpKeyExtractor {
+                trusted_proxy_hops: 1,
+            })
+            .seconds_per_request(3600)
+            .burst_size(2)
+            .use_headers()
+            .finish()
+            .unwrap();
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(Governor::new(&config))
+                .route("/", web::post().to(HttpResponse::Created)),
+        )
+        .await;
+        let proxy = "10.0.0.2:1234".parse::<SocketAddr>().unwrap();
+        let request = |client: &str| {
+            actix_web::test::TestRequest::post()
+                .uri("/")
+                .peer_addr(proxy)
+                .insert_header(("X-Forwarded-For", client))
+                .to_request()
+        };
+
+        let res = actix_web::test::call_service(&app, request("1.2.3.4")).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get("x-ratelimit-remaining").unwrap(), "1");
+        actix_web::test::call_service(&app, request("1.2.3.4")).await;
+        let res = actix_web::test::call_service(&app, request("1.2.3.4")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = res.headers().get(RETRY_AFTER).unwrap().to_str().unwrap();
+        assert!(retry_after.parse::<u64>().unwrap() > 3500, "{retry_after}");
+
+        // another client (even behind the same proxy) is unaffected
+        let res = actix_web::test::call_service(&app, request("5.6.7.8")).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        // spoofing another address does not help
+        let res = actix_web::test::call_service(&app, request("5.6.7.8, 1.2.3.4")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_web::test]
+    async fn test_ratelimit_headers() {
+        let config = GovernorConfigBuilder::default()
+            .key_extractor(ClientIpKeyExtractor {
+                trusted_proxy_hops: 0,
+            })
+            .seconds_per_request(3600)
+            .burst_size(2)
+            .use_headers()
+            .finish()
+            .unwrap();
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(Governor::new(&config))
+                .wrap(from_fn(ratelimit_headers))
+                .route("/", web::post().to(HttpResponse::Created)),
+        )
+        .await;
+        let request = || {
+            actix_web::test::TestRequest::post()
+                .uri("/")
+                .peer_addr("1.2.3.4:1234".parse().unwrap())
+                .to_request()
+        };
+
+        // below the limit
+        let res = actix_web::test::call_service(&app, request()).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get("ratelimit-limit").unwrap(), "2");
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "1");
+        assert!(res.headers().get(RETRY_AFTER).is_none());
+        // at the limit
+        let res = actix_web::test::call_service(&app, request()).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get("ratelimit-limit").unwrap(), "2");
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "0");
+        // above the limit
+        let res = actix_web::test::call_service(&app, request()).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get("ratelimit-limit").unwrap(), "2");
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "0");
+        let retry_after = res.headers().get(RETRY_AFTER).unwrap().to_str().unwrap();
+        let retry_after = retry_after.parse::<u64>().unwrap();
+        assert!(retry_after > 3500, "{retry_after}");
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        let body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(body["code"], "rate_limited");
+        assert_eq!(
+            body["error"],
+            format!("Too many requests, please try again in {retry_after}s")
+        );
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let peer = Some("10.0.0.2".parse().unwrap());
+        let client_ip = |trusted_proxy_hops, forwarded_for| {
+            ClientIpKeyExtractor { trusted_proxy_hops }
+                .client_ip(peer, forwarded_for)
+                .map(|ip| ip.to_string())
+        };
+        // not behind a proxy => spoofed headers are ignored
+        assert_eq!(client_ip(0, Some("1.2.3.4")), Some("10.0.0.2".into()));
+        assert_eq!(client_ip(0, None), Some("10.0.0.2".into()));
+        // behind one proxy => only its entry counts
+        assert_eq!(client_ip(1, Some("1.2.3.4")), Some("1.2.3.4".into()));
+        assert_eq!(
+            client_ip(1, Some("6.6.6.6, 1.2.3.4")),
+            Some("1.2.3.4".into())
+        );
+        assert_eq!(
+            client_ip(1, Some("6.6.6.6,2001:db8::1")),
+            Some("2001:db8::1".into())
+        );
+        // behind two proxies
+        assert_eq!(
+            client_ip(2, Some("6.6.6.6, 1.2.3.4, 10.0.0.1")),
+            Some("1.2.3.4".into())
+        );
+        // the request bypassed the proxies
+        assert_eq!(client_ip(2, Some("1.2.3.4")), Some("10.0.0.2".into()));
+        assert_eq!(client_ip(1, None), Some("10.0.0.2".into()));
+        assert_eq!(client_ip(1, Some("garbage")), Some("10.0.0.2".into()));
+    }
+}
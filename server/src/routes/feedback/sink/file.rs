@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+
+use actix_web::http::StatusCode;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::error;
+
+use super::{Feedback, FeedbackSink, Submitted, required_env};
+use crate::error::ApiError;
+use crate::routes::feedback::triage::FeedbackCategory;
+
+/// Appends feedback as one JSON object per line to `FEEDBACK_FILE_PATH`
+///
+/// Writes are serialised, as a single append of a long line can be split by the os and interleave with concurrent submissions.
+#[derive(Debug, Default)]
+pub struct FileSink {
+    path: Option<PathBuf>,
+    writing: Mutex<()>,
+}
+
+/// A line of the file
+#[derive(Serialize, Debug)]
+struct Record<'a> {
+    reference: &'a str,
+    submitted_at: chrono::DateTime<chrono::Utc>,
+    category: FeedbackCategory,
+    subject: &'a str,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit_proposal: Option<&'a str>,
+    attachments: Vec<&'a str>,
+    labels: &'a [String],
+    assignees: &'a [String],
+}
+
+impl FileSink {
+    pub fn from_env() -> Self {
+        Self {
+            path: required_env("FEEDBACK_FILE_PATH").map(PathBuf::from),
+            writing: Mutex::default(),
+        }
+    }
+
+    async fn append(&self, line: &[u8]) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(std::io::Error::other("FEEDBACK_FILE_PATH is not set"));
+        };
+        let _writing = self.writing.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line).await?;
+        file.flush().await
+    }
+}
+
+impl FeedbackSink for FileSink {
+    fn is_configured(&self) -> bool {
+        self.path.is_some()
+    }
+
+    async fn submit(&self, feedback: &Feedback<'_>) -> Result<Submitted, ApiError> {
+        let submitted = Submitted::reference();
+        let record = Record {
+            reference: submitted.url.as_str(),
+            submitted_at: chrono::Utc::now(),
+            category: feedback.category,
+            subject: &feedback.subject,
+            description: &feedback.description,
+            location: feedback.extras.location,
+            edit_proposal: feedback.extras.edit_proposal,
+            attachments: feedback
+                .extras
+                .attachments
+                .iter()
+                .map(url::Url::as_str)
+                .collect(),
+            labels: &feedback.triage.labels,
+            assignees: &feedback.triage.assignees,
+        };
+        let mut line = serde_json::to_vec(&record).expect("records are serialisable");
+        line.push(b'\n');
+        self.append(&line).await.map_err(|e| {
+            error!(error = ?e, path = ?self.path, "could not write the feedback to the file");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Failed to store the feedback, please try again later",
+            )
+        })?;
+        Ok(submitted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::super::tests::feedback;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_feedback_is_appended() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("feedback.jsonl");
+        let sink = FileSink {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        assert!(sink.is_configured());
+
+        let first = sink.submit(&feedback()).await.unwrap();
+        let second = sink.submit(&feedback()).await.unwrap();
+        assert_ne!(first.url, second.url);
+        assert_eq!(first.issue_number, None);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["reference"], first.url.as_str());
+        assert_eq!(lines[1]["reference"], second.url.as_str());
+        assert_eq!(lines[0]["category"], "bug");
+        assert_eq!(lines[0]["subject"], "A catchy title");
+        assert_eq!(lines[0]["location"], "mi");
+        assert_eq!(lines[0]["labels"], serde_json::json!(["webform", "bug"]));
+        assert!(lines[0].get("edit_proposal").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_concurrent_feedback_does_not_interleave() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("feedback.jsonl");
+        let sink = FileSink {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        let long = "a long description ".repeat(10_000);
+        let feedback = Feedback {
+            description: long.clone(),
+            ..feedback()
+        };
+        let submitted = futures::future::join_all((0..20).map(|_| sink.submit(&feedback))).await;
+        assert!(submitted.iter().all(Result::is_ok));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 20);
+        assert!(
+            lines
+                .iter()
+                .all(|line| line["description"] == long.as_str())
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_unwritable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // a directory cannot be appended to
+        let sink = FileSink {
+            path: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let err = sink.submit(&feedback()).await.unwrap_err();
+        let err = serde_json::to_value(err).unwrap();
+        assert_eq!(err["code"], "internal_error");
+        assert!(!FileSink::default().is_configured());
+    }
+}
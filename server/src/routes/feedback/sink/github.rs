@@ -0,0 +1,268 @@
+use std::sync::atomic::Ordering;
+
+use url::Url;
+
+use super::{Feedback, FeedbackSink, Submitted};
+use crate::error::ApiError;
+use crate::external::github::{FoundIssue, GitHub, location_link};
+use crate::routes::feedback::dedupe::similar_subjects;
+use crate::routes::feedback::tokens::GITHUB_TOKEN_USABLE;
+
+impl FeedbackSink for GitHub {
+    fn is_configured(&self) -> bool {
+        self.has_token() && GITHUB_TOKEN_USABLE.load(Ordering::Relaxed)
+    }
+
+    async fn submit(&self, feedback: &Feedback<'_>) -> Result<Submitted, ApiError> {
+        let issue = self.open_issue(feedback).await?;
+        Ok(Submitted {
+            url: issue.url,
+            issue_number: Some(issue.number),
+        })
+    }
+
+    /// Searches the open issues about the same location for a similar title
+    ///
+    /// Feedback without a location is too vague to be matched reliably.
+    /// Edit proposals are never duplicates, as each one proposes a specific value.
+    async fn find_duplicate(&self, feedback: &Feedback<'_>) -> anyhow::Result<Option<Submitted>> {
+        let Some(key) = feedback.extras.location else {
+            return Ok(None);
+        };
+        if feedback.extras.edit_proposal.is_some() {
+            return Ok(None);
+        }
+        let issues = self.open_issues_about(key).await?;
+        Ok(
+            closest_duplicate(&issues, key, &feedback.subject).map(|issue| Submitted {
+                url: issue.html_url.clone(),
+                issue_number: Some(issue.number),
+            }),
+        )
+    }
+
+    async fn add_comment(&self, issue_number: u64, comment: &str) -> anyhow::Result<Option<Url>> {
+        self.comment_if_open(issue_number, comment).await
+    }
+}
+
+/// The first of the `issues` which is about the location `key` and has a title similar to `subject`
+///
+/// The search also matches issues merely mentioning the location somewhere.
+/// Only those having the location set via the feedback form are considered.
+fn closest_duplicate<'a>(
+    issues: &'a [FoundIssue],
+    key: &str,
+    subject: &str,
+) -> Option<&'a FoundIssue> {
+    let link = format!("({link})", link = location_link(key));
+    issues.iter().find(|issue| {
+        issue
+            .body
+            .as_deref()
+            .is_some_and(|body| body.contains(&link))
+            && similar_subjects(&issue.title, subject)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{HttpResponse, web};
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    use super::super::tests::feedback;
+    use super::*;
+    use crate::setup::tests::mock_server;
+
+    /// Issues as GitHub returns them when searching for `mi`
+    fn search_results() -> serde_json::Value {
+        serde_json::json!({
+            "total_count": 3,
+            "incomplete_results": false,
+            "items": [
+                {
+                    "number": 1,
+                    "html_url": "https://github.com/TUM-Dev/navigatum/issues/1",
+                    "title": "Coffee machine is broken",
+                    "body": "## Description\n\n**Location:** [`mi`](https://nav.tum.de/view/mi)\n\nno coffee",
+                    "state": "open",
+                },
+                {
+                    "number": 2,
+                    "html_url": "https://github.com/TUM-Dev/navigatum/issues/2",
+                    "title": "A catchy title",
+                    "body": "mentions https://nav.tum.de/view/mi, but is about something else",
+                    "state": "open",
+                },
+                {
+                    "number": 3,
+                    "html_url": "https://github.com/TUM-Dev/navigatum/issues/3",
+                    "title": "catchy title!",
+                    "body": "## Description\n\n**Location:** [`mi`](https://nav.tum.de/view/mi)\n\nsame problem",
+                    "state": "open",
+                },
+            ]
+        })
+    }
+
+    fn issues() -> Vec<FoundIssue> {
+        let results = search_results();
+        serde_json::from_value(results["items"].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_closest_duplicate() {
+        let issues = issues();
+        let duplicate = closest_duplicate(&issues, "mi", "A catchy title").unwrap();
+        assert_eq!(duplicate.number, 3);
+        assert_eq!(
+            closest_duplicate(&issues, "mi", "Coffee machine broken")
+                .unwrap()
+                .number,
+            1
+        );
+        // a different location, even if mentioned in the body of one of the issues
+        assert_eq!(closest_duplicate(&issues, "5602", "A catchy title"), None);
+        assert_eq!(closest_duplicate(&issues, "mi", "Elevator is broken"), None);
+        assert_eq!(closest_duplicate(&[], "mi", "A catchy title"), None);
+    }
+
+    #[test]
+    fn test_location_is_not_a_prefix_match() {
+        let issues = issues();
+        // `m` must not match issues about `mi`
+        assert_eq!(closest_duplicate(&issues, "m", "A catchy title"), None);
+    }
+
+    #[actix_web::test]
+    async fn test_find_duplicate() {
+        type Received = web::Data<Mutex<Option<String>>>;
+        let received: Received = web::Data::new(Mutex::new(None));
+        let mock_data = received.clone();
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(mock_data.clone()).route(
+                "/search/issues",
+                web::get().to(
+                    |received: Received, req: actix_web::HttpRequest| async move {
+                        *received.lock().unwrap() = Some(req.query_string().to_string());
+                        HttpResponse::Ok().json(search_results())
+                    },
+                ),
+            );
+        });
+        let github = GitHub::with_base_uri(&format!("http://{addr}"));
+
+        let duplicate = github.find_duplicate(&feedback()).await.unwrap().unwrap();
+        assert_eq!(duplicate.issue_number, Some(3));
+        assert_eq!(
+            duplicate.url.as_str(),
+            "https://github.com/TUM-Dev/navigatum/issues/3"
+        );
+        let query = received
+            .lock()
+            .unwrap()
+            .take()
+            .expect("GitHub was searched");
+        let query = url::form_urlencoded::parse(query.as_bytes())
+            .find(|(name, _)| name == "q")
+            .unwrap()
+            .1
+            .into_owned();
+        assert_eq!(
+            query,
+            "repo:TUM-Dev/navigatum is:issue is:open in:body \"https://nav.tum.de/view/mi\""
+        );
+
+        // without a location, GitHub is not searched
+        let mut without_location = feedback();
+        without_location.extras.location = None;
+        assert_eq!(
+            github.find_duplicate(&without_location).await.unwrap(),
+            None
+        );
+        assert_eq!(received.lock().unwrap().take(), None);
+    }
+
+    #[actix_web::test]
+    async fn test_add_comment() {
+        type Comments = web::Data<Mutex<Vec<(u64, serde_json::Value)>>>;
+        let comments: Comments = web::Data::new(Mutex::new(Vec::new()));
+        let mock_data = comments.clone();
+        let addr = mock_server(move |cfg| {
+            cfg
+                .app_data(mock_data.clone())
+                .route(
+                    "/repos/TUM-Dev/navigatum/issues/{number}",
+                    web::get().to(|number: web::Path<u64>| async move {
+                        let number = number.into_inner();
+                        let issue = |state: &str| {
+                            serde_json::json!({
+                                "number": number,
+                                "html_url": format!("https://github.com/TUM-Dev/navigatum/issues/{number}"),
+                                "state": state,
+                            })
+                        };
+                        match number {
+                            1 => HttpResponse::Ok().json(issue("open")),
+                            2 => HttpResponse::Ok().json(issue("closed")),
+                            3 => {
+                                let mut pull_request = issue("open");
+                                pull_request["pull_request"] = serde_json::json!({});
+                                HttpResponse::Ok().json(pull_request)
+                            }
+                            _ => HttpResponse::NotFound().json(serde_json::json!({"message": "Not Found"})),
+                        }
+                    }),
+                )
+                .route(
+                    "/repos/TUM-Dev/navigatum/issues/{number}/comments",
+                    web::post().to(
+                        |comments: Comments,
+                         number: web::Path<u64>,
+                         body: web::Json<serde_json::Value>| async move {
+                            let number = number.into_inner();
+                            let mut comments = comments.lock().unwrap();
+                            comments.push((number, body.into_inner()));
+                            HttpResponse::Created().json(serde_json::json!({
+                                "id": comments.len(),
+                                "html_url": format!("https://github.com/TUM-Dev/navigatum/issues/{number}#issuecomment-{id}", id = comments.len()),
+                            }))
+                        },
+                    ),
+                );
+        });
+        let github = GitHub::with_base_uri(&format!("http://{addr}"));
+
+        // open issue => commented on
+        let url = github.add_comment(1, "more details").await.unwrap();
+        assert_eq!(
+            url.unwrap().as_str(),
+            "https://github.com/TUM-Dev/navigatum/issues/1#issuecomment-1"
+        );
+        // closed issues, pull requests and nonexistent issues => not commented on
+        assert_eq!(github.add_comment(2, "more details").await.unwrap(), None);
+        assert_eq!(github.add_comment(3, "more details").await.unwrap(), None);
+        assert_eq!(github.add_comment(4, "more details").await.unwrap(), None);
+        assert_eq!(
+            *comments.lock().unwrap(),
+            vec![(1, serde_json::json!({"body": "more details"}))]
+        );
+    }
+
+    #[test]
+    fn test_related_issue_is_referenced() {
+        let mut feedback = feedback();
+        feedback.extras.related_issue = Some(2);
+        // if a new issue is opened, it references the closed/nonexistent one
+        assert!(
+            feedback.render().contains("- **Related issue:** #2\n"),
+            "{}",
+            feedback.render()
+        );
+        // commenting on the issue itself does not
+        let comment = feedback.render_comment();
+        assert!(comment.starts_with("**A catchy title**\n\n## Description\n\n"));
+        assert!(!comment.contains("Related issue"), "{comment}");
+    }
+}
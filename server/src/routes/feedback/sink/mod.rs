@@ -0,0 +1,273 @@
+use std::future::Future;
+use std::sync::LazyLock;
+
+use actix_web::http::StatusCode;
+use tracing::{error, warn};
+use url::Url;
+
+use super::triage::{FeedbackCategory, Triage};
+use crate::error::ApiError;
+use crate::external::github::{GitHub, IssueExtras, IssueTemplate, neutralise_references};
+
+pub mod file;
+mod github;
+pub mod smtp;
+
+/// The backend selected via `FEEDBACK_BACKEND`
+pub static FEEDBACK_BACKEND: LazyLock<FeedbackBackend> = LazyLock::new(FeedbackBackend::from_env);
+
+/// Validated feedback, ready to be handed to a [`FeedbackSink`]
+#[derive(Debug)]
+pub struct Feedback<'a> {
+    pub category: FeedbackCategory,
+    /// Control characters stripped, truncated and newlines made to render in markdown
+    pub subject: String,
+    /// Control characters stripped, truncated and newlines made to render in markdown
+    pub description: String,
+    pub extras: IssueExtras<'a>,
+    pub triage: Triage,
+    pub template: IssueTemplate,
+}
+
+impl<'a> Feedback<'a> {
+    pub fn new(
+        category: FeedbackCategory,
+        subject: &str,
+        description: &str,
+        extras: IssueExtras<'a>,
+        triage: Triage,
+        template: IssueTemplate,
+    ) -> Result<Self, ApiError> {
+        let subject = GitHub::clean_feedback_data(subject, 512);
+        let description = GitHub::clean_feedback_data(description, 1024 * 1024);
+        // how long the description has to be is checked by the endpoints (see `FEEDBACK_MIN_BODY_LENGTH`)
+        if subject.len() < 3 || description.is_empty() {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "too_short",
+                "Subject or body missing or too short",
+            ));
+        }
+        Ok(Self {
+            category,
+            subject,
+            description,
+            extras,
+            triage,
+            template,
+        })
+    }
+
+    /// The title of the issue, which must not ping anyone either
+    pub fn title(&self) -> String {
+        neutralise_references(&self.subject)
+    }
+
+    /// The full text of the feedback, as it would appear in an issue
+    pub fn render(&self) -> String {
+        self.template.render(&self.description, self.extras)
+    }
+
+    /// The full text of the feedback, as it would appear in a comment on the `related_issue`
+    pub fn render_comment(&self) -> String {
+        let extras = IssueExtras {
+            related_issue: None,
+            ..self.extras
+        };
+        self.template
+            .render_comment(&self.subject, &self.description, extras)
+    }
+}
+
+/// Where feedback has been submitted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submitted {
+    /// Shown to the user
+    ///
+    /// Backends without a public page return a reference maintainers can search for instead.
+    pub url: Url,
+    /// Set if further feedback can be added as a comment, see [`FeedbackSink::add_comment`]
+    pub issue_number: Option<u64>,
+}
+
+impl Submitted {
+    /// A random reference for backends without a public page
+    pub fn reference() -> Self {
+        let url = format!("urn:navigatum:feedback:{:032x}", rand::random::<u128>());
+        Self {
+            url: Url::parse(&url).expect("the reference is a valid urn"),
+            issue_number: None,
+        }
+    }
+}
+
+/// Somewhere feedback can be delivered to
+pub trait FeedbackSink {
+    /// Whether all settings this backend needs are present
+    fn is_configured(&self) -> bool;
+
+    fn submit(
+        &self,
+        feedback: &Feedback<'_>,
+    ) -> impl Future<Output = Result<Submitted, ApiError>> + Send;
+
+    /// Open feedback submitted earlier, which likely reports the same problem
+    ///
+    /// Backends which cannot be searched never find duplicates.
+    fn find_duplicate(
+        &self,
+        _feedback: &Feedback<'_>,
+    ) -> impl Future<Output = anyhow::Result<Option<Submitted>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Adds `comment` to feedback submitted earlier, if that is still being worked on
+    ///
+    /// Returns where the comment can be found, if it was added.
+    /// Backends without issues cannot do this, the feedback is submitted as new feedback instead.
+    fn add_comment(
+        &self,
+        _issue_number: u64,
+        _comment: &str,
+    ) -> impl Future<Output = anyhow::Result<Option<Url>>> + Send {
+        async { Ok(None) }
+    }
+}
+
+/// The configured [`FeedbackSink`]
+#[derive(Debug)]
+pub enum FeedbackBackend {
+    /// Opens issues on our repository (default)
+    GitHub(GitHub),
+    /// Sends an email to the maintainers
+    Smtp(smtp::SmtpSink),
+    /// Appends to a JSON-lines file, e.g. for development or air-gapped deployments
+    File(file::FileSink),
+}
+
+impl FeedbackBackend {
+    fn from_env() -> Self {
+        let backend = std::env::var("FEEDBACK_BACKEND").unwrap_or_default();
+        match backend.trim() {
+            "" | "github" => Self::GitHub(GitHub::default()),
+            "smtp" => Self::Smtp(smtp::SmtpSink::from_env()),
+            "file" => Self::File(file::FileSink::from_env()),
+            other => {
+                error!(
+                    backend = other,
+                    "FEEDBACK_BACKEND has to be one of github, smtp or file, using github"
+                );
+                Self::GitHub(GitHub::default())
+            }
+        }
+    }
+
+    pub fn is_github(&self) -> bool {
+        matches!(self, Self::GitHub(_))
+    }
+
+    /// The GitHub client, if feedback is delivered to GitHub
+    pub fn github(&self) -> Option<&GitHub> {
+        match self {
+            Self::GitHub(github) => Some(github),
+            _ => None,
+        }
+    }
+}
+
+impl FeedbackSink for FeedbackBackend {
+    fn is_configured(&self) -> bool {
+        match self {
+            Self::GitHub(github) => github.is_configured(),
+            Self::Smtp(smtp) => smtp.is_configured(),
+            Self::File(file) => file.is_configured(),
+        }
+    }
+
+    async fn submit(&self, feedback: &Feedback<'_>) -> Result<Submitted, ApiError> {
+        match self {
+            Self::GitHub(github) => github.submit(feedback).await,
+            Self::Smtp(smtp) => smtp.submit(feedback).await,
+            Self::File(file) => file.submit(feedback).await,
+        }
+    }
+
+    async fn find_duplicate(&self, feedback: &Feedback<'_>) -> anyhow::Result<Option<Submitted>> {
+        match self {
+            Self::GitHub(github) => github.find_duplicate(feedback).await,
+            Self::Smtp(smtp) => smtp.find_duplicate(feedback).await,
+            Self::File(file) => file.find_duplicate(feedback).await,
+        }
+    }
+
+    async fn add_comment(&self, issue_number: u64, comment: &str) -> anyhow::Result<Option<Url>> {
+        match self {
+            Self::GitHub(github) => github.add_comment(issue_number, comment).await,
+            Self::Smtp(smtp) => smtp.add_comment(issue_number, comment).await,
+            Self::File(file) => file.add_comment(issue_number, comment).await,
+        }
+    }
+}
+
+/// A required setting of a backend, warning if it is missing
+fn required_env(name: &str) -> Option<String> {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        _ => {
+            warn!(name, "has to be set for the configured FEEDBACK_BACKEND");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    pub(in crate::routes::feedback) fn feedback() -> Feedback<'static> {
+        Feedback::new(
+            FeedbackCategory::Bug,
+            "A catchy title",
+            "A clear description\nwhat happened",
+            IssueExtras {
+                location: Some("mi"),
+                ..Default::default()
+            },
+            Triage {
+                labels: vec!["webform".to_string(), "bug".to_string()],
+                assignees: vec!["octocat".to_string()],
+            },
+            IssueTemplate::for_test(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_feedback_is_cleaned() {
+        let feedback = feedback();
+        assert_eq!(feedback.description, "A clear description  \nwhat happened");
+        assert!(feedback.render().contains("[`mi`]"));
+
+        let too_short = Feedback::new(
+            FeedbackCategory::Bug,
+            "A catchy title",
+            "\u{7}\u{7}",
+            IssueExtras::default(),
+            Triage::default(),
+            feedback.template,
+        );
+        let too_short = serde_json::to_value(too_short.unwrap_err()).unwrap();
+        assert_eq!(too_short["code"], "too_short");
+    }
+
+    #[test]
+    fn test_reference_is_unique() {
+        let a = Submitted::reference();
+        let b = Submitted::reference();
+        assert_ne!(a.url, b.url);
+        assert_eq!(a.url.scheme(), "urn");
+        assert_eq!(a.issue_number, None);
+    }
+}
@@ -0,0 +1,467 @@
+use std::sync::LazyLock;
+
+use actix_web::http::StatusCode;
+use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::triage::Triage;
+use crate::error::ApiError;
+
+/// Why a submission was discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamReason {
+    /// The invisible honeypot field was filled, which only bots do
+    Honeypot,
+    /// The body consists only of links
+    OnlyLinks,
+    /// The body contains more than [`SpamFilter::max_links`] links
+    TooManyLinks,
+    /// The body is a few characters repeated over and over
+    RepeatedCharacters,
+    /// The [`SpamFilter::score`] of the body reached the [`SpamFilter::score_threshold`]
+    HighScore,
+}
+impl SpamReason {
+    fn as_label(self) -> &'static str {
+        match self {
+            SpamReason::Honeypot => "honeypot",
+            SpamReason::OnlyLinks => "only_links",
+            SpamReason::TooManyLinks => "too_many_links",
+            SpamReason::RepeatedCharacters => "repeated_characters",
+            SpamReason::HighScore => "high_score",
+        }
+    }
+}
+
+/// How much each of the [`SpamFilter::phrases`] found in a body adds to its [`SpamFilter::score`]
+const PHRASE_WEIGHT: f64 = 0.5;
+/// Runs of at least this many identical characters count towards the [`SpamFilter::score`]
+const MIN_RUN_LEN: usize = 4;
+
+/// What happens to submissions reaching the [`SpamFilter::score_threshold`]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpamAction {
+    /// Rejected like submissions tripping one of the hard limits
+    #[default]
+    Reject,
+    /// Delivered with only the [`SpamFilter::quarantine_label`], so that maintainers can review them separately
+    Quarantine,
+}
+
+/// How a submission which is not rejected is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamVerdict {
+    Accepted,
+    /// See [`SpamAction::Quarantine`]
+    Quarantined,
+}
+
+/// Heuristics to reject junk submissions, configurable via `FEEDBACK_SPAM_FILTER`
+///
+/// Each heuristic can be disabled on its own, e.g. `{"max_links":null}`.
+/// Bodies tripping one of the hard limits are rejected, others are [scored](SpamFilter::score).
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpamFilter {
+    /// Reject bodies consisting only of links
+    pub reject_only_links: bool,
+    /// Reject bodies with more links than this
+    pub max_links: Option<usize>,
+    /// Reject bodies which are a pattern of up to this many characters repeated (ignoring whitespace and casing)
+    ///
+    /// `0` disables this heuristic.
+    pub max_repeated_pattern_len: usize,
+    /// Bodies with a [`SpamFilter::score`] of at least this are handled according to `above_threshold`
+    ///
+    /// `null` disables scoring.
+    pub score_threshold: Option<f64>,
+    pub above_threshold: SpamAction,
+    /// Phrases (ignoring casing) typical for spam
+    pub phrases: Vec<String>,
+    /// The only label of quarantined issues
+    pub quarantine_label: String,
+}
+impl Default for SpamFilter {
+    fn default() -> Self {
+        Self {
+            reject_only_links: true,
+            max_links: Some(5),
+            max_repeated_pattern_len: 3,
+            score_threshold: Some(1.0),
+            above_threshold: SpamAction::Reject,
+            phrases: [
+                "buy now",
+                "casino",
+                "click here",
+                "crypto",
+                "seo services",
+                "viagra",
+            ]
+            .map(String::from)
+            .to_vec(),
+            quarantine_label: "spam-suspected".to_string(),
+        }
+    }
+}
+
+static SPAM_FILTER: LazyLock<SpamFilter> = LazyLock::new(|| {
+    let Ok(raw) = std::env::var("FEEDBACK_SPAM_FILTER") else {
+        return SpamFilter::default();
+    };
+    serde_json::from_str(&raw).unwrap_or_else(|e| {
+        warn!(error = ?e, "FEEDBACK_SPAM_FILTER is not a valid configuration of the spam heuristics, using the default");
+        SpamFilter::default()
+    })
+});
+
+/// What the heuristics look at, so that both the hard limits and the [`SpamFilter::score`] count the same way
+struct Analysis {
+    words: usize,
+    links: usize,
+    /// Lowercased, without whitespace
+    chars: Vec<char>,
+    lowercased: String,
+}
+impl Analysis {
+    fn of(body: &str) -> Self {
+        let words = body.split_whitespace().collect::<Vec<_>>();
+        Self {
+            words: words.len(),
+            links: words.iter().filter(|word| is_link(word)).count(),
+            chars: words
+                .iter()
+                .flat_map(|word| word.chars())
+                .flat_map(char::to_lowercase)
+                .collect(),
+            lowercased: body.to_lowercase(),
+        }
+    }
+
+    fn only_links(&self) -> bool {
+        self.links > 0 && self.links == self.words
+    }
+
+    fn link_density(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        self.links as f64 / self.words as f64
+    }
+
+    /// Whether the characters are a pattern of at most `max_len` characters, repeated at least twice
+    fn is_repeated_pattern(&self, max_len: usize) -> bool {
+        let chars = &self.chars;
+        // a shorter last chunk differs from the pattern => only whole repetitions match
+        (1..=max_len).any(|len| {
+            chars.len() >= 2 * len && chars.chunks(len).all(|chunk| chunk == &chars[..len])
+        })
+    }
+
+    /// Share of the characters in runs of at least [`MIN_RUN_LEN`] identical characters
+    fn repeated_share(&self) -> f64 {
+        if self.chars.is_empty() {
+            return 0.0;
+        }
+        let in_runs = self
+            .chars
+            .chunk_by(|a, b| a == b)
+            .filter(|run| run.len() >= MIN_RUN_LEN)
+            .map(<[char]>::len)
+            .sum::<usize>();
+        in_runs as f64 / self.chars.len() as f64
+    }
+}
+
+impl SpamFilter {
+    /// How spammy the `body` looks
+    ///
+    /// Sums up the share of words which are links, the share of characters in runs of the same character and [`PHRASE_WEIGHT`] per spam phrase.
+    /// Typical feedback scores well below `0.5`.
+    pub fn score(&self, body: &str) -> f64 {
+        self.score_of(&Analysis::of(body))
+    }
+
+    fn score_of(&self, analysis: &Analysis) -> f64 {
+        let phrases = self
+            .phrases
+            .iter()
+            .filter(|phrase| {
+                !phrase.is_empty() && analysis.lowercased.contains(&phrase.to_lowercase())
+            })
+            .count();
+        analysis.link_density() + analysis.repeated_share() + PHRASE_WEIGHT * phrases as f64
+    }
+
+    /// Why the `body` looks like spam, if it does
+    pub fn check(&self, body: &str) -> Option<SpamReason> {
+        let analysis = Analysis::of(body);
+        if self.reject_only_links && analysis.only_links() {
+            return Some(SpamReason::OnlyLinks);
+        }
+        if self
+            .max_links
+            .is_some_and(|max_links| analysis.links > max_links)
+        {
+            return Some(SpamReason::TooManyLinks);
+        }
+        if analysis.is_repeated_pattern(self.max_repeated_pattern_len) {
+            return Some(SpamReason::RepeatedCharacters);
+        }
+        if self
+            .score_threshold
+            .is_some_and(|threshold| self.score_of(&analysis) >= threshold)
+        {
+            return Some(SpamReason::HighScore);
+        }
+        None
+    }
+}
+
+pub(super) fn is_link(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.contains("http://") || word.contains("https://") || word.starts_with("www.")
+}
+
+/// Submissions we discarded, by [`SpamReason`], and those we quarantined
+///
+/// Registered against the registry of [`actix_web_prom::PrometheusMetrics`] to be exposed on `/api/metrics`
+#[derive(Clone, Debug)]
+pub struct SpamMetrics {
+    discarded: IntCounterVec,
+    quarantined: IntCounter,
+}
+impl SpamMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let discarded = IntCounterVec::new(
+            Opts::new(
+                "feedback_discarded_total",
+                "Feedback submissions discarded as spam, by reason",
+            )
+            .namespace("navigatum_api"),
+            &["reason"],
+        )?;
+        let quarantined = IntCounter::with_opts(
+            Opts::new(
+                "feedback_quarantined_total",
+                "Feedback submissions delivered with the quarantine label, as they looked like spam",
+            )
+            .namespace("navigatum_api"),
+        )?;
+        registry.register(Box::new(discarded.clone()))?;
+        registry.register(Box::new(quarantined.clone()))?;
+        Ok(Self {
+            discarded,
+            quarantined,
+        })
+    }
+
+    pub fn record(&self, reason: SpamReason) {
+        info!(reason = reason.as_label(), "discarded feedback as spam");
+        self.discarded.with_label_values(&[reason.as_label()]).inc();
+    }
+}
+
+/// Checks the `body` against the configured [`SpamFilter`]
+///
+/// The messages are deliberately generic, so that they do not tell spammers which heuristic to evade.
+pub fn check_body(metrics: &SpamMetrics, body: &str) -> Result<SpamVerdict, ApiError> {
+    check_body_with(&SPAM_FILTER, metrics, body)
+}
+
+fn check_body_with(
+    filter: &SpamFilter,
+    metrics: &SpamMetrics,
+    body: &str,
+) -> Result<SpamVerdict, ApiError> {
+    let Some(reason) = filter.check(body) else {
+        return Ok(SpamVerdict::Accepted);
+    };
+    if reason == SpamReason::HighScore && filter.above_threshold == SpamAction::Quarantine {
+        info!("quarantined feedback looking like spam");
+        metrics.quarantined.inc();
+        return Ok(SpamVerdict::Quarantined);
+    }
+    metrics.record(reason);
+    Err(ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "rejected_as_spam",
+        "The feedback looks like spam, please describe the problem in your own words",
+    ))
+}
+
+/// How quarantined feedback is triaged: only labeled, so that it does not reach the usual assignees
+pub fn quarantine_triage() -> Triage {
+    Triage {
+        labels: vec![SPAM_FILTER.quarantine_label.clone()],
+        assignees: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_only_links() {
+        let filter = SpamFilter::default();
+        assert_eq!(
+            filter.check("https://spam.example.com"),
+            Some(SpamReason::OnlyLinks)
+        );
+        assert_eq!(
+            filter.check("www.spam.example.com\n  HTTP://spam.example.com"),
+            Some(SpamReason::OnlyLinks)
+        );
+        assert_eq!(
+            filter.check("The room is shown on https://nav.tum.de/view/mi but is elsewhere"),
+            None
+        );
+        let disabled = SpamFilter {
+            reject_only_links: false,
+            ..Default::default()
+        };
+        assert_eq!(disabled.check("https://spam.example.com"), None);
+    }
+
+    #[test]
+    fn test_too_many_links() {
+        let filter = SpamFilter::default();
+        let links = |n: usize| {
+            let links = vec!["https://spam.example.com"; n].join(" and ");
+            format!("see {links}")
+        };
+        assert_eq!(filter.check(&links(5)), None);
+        assert_eq!(filter.check(&links(6)), Some(SpamReason::TooManyLinks));
+        let stricter = SpamFilter {
+            max_links: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(stricter.check(&links(2)), Some(SpamReason::TooManyLinks));
+        let disabled = SpamFilter {
+            max_links: None,
+            ..Default::default()
+        };
+        assert_eq!(disabled.check(&links(100)), None);
+    }
+
+    #[test]
+    fn test_repeated_characters() {
+        let filter = SpamFilter::default();
+        assert_eq!(
+            filter.check("aaaaaaaaaaaa"),
+            Some(SpamReason::RepeatedCharacters)
+        );
+        assert_eq!(
+            filter.check("!!!!! !!!!!\n!!!!!"),
+            Some(SpamReason::RepeatedCharacters)
+        );
+        assert_eq!(
+            filter.check("asdASDasdasd"),
+            Some(SpamReason::RepeatedCharacters)
+        );
+        // longer patterns are not detected by default
+        assert_eq!(filter.check("qwerqwerqwer"), None);
+        assert_eq!(filter.check("The elevator in MI is broken"), None);
+        assert_eq!(filter.check("Aaaah, the elevator is broken"), None);
+        let disabled = SpamFilter {
+            max_repeated_pattern_len: 0,
+            ..Default::default()
+        };
+        assert_eq!(disabled.check("aaaaaaaaaaaa"), None);
+    }
+
+    #[test]
+    fn test_score() {
+        let filter = SpamFilter::default();
+        assert_eq!(filter.score(""), 0.0);
+        assert_eq!(filter.score("The elevator in MI is broken"), 0.0);
+        // half the words are links
+        assert_eq!(
+            filter.score("see https://a.example.com and www.b.example.com"),
+            0.5
+        );
+        // 5 of the 10 characters are in a run
+        assert_eq!(filter.score("Helppppp me"), 0.5);
+        assert_eq!(filter.score("Best CASINO, click here"), 1.0);
+        let without_phrases = SpamFilter {
+            phrases: Vec::new(),
+            ..Default::default()
+        };
+        assert_eq!(without_phrases.score("Best CASINO, click here"), 0.0);
+    }
+
+    #[test]
+    fn test_high_scores() {
+        let metrics = SpamMetrics::register(&Registry::new()).unwrap();
+        let spam = "Best casino https://casino.example.com, click here";
+        let filter = SpamFilter::default();
+        assert_eq!(filter.check(spam), Some(SpamReason::HighScore));
+        let err = check_body_with(&filter, &metrics, spam).unwrap_err();
+        let err = serde_json::to_value(err).unwrap();
+        assert_eq!(err["code"], "rejected_as_spam");
+        assert_eq!(
+            metrics.discarded.with_label_values(&["high_score"]).get(),
+            1
+        );
+
+        let quarantining: SpamFilter =
+            serde_json::from_str(r#"{"above_threshold":"quarantine"}"#).unwrap();
+        assert_eq!(
+            check_body_with(&quarantining, &metrics, spam).unwrap(),
+            SpamVerdict::Quarantined
+        );
+        assert_eq!(
+            check_body_with(&quarantining, &metrics, "The elevator in MI is broken").unwrap(),
+            SpamVerdict::Accepted
+        );
+        assert_eq!(metrics.quarantined.get(), 1);
+
+        let disabled = SpamFilter {
+            score_threshold: None,
+            ..Default::default()
+        };
+        assert_eq!(disabled.check(spam), None);
+    }
+
+    #[test]
+    fn test_filter_is_configurable() {
+        let filter: SpamFilter = serde_json::from_str(r#"{"max_links":null}"#).unwrap();
+        assert_eq!(
+            filter,
+            SpamFilter {
+                max_links: None,
+                ..Default::default()
+            }
+        );
+        assert!(serde_json::from_str::<SpamFilter>(r#"{"max_link":3}"#).is_err());
+    }
+
+    #[test]
+    fn test_discarded_are_counted() {
+        let registry = Registry::new();
+        let metrics = SpamMetrics::register(&registry).unwrap();
+        let err = check_body(&metrics, "https://spam.example.com").unwrap_err();
+        assert_eq!(
+            serde_json::to_value(err).unwrap()["code"],
+            "rejected_as_spam"
+        );
+        assert!(check_body(&metrics, "The elevator in MI is broken").is_ok());
+        metrics.record(SpamReason::Honeypot);
+        assert_eq!(
+            metrics.discarded.with_label_values(&["only_links"]).get(),
+            1
+        );
+        assert_eq!(metrics.discarded.with_label_values(&["honeypot"]).get(), 1);
+        assert_eq!(
+            metrics
+                .discarded
+                .with_label_values(&["too_many_links"])
+                .get(),
+            0
+        );
+    }
+}
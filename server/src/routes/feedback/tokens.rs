@@ -1,13 +1,63 @@
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use actix_web::{HttpResponse, post};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web::Query;
+use actix_web::{HttpResponse, ResponseError, post};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode};
+use prometheus::{IntCounterVec, Opts, Registry};
 use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::PgPool;
 use tokio::sync::Mutex;
-use tracing::error;
+use tracing::{error, info, warn};
+use url::Url;
+
+use super::blocklist::{self, Blocklist};
+use super::challenge::ChallengeSolution;
+use super::sink::{FEEDBACK_BACKEND, FeedbackSink};
+use crate::db::feedback::UsedToken;
+use crate::error::ApiError;
+use crate::external::github::{GitHub, TokenValidity};
 
-#[derive(Default)]
-pub struct RecordedTokens(Mutex<Vec<TokenRecord>>);
+/// Tokens which were already used
+///
+/// Usage is recorded in memory and, if a database is configured, in the database.
+/// The latter persists the replay protection across restarts and shares it between instances.
+/// Idempotent responses are only remembered in memory.
+pub struct RecordedTokens {
+    records: Mutex<Vec<TokenRecord>>,
+    store: Option<PgPool>,
+    /// Validated tokens, by whether they were signed with the primary key
+    validations: IntCounterVec,
+    blocklist: Blocklist,
+}
+impl Default for RecordedTokens {
+    fn default() -> Self {
+        let validations = IntCounterVec::new(
+            Opts::new(
+                "feedback_token_validations_total",
+                "Valid feedback tokens, by whether they were signed with the primary or a previous JWT_KEY",
+            )
+            .namespace("navigatum_api"),
+            &["key"],
+        )
+        .expect("the metric options are valid");
+        Self {
+            records: Mutex::default(),
+            store: None,
+            validations,
+            blocklist: Blocklist::default(),
+        }
+    }
+}
 
 impl fmt::Debug for RecordedTokens {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -19,17 +69,239 @@ impl fmt::Debug for RecordedTokens {
 pub struct TokenRecord {
     kid: u64,
     next_reset: i64,
+    /// `Idempotency-Key` of the request which used the token
+    idempotency_key: Option<String>,
+    /// Response to said request, once it succeeded
+    outcome: Option<FeedbackOutcome>,
+    /// `id` of the challenge, which was used up together with the token
+    challenge: Option<u64>,
+}
+
+/// Successful response to a feedback submission
+///
+/// Repeated for retries with the same `Idempotency-Key`, instead of submitting the feedback again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedbackOutcome {
+    pub status: StatusCode,
+    /// Missing for discarded feedback, which is answered without a body
+    pub url: Option<Url>,
+    /// The feedback was added to an existing issue instead of opening a new one
+    pub deduplicated: bool,
+    /// Lets the reporter withdraw the feedback via [`super::withdraw::withdraw_feedback`]
+    pub deletion_token: Option<String>,
+}
+impl From<FeedbackOutcome> for HttpResponse {
+    fn from(value: FeedbackOutcome) -> Self {
+        let mut response = HttpResponse::build(value.status);
+        if value.deduplicated {
+            response.insert_header(("Deduplicated", "true"));
+        }
+        if let Some(token) = value.deletion_token {
+            response.insert_header((super::withdraw::DELETION_TOKEN, token));
+        }
+        match value.url {
+            Some(url) => response.content_type("text/plain").body(url.to_string()),
+            None => response.finish(),
+        }
+    }
+}
+
+/// A token (or solved challenge), which was accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidatedToken {
+    /// Identifies the token, e.g. to record the outcome of the request or the attachments of the feedback
+    pub kid: u64,
+    /// Unix timestamp after which the token is no longer valid
+    pub expires_at: i64,
+}
+
+/// Why a token (or solved challenge) was not accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    /// Feedback is not configured, see [`configured_keys`]
+    NotConfigured,
+    /// Tokens are only valid [`TOKEN_MIN_AGE`] seconds after being issued
+    Immature,
+    Expired,
+    /// Not issued by us, malformed or missing
+    Invalid,
+    AlreadyUsed,
+    /// On the abuse blocklist
+    Blocked,
+    /// Issued for another purpose, `scope` is `None` for tokens issued before tokens had scopes
+    WrongScope {
+        scope: Option<TokenScope>,
+        required: TokenScope,
+    },
+    /// Only a solved challenge is accepted, see `FEEDBACK_REQUIRE_CHALLENGE`
+    ChallengeRequired,
+    /// The solved challenge was not accepted
+    Challenge(ApiError),
+    /// A request with the same `Idempotency-Key` is still being processed
+    RequestInProgress,
+    /// A request with the same `Idempotency-Key` already succeeded => it is answered the same way
+    Replayed(Box<FeedbackOutcome>),
+}
+
+impl TokenError {
+    fn api_error(&self) -> ApiError {
+        let forbidden = |code, message| ApiError::new(StatusCode::FORBIDDEN, code, message);
+        match self {
+            TokenError::NotConfigured => ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "feedback_not_configured",
+                "Feedback is currently not configured on this server.",
+            ),
+            TokenError::Immature => forbidden("token_not_yet_valid", "Token is not yet valid."),
+            TokenError::Expired => forbidden("token_expired", "Token expired"),
+            TokenError::Invalid => forbidden("invalid_token", "Invalid token"),
+            TokenError::AlreadyUsed => forbidden("token_already_used", "Token already used."),
+            TokenError::Blocked => blocklist::blocked(),
+            TokenError::WrongScope {
+                scope: None,
+                required,
+            } => ApiError::new(
+                StatusCode::FORBIDDEN,
+                "wrong_token_scope",
+                format!(
+                    "Tokens without a scope are no longer accepted, request a token with the scope {required}",
+                    required = required.as_str()
+                ),
+            ),
+            TokenError::WrongScope {
+                scope: Some(scope),
+                required,
+            } => ApiError::new(
+                StatusCode::FORBIDDEN,
+                "wrong_token_scope",
+                format!(
+                    "Token was issued for the scope {scope}, but {required} is required",
+                    scope = scope.as_str(),
+                    required = required.as_str()
+                ),
+            ),
+            TokenError::ChallengeRequired => forbidden(
+                "challenge_required",
+                "A solved challenge is required, see /api/feedback/challenge",
+            ),
+            TokenError::Challenge(e) => e.clone(),
+            TokenError::RequestInProgress => ApiError::new(
+                StatusCode::CONFLICT,
+                "request_in_progress",
+                "A request with this Idempotency-Key is still being processed, please try again later",
+            ),
+            TokenError::Replayed(outcome) => ApiError::new(
+                outcome.status,
+                "idempotent_replayed",
+                "A request with this Idempotency-Key already succeeded",
+            ),
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for TokenError {
+    fn from(value: jsonwebtoken::errors::Error) -> Self {
+        match value.kind() {
+            jsonwebtoken::errors::ErrorKind::ImmatureSignature => TokenError::Immature,
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => TokenError::Expired,
+            _ => TokenError::Invalid,
+        }
+    }
 }
 
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.api_error())
+    }
+}
+
+impl ResponseError for TokenError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TokenError::Replayed(outcome) => outcome.status,
+            _ => self.api_error().status_code(),
+        }
+    }
+    fn error_response(&self) -> HttpResponse {
+        let TokenError::Replayed(outcome) = self else {
+            return self.api_error().error_response();
+        };
+        let mut response = HttpResponse::from(outcome.as_ref().clone());
+        response.headers_mut().insert(
+            HeaderName::from_static("idempotent-replayed"),
+            HeaderValue::from_static("true"),
+        );
+        response
+    }
+}
+
+impl From<TokenError> for HttpResponse {
+    fn from(value: TokenError) -> Self {
+        value.error_response()
+    }
+}
+
+/// Cleared by [`check_github_token`] if the token cannot be used to open issues
+pub(super) static GITHUB_TOKEN_USABLE: AtomicBool = AtomicBool::new(true);
+
+/// Whether we can hand out tokens and deliver the feedback to the configured backend
 fn able_to_process_feedback() -> bool {
-    std::env::var("GITHUB_TOKEN").is_ok() && std::env::var("JWT_KEY").is_ok()
+    JwtKeys::from_env().is_some() && FEEDBACK_BACKEND.is_configured()
+}
+
+/// Checks at startup that `GITHUB_TOKEN` actually works
+///
+/// Otherwise, we would only notice once the feedback of the first user is lost.
+/// If GitHub rejects the token, feedback is reported as not being configured.
+/// If GitHub cannot be reached, we give the token the benefit of the doubt.
+pub async fn check_github_token() {
+    if !FEEDBACK_BACKEND.is_github() || std::env::var("GITHUB_TOKEN").is_err() {
+        return; // already reported when GitHub is used
+    }
+    match GitHub::default().validate_token().await {
+        Ok(TokenValidity::Valid) => info!("GITHUB_TOKEN is valid, feedback is enabled"),
+        Ok(TokenValidity::Invalid) => {
+            error!("GITHUB_TOKEN is invalid (expired or revoked), feedback is disabled");
+            GITHUB_TOKEN_USABLE.store(false, Ordering::Relaxed);
+        }
+        Ok(TokenValidity::MissingScope { scopes }) => {
+            error!(
+                scopes,
+                "GITHUB_TOKEN lacks the `repo` scope, feedback is disabled"
+            );
+            GITHUB_TOKEN_USABLE.store(false, Ordering::Relaxed);
+        }
+        Err(e) => warn!(error = ?e, "could not validate GITHUB_TOKEN, assuming it is valid"),
+    }
 }
 
 // Additionally, there is a short delay until a token can be used.
 // Clients need to wait that time if (for some reason) the user submitted
 // faster than limited here.
 const TOKEN_MIN_AGE: i64 = 5;
-const TOKEN_MAX_AGE: i64 = 3600 * 12; // 12h
+pub(super) const TOKEN_MAX_AGE: i64 = 3600 * 12; // 12h
+
+/// What a token may be used for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Submitting feedback via [`super::post_feedback::send_feedback`]
+    #[default]
+    Feedback,
+    /// Attaching images via [`super::attachments::attach_image`], which the feedback then references via its `attachment_token`
+    Attachment,
+    /// Proposing edits via [`super::proposed_edits::propose_edits`] or feedback of the `edit_proposal` category
+    EditProposal,
+}
+impl TokenScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Feedback => "feedback",
+            TokenScope::Attachment => "attachment",
+            TokenScope::EditProposal => "edit_proposal",
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -37,75 +309,486 @@ pub struct Claims {
     iat: i64, // Optional. Issued at (as UTC timestamp)
     nbf: i64, // Optional. Not Before (as UTC timestamp)
     kid: u64, // Optional. Key ID
+    /// Missing in tokens issued before tokens had scopes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<TokenScope>,
 }
 
-impl Default for Claims {
-    fn default() -> Self {
+impl Claims {
+    fn new(scope: TokenScope) -> Self {
         let now = chrono::Utc::now().timestamp();
         Self {
             exp: now + TOKEN_MAX_AGE,
             iat: now,
             nbf: now + TOKEN_MIN_AGE,
             kid: rand::random(),
+            scope: Some(scope),
         }
     }
 }
 
-impl RecordedTokens {
-    #[tracing::instrument(skip(token))]
-    pub async fn validate(&self, token: &str) -> Option<HttpResponse> {
-        if !able_to_process_feedback() {
-            return Some(
-                HttpResponse::ServiceUnavailable()
-                    .content_type("text/plain")
-                    .body("Feedback is currently not configured on this server."),
-            );
+impl Default for Claims {
+    fn default() -> Self {
+        Self::new(TokenScope::default())
+    }
+}
+
+/// Keys to sign and validate tokens with, configured via `JWT_KEY`
+///
+/// To rotate keys, the new key is prepended to the old ones.
+/// Once no tokens signed with the old keys are valid anymore (i.e. after 12h), they can be removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct JwtKeys {
+    /// The first key signs new tokens, all keys validate tokens
+    keys: Vec<JwtKey>,
+}
+#[derive(Clone, PartialEq, Eq)]
+struct JwtKey {
+    /// Identifies the key in the header of tokens, without revealing it
+    id: String,
+    secret: String,
+}
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //secret purposely omitted
+        f.debug_struct("JwtKey").field("id", &self.id).finish()
+    }
+}
+/// Label the key ids are derived from
+///
+/// A plain hash of the secret would be a public fingerprint of it, as the id is sent in every token.
+const KEY_ID_LABEL: &[u8] = b"navigatum feedback token key id";
+impl JwtKey {
+    fn new(secret: &str) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(KEY_ID_LABEL);
+        let id = format!("{:x}", mac.finalize().into_bytes());
+        Self {
+            id: id[..16].to_string(),
+            secret: secret.to_string(),
         }
+    }
+}
 
-        let secret = std::env::var("JWT_KEY").unwrap(); // we checked the ability to process feedback
-        let x = DecodingKey::from_secret(secret.as_bytes());
-        let jwt_token = decode::<Claims>(token, &x, &Validation::default());
-        let kid = match jwt_token {
-            Ok(token) => token.claims.kid,
-            Err(e) => {
-                error!(kind=?e.kind(),"Failed to decode token");
-                return Some(HttpResponse::Forbidden().content_type("text/plain").body(
-                    match e.kind() {
-                        jsonwebtoken::errors::ErrorKind::ImmatureSignature => {
-                            "Token is not yet valid."
-                        }
-                        jsonwebtoken::errors::ErrorKind::ExpiredSignature => "Token expired",
-                        _ => "Invalid token",
-                    },
-                ));
+impl JwtKeys {
+    pub(super) fn from_env() -> Option<Self> {
+        Self::parse(&std::env::var("JWT_KEY").ok()?)
+    }
+    /// Either a comma-separated list or a JSON array of keys, the primary key first
+    pub(super) fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let secrets = if raw.starts_with('[') {
+            serde_json::from_str::<Vec<String>>(raw)
+                .map_err(|e| error!(error = ?e, "JWT_KEY is not a valid JSON array of keys"))
+                .ok()?
+        } else {
+            raw.split(',').map(ToString::to_string).collect()
+        };
+        let keys = secrets
+            .iter()
+            .map(|secret| secret.trim())
+            .filter(|secret| !secret.is_empty())
+            .map(JwtKey::new)
+            .collect::<Vec<_>>();
+        (!keys.is_empty()).then_some(Self { keys })
+    }
+    /// The key signing new tokens and challenges
+    pub(super) fn primary_secret(&self) -> &[u8] {
+        self.keys[0].secret.as_bytes()
+    }
+    /// All keys, the primary key first
+    pub(super) fn secrets(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|key| key.secret.as_bytes())
+    }
+    /// Signs the claims with the primary key
+    fn encode(&self, claims: &Claims) -> jsonwebtoken::errors::Result<String> {
+        let primary = &self.keys[0];
+        let header = Header {
+            kid: Some(primary.id.clone()),
+            ..Header::default()
+        };
+        encode(
+            &header,
+            claims,
+            &EncodingKey::from_secret(primary.secret.as_bytes()),
+        )
+    }
+    /// The claims of the token and whether it was signed with the primary key
+    ///
+    /// Tokens name their key in the header.
+    /// Tokens without a known key id (issued before keys had ids or before they were derived via HMAC) are tried against all keys.
+    fn decode(&self, token: &str) -> jsonwebtoken::errors::Result<(Claims, bool)> {
+        let kid = decode_header(token)?.kid;
+        let known = |key: &JwtKey| kid.as_deref() == Some(key.id.as_str());
+        let candidates = if self.keys.iter().any(known) {
+            self.keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| known(key))
+                .collect::<Vec<_>>()
+        } else {
+            self.keys.iter().enumerate().collect()
+        };
+        let mut error: jsonwebtoken::errors::Error =
+            jsonwebtoken::errors::ErrorKind::InvalidSignature.into();
+        for (index, key) in candidates {
+            let decoded = decode::<Claims>(
+                token,
+                &DecodingKey::from_secret(key.secret.as_bytes()),
+                &Validation::default(),
+            );
+            match decoded {
+                Ok(token) => return Ok((token.claims, index == 0)),
+                Err(e) => error = e,
+            }
+            // the signature matched => the token was issued with this key, but is not valid (yet)
+            if !matches!(
+                error.kind(),
+                jsonwebtoken::errors::ErrorKind::InvalidSignature
+            ) {
+                break;
             }
+        }
+        Err(error)
+    }
+    /// The token and whether it was signed with the primary key, if it is valid for the `scope`
+    fn validate(
+        &self,
+        token: &str,
+        scope: TokenScope,
+        accept_unscoped: bool,
+    ) -> Result<(ValidatedToken, bool), TokenError> {
+        let (claims, primary) = self.decode(token)?;
+        check_scope(claims.scope, scope, accept_unscoped)?;
+        let token = ValidatedToken {
+            kid: claims.kid,
+            expires_at: claims.exp,
         };
+        Ok((token, primary))
+    }
+}
+
+impl RecordedTokens {
+    /// Also records used tokens in the database
+    pub fn persistent(pool: PgPool) -> Self {
+        Self {
+            store: Some(pool.clone()),
+            blocklist: Blocklist::persistent(pool),
+            ..Self::default()
+        }
+    }
+
+    /// Exposes on `/api/metrics` whether tokens signed with previous keys are still in use and how many requests were blocked
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.validations.clone()))?;
+        self.blocklist.register(registry)
+    }
+
+    /// Tokens and clients, which are not allowed to submit feedback
+    pub fn blocklist(&self) -> &Blocklist {
+        &self.blocklist
+    }
+
+    /// Uses the token, if it was issued by us for the `scope`, is within its time limits and was not used yet
+    #[tracing::instrument(skip(token))]
+    pub async fn validate(
+        &self,
+        token: &str,
+        scope: TokenScope,
+    ) -> Result<ValidatedToken, TokenError> {
+        self.use_token(token, scope, None).await
+    }
+
+    /// Like [`RecordedTokens::validate`], but retries of a request with the same `idempotency_key` get its original response
+    ///
+    /// The outcome of the request has to be recorded for the `kid` of the token.
+    /// Keys are remembered as long as their token, i.e. for up to 12h.
+    #[tracing::instrument(skip(token))]
+    pub async fn use_token(
+        &self,
+        token: &str,
+        scope: TokenScope,
+        idempotency_key: Option<&str>,
+    ) -> Result<ValidatedToken, TokenError> {
+        let token = self.decode(token, scope).await?;
+        self.admit(token.kid, idempotency_key).await?;
+        Ok(token)
+    }
 
+    /// Records that the token is used, if it was not used already (here, before a restart or by another instance)
+    async fn admit(&self, kid: u64, idempotency_key: Option<&str>) -> Result<(), TokenError> {
         // now we know from token-validity, that it is within our time limits and created by us.
         // The problem is, that it could be used multiple times.
         // To prevent this, we need to check if the token was already used.
-        // This is means that if this usage+our ratelimits are
+        // Without a database, this usage is
         // - neither synced across multiple feedback instances, nor
         // - persisted between reboots
 
         let now = chrono::Utc::now().timestamp();
-        let mut tokens = self.0.lock().await;
-        // remove outdated tokens (no longer relevant for rate limit)
-        tokens.retain(|t| t.next_reset > now);
-        // check if token is already used
-        if tokens.iter().any(|r| r.kid == kid) {
-            return Some(
-                HttpResponse::Forbidden()
-                    .content_type("text/plain")
-                    .body("Token already used."),
-            );
-        }
-        tokens.push(TokenRecord {
+        admit(&mut *self.records.lock().await, kid, idempotency_key, now)?;
+        let Some(pool) = &self.store else {
+            return Ok(());
+        };
+        let used = UsedToken {
             kid,
-            next_reset: now + TOKEN_MAX_AGE,
-        });
-        None
+            expires_at: chrono::DateTime::from_timestamp(now + TOKEN_MAX_AGE, 0)
+                .unwrap_or_default(),
+        };
+        match used.insert(pool).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                self.records.lock().await.retain(|t| t.kid != kid);
+                Err(TokenError::AlreadyUsed)
+            }
+            Err(e) => {
+                // replays on this instance are still prevented
+                error!(error = ?e, "could not persist the usage of a token, relying on memory only");
+                Ok(())
+            }
+        }
+    }
+
+    /// Remembers the response for retries with the same `Idempotency-Key`
+    ///
+    /// The `deletion_token` is not repeated, so that it is only ever handed out once.
+    pub async fn record_outcome(&self, kid: u64, outcome: FeedbackOutcome) {
+        let outcome = FeedbackOutcome {
+            deletion_token: None,
+            ..outcome
+        };
+        let mut tokens = self.records.lock().await;
+        if let Some(record) = tokens.iter_mut().find(|t| t.kid == kid) {
+            record.outcome = Some(outcome);
+        }
+    }
+
+    /// Makes the token (and the challenge used up with it) usable again, as the request using it failed
+    pub async fn release(&self, kid: u64) {
+        let challenge = {
+            let mut tokens = self.records.lock().await;
+            let challenge = tokens
+                .iter()
+                .find(|t| t.kid == kid)
+                .and_then(|t| t.challenge);
+            tokens.retain(|t| t.kid != kid && Some(t.kid) != challenge);
+            challenge
+        };
+        let Some(pool) = &self.store else {
+            return;
+        };
+        for kid in std::iter::once(kid).chain(challenge) {
+            if let Err(e) = UsedToken::delete(pool, kid).await {
+                error!(error = ?e, "could not release the token, it cannot be used again");
+            }
+        }
     }
+
+    /// Records that both the token and the challenge sent with it are used
+    ///
+    /// Otherwise, one solved challenge could vouch for any number of tokens.
+    async fn admit_with_challenge(
+        &self,
+        kid: u64,
+        challenge: u64,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), TokenError> {
+        self.admit(kid, idempotency_key).await?;
+        // retries are recognised by the token => the key is not needed for the challenge
+        if let Err(e) = self.admit(challenge, None).await {
+            self.release(kid).await;
+            return Err(e);
+        }
+        let mut tokens = self.records.lock().await;
+        if let Some(record) = tokens.iter_mut().find(|t| t.kid == kid) {
+            record.challenge = Some(challenge);
+        }
+        Ok(())
+    }
+
+    /// Checks the token without using it, e.g. to attach images to the feedback it will be used for
+    #[tracing::instrument(skip(token))]
+    pub async fn peek(&self, token: &str, scope: TokenScope) -> Result<ValidatedToken, TokenError> {
+        let token = self.decode(token, scope).await?;
+        if self.is_used(token.kid).await {
+            return Err(TokenError::AlreadyUsed);
+        }
+        Ok(token)
+    }
+
+    async fn is_used(&self, kid: u64) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        {
+            let mut tokens = self.records.lock().await;
+            tokens.retain(|t| t.next_reset > now);
+            if tokens.iter().any(|r| r.kid == kid) {
+                return true;
+            }
+        }
+        let Some(pool) = &self.store else {
+            return false;
+        };
+        UsedToken::exists(pool, kid).await.unwrap_or_else(|e| {
+            error!(error = ?e, "could not check if the token was used, relying on memory only");
+            false
+        })
+    }
+
+    /// Uses the token and/or the solved challenge the feedback was sent with
+    ///
+    /// If both are sent, both have to be valid and both are used up.
+    /// The token identifies retries and has to be issued for the `scope`.
+    /// Challenges have no scope => they only stand in for [`TokenScope::Feedback`] tokens.
+    /// With `FEEDBACK_REQUIRE_CHALLENGE=true`, a token alone is not enough.
+    #[tracing::instrument(skip(token, challenge))]
+    pub async fn use_credentials(
+        &self,
+        token: Option<&str>,
+        challenge: Option<&ChallengeSolution>,
+        scope: TokenScope,
+        idempotency_key: Option<&str>,
+    ) -> Result<ValidatedToken, TokenError> {
+        match (token, challenge) {
+            (Some(token), Some(challenge)) => {
+                let solved = self.verify_challenge(challenge)?;
+                let token = self.decode(token, scope).await?;
+                self.admit_with_challenge(token.kid, solved.kid, idempotency_key)
+                    .await?;
+                Ok(token)
+            }
+            (None, Some(_)) if scope != TokenScope::Feedback => Err(TokenError::WrongScope {
+                scope: None,
+                required: scope,
+            }),
+            (None, Some(challenge)) => {
+                let solved = self.verify_challenge(challenge)?;
+                self.admit(solved.kid, idempotency_key).await?;
+                Ok(solved)
+            }
+            (Some(token), None) if !challenge_required() => {
+                self.use_token(token, scope, idempotency_key).await
+            }
+            (None, None) if !challenge_required() => Err(TokenError::Invalid),
+            _ => Err(TokenError::ChallengeRequired),
+        }
+    }
+
+    /// The challenge, if it was created by us, is not expired and is solved
+    fn verify_challenge(
+        &self,
+        challenge: &ChallengeSolution,
+    ) -> Result<ValidatedToken, TokenError> {
+        let keys = configured_keys()?;
+        let expires_at = challenge
+            .verify(&keys, chrono::Utc::now().timestamp())
+            .map_err(TokenError::Challenge)?;
+        Ok(ValidatedToken {
+            kid: challenge.id(),
+            expires_at,
+        })
+    }
+
+    /// The token, if it was created by us, is within its time limits, was issued for the `scope` and is not blocked
+    async fn decode(&self, token: &str, scope: TokenScope) -> Result<ValidatedToken, TokenError> {
+        let keys = configured_keys()?;
+        let (token, primary) = keys
+            .validate(token, scope, accept_unscoped_tokens())
+            .inspect_err(|e| error!(error = %e, "Failed to validate token"))?;
+        if !primary {
+            info!("token was signed with a previous JWT_KEY");
+        }
+        let key = if primary { "primary" } else { "previous" };
+        self.validations.with_label_values(&[key]).inc();
+        if self.blocklist.blocks_kid(token.kid).await {
+            return Err(TokenError::Blocked);
+        }
+        Ok(token)
+    }
+}
+
+/// The keys to validate tokens and challenges with, if feedback is configured
+pub(super) fn configured_keys() -> Result<JwtKeys, TokenError> {
+    match JwtKeys::from_env() {
+        Some(keys) if able_to_process_feedback() => Ok(keys),
+        _ => Err(TokenError::NotConfigured),
+    }
+}
+
+/// Whether feedback has to be sent with a solved challenge, instead of just a token
+fn challenge_required() -> bool {
+    std::env::var("FEEDBACK_REQUIRE_CHALLENGE") == Ok("true".to_string())
+}
+
+/// Whether tokens issued before tokens had scopes are still accepted as [`TokenScope::Feedback`] tokens
+///
+/// Set `FEEDBACK_ACCEPT_UNSCOPED_TOKENS=false` once all of them have expired (i.e. 12h after the upgrade).
+fn accept_unscoped_tokens() -> bool {
+    std::env::var("FEEDBACK_ACCEPT_UNSCOPED_TOKENS") != Ok("false".to_string())
+}
+
+/// Rejects tokens, which were issued for another purpose than `required`
+fn check_scope(
+    scope: Option<TokenScope>,
+    required: TokenScope,
+    accept_unscoped: bool,
+) -> Result<(), TokenError> {
+    let permitted = match scope {
+        Some(scope) => scope == required,
+        None => accept_unscoped && required == TokenScope::Feedback,
+    };
+    if permitted {
+        Ok(())
+    } else {
+        Err(TokenError::WrongScope { scope, required })
+    }
+}
+
+/// Records that the token is used, if it was not used already
+fn admit(
+    tokens: &mut Vec<TokenRecord>,
+    kid: u64,
+    idempotency_key: Option<&str>,
+    now: i64,
+) -> Result<(), TokenError> {
+    // remove outdated tokens (no longer relevant for rate limit)
+    tokens.retain(|t| t.next_reset > now);
+    // check if this is a retry of an earlier request.
+    // Keys are chosen by clients => only retries with the same token are answered from the earlier request
+    if let Some(key) = idempotency_key {
+        let earlier = tokens
+            .iter()
+            .find(|t| t.kid == kid && t.idempotency_key.as_deref() == Some(key));
+        if let Some(earlier) = earlier {
+            return Err(match &earlier.outcome {
+                Some(outcome) => TokenError::Replayed(Box::new(outcome.clone())),
+                None => TokenError::RequestInProgress,
+            });
+        }
+    }
+    // check if token is already used
+    if tokens.iter().any(|r| r.kid == kid) {
+        return Err(TokenError::AlreadyUsed);
+    }
+    tokens.push(TokenRecord {
+        kid,
+        next_reset: now + TOKEN_MAX_AGE,
+        idempotency_key: idempotency_key.map(str::to_string),
+        outcome: None,
+        challenge: None,
+    });
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+struct TokenQuery {
+    /// What the token will be used for
+    ///
+    /// - `feedback`: submitting feedback
+    /// - `attachment`: attaching images to feedback
+    /// - `edit_proposal`: proposing edits, also as feedback of the `edit_proposal` category
+    #[serde(default)]
+    #[param(inline, default = "feedback")]
+    scope: TokenScope,
 }
 
 #[derive(Debug, Serialize, Deserialize, utoipa::IntoParams, utoipa::ToSchema)]
@@ -132,32 +815,31 @@ struct TokenResponse {
 ///
 /// Tokens gain validity after 5s, and are invalid after 12h of being issued.
 /// They are not refreshable, and are only valid for one usage.
+/// Tokens are only accepted by the endpoints of the `scope` they were requested for.
 ///
 /// # Note:
 ///
-/// Global Rate-Limiting allows bursts with up to 20 requests and replenishes 50 requests per day
+/// Rate-Limiting allows bursts of up to 10 requests per client and replenishes 50 requests per client and day.
+/// Additionally, at most 300 requests per day are allowed across all clients.
+/// How many requests a client has left is returned in the `RateLimit-Remaining` header, the burst size in `RateLimit-Limit`.
+/// Rejected requests include a `Retry-After` header with the seconds until the next request is allowed.
 #[utoipa::path(
     tags=["feedback"],
+    params(TokenQuery),
     responses(
         (status = 201, description = "**Created** a usable token", body= TokenResponse, content_type="application/json"),
-        (status = 429, description = "**Too many requests.** We are rate-limiting everyone's requests, please try again later."),
-        (status = 503, description= "**Service unavailable.** We have not configured a GitHub Access Token. This could be because we are experiencing technical difficulties or intentional. Please try again later."),
+        (status = 429, description = "**Too many requests.** We are rate-limiting requests per client and in total. The `Retry-After` header tells you when to try again.", body = ApiError, content_type = "application/json", example = json!({"error": "Too many requests, please try again in 1728s", "code": "rate_limited"})),
+        (status = 500, description= "**Internal Server Error.** We could not generate a token. Please try again later.", body = ApiError, content_type = "application/json", example = json!({"error": "Failed to generate token, please try again later", "code": "internal_error"})),
+        (status = 503, description= "**Service unavailable.** We have not configured where feedback is delivered to (e.g. a GitHub Access Token). This could be because we are experiencing technical difficulties or intentional. Please try again later.", body = ApiError, content_type = "application/json", example = json!({"error": "Feedback is currently not configured on this server.", "code": "feedback_not_configured"})),
     )
 )]
 #[post("")]
-pub async fn get_token() -> HttpResponse {
-    if !able_to_process_feedback() {
-        return HttpResponse::ServiceUnavailable()
-            .content_type("text/plain")
-            .body("Feedback is currently not configured on this server.");
-    }
-
-    let secret = std::env::var("JWT_KEY").unwrap(); // we checked the ability to process feedback
-    let token = encode(
-        &Header::default(),
-        &Claims::default(),
-        &EncodingKey::from_secret(secret.as_bytes()),
-    );
+pub async fn get_token(Query(args): Query<TokenQuery>) -> HttpResponse {
+    let keys = match configured_keys() {
+        Ok(keys) => keys,
+        Err(e) => return e.into(),
+    };
+    let token = keys.encode(&Claims::new(args.scope));
 
     match token {
         Ok(token) => {
@@ -166,9 +848,514 @@ pub async fn get_token() -> HttpResponse {
         }
         Err(e) => {
             error!(error = ?e, "Failed to generate token");
-            HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Failed to generate token, please try again later")
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Failed to generate token, please try again later",
+            )
+            .into()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::MessageBody;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn status_and_body(resp: HttpResponse) -> (u16, String) {
+        let status = resp.status().as_u16();
+        let body = match resp.into_body().try_into_bytes() {
+            Ok(body) => String::from_utf8(body.to_vec()).unwrap(),
+            Err(_) => panic!("body should be complete"),
+        };
+        (status, body)
+    }
+
+    #[test]
+    fn test_token_is_single_use() {
+        let mut tokens = Vec::new();
+        assert!(admit(&mut tokens, 1, None, 0).is_ok());
+        assert_eq!(
+            admit(&mut tokens, 1, None, 1).unwrap_err(),
+            TokenError::AlreadyUsed
+        );
+        // other tokens are unaffected
+        assert!(admit(&mut tokens, 2, None, 1).is_ok());
+        // once outdated, records are forgotten
+        assert!(admit(&mut tokens, 1, None, TOKEN_MAX_AGE).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_credentials_are_required() {
+        let tokens = RecordedTokens::default();
+        let err = tokens
+            .use_credentials(None, None, TokenScope::Feedback, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, TokenError::Invalid);
+    }
+
+    #[test]
+    fn test_deduplicated_outcome() {
+        let url = Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap();
+        let outcome = |deduplicated| FeedbackOutcome {
+            status: StatusCode::OK,
+            url: Some(url.clone()),
+            deduplicated,
+            deletion_token: None,
+        };
+        let resp = HttpResponse::from(outcome(true));
+        assert_eq!(resp.headers().get("deduplicated").unwrap(), "true");
+        assert_eq!(status_and_body(resp), (200, url.to_string()));
+        let resp = HttpResponse::from(outcome(false));
+        assert!(resp.headers().get("deduplicated").is_none());
+        assert!(resp.headers().get("deletion-token").is_none());
+        let resp = HttpResponse::from(FeedbackOutcome {
+            deletion_token: Some("secret".to_string()),
+            ..outcome(false)
+        });
+        assert_eq!(resp.headers().get("deletion-token").unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_idempotent_retries() {
+        let mut tokens = Vec::new();
+        assert!(admit(&mut tokens, 1, Some("key"), 0).is_ok());
+        // the first request is still running
+        assert_eq!(
+            admit(&mut tokens, 1, Some("key"), 1).unwrap_err(),
+            TokenError::RequestInProgress
+        );
+
+        let url = Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap();
+        tokens[0].outcome = Some(FeedbackOutcome {
+            status: StatusCode::CREATED,
+            url: Some(url.clone()),
+            deduplicated: false,
+            deletion_token: None,
+        });
+        // retries with the same token get the original response
+        let err = admit(&mut tokens, 1, Some("key"), 2).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CREATED);
+        let resp = HttpResponse::from(err);
+        assert_eq!(resp.headers().get("idempotent-replayed").unwrap(), "true");
+        assert_eq!(status_and_body(resp), (201, url.to_string()));
+        // a different key does not allow reusing the token
+        assert_eq!(
+            admit(&mut tokens, 1, Some("other"), 2).unwrap_err(),
+            TokenError::AlreadyUsed
+        );
+        // keys are forgotten with their token
+        assert!(admit(&mut tokens, 3, Some("key"), TOKEN_MAX_AGE).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_deletion_tokens_are_not_replayed() {
+        let tokens = RecordedTokens::default();
+        tokens.admit(1, Some("key")).await.unwrap();
+        let outcome = FeedbackOutcome {
+            status: StatusCode::CREATED,
+            url: Some(Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap()),
+            deduplicated: false,
+            deletion_token: Some("secret".to_string()),
+        };
+        tokens.record_outcome(1, outcome.clone()).await;
+        let err = tokens.admit(1, Some("key")).await.unwrap_err();
+        let expected = FeedbackOutcome {
+            deletion_token: None,
+            ..outcome
+        };
+        assert_eq!(err, TokenError::Replayed(Box::new(expected)));
+        let resp = HttpResponse::from(err);
+        assert!(resp.headers().get("deletion-token").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_challenges_cannot_be_replayed_with_other_tokens() {
+        let tokens = RecordedTokens::default();
+        let challenge = 100;
+        assert!(
+            tokens
+                .admit_with_challenge(1, challenge, None)
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            tokens.admit_with_challenge(2, challenge, None).await,
+            Err(TokenError::AlreadyUsed)
+        );
+        // the second token was not used up by the rejected request
+        assert!(!tokens.is_used(2).await);
+        // failed requests give back both
+        tokens.release(1).await;
+        assert!(!tokens.is_used(challenge).await);
+        assert!(
+            tokens
+                .admit_with_challenge(2, challenge, None)
+                .await
+                .is_ok()
+        );
+        assert!(tokens.is_used(2).await);
+        assert!(tokens.is_used(challenge).await);
+    }
+
+    #[test]
+    fn test_idempotency_keys_are_scoped_to_the_token() {
+        let mut tokens = Vec::new();
+        assert!(admit(&mut tokens, 1, Some("key"), 0).is_ok());
+        // another token reusing the key is neither blocked by the running request ...
+        assert!(admit(&mut tokens, 2, Some("key"), 1).is_ok());
+        tokens[0].outcome = Some(FeedbackOutcome {
+            status: StatusCode::CREATED,
+            url: Some(Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap()),
+            deduplicated: false,
+            deletion_token: Some("secret".to_string()),
+        });
+        // ... nor gets its response
+        assert!(admit(&mut tokens, 3, Some("key"), 2).is_ok());
+        assert_eq!(tokens.len(), 3);
+        assert!(tokens[1..].iter().all(|t| t.outcome.is_none()));
+    }
+
+    fn is_invalid_signature(error: &jsonwebtoken::errors::Error) -> bool {
+        matches!(
+            error.kind(),
+            jsonwebtoken::errors::ErrorKind::InvalidSignature
+        )
+    }
+
+    #[test]
+    fn test_tokens_of_previous_keys_validate() {
+        let claims = Claims::default();
+        let old = JwtKeys::parse("old-secret").unwrap();
+        let rotated = JwtKeys::parse("new-secret, old-secret").unwrap();
+
+        let token = old.encode(&claims).unwrap();
+        let (decoded, primary) = rotated.decode(&token).unwrap();
+        assert_eq!(decoded.kid, claims.kid);
+        assert!(!primary);
+
+        let token = rotated.encode(&claims).unwrap();
+        assert!(rotated.decode(&token).unwrap().1);
+        // instances which were not redeployed yet do not know the new key
+        assert!(is_invalid_signature(&old.decode(&token).unwrap_err()));
+
+        // tokens issued before keys had ids
+        let legacy = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"old-secret"),
+        )
+        .unwrap();
+        assert!(!rotated.decode(&legacy).unwrap().1);
+        // issued while the key id was a plain hash of the secret
+        let outdated = encode(
+            &Header {
+                kid: Some("0123456789abcdef".to_string()),
+                ..Header::default()
+            },
+            &claims,
+            &EncodingKey::from_secret(b"old-secret"),
+        )
+        .unwrap();
+        assert!(!rotated.decode(&outdated).unwrap().1);
+    }
+
+    #[test]
+    fn test_tokens_of_unknown_keys_are_rejected() {
+        let claims = Claims::default();
+        let rotated = JwtKeys::parse("new-secret,old-secret").unwrap();
+        let unknown = JwtKeys::parse("unknown-secret").unwrap();
+
+        let token = unknown.encode(&claims).unwrap();
+        assert!(is_invalid_signature(&rotated.decode(&token).unwrap_err()));
+        let legacy = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"unknown-secret"),
+        )
+        .unwrap();
+        assert!(is_invalid_signature(&rotated.decode(&legacy).unwrap_err()));
+        // naming one of our keys does not help
+        let forged = encode(
+            &Header {
+                kid: Some(rotated.keys[1].id.clone()),
+                ..Header::default()
+            },
+            &claims,
+            &EncodingKey::from_secret(b"unknown-secret"),
+        )
+        .unwrap();
+        assert!(is_invalid_signature(&rotated.decode(&forged).unwrap_err()));
+
+        // expired tokens stay expired, regardless of the key
+        let now = chrono::Utc::now().timestamp();
+        let expired = Claims {
+            exp: now - TOKEN_MAX_AGE,
+            iat: now - 2 * TOKEN_MAX_AGE,
+            nbf: now - 2 * TOKEN_MAX_AGE,
+            kid: 1,
+            scope: None,
+        };
+        let token = JwtKeys::parse("old-secret")
+            .unwrap()
+            .encode(&expired)
+            .unwrap();
+        assert!(matches!(
+            rotated.decode(&token).unwrap_err().kind(),
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature
+        ));
+    }
+
+    #[test]
+    fn test_key_formats() {
+        let listed = JwtKeys::parse("new-secret,old-secret").unwrap();
+        assert_eq!(
+            JwtKeys::parse(r#"["new-secret", "old-secret"]"#),
+            Some(listed.clone())
+        );
+        assert_eq!(listed.keys[0].secret, "new-secret");
+        assert_ne!(listed.keys[0].id, listed.keys[1].id);
+        assert!(!listed.keys[0].id.contains("secret"));
+        // not a fingerprint of the secret
+        let fingerprint = format!("{:x}", <Sha256 as sha2::Digest>::digest(b"new-secret"));
+        assert!(!fingerprint.starts_with(&listed.keys[0].id));
+        assert_eq!(
+            JwtKeys::parse("new-secret").unwrap().keys[0].id,
+            listed.keys[0].id
+        );
+        // keys containing commas can only be configured as JSON
+        let json = JwtKeys::parse(r#"["with,comma"]"#).unwrap();
+        assert_eq!(json.keys.len(), 1);
+        assert_eq!(json.keys[0].secret, "with,comma");
+
+        assert_eq!(JwtKeys::parse(""), None);
+        assert_eq!(JwtKeys::parse(" , "), None);
+        assert_eq!(JwtKeys::parse("[]"), None);
+        assert_eq!(JwtKeys::parse("[\"unterminated\""), None);
+    }
+
+    #[test]
+    fn test_scopes_of_endpoints() {
+        use TokenScope::{Attachment, EditProposal, Feedback};
+        // (scope of the token, scope required by the endpoint, accepted)
+        let combinations = [
+            // send_feedback
+            (Feedback, Feedback, true),
+            (Attachment, Feedback, false),
+            (EditProposal, Feedback, false),
+            // send_feedback of the edit_proposal category
+            (Feedback, EditProposal, false),
+            (Attachment, EditProposal, false),
+            (EditProposal, EditProposal, true),
+            // attach_image and the attachment_token of send_feedback
+            (Feedback, Attachment, false),
+            (Attachment, Attachment, true),
+            (EditProposal, Attachment, false),
+            // propose_edits
+            (Feedback, EditProposal, false),
+            (Attachment, EditProposal, false),
+            (EditProposal, EditProposal, true),
+        ];
+        for (scope, required, accepted) in combinations {
+            for accept_unscoped in [true, false] {
+                let result = check_scope(Some(scope), required, accept_unscoped);
+                let expected = if accepted {
+                    Ok(())
+                } else {
+                    Err(TokenError::WrongScope {
+                        scope: Some(scope),
+                        required,
+                    })
+                };
+                assert_eq!(result, expected, "{scope:?} for {required:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unscoped_tokens() {
+        use TokenScope::{Attachment, EditProposal, Feedback};
+        // during the deprecation window, they are feedback tokens
+        assert!(check_scope(None, Feedback, true).is_ok());
+        assert!(check_scope(None, Attachment, true).is_err());
+        assert!(check_scope(None, EditProposal, true).is_err());
+        for required in [Feedback, Attachment, EditProposal] {
+            assert_eq!(
+                check_scope(None, required, false),
+                Err(TokenError::WrongScope {
+                    scope: None,
+                    required
+                })
+            );
+        }
+    }
+
+    /// The claims of the token, without validating it
+    fn claims_of(token: &str) -> serde_json::Value {
+        let payload = token.split('.').nth(1).unwrap();
+        let payload =
+            base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, payload)
+                .unwrap();
+        serde_json::from_slice(&payload).unwrap()
+    }
+
+    #[test]
+    fn test_scope_is_a_claim() {
+        let keys = JwtKeys::parse("secret").unwrap();
+        let token = keys.encode(&Claims::new(TokenScope::EditProposal)).unwrap();
+        assert_eq!(claims_of(&token)["scope"], "edit_proposal");
+        let token = keys.encode(&Claims::default()).unwrap();
+        assert_eq!(claims_of(&token)["scope"], "feedback");
+
+        // tokens issued before tokens had scopes
+        let legacy = serde_json::json!({"exp": 2, "iat": 0, "nbf": 1, "kid": 1});
+        let claims: Claims = serde_json::from_value(legacy).unwrap();
+        assert_eq!(claims.scope, None);
+    }
+
+    #[test]
+    fn test_validation() {
+        let keys = JwtKeys::parse("new-secret,old-secret").unwrap();
+        let claims = Claims::new(TokenScope::Attachment);
+        let token = keys.encode(&claims).unwrap();
+        let expected = ValidatedToken {
+            kid: claims.kid,
+            expires_at: claims.exp,
+        };
+        assert_eq!(
+            keys.validate(&token, TokenScope::Attachment, false),
+            Ok((expected, true))
+        );
+        assert_eq!(
+            keys.validate(&token, TokenScope::EditProposal, true),
+            Err(TokenError::WrongScope {
+                scope: Some(TokenScope::Attachment),
+                required: TokenScope::EditProposal
+            })
+        );
+        let unknown = JwtKeys::parse("unknown-secret").unwrap();
+        let token = unknown.encode(&claims).unwrap();
+        assert_eq!(
+            keys.validate(&token, TokenScope::Attachment, true),
+            Err(TokenError::Invalid)
+        );
+        assert_eq!(
+            keys.validate("not a token", TokenScope::Feedback, true),
+            Err(TokenError::Invalid)
+        );
+        let now = chrono::Utc::now().timestamp();
+        let expired = Claims {
+            exp: now - TOKEN_MAX_AGE,
+            iat: now - 2 * TOKEN_MAX_AGE,
+            nbf: now - 2 * TOKEN_MAX_AGE,
+            ..Claims::default()
+        };
+        let token = keys.encode(&expired).unwrap();
+        assert_eq!(
+            keys.validate(&token, TokenScope::Feedback, true),
+            Err(TokenError::Expired)
+        );
+        let immature: jsonwebtoken::errors::Error =
+            jsonwebtoken::errors::ErrorKind::ImmatureSignature.into();
+        assert_eq!(TokenError::from(immature), TokenError::Immature);
+    }
+
+    #[test]
+    fn test_error_responses() {
+        let cases = [
+            (TokenError::NotConfigured, 503, "feedback_not_configured"),
+            (TokenError::Immature, 403, "token_not_yet_valid"),
+            (TokenError::Expired, 403, "token_expired"),
+            (TokenError::Invalid, 403, "invalid_token"),
+            (TokenError::AlreadyUsed, 403, "token_already_used"),
+            (TokenError::Blocked, 403, "forbidden"),
+            (
+                TokenError::WrongScope {
+                    scope: None,
+                    required: TokenScope::Attachment,
+                },
+                403,
+                "wrong_token_scope",
+            ),
+            (TokenError::ChallengeRequired, 403, "challenge_required"),
+            (TokenError::RequestInProgress, 409, "request_in_progress"),
+        ];
+        for (err, status, code) in cases {
+            assert_eq!(err.status_code().as_u16(), status, "{err}");
+            let resp = HttpResponse::from(err);
+            assert_eq!(
+                resp.headers().get("content-type").unwrap(),
+                "application/json"
+            );
+            let (actual_status, body) = status_and_body(resp);
+            assert_eq!(actual_status, status);
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["code"], code);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_without_a_database_tokens_are_forgotten() {
+        let tokens = RecordedTokens::default();
+        assert!(tokens.admit(1, None).await.is_ok());
+        assert!(tokens.is_used(1).await);
+        let tokens = RecordedTokens::default();
+        assert!(!tokens.is_used(1).await);
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::setup::tests::PostgresTestContainer;
+
+    #[actix_web::test]
+    async fn test_used_tokens_survive_a_restart() {
+        let pg = PostgresTestContainer::new().await;
+        let tokens = RecordedTokens::persistent(pg.pool.clone());
+        assert!(tokens.admit(1, None).await.is_ok());
+        // kids are u64 and have to survive the round trip through a signed column
+        assert!(tokens.admit(u64::MAX, None).await.is_ok());
+
+        // restart => nothing in memory survives
+        let tokens = RecordedTokens::persistent(pg.pool.clone());
+        assert!(tokens.is_used(1).await);
+        assert!(tokens.is_used(u64::MAX).await);
+        assert!(!tokens.is_used(2).await);
+        let err = tokens.admit(1, None).await.unwrap_err();
+        assert_eq!(err, TokenError::AlreadyUsed);
+        // a rejected token is not admitted in memory either
+        assert!(!tokens.records.lock().await.iter().any(|t| t.kid == 1));
+
+        // released tokens can be used again, also by other instances
+        let other = RecordedTokens::persistent(pg.pool.clone());
+        assert!(other.admit(2, None).await.is_ok());
+        other.release(2).await;
+        assert!(tokens.admit(2, None).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_expired_tokens_are_pruned() {
+        let pg = PostgresTestContainer::new().await;
+        let expired = UsedToken {
+            kid: 1,
+            expires_at: chrono::Utc::now() - chrono::Duration::hours(1),
+        };
+        assert!(expired.insert(&pg.pool).await.unwrap());
+        assert!(!UsedToken::exists(&pg.pool, 1).await.unwrap());
+
+        let tokens = RecordedTokens::persistent(pg.pool.clone());
+        assert!(tokens.admit(2, None).await.is_ok());
+        let remaining = sqlx::query_scalar::<_, i64>("SELECT kid FROM feedback_used_tokens")
+            .fetch_all(&pg.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![2]);
+    }
+}
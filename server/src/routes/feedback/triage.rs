@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// What the feedback is about
+#[derive(
+    Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackCategory {
+    Bug,
+    /// Something about a location (e.g. its name or coordinates) is wrong
+    DataError,
+    FeatureRequest,
+    Navigation,
+    /// A structured correction of a location, see `edit_proposal`
+    EditProposal,
+    #[default]
+    Other,
+}
+impl FeedbackCategory {
+    const ALL: [FeedbackCategory; 6] = [
+        FeedbackCategory::Bug,
+        FeedbackCategory::DataError,
+        FeedbackCategory::FeatureRequest,
+        FeedbackCategory::Navigation,
+        FeedbackCategory::EditProposal,
+        FeedbackCategory::Other,
+    ];
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            FeedbackCategory::Bug => "bug",
+            FeedbackCategory::DataError => "data_error",
+            FeedbackCategory::FeatureRequest => "feature_request",
+            FeedbackCategory::Navigation => "navigation",
+            FeedbackCategory::EditProposal => "edit_proposal",
+            FeedbackCategory::Other => "other",
+        }
+    }
+}
+impl std::fmt::Display for FeedbackCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl FromStr for FeedbackCategory {
+    type Err = ();
+
+    /// Also accepts the categories of older clients
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(category) = FeedbackCategory::ALL.into_iter().find(|c| c.as_str() == s) {
+            return Ok(category);
+        }
+        match s {
+            "feature" => Ok(FeedbackCategory::FeatureRequest),
+            "entry" => Ok(FeedbackCategory::DataError),
+            "search" | "general" => Ok(FeedbackCategory::Other),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How issues of a category are triaged on GitHub
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Triage {
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// GitHub usernames
+    #[serde(default)]
+    pub assignees: Vec<String>,
+}
+
+/// Our data pipeline picks up issues with this label
+pub const EDIT_PROPOSAL_LABEL: &str = "edit-proposal";
+
+/// Triage per category, configurable via `FEEDBACK_TRIAGE`
+///
+/// Categories which are not configured keep their default.
+static TRIAGE: LazyLock<HashMap<FeedbackCategory, Triage>> = LazyLock::new(|| {
+    let mut triage = default_triage();
+    if let Ok(raw) = std::env::var("FEEDBACK_TRIAGE") {
+        match serde_json::from_str::<HashMap<FeedbackCategory, Triage>>(&raw) {
+            Ok(configured) => triage.extend(configured),
+            Err(e) => {
+                warn!(error = ?e, "FEEDBACK_TRIAGE is not a valid mapping of categories to labels and assignees, using the default");
+            }
+        }
+    }
+    triage
+});
+
+/// Each category is labeled as itself, wrong data is assigned to whoever also reviews proposed edits
+fn default_triage() -> HashMap<FeedbackCategory, Triage> {
+    FeedbackCategory::ALL
+        .into_iter()
+        .map(|category| {
+            let assignees = match category {
+                FeedbackCategory::DataError | FeedbackCategory::EditProposal => {
+                    vec!["CommanderStorm".to_string()]
+                }
+                _ => vec![],
+            };
+            let label = match category {
+                FeedbackCategory::EditProposal => EDIT_PROPOSAL_LABEL.to_string(),
+                _ => category.to_string(),
+            };
+            let triage = Triage {
+                labels: vec![label],
+                assignees,
+            };
+            (category, triage)
+        })
+        .collect()
+}
+
+pub fn triage(category: FeedbackCategory) -> Triage {
+    TRIAGE.get(&category).cloned().unwrap_or_default()
+}
+
+/// Whether this looks like the key of a location, e.g. `5602.EG.001` or `mi`
+///
+/// Whether it exists is left to whoever triages the issue.
+pub fn is_location_key(key: &str) -> bool {
+    (1..=64).contains(&key.len())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_category() {
+        for category in FeedbackCategory::ALL {
+            assert_eq!(category.to_string().parse(), Ok(category));
+        }
+        // categories of older clients
+        assert_eq!("entry".parse(), Ok(FeedbackCategory::DataError));
+        assert_eq!("feature".parse(), Ok(FeedbackCategory::FeatureRequest));
+        assert_eq!("general".parse(), Ok(FeedbackCategory::Other));
+        assert_eq!("Bug".parse::<FeedbackCategory>(), Err(()));
+        assert_eq!("".parse::<FeedbackCategory>(), Err(()));
+    }
+
+    #[test]
+    fn test_configured_triage_is_parsed() {
+        let configured = serde_json::from_str::<HashMap<FeedbackCategory, Triage>>(
+            r#"{"bug": {"labels": ["bug", "webclient"], "assignees": ["octocat"]}, "navigation": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            configured[&FeedbackCategory::Bug],
+            Triage {
+                labels: vec!["bug".to_string(), "webclient".to_string()],
+                assignees: vec!["octocat".to_string()],
+            }
+        );
+        assert_eq!(configured[&FeedbackCategory::Navigation], Triage::default());
+        assert!(
+            serde_json::from_str::<HashMap<FeedbackCategory, Triage>>(r#"{"typo": {}}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_default_triage() {
+        let triage = default_triage();
+        assert_eq!(triage.len(), FeedbackCategory::ALL.len());
+        assert_eq!(triage[&FeedbackCategory::Bug].labels, vec!["bug"]);
+        assert_eq!(
+            triage[&FeedbackCategory::DataError].assignees,
+            vec!["CommanderStorm"]
+        );
+        assert_eq!(
+            triage[&FeedbackCategory::EditProposal].labels,
+            vec!["edit-proposal"]
+        );
+    }
+
+    #[test]
+    fn test_location_key() {
+        assert!(is_location_key("5602.EG.001"));
+        assert!(is_location_key("mi"));
+        assert!(is_location_key("garching-hochbrueck"));
+        assert!(!is_location_key(""));
+        assert!(!is_location_key("5602.EG.001 "));
+        assert!(!is_location_key("../../etc/passwd"));
+        assert!(!is_location_key(&"a".repeat(65)));
+    }
+}
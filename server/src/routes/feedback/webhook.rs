@@ -0,0 +1,275 @@
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{error, warn};
+use url::Url;
+
+use super::sink::Feedback;
+use super::triage::FeedbackCategory;
+use crate::routes::admin::constant_time_eq;
+
+/// Header carrying the [`sign`]ature of the body
+pub const SIGNATURE_HEADER: &str = "X-Navigatum-Signature-256";
+/// How long the receiver has to answer each attempt
+const TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How many characters of the description are included
+const MAX_EXCERPT_LEN: usize = 280;
+
+/// The webhook configured via `FEEDBACK_WEBHOOK_URL` and `FEEDBACK_WEBHOOK_SECRET`, if any
+static WEBHOOK: LazyLock<Option<Webhook>> = LazyLock::new(Webhook::from_env);
+
+/// What the receiver is sent, once an issue was opened for feedback
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WebhookPayload {
+    pub category: FeedbackCategory,
+    /// The link to the issue (or the reference, if feedback is not delivered to GitHub)
+    pub url: Url,
+    /// The start of the description
+    pub excerpt: String,
+}
+
+impl WebhookPayload {
+    pub(super) fn new(feedback: &Feedback<'_>, url: &Url) -> Self {
+        let mut excerpt = feedback
+            .description
+            .chars()
+            .take(MAX_EXCERPT_LEN)
+            .collect::<String>();
+        if feedback.description.chars().nth(MAX_EXCERPT_LEN).is_some() {
+            excerpt.push('…');
+        }
+        Self {
+            category: feedback.category,
+            url: url.clone(),
+            excerpt,
+        }
+    }
+}
+
+/// Notifies a chat (e.g. via a Matrix or Slack bridge) about new feedback
+///
+/// The body is signed via HMAC-SHA256, so that the receiver can check that we sent it (see [`verify_signature`]).
+pub struct Webhook {
+    url: Url,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //fields purposely omitted
+        f.debug_struct("Webhook").field("url", &self.url).finish()
+    }
+}
+
+impl Webhook {
+    fn new(url: Url, secret: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .expect("the request client builder is correctly configured");
+        Self {
+            url,
+            secret,
+            client,
+        }
+    }
+
+    fn from_env() -> Option<Self> {
+        let url = std::env::var("FEEDBACK_WEBHOOK_URL").ok()?;
+        let url = match Url::parse(url.trim()) {
+            Ok(url) => url,
+            Err(e) => {
+                error!(error = ?e, "FEEDBACK_WEBHOOK_URL is not a valid url, no webhook is sent");
+                return None;
+            }
+        };
+        match std::env::var("FEEDBACK_WEBHOOK_SECRET") {
+            Ok(secret) if !secret.trim().is_empty() => {
+                Some(Self::new(url, secret.trim().to_string()))
+            }
+            _ => {
+                error!("FEEDBACK_WEBHOOK_SECRET has to be set for the webhook, no webhook is sent");
+                None
+            }
+        }
+    }
+
+    /// Posts the payload, retrying once if that fails
+    async fn deliver(&self, payload: &WebhookPayload) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(payload)?;
+        let signature = sign(self.secret.as_bytes(), &body);
+        let attempt = || {
+            let request = self
+                .client
+                .post(self.url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone());
+            async move { request.send().await?.error_for_status() }
+        };
+        if let Err(e) = attempt().await {
+            warn!(error = ?e, "could not deliver the webhook, retrying");
+            tokio::time::sleep(RETRY_DELAY).await;
+            attempt().await?;
+        }
+        Ok(())
+    }
+}
+
+/// The value of the [`SIGNATURE_HEADER`]: `sha256=` followed by the hex-encoded HMAC-SHA256 of the `body`
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Whether the `signature` (the value of the [`SIGNATURE_HEADER`]) proves that the `body` was sent by someone knowing the `secret`
+///
+/// Receivers have to check this against the raw body, before parsing it.
+pub fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    constant_time_eq(&sign(secret, body), signature.trim())
+}
+
+/// Announces the feedback in the background, if a webhook is configured
+///
+/// Never delays the response, failures are only logged.
+pub(super) fn notify(feedback: &Feedback<'_>, url: &Url) {
+    send(WebhookPayload::new(feedback, url));
+}
+
+/// Like [`notify`], for a payload prepared earlier (e.g. before the feedback was queued)
+pub(super) fn send(payload: WebhookPayload) {
+    let Some(webhook) = &*WEBHOOK else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = webhook.deliver(&payload).await {
+            error!(error = ?e, url = %payload.url, "could not deliver the webhook");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use actix_web::{HttpRequest, HttpResponse, web};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::routes::feedback::sink::tests::feedback;
+    use crate::setup::tests::mock_server;
+
+    /// A receiver, which fails the first `failures` requests
+    #[derive(Default)]
+    struct MockReceiver {
+        failures: AtomicUsize,
+        received: Mutex<Vec<(web::Bytes, String)>>,
+    }
+
+    async fn receive(
+        mock: web::Data<MockReceiver>,
+        req: HttpRequest,
+        body: web::Bytes,
+    ) -> HttpResponse {
+        let signature = req
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        mock.received.lock().unwrap().push((body, signature));
+        let failing = mock
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            HttpResponse::InternalServerError().finish()
+        } else {
+            HttpResponse::NoContent().finish()
+        }
+    }
+
+    async fn mock_receiver(failures: usize) -> (Webhook, web::Data<MockReceiver>) {
+        let mock = web::Data::new(MockReceiver {
+            failures: AtomicUsize::new(failures),
+            ..Default::default()
+        });
+        let mock_data = mock.clone();
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(mock_data.clone())
+                .route("/hook", web::post().to(receive));
+        });
+        let url = Url::parse(&format!("http://{addr}/hook")).unwrap();
+        (Webhook::new(url, "webhook-secret".to_string()), mock)
+    }
+
+    fn payload() -> WebhookPayload {
+        let url = Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap();
+        WebhookPayload::new(&feedback(), &url)
+    }
+
+    #[test]
+    fn test_signature() {
+        let body = br#"{"category":"bug"}"#;
+        let signature = sign(b"webhook-secret", body);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert!(verify_signature(b"webhook-secret", body, &signature));
+        assert!(!verify_signature(b"other-secret", body, &signature));
+        assert!(!verify_signature(
+            b"webhook-secret",
+            br#"{"category":"wrong_data"}"#,
+            &signature
+        ));
+        assert!(!verify_signature(b"webhook-secret", body, ""));
+    }
+
+    #[test]
+    fn test_excerpt_is_truncated() {
+        let mut feedback = feedback();
+        assert_eq!(payload().excerpt, feedback.description);
+        feedback.description = "ä".repeat(MAX_EXCERPT_LEN + 1);
+        let url = Url::parse("https://github.com/TUM-Dev/navigatum/issues/9").unwrap();
+        let excerpt = WebhookPayload::new(&feedback, &url).excerpt;
+        assert_eq!(excerpt.chars().count(), MAX_EXCERPT_LEN + 1);
+        assert!(excerpt.ends_with("ä…"));
+    }
+
+    #[actix_web::test]
+    async fn test_delivered_signed() {
+        let (webhook, mock) = mock_receiver(0).await;
+        webhook.deliver(&payload()).await.unwrap();
+        let received = mock.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (body, signature) = &received[0];
+        assert!(verify_signature(b"webhook-secret", body, signature));
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "category": "bug",
+                "url": "https://github.com/TUM-Dev/navigatum/issues/9",
+                "excerpt": "A clear description  \nwhat happened",
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_retried_once() {
+        let (webhook, mock) = mock_receiver(1).await;
+        webhook.deliver(&payload()).await.unwrap();
+        assert_eq!(mock.received.lock().unwrap().len(), 2);
+
+        let (webhook, mock) = mock_receiver(2).await;
+        assert!(webhook.deliver(&payload()).await.is_err());
+        assert_eq!(mock.received.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,166 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, delete, web};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use serde::Deserialize;
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::AppData;
+use crate::db::feedback::DeletionToken;
+use crate::error::ApiError;
+use crate::external::github::GitHub;
+use crate::routes::admin::constant_time_eq;
+
+/// Header the deletion token is handed out in and expected in
+pub const DELETION_TOKEN: &str = "Deletion-Token";
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct WithdrawPathParams {
+    /// Number of the GitHub issue the feedback was submitted as
+    #[param(example = 9)]
+    issue_number: u64,
+}
+
+/// Hex-encoded SHA-256 of the token, which is all we store of it
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Whether the `token` is the one the `stored_hash` was created from
+fn token_matches(token: &str, stored_hash: &str) -> bool {
+    constant_time_eq(&hash_token(token), stored_hash)
+}
+
+/// Creates the token which allows the reporter to withdraw the feedback of the issue
+///
+/// If the hash cannot be stored, the feedback was still delivered => no token is handed out instead of failing.
+pub(super) async fn issue_token(pool: &PgPool, issue_number: u64) -> Option<String> {
+    let token = BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+    match DeletionToken::insert(pool, issue_number, &hash_token(&token)).await {
+        Ok(()) => Some(token),
+        Err(e) => {
+            error!(error = ?e, issue_number, "could not store the deletion token");
+            None
+        }
+    }
+}
+
+fn invalid_token() -> ApiError {
+    ApiError::new(
+        StatusCode::FORBIDDEN,
+        "invalid_deletion_token",
+        "The deletion token does not match this issue",
+    )
+    .with_parameter(DELETION_TOKEN)
+}
+
+/// Withdraw submitted feedback
+///
+/// Redacts the title and body of the GitHub issue the feedback was submitted as and closes it with a comment stating that the reporter withdrew it.
+/// Requires the `Deletion-Token` returned when [submitting the feedback](#tag/feedback/operation/send_feedback).
+///
+/// Withdrawing an already withdrawn issue succeeds without changing anything.
+/// Comments added to the issue (e.g. by maintainers) are not removed.
+#[utoipa::path(
+    tags=["feedback"],
+    params(
+        WithdrawPathParams,
+        ("Deletion-Token" = String, Header, description = "The token returned when submitting the feedback"),
+    ),
+    responses(
+        (status = 204, description = "The feedback was **withdrawn**. The issue is redacted and closed."),
+        (status = 403, description = "**Forbidden.** No deletion token was supplied or it does not belong to this issue", body = ApiError, content_type = "application/json", example = json!({"error": "The deletion token does not match this issue", "code": "invalid_deletion_token"})),
+        (status = 500, description = "**Internal Server Error.** We could not redact the issue. Please try again later", body = ApiError, content_type = "application/json", example = json!({"error": "Failed to withdraw the feedback, please try again later", "code": "github_error"})),
+    )
+)]
+#[delete("/api/feedback/{issue_number}")]
+pub async fn withdraw_feedback(
+    req: HttpRequest,
+    params: web::Path<WithdrawPathParams>,
+    data: web::Data<AppData>,
+) -> HttpResponse {
+    let issue_number = params.issue_number;
+    let Some(token) = req
+        .headers()
+        .get(DELETION_TOKEN)
+        .and_then(|token| token.to_str().ok())
+    else {
+        return invalid_token().into();
+    };
+    let stored = match DeletionToken::get(&data.pool, issue_number).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!(error = ?e, issue_number, "could not get the deletion token");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Failed to withdraw the feedback, please try again later",
+            )
+            .into();
+        }
+    };
+    // unknown issues are indistinguishable from wrong tokens, so that they cannot be enumerated
+    let Some(stored) = stored.filter(|stored| token_matches(token.trim(), &stored.token_hash))
+    else {
+        return invalid_token().into();
+    };
+    if stored.redacted_at.is_some() {
+        return HttpResponse::NoContent().finish();
+    }
+
+    if let Err(e) = GitHub::default().withdraw_issue(issue_number).await {
+        error!(error = ?e, issue_number, "could not withdraw the issue");
+        return ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "github_error",
+            "Failed to withdraw the feedback, please try again later",
+        )
+        .into();
+    }
+    info!(issue_number, "feedback was withdrawn by the reporter");
+    // the issue is already redacted => redacting it again on a retry is harmless
+    if let Err(e) = DeletionToken::mark_redacted(&data.pool, issue_number).await {
+        error!(error = ?e, issue_number, "could not record that the issue was withdrawn");
+    }
+    HttpResponse::NoContent().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_hash_comparison() {
+        let token = "c2VjcmV0LXRva2VuLWZvci10aGUtaXNzdWU";
+        let stored = hash_token(token);
+        assert_eq!(stored.len(), 64);
+        assert!(stored.chars().all(|c| c.is_ascii_hexdigit()));
+        // the token itself is not stored
+        assert!(!stored.contains(token));
+        assert!(token_matches(token, &stored));
+        assert!(!token_matches(
+            "c2VjcmV0LXRva2VuLWZvci10aGUtaXNzdWF",
+            &stored
+        ));
+        assert!(!token_matches("", &stored));
+        // the hash itself is no valid token
+        assert!(!token_matches(&stored, &stored));
+    }
+
+    #[test]
+    fn test_known_hash() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -52,7 +52,7 @@ fn draw_pin(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>) {
 fn wrap_image_in_response(img: &image::RgbaImage) -> LimitedVec<u8> {
     let mut w = Cursor::new(Vec::new());
     img.write_to(&mut w, image::ImageFormat::Png).unwrap();
-    LimitedVec(w.into_inner())
+    LimitedVec::from(w.into_inner())
 }
 const WHITE_PIXEL: Rgba<u8> = Rgba([255, 255, 255, 255]);
 
@@ -93,7 +93,7 @@ fn load_default_image() -> LimitedVec<u8> {
     // encode the image as PNG
     let mut w = Cursor::new(Vec::new());
     img.write_to(&mut w, image::ImageFormat::Png).unwrap();
-    LimitedVec(w.into_inner())
+    LimitedVec::from(w.into_inner())
 }
 
 #[tracing::instrument(skip(pool))]
@@ -194,5 +194,5 @@ pub async fn maps_handler(
             CacheDirective::MaxAge(2 * 24 * 60 * 60), // valid for 2d
             CacheDirective::Public,
         ]))
-        .body(img.0)
+        .body(img.into_inner())
 }
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use prometheus::{IntCounterVec, Opts, Registry};
+use tracing::error;
+
+use super::route::Coordinate;
+
+/// How many keys are cached, if `ROUTE_COORDINATE_CACHE_CAPACITY` is not set
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// What we know about a key to route to/from it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct CachedCoordinate {
+    pub(super) coordinate: Coordinate,
+    /// Whether the type of the location makes sense as the start or destination of a route
+    pub(super) routable: bool,
+}
+
+/// Cache for the coordinates of our keys, consulted before asking the database while routing
+///
+/// Coordinates only change when the data is re-imported, which invalidates the whole cache.
+/// Once `capacity` keys are cached, further keys are looked up each time.
+#[derive(Clone, Debug)]
+pub struct CoordinateCache {
+    entries: Arc<RwLock<HashMap<String, CachedCoordinate>>>,
+    /// Incremented on each invalidation, so that lookups racing an invalidation don't store stale data
+    generation: Arc<AtomicU64>,
+    capacity: usize,
+    /// Looked up keys, by whether they were cached
+    lookups: IntCounterVec,
+}
+impl Default for CoordinateCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+impl CoordinateCache {
+    fn new(capacity: usize) -> Self {
+        let lookups = IntCounterVec::new(
+            Opts::new(
+                "route_coordinate_cache_lookups_total",
+                "Keys looked up for routing, by whether they were cached",
+            )
+            .namespace("navigatum_api"),
+            &["result"],
+        )
+        .expect("the metric options are valid");
+        Self {
+            entries: Arc::default(),
+            generation: Arc::default(),
+            capacity,
+            lookups,
+        }
+    }
+    /// Capacity configurable via `ROUTE_COORDINATE_CACHE_CAPACITY`, `0` disables caching
+    pub fn from_env() -> Self {
+        let Ok(raw) = std::env::var("ROUTE_COORDINATE_CACHE_CAPACITY") else {
+            return Self::default();
+        };
+        match raw.trim().parse() {
+            Ok(capacity) => Self::new(capacity),
+            Err(e) => {
+                error!(
+                    error = ?e,
+                    %raw,
+                    "ROUTE_COORDINATE_CACHE_CAPACITY is not a valid number of keys, using the default"
+                );
+                Self::default()
+            }
+        }
+    }
+    /// Exposes the hit rate on `/api/metrics`
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.lookups.clone()))
+    }
+    /// The cached `keys` and the ones which have to be looked up
+    ///
+    /// Pass the returned generation to [`Self::store`] once the missing keys were looked up.
+    pub(super) fn lookup(
+        &self,
+        keys: &[String],
+    ) -> (HashMap<String, CachedCoordinate>, Vec<String>, u64) {
+        let mut cached = HashMap::new();
+        let mut missing = Vec::new();
+        let entries = self.entries.read().expect("lock is not poisoned");
+        let generation = self.generation.load(Ordering::Acquire);
+        for key in keys {
+            match entries.get(key) {
+                Some(entry) => {
+                    cached.insert(key.clone(), *entry);
+                }
+                None => missing.push(key.clone()),
+            }
+        }
+        self.lookups
+            .with_label_values(&["hit"])
+            .inc_by(cached.len() as u64);
+        self.lookups
+            .with_label_values(&["miss"])
+            .inc_by(missing.len() as u64);
+        (cached, missing, generation)
+    }
+    /// Caches looked up keys, unless the cache was invalidated since the `generation` of the lookup
+    pub(super) fn store(&self, generation: u64, fetched: &HashMap<String, CachedCoordinate>) {
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+        for (key, entry) in fetched {
+            if entries.len() >= self.capacity {
+                break;
+            }
+            entries.insert(key.clone(), *entry);
+        }
+    }
+    /// Forgets all coordinates, e.g. because the data was re-imported
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn entry(lat: f64) -> CachedCoordinate {
+        CachedCoordinate {
+            coordinate: Coordinate { lat, lon: 11.6 },
+            routable: true,
+        }
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_lookup_store_invalidate() {
+        let cache = CoordinateCache::default();
+        let (cached, missing, generation) = cache.lookup(&keys(&["mi", "5602"]));
+        assert!(cached.is_empty());
+        assert_eq!(missing, keys(&["mi", "5602"]));
+        cache.store(
+            generation,
+            &HashMap::from([("mi".to_string(), entry(48.2))]),
+        );
+
+        let (cached, missing, generation) = cache.lookup(&keys(&["mi", "5602"]));
+        assert_eq!(cached, HashMap::from([("mi".to_string(), entry(48.2))]));
+        assert_eq!(missing, keys(&["5602"]));
+        assert_eq!(cache.lookups.with_label_values(&["hit"]).get(), 1);
+        assert_eq!(cache.lookups.with_label_values(&["miss"]).get(), 3);
+
+        // a lookup racing the re-import must not store the outdated coordinate
+        cache.invalidate_all();
+        cache.store(
+            generation,
+            &HashMap::from([("5602".to_string(), entry(48.3))]),
+        );
+        let (cached, missing, _) = cache.lookup(&keys(&["mi", "5602"]));
+        assert!(cached.is_empty());
+        assert_eq!(missing, keys(&["mi", "5602"]));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let cache = CoordinateCache::new(1);
+        let (_, _, generation) = cache.lookup(&[]);
+        cache.store(
+            generation,
+            &HashMap::from([
+                ("mi".to_string(), entry(48.2)),
+                ("5602".to_string(), entry(48.3)),
+            ]),
+        );
+        assert_eq!(cache.entries.read().unwrap().len(), 1);
+
+        let disabled = CoordinateCache::new(0);
+        disabled.store(
+            generation,
+            &HashMap::from([("mi".to_string(), entry(48.2))]),
+        );
+        assert!(disabled.entries.read().unwrap().is_empty());
+    }
+}
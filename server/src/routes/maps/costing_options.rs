@@ -0,0 +1,505 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, get};
+use serde::{Deserialize, Serialize};
+use valhalla_client::costing::{
+    AutoCostingOptions, BicycleCostingOptions, MotorScooterCostingOptions,
+    MotorcycleCostingOptions, PedestrianCostingOptions,
+};
+
+use super::route::CostingRequest;
+
+/// How many seconds an elevator costs if indoor ways are preferred, instead of valhallas default of 60
+const INDOOR_ELEVATOR_PENALTY: f32 = 15.0;
+
+/// Biases pedestrian routing towards indoor and covered ways
+///
+/// Valhalla does not know whether a way is covered, so we approximate it:
+/// corridors, covered walkways and tunnels on campus are lit and are often only connected to the rest of a route via elevators.
+/// User supplied costing options are applied on top of this.
+pub(super) fn prefer_indoor(options: PedestrianCostingOptions) -> PedestrianCostingOptions {
+    options
+        .use_lit(1.0)
+        .elevator_penalty(INDOOR_ELEVATOR_PENALTY)
+}
+
+/// Tunable options for pedestrian routing
+///
+/// See <https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#pedestrian-costing-options> for their meaning.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct PedestrianCostingOptionsRequest {
+    walking_speed: Option<f32>,
+    step_penalty: Option<f32>,
+    elevator_penalty: Option<f32>,
+    use_hills: Option<f32>,
+    use_ferry: Option<f32>,
+    use_living_streets: Option<f32>,
+    use_lit: Option<f32>,
+    service_penalty: Option<f32>,
+    shortest: Option<bool>,
+}
+impl PedestrianCostingOptionsRequest {
+    const OPTIONS: &[CostingOption] = &[
+        CostingOption::number("walking_speed"),
+        CostingOption::number("step_penalty"),
+        CostingOption::number("elevator_penalty"),
+        CostingOption::number("use_hills"),
+        CostingOption::number("use_ferry"),
+        CostingOption::number("use_living_streets"),
+        CostingOption::number("use_lit"),
+        CostingOption::number("service_penalty"),
+        CostingOption::boolean("shortest"),
+    ];
+    pub(super) fn apply_to(
+        self,
+        mut options: PedestrianCostingOptions,
+    ) -> PedestrianCostingOptions {
+        if let Some(walking_speed) = self.walking_speed {
+            options = options.walking_speed(walking_speed);
+        }
+        if let Some(step_penalty) = self.step_penalty {
+            options = options.step_penalty(step_penalty);
+        }
+        if let Some(elevator_penalty) = self.elevator_penalty {
+            options = options.elevator_penalty(elevator_penalty);
+        }
+        if let Some(use_hills) = self.use_hills {
+            options = options.use_hills(use_hills);
+        }
+        if let Some(use_ferry) = self.use_ferry {
+            options = options.use_ferry(use_ferry);
+        }
+        if let Some(use_living_streets) = self.use_living_streets {
+            options = options.use_living_streets(use_living_streets);
+        }
+        if let Some(use_lit) = self.use_lit {
+            options = options.use_lit(use_lit);
+        }
+        if let Some(service_penalty) = self.service_penalty {
+            options = options.service_penalty(service_penalty);
+        }
+        if let Some(shortest) = self.shortest {
+            options = options.shortest(shortest);
+        }
+        options
+    }
+}
+
+/// Tunable options for bicycle routing
+///
+/// See <https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#bicycle-costing-options> for their meaning.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct BicycleCostingOptionsRequest {
+    cycling_speed: Option<f32>,
+    use_roads: Option<f32>,
+    use_hills: Option<f32>,
+    use_ferry: Option<f32>,
+    use_living_streets: Option<f32>,
+    avoid_bad_surfaces: Option<f32>,
+    service_penalty: Option<f32>,
+    shortest: Option<bool>,
+}
+impl BicycleCostingOptionsRequest {
+    const OPTIONS: &[CostingOption] = &[
+        CostingOption::number("cycling_speed"),
+        CostingOption::number("use_roads"),
+        CostingOption::number("use_hills"),
+        CostingOption::number("use_ferry"),
+        CostingOption::number("use_living_streets"),
+        CostingOption::number("avoid_bad_surfaces"),
+        CostingOption::number("service_penalty"),
+        CostingOption::boolean("shortest"),
+    ];
+    pub(super) fn apply_to(self, mut options: BicycleCostingOptions) -> BicycleCostingOptions {
+        if let Some(cycling_speed) = self.cycling_speed {
+            options = options.cycling_speed(cycling_speed);
+        }
+        if let Some(use_roads) = self.use_roads {
+            options = options.use_roads(use_roads);
+        }
+        if let Some(use_hills) = self.use_hills {
+            options = options.use_hills(use_hills);
+        }
+        if let Some(use_ferry) = self.use_ferry {
+            options = options.use_ferry(use_ferry);
+        }
+        if let Some(use_living_streets) = self.use_living_streets {
+            options = options.use_living_streets(use_living_streets);
+        }
+        if let Some(avoid_bad_surfaces) = self.avoid_bad_surfaces {
+            options = options.avoid_bad_surfaces(avoid_bad_surfaces);
+        }
+        if let Some(service_penalty) = self.service_penalty {
+            options = options.service_penalty(service_penalty);
+        }
+        if let Some(shortest) = self.shortest {
+            options = options.shortest(shortest);
+        }
+        options
+    }
+}
+
+/// Tunable options for car routing
+///
+/// See <https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#automobile-and-bus-costing-options> for their meaning.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct CarCostingOptionsRequest {
+    use_highways: Option<f32>,
+    use_tolls: Option<f32>,
+    use_ferry: Option<f32>,
+    use_living_streets: Option<f32>,
+    service_penalty: Option<f32>,
+    top_speed: Option<f32>,
+    shortest: Option<bool>,
+}
+impl CarCostingOptionsRequest {
+    const OPTIONS: &[CostingOption] = &[
+        CostingOption::number("use_highways"),
+        CostingOption::number("use_tolls"),
+        CostingOption::number("use_ferry"),
+        CostingOption::number("use_living_streets"),
+        CostingOption::number("service_penalty"),
+        CostingOption::number("top_speed"),
+        CostingOption::boolean("shortest"),
+    ];
+    pub(super) fn apply_to(self, mut options: AutoCostingOptions) -> AutoCostingOptions {
+        if let Some(use_highways) = self.use_highways {
+            options = options.use_highways(use_highways);
+        }
+        if let Some(use_tolls) = self.use_tolls {
+            options = options.use_tolls(use_tolls);
+        }
+        if let Some(use_ferry) = self.use_ferry {
+            options = options.use_ferry(use_ferry);
+        }
+        if let Some(use_living_streets) = self.use_living_streets {
+            options = options.use_living_streets(use_living_streets);
+        }
+        if let Some(service_penalty) = self.service_penalty {
+            options = options.service_penalty(service_penalty);
+        }
+        if let Some(top_speed) = self.top_speed {
+            options = options.top_speed(top_speed);
+        }
+        if let Some(shortest) = self.shortest {
+            options = options.shortest(shortest);
+        }
+        options
+    }
+}
+
+/// Tunable options for powered two-wheeled routing
+///
+/// Only options understood by both mopeds and motorcycles are accepted.
+/// See <https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#motorcycle-costing-options> for their meaning.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(super) struct PoweredTwoWheeledCostingOptionsRequest {
+    use_ferry: Option<f32>,
+    use_living_streets: Option<f32>,
+    service_penalty: Option<f32>,
+    shortest: Option<bool>,
+}
+impl PoweredTwoWheeledCostingOptionsRequest {
+    const OPTIONS: &[CostingOption] = &[
+        CostingOption::number("use_ferry"),
+        CostingOption::number("use_living_streets"),
+        CostingOption::number("service_penalty"),
+        CostingOption::boolean("shortest"),
+    ];
+    pub(super) fn apply_to_motorcycle(
+        self,
+        mut options: MotorcycleCostingOptions,
+    ) -> MotorcycleCostingOptions {
+        if let Some(use_ferry) = self.use_ferry {
+            options = options.use_ferry(use_ferry);
+        }
+        if let Some(use_living_streets) = self.use_living_streets {
+            options = options.use_living_streets(use_living_streets);
+        }
+        if let Some(service_penalty) = self.service_penalty {
+            options = options.service_penalty(service_penalty);
+        }
+        if let Some(shortest) = self.shortest {
+            options = options.shortest(shortest);
+        }
+        options
+    }
+    pub(super) fn apply_to_motor_scooter(
+        self,
+        mut options: MotorScooterCostingOptions,
+    ) -> MotorScooterCostingOptions {
+        if let Some(use_ferry) = self.use_ferry {
+            options = options.use_ferry(use_ferry);
+        }
+        if let Some(use_living_streets) = self.use_living_streets {
+            options = options.use_living_streets(use_living_streets);
+        }
+        if let Some(service_penalty) = self.service_penalty {
+            options = options.service_penalty(service_penalty);
+        }
+        if let Some(shortest) = self.shortest {
+            options = options.shortest(shortest);
+        }
+        options
+    }
+}
+
+/// Type of the value of a costing option
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum CostingOptionType {
+    Number,
+    Boolean,
+}
+
+/// A key accepted in the `costing_options` of a transport mode
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+struct CostingOption {
+    /// Key in the `costing_options` object
+    #[schema(examples("use_hills"))]
+    name: &'static str,
+    /// Type of the value
+    r#type: CostingOptionType,
+}
+impl CostingOption {
+    const fn number(name: &'static str) -> Self {
+        Self {
+            name,
+            r#type: CostingOptionType::Number,
+        }
+    }
+    const fn boolean(name: &'static str) -> Self {
+        Self {
+            name,
+            r#type: CostingOptionType::Boolean,
+        }
+    }
+}
+
+/// A transport mode and how it can be tuned
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CostingModeResponse {
+    /// Value of `route_costing` selecting this mode
+    route_costing: CostingRequest,
+    /// Whether routes can be calculated for this mode yet
+    ///
+    /// Routing requests for modes which are not implemented are answered with `501 Not Implemented`.
+    implemented: bool,
+    /// Query parameters, besides `costing_options`, which affect this mode
+    parameters: Vec<&'static str>,
+    /// Keys accepted in the `costing_options`
+    costing_options: Vec<CostingOption>,
+}
+impl From<CostingRequest> for CostingModeResponse {
+    fn from(route_costing: CostingRequest) -> Self {
+        let (parameters, costing_options): (&[&str], _) = match route_costing {
+            CostingRequest::Pedestrian => (
+                &["pedestrian_type", "prefer_indoor"],
+                PedestrianCostingOptionsRequest::OPTIONS,
+            ),
+            CostingRequest::Bicycle => (&["bicycle_type"], BicycleCostingOptionsRequest::OPTIONS),
+            CostingRequest::Motorcycle => (
+                &["ptw_type"],
+                PoweredTwoWheeledCostingOptionsRequest::OPTIONS,
+            ),
+            CostingRequest::Car => (&[], CarCostingOptionsRequest::OPTIONS),
+            CostingRequest::PublicTransit => (
+                &["pedestrian_type"],
+                PedestrianCostingOptionsRequest::OPTIONS,
+            ),
+        };
+        Self {
+            route_costing,
+            implemented: route_costing.is_implemented(),
+            parameters: parameters.to_vec(),
+            costing_options: costing_options.to_vec(),
+        }
+    }
+}
+
+/// Supported transport modes
+///
+/// Lists every `route_costing` accepted by [`/api/maps/route`](#tag/maps/operation/route_handler), with the parameters and `costing_options` each of them can be tuned with.
+/// Clients should build their mode selection from this, instead of hardcoding it, as modes are added over time.
+#[utoipa::path(
+    tags=["maps"],
+    responses(
+        (status = 200, description = "**Transport modes**, in the order they should be offered", body = Vec<CostingModeResponse>, content_type = "application/json"),
+    )
+)]
+#[get("/api/maps/costing_options")]
+pub async fn costing_options_handler() -> HttpResponse {
+    let modes = CostingRequest::ALL
+        .into_iter()
+        .map(CostingModeResponse::from)
+        .collect::<Vec<_>>();
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::MaxAge(24 * 60 * 60), // valid for 1d
+            CacheDirective::Public,
+        ]))
+        .json(modes)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn known_options_are_accepted() {
+        let options: PedestrianCostingOptionsRequest =
+            serde_json::from_str(r#"{"use_hills":0.2,"shortest":true}"#).unwrap();
+        assert_eq!(options.use_hills, Some(0.2));
+        assert_eq!(options.shortest, Some(true));
+        let options: BicycleCostingOptionsRequest =
+            serde_json::from_str(r#"{"use_roads":0.9}"#).unwrap();
+        assert_eq!(options.use_roads, Some(0.9));
+        let options: CarCostingOptionsRequest = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, CarCostingOptionsRequest::default());
+    }
+
+    #[test]
+    fn user_options_override_indoor_preference() {
+        let indoor =
+            serde_json::to_value(prefer_indoor(PedestrianCostingOptions::builder())).unwrap();
+        assert_eq!(indoor["use_lit"], 1.0);
+        assert_eq!(indoor["elevator_penalty"], INDOOR_ELEVATOR_PENALTY);
+        let options: PedestrianCostingOptionsRequest =
+            serde_json::from_str(r#"{"use_lit":0.2}"#).unwrap();
+        let merged = serde_json::to_value(
+            options.apply_to(prefer_indoor(PedestrianCostingOptions::builder())),
+        )
+        .unwrap();
+        assert_eq!(merged["use_lit"], 0.2_f32);
+        assert_eq!(merged["elevator_penalty"], INDOOR_ELEVATOR_PENALTY);
+    }
+
+    /// The keys serde expects, according to its error for an unknown key
+    fn expected_keys<T: serde::de::DeserializeOwned + std::fmt::Debug>() -> Vec<String> {
+        let error = serde_json::from_str::<T>(r#"{"unknown":0}"#)
+            .unwrap_err()
+            .to_string();
+        let (_, expected) = error.split_once("expected one of ").unwrap();
+        let (expected, _) = expected.split_once(" at line").unwrap();
+        expected
+            .split(", ")
+            .map(|key| key.trim_matches('`').to_string())
+            .collect()
+    }
+
+    fn names(options: &[CostingOption]) -> Vec<String> {
+        options.iter().map(|o| o.name.to_string()).collect()
+    }
+
+    #[test]
+    fn listed_options_are_the_accepted_ones() {
+        assert_eq!(
+            names(PedestrianCostingOptionsRequest::OPTIONS),
+            expected_keys::<PedestrianCostingOptionsRequest>()
+        );
+        assert_eq!(
+            names(BicycleCostingOptionsRequest::OPTIONS),
+            expected_keys::<BicycleCostingOptionsRequest>()
+        );
+        assert_eq!(
+            names(CarCostingOptionsRequest::OPTIONS),
+            expected_keys::<CarCostingOptionsRequest>()
+        );
+        assert_eq!(
+            names(PoweredTwoWheeledCostingOptionsRequest::OPTIONS),
+            expected_keys::<PoweredTwoWheeledCostingOptionsRequest>()
+        );
+    }
+
+    #[test]
+    fn listed_types_are_accepted() {
+        let value = |option: &CostingOption| match option.r#type {
+            CostingOptionType::Number => "0.5",
+            CostingOptionType::Boolean => "true",
+        };
+        for option in PedestrianCostingOptionsRequest::OPTIONS {
+            let json = format!(r#"{{"{}":{}}}"#, option.name, value(option));
+            let options: PedestrianCostingOptionsRequest = serde_json::from_str(&json).unwrap();
+            assert_ne!(
+                options,
+                PedestrianCostingOptionsRequest::default(),
+                "{json}"
+            );
+        }
+        for option in BicycleCostingOptionsRequest::OPTIONS {
+            let json = format!(r#"{{"{}":{}}}"#, option.name, value(option));
+            let options: BicycleCostingOptionsRequest = serde_json::from_str(&json).unwrap();
+            assert_ne!(options, BicycleCostingOptionsRequest::default(), "{json}");
+        }
+        for option in CarCostingOptionsRequest::OPTIONS {
+            let json = format!(r#"{{"{}":{}}}"#, option.name, value(option));
+            let options: CarCostingOptionsRequest = serde_json::from_str(&json).unwrap();
+            assert_ne!(options, CarCostingOptionsRequest::default(), "{json}");
+        }
+        for option in PoweredTwoWheeledCostingOptionsRequest::OPTIONS {
+            let json = format!(r#"{{"{}":{}}}"#, option.name, value(option));
+            let options: PoweredTwoWheeledCostingOptionsRequest =
+                serde_json::from_str(&json).unwrap();
+            assert_ne!(
+                options,
+                PoweredTwoWheeledCostingOptionsRequest::default(),
+                "{json}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn all_modes_are_listed() {
+        // importing `test` in the module would shadow `#[test]`
+        use actix_web::{App, test};
+        let app = test::init_service(App::new().service(costing_options_handler)).await;
+        let req = test::TestRequest::get()
+            .uri("/api/maps/costing_options")
+            .to_request();
+        let modes: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let modes = modes.as_array().unwrap();
+        let labels = modes
+            .iter()
+            .map(|m| m["route_costing"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            CostingRequest::ALL.map(CostingRequest::as_label).to_vec()
+        );
+        let transit = &modes[4];
+        assert_eq!(transit["implemented"], false);
+        assert!(modes[..4].iter().all(|m| m["implemented"] == true));
+        assert_eq!(
+            modes[0]["parameters"],
+            serde_json::json!(["pedestrian_type", "prefer_indoor"])
+        );
+        assert_eq!(
+            modes[0]["costing_options"][8],
+            serde_json::json!({"name": "shortest", "type": "boolean"})
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(
+            serde_json::from_str::<PedestrianCostingOptionsRequest>(r#"{"use_hils":0.2}"#).is_err()
+        );
+        // options of other modes are not silently ignored either
+        assert!(
+            serde_json::from_str::<PedestrianCostingOptionsRequest>(r#"{"use_roads":0.2}"#)
+                .is_err()
+        );
+        assert!(
+            serde_json::from_str::<CarCostingOptionsRequest>(r#"{"walking_speed":5}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<PoweredTwoWheeledCostingOptionsRequest>(r#"{"top_speed":5}"#)
+                .is_err()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use actix_web::http::StatusCode;
 use actix_web::http::header::{CacheControl, CacheDirective};
 use actix_web::{HttpResponse, get, web};
 use geo_types::Geometry;
@@ -6,6 +7,13 @@ use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use tracing::error;
 
+use crate::error::ApiError;
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+
 #[tracing::instrument(skip(pool))]
 pub async fn fetch_indoor_maps_inside_of(
     pool: &PgPool,
@@ -67,7 +75,8 @@ struct IndoorPathParams {
     params(IndoorPathParams),
     responses(
         (status = 200, description = "**Indoor features** as GeoJSON", content_type = "application/json"),
-        (status = 404, description = "**Not found.** The requested location does not have a calendar", body = String, content_type = "text/plain", example = "Not found"),
+        (status = 404, description = "**Not found.** The requested indoor map does not exist", body = ApiError, content_type = "application/json", example = json!({"error": "Not found", "code": "not_found"})),
+        (status = 500, description = "**Internal Server Error.** We could not load the indoor map", body = ApiError, content_type = "application/json", example = json!({"error": "Cannot fetch indoor map, please try again later", "code": "internal_error"})),
     )
 )]
 #[get("/api/maps/indoor/{id}")]
@@ -77,9 +86,7 @@ pub async fn get_indoor_map(
 ) -> HttpResponse {
     let map = fetch_indoor_map(&data.pool, params.id).await;
     match map {
-        Ok(None) => HttpResponse::NotFound()
-            .content_type("text/plain")
-            .body("Not found"),
+        Ok(None) => ApiError::new(StatusCode::NOT_FOUND, "not_found", "Not found").into(),
         Ok(Some(geometry)) => HttpResponse::Ok()
             .insert_header(CacheControl(vec![
                 CacheDirective::MaxAge(2 * 24 * 60 * 60), // valid for 2d
@@ -92,9 +99,12 @@ pub async fn get_indoor_map(
                 id = params.id,
                 "Failed to fetch indoor map"
             );
-            HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Cannot fetch indoor map, please try again later")
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Cannot fetch indoor map, please try again later",
+            )
+            .into()
         }
     }
 }
@@ -121,16 +131,18 @@ struct Arguments {
     bbox: String,
 }
 impl Arguments {
-    fn validate_bbox(&self) -> Result<geo::Rect<f64>, HttpResponse> {
+    fn validate_bbox(&self) -> Result<geo::Rect<f64>, ApiError> {
         let bbox: Vec<f64> = self
             .bbox
             .split(",")
             .filter_map(|s| s.parse().ok())
             .collect();
         if bbox.len() != 4 {
-            return Err(HttpResponse::BadRequest()
-                .content_type("text/plain")
-                .body("the bbox-parameter needs 4 floating point numbers of format y,x,y,x"));
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_bbox",
+                "the bbox-parameter needs 4 floating point numbers of format y,x,y,x",
+            ));
         }
         Ok(geo::Rect::new(
             geo::Coord::from((bbox[1], bbox[0])),
@@ -147,7 +159,8 @@ impl Arguments {
     params(Arguments),
     responses(
         (status = 200, description = "**List indoor maps** in bounding box", body = Vec<RemoteMap>, content_type = "application/json"),
-        (status = 400, description = "**Bad Request.** Please check that the input provided matches above.", body = String, content_type = "text/plain", example = "the bbox-parameter needs 4 floating point numbers of format y,x,y,x"),
+        (status = 400, description = "**Bad Request.** Please check that the input provided matches above.", body = ApiError, content_type = "application/json", example = json!({"error": "the bbox-parameter needs 4 floating point numbers of format y,x,y,x", "code": "invalid_bbox"})),
+        (status = 500, description = "**Internal Server Error.** We could not list the indoor maps", body = ApiError, content_type = "application/json", example = json!({"error": "could not get indoor maps, please try again later", "code": "internal_error"})),
     )
 )]
 #[get("/api/maps/indoor")]
@@ -157,16 +170,19 @@ pub async fn list_indoor_maps(
 ) -> HttpResponse {
     let bbox = match args.validate_bbox() {
         Ok(bbox) => bbox,
-        Err(e) => return e,
+        Err(e) => return e.into(),
     };
     let maps = fetch_indoor_maps_inside_of(&data.pool, bbox.into()).await;
     let maps = match maps {
         Ok(m) => m,
         Err(e) => {
             error!(error = ?e,"could not list maps");
-            return HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("could not get indoor maps, please try again later");
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "could not get indoor maps, please try again later",
+            )
+            .into();
         }
     };
     let mut response = Vec::new();
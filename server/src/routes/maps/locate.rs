@@ -0,0 +1,210 @@
+use crate::error::ApiError;
+use crate::external::valhalla::LocateResult;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, get, web};
+use serde::{Deserialize, Serialize};
+#[expect(
+    unused_imports,
+    reason = "has to be imported as otherwise utoipa generates incorrect code"
+)]
+use serde_json::json;
+use tracing::error;
+use valhalla_client::costing::Costing;
+
+use super::route::{
+    BicycleRestrictionRequest, Coordinate, CostingRequest, CostingSelection, PedestrianTypeRequest,
+    PoweredTwoWheeledRestrictionRequest,
+};
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+struct LocateRequest {
+    /// Latitude of the point to locate
+    #[param(example = 48.26244490906312)]
+    lat: f64,
+    /// Longitude of the point to locate
+    #[param(example = 11.66863424359771)]
+    lon: f64,
+    /// Transport mode the user wants to use
+    ///
+    /// Different transport modes snap to different parts of the network (e.g. cars don't snap onto footpaths)
+    route_costing: CostingRequest,
+    /// Does the user have specific walking restrictions?
+    #[serde(default)]
+    pedestrian_type: PedestrianTypeRequest,
+    /// Does the user prefer mopeds or motorcycles for powered two-wheeled (ptw)?
+    #[serde(default)]
+    ptw_type: PoweredTwoWheeledRestrictionRequest,
+    /// Which kind of bicycle do you ride?
+    #[serde(default)]
+    bicycle_type: BicycleRestrictionRequest,
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct LocateResponse {
+    /// Where the coordinate snaps to on the closest edge of the routing network
+    ///
+    /// `null` if there is no edge usable by the selected `route_costing` nearby
+    snapped: Option<Coordinate>,
+    /// Name of the closest road/path
+    ///
+    /// `null` if the road/path does not have a name or no edge is nearby
+    #[schema(examples("Boltzmannstraße"))]
+    nearest_road_name: Option<String>,
+    /// All candidate edges the coordinate could snap to
+    edges: Vec<LocatedEdgeResponse>,
+    /// Nodes of the routing network the coordinate could snap to
+    nodes: Vec<Coordinate>,
+}
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct LocatedEdgeResponse {
+    /// Where the coordinate snaps to on this edge
+    snapped: Coordinate,
+    /// Side of the street the coordinate is on
+    #[schema(examples("left", "right", "neither"))]
+    side_of_street: Option<String>,
+    /// Names of the road/path
+    #[schema(examples(json!(["Boltzmannstraße"])))]
+    names: Vec<String>,
+}
+impl From<LocateResult> for LocateResponse {
+    fn from(value: LocateResult) -> Self {
+        let edges = value
+            .edges
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| LocatedEdgeResponse {
+                snapped: Coordinate {
+                    lat: e.correlated_lat,
+                    lon: e.correlated_lon,
+                },
+                side_of_street: e.side_of_street,
+                names: e.edge_info.map(|i| i.names).unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        let nodes = value
+            .nodes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|n| Coordinate {
+                lat: n.lat,
+                lon: n.lon,
+            })
+            .collect();
+        LocateResponse {
+            snapped: edges.first().map(|e| e.snapped),
+            nearest_road_name: edges.first().and_then(|e| e.names.first().cloned()),
+            edges,
+            nodes,
+        }
+    }
+}
+
+/// Locate a coordinate on the routing network
+///
+/// **API IS EXPERIMENTAL AND ACTIVELY SUBJECT TO CHANGE**
+///
+/// Shows where a coordinate snaps onto the routing network for a transport mode (`route_costing`).
+/// Routes calculated via [`/api/maps/route`](#tag/maps/operation/route_handler) start/end at these points.
+///
+/// Internally, this endpoint relies on [Valhalla](https://github.com/valhalla/valhalla)s `/locate` endpoint.
+#[utoipa::path(
+    tags=["maps"],
+    params(LocateRequest),
+    responses(
+        (status = 200, description = "**Where the coordinate snaps to**", body = LocateResponse, content_type = "application/json"),
+        (status = 500, description = "**Internal Server Error.** We could not locate the coordinate", body = ApiError, content_type = "application/json", example = json!({"error": "Could not locate the coordinate, please try again later", "code": "locate_failed"})),
+        (status = 501, description = "**Not Implemented.** The requested transport mode is not yet supported", body = ApiError, content_type = "application/json", example = json!({"error": "public transit routing is not yet implemented", "code": "not_implemented"})),
+    )
+)]
+#[get("/api/maps/locate")]
+#[tracing::instrument(skip(data))]
+pub async fn locate_handler(
+    args: web::Query<LocateRequest>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if !args.route_costing.is_implemented() {
+        return ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "not_implemented",
+            "public transit routing is not yet implemented",
+        )
+        .into();
+    }
+    let costing = Costing::try_from(CostingSelection {
+        route_costing: args.route_costing,
+        pedestrian_type: args.pedestrian_type,
+        ptw_type: args.ptw_type,
+        bicycle_type: args.bicycle_type,
+        // only changes which ways are preferred, not which are reachable
+        prefer_indoor: false,
+        costing_options: None,
+    })
+    .expect("without costing_options, the costing is always valid");
+    let located = data
+        .valhalla
+        .locate((args.lat as f32, args.lon as f32), costing)
+        .await;
+    match located {
+        Ok(located) => HttpResponse::Ok().json(LocateResponse::from(located)),
+        Err(e) => {
+            error!(error = ?e, "error locating");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "locate_failed",
+                "Could not locate the coordinate, please try again later",
+            )
+            .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_locate_response_from_valhalla() {
+        let located: LocateResult = serde_json::from_value(serde_json::json!({
+            "input_lat": 48.262,
+            "input_lon": 11.668,
+            "edges": [
+                {"correlated_lat": 48.2621, "correlated_lon": 11.6681, "side_of_street": "left", "percent_along": 0.4, "edge_info": {"names": ["Boltzmannstraße"], "shape": "abc"}},
+                {"correlated_lat": 48.2625, "correlated_lon": 11.6685, "side_of_street": "neither", "percent_along": 0.1, "edge_info": {"names": []}},
+            ],
+            "nodes": [{"lat": 48.263, "lon": 11.669}],
+        }))
+        .unwrap();
+        let response = LocateResponse::from(located);
+        assert_eq!(
+            response.snapped,
+            Some(Coordinate {
+                lat: 48.2621,
+                lon: 11.6681
+            })
+        );
+        assert_eq!(
+            response.nearest_road_name.as_deref(),
+            Some("Boltzmannstraße")
+        );
+        assert_eq!(response.edges.len(), 2);
+        assert_eq!(response.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_nothing_nearby() {
+        let located: LocateResult = serde_json::from_value(serde_json::json!({
+            "input_lat": 48.262,
+            "input_lon": 11.668,
+            "edges": null,
+            "nodes": null,
+        }))
+        .unwrap();
+        let response = LocateResponse::from(located);
+        assert_eq!(response.snapped, None);
+        assert_eq!(response.nearest_road_name, None);
+        assert!(response.edges.is_empty());
+        assert!(response.nodes.is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+/// Metrics of the routing endpoint
+///
+/// Registered against the registry of [`actix_web_prom::PrometheusMetrics`] to be exposed on `/api/metrics`
+#[derive(Clone, Debug)]
+pub struct RouteMetrics {
+    /// How long valhalla took to calculate a route, by `route_costing`
+    pub(super) valhalla_duration: HistogramVec,
+    /// Handled routing requests, by `route_costing` and response status code
+    pub(super) requests: IntCounterVec,
+}
+
+impl RouteMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let valhalla_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "route_valhalla_duration_seconds",
+                "Time valhalla took to calculate a route",
+            )
+            .namespace("navigatum_api")
+            .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+            &["route_costing"],
+        )?;
+        let requests = IntCounterVec::new(
+            Opts::new("route_requests_total", "Handled routing requests")
+                .namespace("navigatum_api"),
+            &["route_costing", "status"],
+        )?;
+        registry.register(Box::new(valhalla_duration.clone()))?;
+        registry.register(Box::new(requests.clone()))?;
+        Ok(Self {
+            valhalla_duration,
+            requests,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn metrics_are_registered() {
+        let registry = Registry::new();
+        let metrics = RouteMetrics::register(&registry).unwrap();
+        metrics
+            .requests
+            .with_label_values(&["pedestrian", "200"])
+            .inc();
+        metrics
+            .valhalla_duration
+            .with_label_values(&["pedestrian"])
+            .observe(0.1);
+        let names = registry
+            .gather()
+            .into_iter()
+            .map(|m| m.get_name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "navigatum_api_route_requests_total",
+                "navigatum_api_route_valhalla_duration_seconds"
+            ]
+        );
+        // registering twice would produce duplicate timeseries
+        assert!(RouteMetrics::register(&registry).is_err());
+    }
+}
@@ -1,2 +1,6 @@
+pub mod coordinates;
+pub mod costing_options;
 pub mod indoor;
+pub mod locate;
+pub mod metrics;
 pub mod route;
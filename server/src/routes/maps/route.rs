@@ -1,5 +1,10 @@
+use crate::error::ApiError;
 use crate::localisation;
-use actix_web::{HttpResponse, get, web};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, get, post, web};
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc};
+use chrono_tz::Europe::Berlin;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 #[expect(
     unused_imports,
@@ -7,25 +12,41 @@ use serde::{Deserialize, Serialize};
 )]
 use serde_json::json;
 use sqlx::PgPool;
-use std::ops::Deref;
-use tracing::{debug, error};
+use std::collections::{BTreeMap, HashMap};
+use tokio::join;
+use tracing::{debug, error, info, warn};
 use valhalla_client::costing::{
     BicycleCostingOptions, Costing, MultimodalCostingOptions, PedestrianCostingOptions,
     bicycle::BicycleType, pedestrian::PedestrianType,
 };
 use valhalla_client::route::{
-    Leg, Maneuver, ManeuverType, ShapePoint, Summary, TransitInfo, TransitStop, TransitStopType,
-    TravelMode, Trip,
+    Leg, Location, Maneuver, ManeuverType, ShapePoint, Summary, TransitInfo, TransitStop,
+    TransitStopType, TravelMode, Trip,
 };
 
+use super::coordinates::{CachedCoordinate, CoordinateCache};
+use super::costing_options::{
+    self, BicycleCostingOptionsRequest, CarCostingOptionsRequest, PedestrianCostingOptionsRequest,
+    PoweredTwoWheeledCostingOptionsRequest,
+};
+use super::metrics::RouteMetrics;
+use crate::external::valhalla;
+use crate::routes::admin;
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, utoipa::ToSchema)]
-struct Coordinate {
+pub(super) struct Coordinate {
     /// Latitude
     #[schema(example = 48.26244490906312)]
-    lat: f64,
+    pub(super) lat: f64,
     /// Longitude
     #[schema(example = 48.26244490906312)]
-    lon: f64,
+    pub(super) lon: f64,
+}
+impl Coordinate {
+    /// Whether the coordinate is on earth, i.e. `-90 <= lat <= 90` and `-180 <= lon <= 180`
+    fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lon)
+    }
 }
 impl From<ShapePoint> for Coordinate {
     fn from(value: ShapePoint) -> Self {
@@ -36,95 +57,310 @@ impl From<ShapePoint> for Coordinate {
     }
 }
 
+/// Coordinate supplied by the user, possibly with an uncertainty
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, utoipa::ToSchema)]
+struct RequestedCoordinate {
+    #[serde(flatten)]
+    coordinate: Coordinate,
+    /// How accurate (in meters) the coordinate is, e.g. the accuracy reported by the GPS of the device
+    ///
+    /// Valhalla will consider roads/paths within this radius when snapping the location to the routing graph.
+    /// If omitted, only the closest roads/paths are considered.
+    #[serde(default)]
+    #[schema(example = 15.0, minimum = 0.0)]
+    accuracy_m: Option<f32>,
+}
+
+/// Upper bound for the snapping radius derived from `accuracy_m`
+///
+/// Larger radii make Valhalla consider a lot of candidate edges, which is slow and rarely helpful.
+const MAX_SNAPPING_RADIUS_M: f32 = 200.0;
+
+impl RequestedCoordinate {
+    /// Snapping radius in meters for valhalla
+    fn snapping_radius(&self) -> Option<u32> {
+        self.accuracy_m
+            .filter(|a| a.is_finite() && *a > 0.0)
+            .map(|a| a.min(MAX_SNAPPING_RADIUS_M).ceil() as u32)
+    }
+}
+
 #[derive(Deserialize, Clone, Debug, PartialEq, utoipa::ToSchema)]
 #[serde(untagged)]
 enum RequestedLocation {
     /// Either an
     /// - external address which was looked up or
     /// - the users current location  
-    Coordinate(Coordinate),
+    Coordinate(RequestedCoordinate),
     /// Our (uni internal) key for location identification
     Location(String),
 }
-impl RequestedLocation {
-    async fn try_resolve_coordinates(&self, pool: &PgPool) -> anyhow::Result<Option<Coordinate>> {
+/// Types of locations which make sense as the start or destination of a route
+///
+/// Larger entities like campuses or areas would be resolved to some arbitrary point inside them.
+const ROUTABLE_TYPES: [&str; 5] = ["room", "virtual_room", "building", "joined_building", "poi"];
+
+/// Outcome of looking up a [`RequestedLocation`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resolution<T> {
+    Found(T),
+    /// The key exists, but is not one of the [`ROUTABLE_TYPES`]
+    NotRoutable,
+    /// The key does not exist or does not have a (valid) coordinate
+    Missing,
+    /// The user supplied a coordinate outside of the valid range
+    InvalidCoordinate,
+}
+impl<T> Resolution<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> Resolution<U> {
         match self {
-            RequestedLocation::Coordinate(coords) => Ok(Some(*coords)),
-            RequestedLocation::Location(key) => {
-                let coords = sqlx::query_as!(
-                    Coordinate,
-                    r#"SELECT lat,lon
-                    FROM de
-                    WHERE key = $1 and
-                          lat IS NOT NULL and
-                          lon IS NOT NULL"#,
-                    key
-                )
-                .fetch_optional(pool)
-                .await?;
-                Ok(coords)
-            }
+            Resolution::Found(t) => Resolution::Found(f(t)),
+            Resolution::NotRoutable => Resolution::NotRoutable,
+            Resolution::Missing => Resolution::Missing,
+            Resolution::InvalidCoordinate => Resolution::InvalidCoordinate,
+        }
+    }
+    fn into_result(self, requested: &RequestedLocation) -> Result<T, ApiError> {
+        match (self, requested) {
+            (Resolution::Found(t), _) => Ok(t),
+            (Resolution::InvalidCoordinate, _) => Err(invalid_coordinate(
+                "Coordinates need a `lat` within -90..=90 and a `lon` within -180..=180",
+            )),
+            (Resolution::NotRoutable, RequestedLocation::Location(key)) => Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "not_routable",
+                format!("{key} is not a routable location"),
+            )),
+            _ => Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "not_found",
+                "Not found",
+            )),
+        }
+    }
+}
+
+impl RequestedLocation {
+    fn to_location(&self, coords: Coordinate) -> Location {
+        let location = Location::from((coords.lat as f32, coords.lon as f32));
+        let radius = match self {
+            RequestedLocation::Coordinate(requested) => requested.snapping_radius(),
+            RequestedLocation::Location(_) => None,
+        };
+        match radius {
+            Some(radius) => location.radius(radius),
+            None => location,
+        }
+    }
+    /// Looks all keys not yet in the `cache` up in a single query
+    async fn try_resolve_all_coordinates(
+        pool: &PgPool,
+        cache: &CoordinateCache,
+        locations: &[RequestedLocation],
+        only_routable: bool,
+    ) -> anyhow::Result<Vec<Resolution<Coordinate>>> {
+        let keys = locations
+            .iter()
+            .filter_map(|l| match l {
+                RequestedLocation::Location(key) => Some(key.clone()),
+                RequestedLocation::Coordinate(_) => None,
+            })
+            .collect::<Vec<String>>();
+        let (mut resolved, missing, generation) = cache.lookup(&keys);
+        if !missing.is_empty() {
+            let rows = sqlx::query!(
+                r#"SELECT key,lat,lon,type
+                FROM de
+                WHERE key = ANY($1::text[]) and
+                      lat IS NOT NULL and
+                      lon IS NOT NULL"#,
+                &missing
+            )
+            .fetch_all(pool)
+            .await?;
+            let fetched = rows
+                .into_iter()
+                .filter_map(|r| {
+                    let coordinate = Coordinate {
+                        lat: r.lat,
+                        lon: r.lon,
+                    };
+                    if !coordinate.is_valid() {
+                        warn!(
+                            key = r.key.as_str(),
+                            ?coordinate,
+                            "data quality: the location has a coordinate outside of the valid range"
+                        );
+                        return None;
+                    }
+                    let routable = ROUTABLE_TYPES.contains(&r.r#type.as_str());
+                    Some((
+                        r.key,
+                        CachedCoordinate {
+                            coordinate,
+                            routable,
+                        },
+                    ))
+                })
+                .collect::<HashMap<_, _>>();
+            cache.store(generation, &fetched);
+            resolved.extend(fetched);
         }
+        Ok(locations
+            .iter()
+            .map(|l| match l {
+                RequestedLocation::Coordinate(requested) if !requested.coordinate.is_valid() => {
+                    Resolution::InvalidCoordinate
+                }
+                RequestedLocation::Coordinate(requested) => Resolution::Found(requested.coordinate),
+                RequestedLocation::Location(key) => match resolved.get(key) {
+                    Some(cached) if only_routable && !cached.routable => Resolution::NotRoutable,
+                    Some(cached) => Resolution::Found(cached.coordinate),
+                    None => Resolution::Missing,
+                },
+            })
+            .collect())
     }
 }
 
 /// Transport mode the user wants to use
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
-enum CostingRequest {
+pub(super) enum CostingRequest {
     Pedestrian,
     Bicycle,
     Motorcycle,
     Car,
     PublicTransit,
 }
-impl From<&RoutingRequest> for Costing {
-    fn from(
-        RoutingRequest {
+impl CostingRequest {
+    pub(super) const ALL: [CostingRequest; 5] = [
+        CostingRequest::Pedestrian,
+        CostingRequest::Bicycle,
+        CostingRequest::Motorcycle,
+        CostingRequest::Car,
+        CostingRequest::PublicTransit,
+    ];
+    /// Label used in metrics
+    pub(super) fn as_label(self) -> &'static str {
+        match self {
+            CostingRequest::Pedestrian => "pedestrian",
+            CostingRequest::Bicycle => "bicycle",
+            CostingRequest::Motorcycle => "motorcycle",
+            CostingRequest::Car => "car",
+            CostingRequest::PublicTransit => "public_transit",
+        }
+    }
+    /// Whether routes can be calculated for this mode, otherwise requests are answered with `501 Not Implemented`
+    pub(super) fn is_implemented(self) -> bool {
+        self != CostingRequest::PublicTransit
+    }
+}
+
+/// Everything the user specified about how they want to travel
+pub(super) struct CostingSelection<'a> {
+    pub(super) route_costing: CostingRequest,
+    pub(super) pedestrian_type: PedestrianTypeRequest,
+    pub(super) ptw_type: PoweredTwoWheeledRestrictionRequest,
+    pub(super) bicycle_type: BicycleRestrictionRequest,
+    pub(super) prefer_indoor: bool,
+    pub(super) costing_options: Option<&'a str>,
+}
+impl TryFrom<CostingSelection<'_>> for Costing {
+    /// The user supplied `costing_options` are not valid for the selected `route_costing`
+    type Error = serde_json::Error;
+    fn try_from(
+        CostingSelection {
             route_costing,
             pedestrian_type,
             ptw_type,
             bicycle_type,
-            ..
-        }: &RoutingRequest,
-    ) -> Self {
-        match route_costing {
-            CostingRequest::Pedestrian => Costing::Pedestrian(
-                PedestrianCostingOptions::builder().r#type(PedestrianType::from(*pedestrian_type)),
-            ),
-            CostingRequest::Bicycle => Costing::Bicycle(
-                BicycleCostingOptions::builder().bicycle_type(BicycleType::from(*bicycle_type)),
-            ),
-            CostingRequest::Motorcycle => match ptw_type {
-                PoweredTwoWheeledRestrictionRequest::Moped => {
-                    Costing::Motorcycle(Default::default())
+            prefer_indoor,
+            costing_options,
+        }: CostingSelection,
+    ) -> Result<Self, Self::Error> {
+        let costing_options = costing_options.unwrap_or("{}");
+        let costing = match route_costing {
+            CostingRequest::Pedestrian => {
+                let options: PedestrianCostingOptionsRequest =
+                    serde_json::from_str(costing_options)?;
+                let mut defaults = PedestrianCostingOptions::builder()
+                    .r#type(PedestrianType::from(pedestrian_type));
+                if prefer_indoor {
+                    defaults = costing_options::prefer_indoor(defaults);
                 }
-                PoweredTwoWheeledRestrictionRequest::Motorcycle => {
-                    Costing::MotorScooter(Default::default())
+                Costing::Pedestrian(options.apply_to(defaults))
+            }
+            CostingRequest::Bicycle => {
+                let options: BicycleCostingOptionsRequest = serde_json::from_str(costing_options)?;
+                Costing::Bicycle(options.apply_to(
+                    BicycleCostingOptions::builder().bicycle_type(BicycleType::from(bicycle_type)),
+                ))
+            }
+            CostingRequest::Motorcycle => {
+                let options: PoweredTwoWheeledCostingOptionsRequest =
+                    serde_json::from_str(costing_options)?;
+                match ptw_type {
+                    PoweredTwoWheeledRestrictionRequest::Moped => {
+                        Costing::Motorcycle(options.apply_to_motorcycle(Default::default()))
+                    }
+                    PoweredTwoWheeledRestrictionRequest::Motorcycle => {
+                        Costing::MotorScooter(options.apply_to_motor_scooter(Default::default()))
+                    }
                 }
-            },
-            CostingRequest::Car => Costing::Auto(Default::default()),
+            }
+            CostingRequest::Car => {
+                let options: CarCostingOptionsRequest = serde_json::from_str(costing_options)?;
+                Costing::Auto(options.apply_to(Default::default()))
+            }
             CostingRequest::PublicTransit => {
-                let pedestrian_costing = PedestrianCostingOptions::builder()
-                    .r#type(PedestrianType::from(*pedestrian_type));
+                let options: PedestrianCostingOptionsRequest =
+                    serde_json::from_str(costing_options)?;
+                let pedestrian_costing = options.apply_to(
+                    PedestrianCostingOptions::builder()
+                        .r#type(PedestrianType::from(pedestrian_type)),
+                );
                 Costing::Multimodal(
                     MultimodalCostingOptions::builder()
                         .pedestrian(pedestrian_costing)
                         .transit(Default::default()),
                 )
             }
-        }
+        };
+        Ok(costing)
     }
 }
 
+#[serde_with::serde_as]
 #[derive(Deserialize, Debug, utoipa::ToSchema, utoipa::IntoParams)]
 struct RoutingRequest {
-    #[serde(flatten, default)]
-    lang: localisation::LangQueryArgs,
+    /// The language of the instructions and the `arrival_clock_time`
+    #[serde(default)]
+    lang: RouteLanguageRequest,
     /// Start of the route
-    from: RequestedLocation,
+    ///
+    /// Alternatively, `from_lat` and `from_lon` can be specified
+    from: Option<RequestedLocation>,
+    /// Latitude of the start of the route, if `from` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    from_lat: Option<f64>,
+    /// Longitude of the start of the route, if `from` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    from_lon: Option<f64>,
     /// Destination of the route
-    to: RequestedLocation,
+    ///
+    /// Alternatively, `to_lat` and `to_lon` can be specified
+    to: Option<RequestedLocation>,
+    /// Latitude of the destination of the route, if `to` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    to_lat: Option<f64>,
+    /// Longitude of the destination of the route, if `to` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    to_lon: Option<f64>,
     /// Transport mode the user wants to use
     route_costing: CostingRequest,
     /// Does the user have specific walking restrictions?
@@ -136,12 +372,142 @@ struct RoutingRequest {
     /// Which kind of bicycle do you ride?
     #[serde(default)]
     bicycle_type: BicycleRestrictionRequest,
+    /// Should covered ways (e.g. corridors, covered walkways and tunnels) be preferred over walking outside?
+    ///
+    /// Only affects `route_costing=pedestrian`.
+    /// Useful in bad weather when walking between distant buildings, at the cost of slightly longer routes.
+    #[serde_as(as = "serde_with::PickFirst<(_, serde_with::DisplayFromStr)>")]
+    #[serde(default)]
+    prefer_indoor: bool,
+    /// Fine-grained costing options for power users, encoded as a JSON object
+    ///
+    /// They are merged on top of our server-side defaults for the selected `route_costing`.
+    /// Accepted keys depend on `route_costing`:
+    /// - `pedestrian`/`public_transit`: `walking_speed`, `step_penalty`, `elevator_penalty`, `use_hills`, `use_ferry`, `use_living_streets`, `use_lit`, `service_penalty`, `shortest`
+    /// - `bicycle`: `cycling_speed`, `use_roads`, `use_hills`, `use_ferry`, `use_living_streets`, `avoid_bad_surfaces`, `service_penalty`, `shortest`
+    /// - `car`: `use_highways`, `use_tolls`, `use_ferry`, `use_living_streets`, `service_penalty`, `top_speed`, `shortest`
+    /// - `motorcycle`: `use_ferry`, `use_living_streets`, `service_penalty`, `shortest`
+    ///
+    /// Unknown keys are rejected.
+    /// The accepted keys are also listed by [`/api/maps/costing_options`](#tag/maps/operation/costing_options_handler).
+    /// See [Valhallas costing options](https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#costing-options) for what they mean.
+    #[schema(example = r#"{"use_hills":0.2,"service_penalty":20}"#)]
+    costing_options: Option<String>,
+    /// Should we retry as `pedestrian`, if no route can be calculated for the requested `route_costing`?
+    ///
+    /// Useful e.g. if both ends are inside a pedestrian zone, which cars cannot reach.
+    /// If the retry succeeds, the response has `used_fallback` set.
+    /// The `costing_options` only apply to the requested `route_costing`, not to the retry.
+    #[serde_as(as = "serde_with::PickFirst<(_, serde_with::DisplayFromStr)>")]
+    #[serde(default)]
+    fallback_to_pedestrian: bool,
+    /// How detailed the `maneuvers` should be
+    #[serde(default)]
+    detail: RouteDetailRequest,
+    /// When the trip starts, as an [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp
+    ///
+    /// Defaults to now.
+    /// The `arrival_time` is returned in the same timezone, which defaults to the one of our campuses.
+    /// In the query string, the `+` of the offset has to be encoded as `%2B`.
+    #[schema(example = "2025-02-20T14:05:00+01:00")]
+    #[serde(default)]
+    departure_time: Option<DateTime<FixedOffset>>,
+}
+
+impl RoutingRequest {
+    /// Start of the route, either from `from` or `from_lat`/`from_lon`
+    fn origin(&self) -> Result<RequestedLocation, ApiError> {
+        requested_location("from", self.from.as_ref(), self.from_lat, self.from_lon)
+    }
+    /// Destination of the route, either from `to` or `to_lat`/`to_lon`
+    fn destination(&self) -> Result<RequestedLocation, ApiError> {
+        requested_location("to", self.to.as_ref(), self.to_lat, self.to_lon)
+    }
+}
+
+/// Picks whichever way of specifying the location `param` was used
+fn requested_location(
+    param: &str,
+    location: Option<&RequestedLocation>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+) -> Result<RequestedLocation, ApiError> {
+    match (location, lat, lon) {
+        (Some(location), None, None) => Ok(location.clone()),
+        (None, Some(lat), Some(lon)) => {
+            let coordinate = Coordinate { lat, lon };
+            if !coordinate.is_valid() {
+                return Err(invalid_coordinate(format!(
+                    "`{param}_lat` has to be within -90..=90 and `{param}_lon` within -180..=180"
+                )));
+            }
+            Ok(RequestedLocation::Coordinate(RequestedCoordinate {
+                coordinate,
+                accuracy_m: None,
+            }))
+        }
+        (Some(_), _, _) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "ambiguous_location",
+            format!("Either `{param}` or `{param}_lat`/`{param}_lon` can be specified, not both"),
+        )),
+        (None, _, _) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "missing_location",
+            format!("Either `{param}` or both `{param}_lat` and `{param}_lon` are required"),
+        )),
+    }
+}
+
+fn invalid_coordinate(message: impl Into<std::borrow::Cow<'static, str>>) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, "invalid_coordinate", message)
+}
+
+/// How detailed the maneuvers of a route should be
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum RouteDetailRequest {
+    /// Every maneuver valhalla returns
+    #[default]
+    Full,
+    /// Only the major maneuvers, minor ones (e.g. `continue`) are merged into the maneuver before them
+    ///
+    /// Intended for giving an overview of long routes.
+    /// Merged maneuvers report how many maneuvers they summarise via `collapsed_maneuvers`.
+    Overview,
+}
+
+/// The language of a route
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum RouteLanguageRequest {
+    #[default]
+    De,
+    En,
+    /// German, but each maneuver has its `instruction` in German (`instruction_de`) and English (`instruction_en`) as well
+    ///
+    /// Intended for bilingual signage.
+    /// The route is calculated once per language, concurrently.
+    Both,
+}
+impl RouteLanguageRequest {
+    fn should_use_english(self) -> bool {
+        self == Self::En
+    }
+    /// The language of everything besides `instruction_de`/`instruction_en`
+    fn primary(self) -> localisation::LangQueryArgs {
+        if self.should_use_english() {
+            localisation::LangQueryArgs::ENGLISH
+        } else {
+            localisation::LangQueryArgs::GERMAN
+        }
+    }
 }
 
 /// Does the user have specific walking restrictions?
 #[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
-enum PedestrianTypeRequest {
+pub(super) enum PedestrianTypeRequest {
     #[default]
     None,
     Blind,
@@ -163,7 +529,7 @@ impl From<PedestrianTypeRequest> for PedestrianType {
 /// Which kind of bicycle do you ride?
 #[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
-enum BicycleRestrictionRequest {
+pub(super) enum BicycleRestrictionRequest {
     /// Road-bike
     ///
     /// A road-style bicycle with narrow tires that is generally lightweight and designed for speed on paved surfaces.
@@ -195,7 +561,7 @@ impl From<BicycleRestrictionRequest> for BicycleType {
 /// Does the user have a moped or motorcycle
 #[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
-enum PoweredTwoWheeledRestrictionRequest {
+pub(super) enum PoweredTwoWheeledRestrictionRequest {
     #[default]
     Motorcycle,
     Moped,
@@ -208,6 +574,13 @@ enum PoweredTwoWheeledRestrictionRequest {
 /// The user specifies using provided origin (`from`) and destination (`to`) locations and a transport mode (`route_costing`) to tune their routing between the two locations.
 /// The costing is fine-tuned by the server side accordingly.
 ///
+/// Instead of `from`/`to`, plain coordinates can be passed via `from_lat`/`from_lon` and `to_lat`/`to_lon`.
+/// This is handy for embedding routes on external sites, as no ids have to be looked up.
+///
+/// With `fallback_to_pedestrian`, a walking route is returned (flagged via `used_fallback`) if the requested transport mode cannot reach the destination.
+///
+/// With `detail=overview`, minor maneuvers (e.g. multiple `continue`s) are merged into summarised steps, which is handy for showing an overview of long routes.
+///
 /// Internally, this endpoint relies on
 /// - [Valhalla](https://github.com/valhalla/valhalla) for routing for route calculation
 /// - our database to resolve ids.
@@ -223,58 +596,654 @@ enum PoweredTwoWheeledRestrictionRequest {
     params(RoutingRequest),
     responses(
         (status = 200, description = "**Routing solution**", body=RoutingResponse, content_type = "application/json"),
-        (status = 404, description = "**Not found.** The requested location does not exist", body = String, content_type = "text/plain", example = "Not found"),
+        (status = 400, description = "**Bad Request.** The `costing_options` are not valid for the selected `route_costing` or the start/destination is missing or not a valid coordinate", body = ApiError, content_type = "application/json", example = json!({"error": "unknown field `use_hils`, expected one of ... at line 1 column 11", "code": "invalid_costing_options"})),
+        (status = 404, description = "**Not found.** The requested location does not exist", body = ApiError, content_type = "application/json", example = json!({"error": "Not found", "code": "not_found"})),
+        (status = 422, description = "**Unprocessable Entity.** The requested location exists, but is not a room, building or point of interest (e.g. a whole campus)", body = ApiError, content_type = "application/json", example = json!({"error": "garching is not a routable location", "code": "not_routable"})),
+        (status = 500, description = "**Internal Server Error.** We could not resolve the locations or generate a route", body = ApiError, content_type = "application/json", example = json!({"error": "Could not generate a route, please try again later", "code": "routing_failed"})),
+        (status = 501, description = "**Not Implemented.** The requested transport mode is not yet supported", body = ApiError, content_type = "application/json", example = json!({"error": "public transit routing is not yet implemented", "code": "not_implemented"})),
     )
 )]
 #[get("/api/maps/route")]
+#[tracing::instrument(skip(data, metrics))]
 pub async fn route_handler(
     args: web::Query<RoutingRequest>,
     data: web::Data<crate::AppData>,
+    metrics: web::Data<RouteMetrics>,
 ) -> HttpResponse {
-    let from = args.from.try_resolve_coordinates(&data.pool).await;
-    let to = args.to.try_resolve_coordinates(&data.pool).await;
-    let (from, to) = match (from, to) {
-        (Ok(Some(from)), Ok(Some(to))) => (from, to),
-        (Ok(None), _) | (_, Ok(None)) => {
-            return HttpResponse::NotFound()
-                .content_type("text/plain")
-                .body("Not found");
-        }
-        (Err(e), _) | (_, Err(e)) => {
-            error!(from=?args.from,to=?args.to,error = ?e,"could not resolve into coordinates");
-            return HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Failed to resolve key");
+    match counted_route(&args, &data, &metrics).await {
+        Ok(route) => HttpResponse::Ok().json(route),
+        Err(e) => e.into(),
+    }
+}
+
+/// How many routes can be requested at once via [`bulk_route_handler`]
+const MAX_BULK_ROUTES: usize = 50;
+/// How many routes of one bulk request are calculated at once
+///
+/// Bulk requests are not latency sensitive, so they should not crowd out interactive ones at Valhalla.
+/// How many bulk requests a client can make is limited by the governor in front of [`bulk_route_handler`].
+const MAX_CONCURRENT_BULK_ROUTES: usize = 4;
+
+/// Outcome of one of the routes of a bulk request
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum BulkRoutingResult {
+    Route(RoutingResponse),
+    /// Why this route could not be calculated, with the same `code`s as [`/api/maps/route`](#tag/maps/operation/route_handler)
+    Error(ApiError),
+}
+impl From<Result<RoutingResponse, ApiError>> for BulkRoutingResult {
+    fn from(value: Result<RoutingResponse, ApiError>) -> Self {
+        match value {
+            Ok(route) => BulkRoutingResult::Route(route),
+            Err(e) => BulkRoutingResult::Error(e),
         }
-    };
+    }
+}
 
-    if args.route_costing == CostingRequest::PublicTransit {
-        return HttpResponse::NotImplemented()
-            .content_type("text/plain")
-            .body("public transit routing is not yet implemented");
+/// Routing requests in bulk
+///
+/// ***Do not abuse this endpoint.***
+///
+/// Calculates many routes at once, e.g. for analytics.
+/// Each entry takes the same parameters as [`/api/maps/route`](#tag/maps/operation/route_handler), as a JSON object.
+///
+/// The results are in the same order as the requested routes.
+/// Routes which cannot be calculated don't fail the whole request, but are reported as an `error` in their place.
+/// At most 50 routes can be requested at once.
+///
+/// Bulk requests are rate-limited per client, the `RateLimit-Limit`, `RateLimit-Remaining` and `Retry-After` headers tell you how many requests are left and when to try again.
+#[utoipa::path(
+    tags=["maps"],
+    request_body(content = Vec<RoutingRequest>, example = json!([{"from": "5602.EG.001", "to": "mi", "route_costing": "pedestrian"}, {"from_lat": 48.26, "from_lon": 11.67, "to": "mw", "route_costing": "bicycle"}])),
+    responses(
+        (status = 200, description = "**Routing solutions** or why they could not be calculated, in the order they were requested", body = Vec<BulkRoutingResult>, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The body is not a list of routing requests", body = ApiError, content_type = "application/json", example = json!({"error": "Json deserialize error: missing field `route_costing` at line 1 column 30", "code": "invalid_body"})),
+        (status = 413, description = "**Payload too large.** At most 50 routes can be requested at once", body = ApiError, content_type = "application/json", example = json!({"error": "At most 50 routes can be requested at once", "code": "too_many_routes"})),
+        (status = 429, description = "**Too many requests.** We are rate-limiting bulk requests per client. The `Retry-After` header tells you when to try again.", body = ApiError, content_type = "application/json", example = json!({"error": "Too many requests, please try again in 60s", "code": "rate_limited"})),
+    )
+)]
+#[post("")]
+#[tracing::instrument(skip(args, data, metrics), fields(routes = args.len()))]
+pub async fn bulk_route_handler(
+    args: web::Json<Vec<RoutingRequest>>,
+    data: web::Data<crate::AppData>,
+    metrics: web::Data<RouteMetrics>,
+) -> HttpResponse {
+    if args.len() > MAX_BULK_ROUTES {
+        return ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "too_many_routes",
+            format!("At most {MAX_BULK_ROUTES} routes can be requested at once"),
+        )
+        .into();
     }
+    let (data, metrics) = (&data, &metrics);
+    let results = futures::stream::iter(args.iter())
+        .map(|args| counted_route(args, data, metrics))
+        .buffered(MAX_CONCURRENT_BULK_ROUTES)
+        .map(BulkRoutingResult::from)
+        .collect::<Vec<_>>()
+        .await;
+    HttpResponse::Ok().json(results)
+}
 
-    let routing = data
-        .valhalla
-        .route(
-            (from.lat as f32, from.lon as f32),
-            (to.lat as f32, to.lon as f32),
-            Costing::from(args.deref()),
-            args.lang.should_use_english(),
+#[serde_with::serde_as]
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+struct CompareRoutesRequest {
+    #[serde(flatten, default)]
+    lang: localisation::LangQueryArgs,
+    /// Start of the route
+    ///
+    /// Alternatively, `from_lat` and `from_lon` can be specified
+    from: Option<RequestedLocation>,
+    /// Latitude of the start of the route, if `from` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    from_lat: Option<f64>,
+    /// Longitude of the start of the route, if `from` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    from_lon: Option<f64>,
+    /// Destination of the route
+    ///
+    /// Alternatively, `to_lat` and `to_lon` can be specified
+    to: Option<RequestedLocation>,
+    /// Latitude of the destination of the route, if `to` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    to_lat: Option<f64>,
+    /// Longitude of the destination of the route, if `to` is not specified
+    #[serde_as(as = "Option<serde_with::PickFirst<(_, serde_with::DisplayFromStr)>>")]
+    #[serde(default)]
+    to_lon: Option<f64>,
+    /// Does the user have specific walking restrictions?
+    #[serde(default)]
+    pedestrian_type: PedestrianTypeRequest,
+    /// Does the user prefer mopeds or motorcycles for powered two-wheeled (ptw)?
+    #[serde(default)]
+    ptw_type: PoweredTwoWheeledRestrictionRequest,
+    /// Which kind of bicycle do you ride?
+    #[serde(default)]
+    bicycle_type: BicycleRestrictionRequest,
+    /// Should covered ways be preferred over walking outside?
+    #[serde_as(as = "serde_with::PickFirst<(_, serde_with::DisplayFromStr)>")]
+    #[serde(default)]
+    prefer_indoor: bool,
+    /// When the trip starts, as an [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp
+    ///
+    /// Defaults to now.
+    #[schema(example = "2025-02-20T14:05:00+01:00")]
+    #[serde(default)]
+    departure_time: Option<DateTime<FixedOffset>>,
+    /// Should the `legs` (i.e. the maneuvers and shape) of each route be included?
+    ///
+    /// Without them, the response is a lot smaller.
+    #[serde_as(as = "serde_with::PickFirst<(_, serde_with::DisplayFromStr)>")]
+    #[serde(default)]
+    include_legs: bool,
+}
+
+impl CompareRoutesRequest {
+    fn costing(&self, route_costing: CostingRequest) -> Result<Costing, ApiError> {
+        Costing::try_from(CostingSelection {
+            route_costing,
+            pedestrian_type: self.pedestrian_type,
+            ptw_type: self.ptw_type,
+            bicycle_type: self.bicycle_type,
+            prefer_indoor: self.prefer_indoor,
+            costing_options: None,
+        })
+        .map_err(invalid_costing_options)
+    }
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct CompareRoutesResponse {
+    /// When the trips start, i.e. the requested `departure_time` or now
+    #[schema(example = "2025-02-20T14:05:00+01:00")]
+    departure_time: DateTime<FixedOffset>,
+    /// The route for each `route_costing`
+    routes: BTreeMap<String, ComparisonResult>,
+}
+
+/// Outcome of routing with one of the transport modes
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ComparisonResult {
+    Route(ComparedRoute),
+    /// Why no route could be calculated for this transport mode, with the same `code`s as [`/api/maps/route`](#tag/maps/operation/route_handler)
+    Error(ApiError),
+}
+impl From<Result<ComparedRoute, ApiError>> for ComparisonResult {
+    fn from(value: Result<ComparedRoute, ApiError>) -> Self {
+        match value {
+            Ok(route) => ComparisonResult::Route(route),
+            Err(e) => ComparisonResult::Error(e),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, utoipa::ToSchema)]
+struct ComparedRoute {
+    /// Trip summary
+    summary: SummaryResponse,
+    /// When the destination is reached, in the timezone of the `departure_time`
+    #[schema(example = "2025-02-20T14:32:00+01:00")]
+    arrival_time: DateTime<FixedOffset>,
+    /// The `arrival_time` as a clock time in the requested language
+    #[schema(examples("14:32", "2:32 PM"))]
+    arrival_clock_time: String,
+    /// Only included if `include_legs` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    legs: Option<Vec<LegResponse>>,
+}
+impl ComparedRoute {
+    fn new(route: RoutingResponse, include_legs: bool) -> Self {
+        ComparedRoute {
+            summary: route.summary,
+            arrival_time: route.arrival_time,
+            arrival_clock_time: route.arrival_clock_time,
+            legs: include_legs.then_some(route.legs),
+        }
+    }
+}
+
+/// Compare transport modes
+///
+/// **API IS EXPERIMENTAL AND ACTIVELY SUBJECT TO CHANGE**
+///
+/// Calculates the route between origin (`from`) and destination (`to`) for every transport mode at once, so that users can compare how long each takes.
+/// Takes the same parameters as [`/api/maps/route`](#tag/maps/operation/route_handler), except `route_costing` and `costing_options`.
+///
+/// Only the start and destination failing to resolve fails the whole request.
+/// If no route can be calculated for one of the transport modes, it has an `error` instead of a `route`.
+#[utoipa::path(
+    tags=["maps"],
+    params(CompareRoutesRequest),
+    responses(
+        (status = 200, description = "**Routes by transport mode** or why they could not be calculated", body = CompareRoutesResponse, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The start/destination is missing or not a valid coordinate", body = ApiError, content_type = "application/json", example = json!({"error": "Either `from` or both `from_lat` and `from_lon` are required", "code": "missing_location"})),
+        (status = 404, description = "**Not found.** The requested location does not exist", body = ApiError, content_type = "application/json", example = json!({"error": "Not found", "code": "not_found"})),
+        (status = 422, description = "**Unprocessable Entity.** The requested location exists, but is not a room, building or point of interest (e.g. a whole campus)", body = ApiError, content_type = "application/json", example = json!({"error": "garching is not a routable location", "code": "not_routable"})),
+        (status = 500, description = "**Internal Server Error.** We could not resolve the locations", body = ApiError, content_type = "application/json", example = json!({"error": "Failed to resolve key", "code": "internal_error"})),
+    )
+)]
+#[get("/api/maps/route/compare")]
+#[tracing::instrument(skip(data, metrics))]
+pub async fn compare_routes_handler(
+    args: web::Query<CompareRoutesRequest>,
+    data: web::Data<crate::AppData>,
+    metrics: web::Data<RouteMetrics>,
+) -> HttpResponse {
+    match compare_routes(&args, &data, &metrics).await {
+        Ok(routes) => HttpResponse::Ok().json(routes),
+        Err(e) => e.into(),
+    }
+}
+
+/// Resolves the start and destination once and routes with all transport modes concurrently
+async fn compare_routes(
+    args: &CompareRoutesRequest,
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+) -> Result<CompareRoutesResponse, ApiError> {
+    let requested = [
+        requested_location("from", args.from.as_ref(), args.from_lat, args.from_lon)?,
+        requested_location("to", args.to.as_ref(), args.to_lat, args.to_lon)?,
+    ];
+    let ends = resolve_ends(data, &requested).await?;
+    let departure_time = args
+        .departure_time
+        .unwrap_or_else(|| campus_time(Utc::now()));
+    let requested = &requested;
+    let routes = futures::future::join_all(CostingRequest::ALL.map(|route_costing| async move {
+        let route = compared_route(
+            args,
+            data,
+            metrics,
+            route_costing,
+            requested,
+            ends,
+            departure_time,
         )
         .await;
-    let response = match routing {
-        Ok(response) => response,
+        count_outcome(metrics, route_costing, &route);
+        (
+            route_costing.as_label().to_string(),
+            ComparisonResult::from(route),
+        )
+    }))
+    .await;
+    Ok(CompareRoutesResponse {
+        departure_time,
+        routes: routes.into_iter().collect(),
+    })
+}
+
+async fn compared_route(
+    args: &CompareRoutesRequest,
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+    route_costing: CostingRequest,
+    requested: &[RequestedLocation; 2],
+    ends: [Coordinate; 2],
+    departure_time: DateTime<FixedOffset>,
+) -> Result<ComparedRoute, ApiError> {
+    if !route_costing.is_implemented() {
+        return Err(transit_not_implemented());
+    }
+    let costing = args.costing(route_costing)?;
+    let (from, to) = to_locations(requested, ends);
+    let trip = valhalla_trip(
+        data,
+        metrics,
+        route_costing,
+        (from, to, costing),
+        args.lang.should_use_english(),
+    )
+    .await?;
+    let route = RoutingResponse::new(trip, departure_time, args.lang);
+    Ok(ComparedRoute::new(route, args.include_legs))
+}
+
+/// [`route`], counting the outcome in the metrics
+async fn counted_route(
+    args: &RoutingRequest,
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+) -> Result<RoutingResponse, ApiError> {
+    let route = route(args, data, metrics).await;
+    count_outcome(metrics, args.route_costing, &route);
+    route
+}
+
+fn count_outcome<T>(
+    metrics: &RouteMetrics,
+    costing: CostingRequest,
+    outcome: &Result<T, ApiError>,
+) {
+    let status = match outcome {
+        Ok(_) => StatusCode::OK,
+        Err(e) => e.status_code(),
+    };
+    metrics
+        .requests
+        .with_label_values(&[costing.as_label(), status.as_str()])
+        .inc();
+}
+
+/// Parses and resolves the request into what valhalla needs
+async fn prepare(
+    args: &RoutingRequest,
+    data: &crate::AppData,
+) -> Result<(Location, Location, Costing), ApiError> {
+    let (requested, ends, costing) = prepare_ends(args, data).await?;
+    let (from, to) = to_locations(&requested, ends);
+    Ok((from, to, costing))
+}
+
+/// [`prepare`], but keeping the resolved ends to route between them again
+async fn prepare_ends(
+    args: &RoutingRequest,
+    data: &crate::AppData,
+) -> Result<([RequestedLocation; 2], [Coordinate; 2], Costing), ApiError> {
+    let costing = Costing::try_from(CostingSelection {
+        route_costing: args.route_costing,
+        pedestrian_type: args.pedestrian_type,
+        ptw_type: args.ptw_type,
+        bicycle_type: args.bicycle_type,
+        prefer_indoor: args.prefer_indoor,
+        costing_options: args.costing_options.as_deref(),
+    })
+    .map_err(invalid_costing_options)?;
+    let requested = [args.origin()?, args.destination()?];
+    let ends = resolve_ends(data, &requested).await?;
+
+    if !args.route_costing.is_implemented() {
+        return Err(transit_not_implemented());
+    }
+    Ok((requested, ends, costing))
+}
+
+fn to_locations(
+    requested: &[RequestedLocation; 2],
+    [from, to]: [Coordinate; 2],
+) -> (Location, Location) {
+    (requested[0].to_location(from), requested[1].to_location(to))
+}
+
+/// Looks up the coordinates of the start and destination of a route
+///
+/// Coordinates are passed on as-is => the database is only queried for our keys.
+async fn resolve_ends(
+    data: &crate::AppData,
+    requested: &[RequestedLocation; 2],
+) -> Result<[Coordinate; 2], ApiError> {
+    let resolved = match RequestedLocation::try_resolve_all_coordinates(
+        &data.pool,
+        &data.coordinates,
+        requested,
+        true,
+    )
+    .await
+    {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            error!(?requested,error = ?e,"could not resolve into coordinates");
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Failed to resolve key",
+            ));
+        }
+    };
+    let mut resolved = resolved
+        .into_iter()
+        .zip(requested)
+        .map(|(resolution, requested)| resolution.into_result(requested));
+    let from = resolved.next().expect("both locations were resolved")?;
+    let to = resolved.next().expect("both locations were resolved")?;
+    Ok([from, to])
+}
+
+fn invalid_costing_options(e: serde_json::Error) -> ApiError {
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "invalid_costing_options",
+        e.to_string(),
+    )
+}
+
+fn transit_not_implemented() -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "not_implemented",
+        "public transit routing is not yet implemented",
+    )
+}
+
+/// Raw routing solution for debugging
+///
+/// **Requires an admin token.**
+///
+/// Takes the same parameters as [`/api/maps/route`](#tag/maps/operation/route_handler), but returns the trip exactly as Valhalla returned it.
+/// This allows diagnosing odd routes without reproducing the request against Valhalla.
+/// The format is Valhallas and may change with any Valhalla update.
+#[utoipa::path(
+    tags=["maps"],
+    params(RoutingRequest),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "**Trip as returned by Valhalla**", body = Object, content_type = "application/json"),
+        (status = 400, description = "**Bad Request.** The `costing_options` are not valid for the selected `route_costing` or the start/destination is missing or not a valid coordinate", body = ApiError, content_type = "application/json", example = json!({"error": "unknown field `use_hils`, expected one of ... at line 1 column 11", "code": "invalid_costing_options"})),
+        (status = 401, description = "**Unauthorized.** No or an invalid admin token was provided", body = ApiError, content_type = "application/json", example = json!({"error": "A valid admin token is required for this endpoint", "code": "unauthorized"})),
+        (status = 404, description = "**Not found.** The requested location does not exist", body = ApiError, content_type = "application/json", example = json!({"error": "Not found", "code": "not_found"})),
+        (status = 500, description = "**Internal Server Error.** We could not resolve the locations or generate a route", body = ApiError, content_type = "application/json", example = json!({"error": "Could not generate a route, please try again later", "code": "routing_failed"})),
+        (status = 503, description = "**Not configured.** Administrative endpoints are not configured on this server", body = ApiError, content_type = "application/json", example = json!({"error": "Administrative endpoints are not configured on this server.", "code": "admin_not_configured"})),
+    )
+)]
+#[get("/api/maps/route/debug")]
+#[tracing::instrument(skip(req, data))]
+pub async fn route_debug_handler(
+    req: HttpRequest,
+    args: web::Query<RoutingRequest>,
+    data: web::Data<crate::AppData>,
+) -> HttpResponse {
+    if let Err(e) = admin::authorise(&req) {
+        return e.into();
+    }
+    let (from, to, costing) = match prepare(&args, &data).await {
+        Ok(prepared) => prepared,
+        Err(e) => return e.into(),
+    };
+    match data
+        .valhalla
+        .route_raw(from, to, costing, args.lang.should_use_english())
+        .await
+    {
+        Ok(trip) => HttpResponse::Ok().json(trip),
         Err(e) => {
             error!(error=?e,"error routing");
-            return HttpResponse::InternalServerError()
-                .content_type("text/plain")
-                .body("Could not generate a route, please try again later");
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "routing_failed",
+                "Could not generate a route, please try again later",
+            )
+            .into()
         }
+    }
+}
+
+async fn route(
+    args: &RoutingRequest,
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+) -> Result<RoutingResponse, ApiError> {
+    let (requested, ends, costing) = prepare_ends(args, data).await?;
+    let ((trip, used_fallback), english) = if args.lang == RouteLanguageRequest::Both {
+        // concurrently, as waiting for one after the other would double the latency
+        let (german, english) = join!(
+            trip_with_fallback(
+                args,
+                data,
+                metrics,
+                &requested,
+                ends,
+                costing.clone(),
+                false
+            ),
+            trip_with_fallback(args, data, metrics, &requested, ends, costing, true),
+        );
+        (german?, Some(english?))
+    } else {
+        let should_use_english = args.lang.should_use_english();
+        let trip = trip_with_fallback(
+            args,
+            data,
+            metrics,
+            &requested,
+            ends,
+            costing,
+            should_use_english,
+        )
+        .await?;
+        (trip, None)
     };
+
+    let departure_time = args
+        .departure_time
+        .unwrap_or_else(|| campus_time(Utc::now()));
+    let mut response = RoutingResponse::new(trip, departure_time, args.lang.primary());
+    response.used_fallback = used_fallback;
+    if let Some((english, english_used_fallback)) = english {
+        if english_used_fallback != used_fallback || !response.add_english_instructions(english) {
+            error!(
+                ?requested,
+                "the german and english routes differ, cannot combine their instructions"
+            );
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "routing_failed",
+                "Could not generate a route, please try again later",
+            ));
+        }
+    }
+    if args.detail == RouteDetailRequest::Overview {
+        for leg in &mut response.legs {
+            leg.maneuvers = overview_maneuvers(std::mem::take(&mut leg.maneuvers));
+        }
+    }
+    Ok(response)
+}
+
+/// Asks valhalla for the trip, retrying as `pedestrian` if there is no route and the request allows it
+///
+/// Returns whether the retry was used.
+async fn trip_with_fallback(
+    args: &RoutingRequest,
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+    requested: &[RequestedLocation; 2],
+    ends: [Coordinate; 2],
+    costing: Costing,
+    should_use_english: bool,
+) -> Result<(Trip, bool), ApiError> {
+    let (from, to) = to_locations(requested, ends);
+    let routing = valhalla_route(
+        data,
+        metrics,
+        args.route_costing,
+        (from, to, costing),
+        should_use_english,
+    )
+    .await;
+    match routing {
+        Ok(trip) => Ok((trip, false)),
+        // other failures (e.g. valhalla being unavailable) would likely fail for pedestrians as well
+        Err(e)
+            if valhalla::is_no_route(&e)
+                && args.fallback_to_pedestrian
+                && args.route_costing != CostingRequest::Pedestrian =>
+        {
+            info!(
+                route_costing = args.route_costing.as_label(),
+                ?requested,
+                "no route found, falling back to pedestrian"
+            );
+            let costing = Costing::try_from(CostingSelection {
+                route_costing: CostingRequest::Pedestrian,
+                pedestrian_type: args.pedestrian_type,
+                ptw_type: args.ptw_type,
+                bicycle_type: args.bicycle_type,
+                prefer_indoor: args.prefer_indoor,
+                costing_options: None,
+            })
+            .map_err(invalid_costing_options)?;
+            let (from, to) = to_locations(requested, ends);
+            let fallback = valhalla_trip(
+                data,
+                metrics,
+                CostingRequest::Pedestrian,
+                (from, to, costing),
+                should_use_english,
+            )
+            .await;
+            // the error of the requested mode is what the client asked about
+            Ok((fallback.map_err(|_| routing_failed(&e))?, true))
+        }
+        Err(e) => Err(routing_failed(&e)),
+    }
+}
+
+/// Asks valhalla for the trip, timing how long that takes
+async fn valhalla_trip(
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+    route_costing: CostingRequest,
+    locations_and_costing: (Location, Location, Costing),
+    should_use_english: bool,
+) -> Result<Trip, ApiError> {
+    valhalla_route(
+        data,
+        metrics,
+        route_costing,
+        locations_and_costing,
+        should_use_english,
+    )
+    .await
+    .map_err(|e| routing_failed(&e))
+}
+
+/// Like [`valhalla_trip`], keeping the error of valhalla
+async fn valhalla_route(
+    data: &crate::AppData,
+    metrics: &RouteMetrics,
+    route_costing: CostingRequest,
+    (from, to, costing): (Location, Location, Costing),
+    should_use_english: bool,
+) -> anyhow::Result<Trip> {
+    let timer = metrics
+        .valhalla_duration
+        .with_label_values(&[route_costing.as_label()])
+        .start_timer();
+    let routing = data
+        .valhalla
+        .route(from, to, costing, should_use_english)
+        .await;
+    timer.observe_duration();
+    let response = routing?;
     debug!(routing_solution=?response,"got routing solution");
+    Ok(response)
+}
 
-    HttpResponse::Ok().json(RoutingResponse::from(response))
+fn routing_failed(error: &anyhow::Error) -> ApiError {
+    error!(error=?error,"error routing");
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "routing_failed",
+        "Could not generate a route, please try again later",
+    )
 }
 #[derive(Serialize, Debug, utoipa::ToSchema)]
 struct RoutingResponse {
@@ -285,14 +1254,126 @@ struct RoutingResponse {
     legs: Vec<LegResponse>,
     /// Trip summary
     summary: SummaryResponse,
+    /// When the trip starts, i.e. the requested `departure_time` or now
+    #[schema(example = "2025-02-20T14:05:00+01:00")]
+    departure_time: DateTime<FixedOffset>,
+    /// When the destination is reached, in the timezone of the `departure_time`
+    ///
+    /// For public transit, this is based on the scheduled arrival at the last stop.
+    #[schema(example = "2025-02-20T14:32:00+01:00")]
+    arrival_time: DateTime<FixedOffset>,
+    /// The `arrival_time` as a clock time in the requested language
+    #[schema(examples("14:32", "2:32 PM"))]
+    arrival_clock_time: String,
+    /// Whether this is a `pedestrian` route, as no route could be calculated for the requested `route_costing`
+    ///
+    /// Only ever set, if `fallback_to_pedestrian` was requested.
+    used_fallback: bool,
 }
-impl From<Trip> for RoutingResponse {
-    fn from(value: Trip) -> Self {
+impl RoutingResponse {
+    /// Maps the trip valhalla calculated to our response
+    ///
+    /// Independent of the request, so that the mapping can be tested against recorded responses of valhalla (see `fixtures/`).
+    fn new(
+        trip: Trip,
+        departure_time: DateTime<FixedOffset>,
+        lang: localisation::LangQueryArgs,
+    ) -> Self {
+        let legs = trip
+            .legs
+            .into_iter()
+            .map(LegResponse::from)
+            .collect::<Vec<_>>();
+        let summary = SummaryResponse::from(trip.summary);
+        let arrival_time = arrival_time(&legs, departure_time, summary.time_seconds);
         RoutingResponse {
-            legs: value.legs.into_iter().map(LegResponse::from).collect(),
-            summary: SummaryResponse::from(value.summary),
+            legs,
+            summary,
+            departure_time,
+            arrival_time,
+            arrival_clock_time: clock_time(arrival_time, lang),
+            used_fallback: false,
+        }
+    }
+
+    /// Adds the instructions of the same route in English as `instruction_en` and the current ones as `instruction_de`
+    ///
+    /// Returns `false` (and leaves the route as is), if the `english` trip has different maneuvers.
+    fn add_english_instructions(&mut self, english: Trip) -> bool {
+        let english = english
+            .legs
+            .into_iter()
+            .map(LegResponse::from)
+            .collect::<Vec<_>>();
+        let same_maneuvers = self.legs.len() == english.len()
+            && self.legs.iter().zip(&english).all(|(leg, english)| {
+                leg.maneuvers.len() == english.maneuvers.len()
+                    && leg.maneuvers.iter().zip(&english.maneuvers).all(|(m, e)| {
+                        m.travel_mode == e.travel_mode
+                            && m.begin_shape_index == e.begin_shape_index
+                            && m.end_shape_index == e.end_shape_index
+                    })
+            });
+        if !same_maneuvers {
+            return false;
+        }
+        for (leg, english) in self.legs.iter_mut().zip(english) {
+            for (maneuver, english) in leg.maneuvers.iter_mut().zip(english.maneuvers) {
+                maneuver.instruction_de = Some(maneuver.instruction.clone());
+                maneuver.instruction_en = Some(english.instruction);
+            }
+        }
+        true
+    }
+}
+
+/// When a trip starting at `departure_time` and taking `time_seconds` arrives
+///
+/// Transit follows its schedule instead of departing right away.
+/// => Arrival at the last stop as scheduled, plus the time of the maneuvers after it.
+fn arrival_time(
+    legs: &[LegResponse],
+    departure_time: DateTime<FixedOffset>,
+    time_seconds: f64,
+) -> DateTime<FixedOffset> {
+    let mut scheduled = None;
+    let mut seconds_after_schedule = 0.0;
+    for maneuver in legs.iter().flat_map(|leg| &leg.maneuvers) {
+        let last_stop = maneuver
+            .transit_info
+            .as_ref()
+            .and_then(|info| info.transit_stops.last());
+        match last_stop {
+            Some(stop) => {
+                // valhalla returns the local time of the stop
+                scheduled = departure_time
+                    .offset()
+                    .from_local_datetime(&stop.arrival_date_time)
+                    .single();
+                seconds_after_schedule = 0.0;
+            }
+            None => seconds_after_schedule += maneuver.time_seconds,
         }
     }
+    let (start, seconds) = match scheduled {
+        Some(scheduled) => (scheduled, seconds_after_schedule),
+        None => (departure_time, time_seconds),
+    };
+    start + Duration::milliseconds((seconds * 1000.0).round() as i64)
+}
+
+/// Formatted like clocks in the language are, e.g. `14:32` or `2:32 PM`
+fn clock_time(time: DateTime<FixedOffset>, lang: localisation::LangQueryArgs) -> String {
+    if lang.should_use_english() {
+        time.format("%-I:%M %p").to_string()
+    } else {
+        time.format("%H:%M").to_string()
+    }
+}
+
+/// The time at our campuses, i.e. in `Europe/Berlin`
+fn campus_time(now: DateTime<Utc>) -> DateTime<FixedOffset> {
+    now.with_timezone(&Berlin).fixed_offset()
 }
 #[derive(Serialize, Debug, utoipa::ToSchema)]
 struct SummaryResponse {
@@ -320,10 +1401,15 @@ struct SummaryResponse {
     /// Maximum longitude of the sections bounding box
     #[schema(example = 48.26244490906312)]
     max_lon: f64,
+    /// Center of the sections bounding box
+    ///
+    /// Together with the bounding box, this allows centering the map on the section without further calculations.
+    center: Coordinate,
 }
 impl From<Summary> for SummaryResponse {
     fn from(value: Summary) -> Self {
         SummaryResponse {
+            center: bbox_center(value.min_lat, value.min_lon, value.max_lat, value.max_lon),
             time_seconds: value.time,
             length_meters: value.length * 1000.0,
             has_toll: value.has_toll,
@@ -337,6 +1423,16 @@ impl From<Summary> for SummaryResponse {
     }
 }
 
+/// Center of a bounding box
+///
+/// Routes are short enough that the curvature of the earth does not matter.
+fn bbox_center(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> Coordinate {
+    Coordinate {
+        lat: (min_lat + max_lat) / 2.0,
+        lon: (min_lon + max_lon) / 2.0,
+    }
+}
+
 #[derive(Serialize, Debug, utoipa::ToSchema)]
 struct LegResponse {
     summary: SummaryResponse,
@@ -356,12 +1452,37 @@ impl From<Leg> for LegResponse {
         }
     }
 }
+
+/// Merges minor maneuvers into the maneuver before them, for [`RouteDetailRequest::Overview`]
+///
+/// Transit maneuvers and changes of the travel mode are never merged, as they are always relevant.
+fn overview_maneuvers(maneuvers: Vec<ManeuverResponse>) -> Vec<ManeuverResponse> {
+    let mut overview: Vec<ManeuverResponse> = Vec::with_capacity(maneuvers.len());
+    for maneuver in maneuvers {
+        match overview.last_mut() {
+            Some(previous) if maneuver.is_minor() && previous.can_absorb(&maneuver) => {
+                previous.absorb(maneuver);
+            }
+            _ => overview.push(maneuver),
+        }
+    }
+    overview
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(Serialize, Debug, utoipa::ToSchema)]
 struct ManeuverResponse {
     r#type: ManeuverTypeResponse,
 
     instruction: String,
+    /// The `instruction` in German, only present for `lang=both`
+    #[schema(examples("Gehen Sie nach Norden auf Boltzmannstraße"))]
+    instruction_de: Option<String>,
+    /// The `instruction` in English, only present for `lang=both`
+    ///
+    /// The verbal instructions are only available in German.
+    #[schema(examples("Walk north on Boltzmannstraße"))]
+    instruction_en: Option<String>,
 
     /// Text suitable for use as a verbal alert in a navigation application
     ///
@@ -437,6 +1558,51 @@ struct ManeuverResponse {
     /// Travel mode
     #[schema(examples("drive", "pedestrian", "bicycle", "public_transit"))]
     travel_mode: TravelModeResponse,
+    /// How many maneuvers this step summarises
+    ///
+    /// Only present for `detail=overview` and if minor maneuvers were merged into this one.
+    /// The time, length and shape indices cover all merged maneuvers, the instructions are the ones of this maneuver.
+    #[schema(example = 3)]
+    collapsed_maneuvers: Option<usize>,
+}
+impl ManeuverResponse {
+    /// Whether the maneuver only continues along the way, instead of changing direction or mode
+    fn is_minor(&self) -> bool {
+        self.transit_info.is_none()
+            && matches!(
+                self.r#type,
+                ManeuverTypeResponse::Continue
+                    | ManeuverTypeResponse::Becomes
+                    | ManeuverTypeResponse::StayStraight
+                    | ManeuverTypeResponse::StayLeft
+                    | ManeuverTypeResponse::StayRight
+                    | ManeuverTypeResponse::SlightLeft
+                    | ManeuverTypeResponse::SlightRight
+            )
+    }
+    fn can_absorb(&self, next: &ManeuverResponse) -> bool {
+        self.transit_info.is_none() && self.travel_mode == next.travel_mode
+    }
+    /// Extends this maneuver by the `next` one
+    ///
+    /// The verbal post transition instruction mentions the length of only this maneuver => it is dropped.
+    fn absorb(&mut self, next: ManeuverResponse) {
+        let any = |a: Option<bool>, b: Option<bool>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or_default() || b.unwrap_or_default()),
+        };
+        self.time_seconds += next.time_seconds;
+        self.length_meters += next.length_meters;
+        self.end_shape_index = next.end_shape_index;
+        self.toll = any(self.toll, next.toll);
+        self.highway = any(self.highway, next.highway);
+        self.rough = any(self.rough, next.rough);
+        self.gate = any(self.gate, next.gate);
+        self.ferry = any(self.ferry, next.ferry);
+        self.verbal_post_transition_instruction = None;
+        self.collapsed_maneuvers =
+            Some(self.collapsed_maneuvers.unwrap_or(1) + next.collapsed_maneuvers.unwrap_or(1));
+    }
 }
 impl From<Maneuver> for ManeuverResponse {
     fn from(value: Maneuver) -> Self {
@@ -447,6 +1613,8 @@ impl From<Maneuver> for ManeuverResponse {
                 .strip_suffix(".")
                 .map(|s| s.to_string())
                 .unwrap_or(value.instruction),
+            instruction_de: None,
+            instruction_en: None,
             verbal_transition_alert_instruction: value.verbal_transition_alert_instruction,
             verbal_pre_transition_instruction: value.verbal_pre_transition_instruction,
             verbal_post_transition_instruction: value.verbal_post_transition_instruction,
@@ -469,6 +1637,7 @@ impl From<Maneuver> for ManeuverResponse {
             transit_info: value.transit_info.map(TransitInfoResponse::from),
             verbal_multi_cue: value.verbal_multi_cue,
             travel_mode: TravelModeResponse::from(value.travel_mode),
+            collapsed_maneuvers: None,
         }
     }
 }
@@ -629,6 +1798,11 @@ Late night service is local"#))]
     operator_url: String,
     /// A list of the stops/stations associated with a specific transit route
     transit_stops: Vec<TransitStopResponse>,
+    /// `true` if the times of this leg are based on real-time data, `false` if they are scheduled
+    ///
+    /// Currently always `false`, as Valhalla only routes on static GTFS schedules.
+    /// Whether a stop had to fall back to an assumed schedule is indicated by its `assumed_schedule`.
+    realtime: bool,
 }
 impl From<TransitInfo> for TransitInfoResponse {
     fn from(value: TransitInfo) -> Self {
@@ -648,10 +1822,12 @@ impl From<TransitInfo> for TransitInfoResponse {
                 .into_iter()
                 .map(TransitStopResponse::from)
                 .collect(),
+            // valhalla does not report whether real-time data was used
+            realtime: false,
         }
     }
 }
-#[derive(Serialize, Debug, utoipa::ToSchema)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
 enum TravelModeResponse {
     Drive,
@@ -720,3 +1896,610 @@ impl From<TransitStopType> for TransitStopTypeResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_requested_location_accuracy() {
+        let location: RequestedLocation =
+            serde_json::from_str(r#"{"lat":48.1,"lon":11.5}"#).unwrap();
+        let RequestedLocation::Coordinate(coordinate) = location else {
+            panic!("expected a coordinate, got {location:?}");
+        };
+        assert_eq!(coordinate.accuracy_m, None);
+        assert_eq!(coordinate.snapping_radius(), None);
+
+        let location: RequestedLocation =
+            serde_json::from_str(r#"{"lat":48.1,"lon":11.5,"accuracy_m":12.3}"#).unwrap();
+        let RequestedLocation::Coordinate(coordinate) = location else {
+            panic!("expected a coordinate, got {location:?}");
+        };
+        assert_eq!(coordinate.snapping_radius(), Some(13));
+
+        let location: RequestedLocation = serde_json::from_str(r#""5602.EG.001""#).unwrap();
+        assert_eq!(location, RequestedLocation::Location("5602.EG.001".into()));
+    }
+
+    #[test]
+    fn test_plain_coordinates_in_query() {
+        let args = web::Query::<RoutingRequest>::from_query(
+            "from_lat=48.1&from_lon=11.5&to=5602.EG.001&route_costing=pedestrian&lang=en",
+        )
+        .unwrap();
+        assert!(args.lang.should_use_english());
+        assert_eq!(
+            args.origin().unwrap(),
+            RequestedLocation::Coordinate(RequestedCoordinate {
+                coordinate: Coordinate {
+                    lat: 48.1,
+                    lon: 11.5
+                },
+                accuracy_m: None,
+            })
+        );
+        assert_eq!(
+            args.destination().unwrap(),
+            RequestedLocation::Location("5602.EG.001".into())
+        );
+        assert!(!args.prefer_indoor);
+        assert!(!args.fallback_to_pedestrian);
+        let args = web::Query::<RoutingRequest>::from_query(
+            "from=5602.EG.001&to=5510.02.001&route_costing=pedestrian&prefer_indoor=true",
+        )
+        .unwrap();
+        assert!(args.prefer_indoor);
+        let args = web::Query::<RoutingRequest>::from_query(
+            "from=5602.EG.001&to=5510.02.001&route_costing=car&fallback_to_pedestrian=true",
+        )
+        .unwrap();
+        assert!(args.fallback_to_pedestrian);
+        assert_eq!(args.detail, RouteDetailRequest::Full);
+        let args = web::Query::<RoutingRequest>::from_query(
+            "from=5602.EG.001&to=5510.02.001&route_costing=car&detail=overview",
+        )
+        .unwrap();
+        assert_eq!(args.detail, RouteDetailRequest::Overview);
+    }
+
+    fn maneuver(
+        r#type: ManeuverTypeResponse,
+        travel_mode: TravelModeResponse,
+        shape: (usize, usize),
+    ) -> ManeuverResponse {
+        ManeuverResponse {
+            instruction: format!("{:?}", r#type),
+            instruction_de: None,
+            instruction_en: None,
+            r#type,
+            verbal_transition_alert_instruction: None,
+            verbal_pre_transition_instruction: None,
+            verbal_post_transition_instruction: Some("Continue for 100 meters".to_string()),
+            street_names: None,
+            begin_street_names: None,
+            time_seconds: 60.0,
+            length_meters: 100.0,
+            begin_shape_index: shape.0,
+            end_shape_index: shape.1,
+            toll: None,
+            highway: None,
+            rough: None,
+            gate: None,
+            ferry: None,
+            roundabout_exit_count: None,
+            depart_instruction: None,
+            verbal_depart_instruction: None,
+            arrive_instruction: None,
+            verbal_arrive_instruction: None,
+            transit_info: None,
+            verbal_multi_cue: None,
+            travel_mode,
+            collapsed_maneuvers: None,
+        }
+    }
+
+    #[test]
+    fn test_overview_maneuvers() {
+        use ManeuverTypeResponse as Type;
+        let walk = |r#type, shape| maneuver(r#type, TravelModeResponse::Pedestrian, shape);
+        let mut rough = walk(Type::Continue, (2, 3));
+        rough.rough = Some(true);
+        let maneuvers = vec![
+            walk(Type::Start, (0, 1)),
+            walk(Type::Continue, (1, 2)),
+            rough,
+            walk(Type::Right, (3, 4)),
+            walk(Type::Continue, (4, 5)),
+            maneuver(Type::Continue, TravelModeResponse::Bicycle, (5, 6)),
+            walk(Type::Destination, (6, 6)),
+        ];
+        let overview = overview_maneuvers(maneuvers);
+        let summary = overview
+            .iter()
+            .map(|m| {
+                (
+                    m.instruction.as_str(),
+                    m.collapsed_maneuvers,
+                    m.begin_shape_index,
+                    m.end_shape_index,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("Start", Some(3), 0, 3),
+                ("Right", Some(2), 3, 5),
+                ("Continue", None, 5, 6),
+                ("Destination", None, 6, 6),
+            ]
+        );
+        assert_eq!(overview[0].length_meters, 300.0);
+        assert_eq!(overview[0].time_seconds, 180.0);
+        assert_eq!(overview[0].rough, Some(true));
+        assert_eq!(overview[0].verbal_post_transition_instruction, None);
+        // untouched maneuvers are exactly what `detail=full` returns
+        assert_eq!(
+            overview[2].verbal_post_transition_instruction.as_deref(),
+            Some("Continue for 100 meters")
+        );
+        assert_eq!(overview[2].rough, None);
+    }
+
+    #[test]
+    fn test_compare_request_in_query() {
+        let args = web::Query::<CompareRoutesRequest>::from_query(
+            "from=5602.EG.001&to_lat=48.1&to_lon=11.5&include_legs=true&bicycle_type=road",
+        )
+        .unwrap();
+        assert!(args.include_legs);
+        assert_eq!(args.bicycle_type, BicycleRestrictionRequest::Road);
+        assert_eq!(
+            args.from,
+            Some(RequestedLocation::Location("5602.EG.001".into()))
+        );
+        for route_costing in CostingRequest::ALL {
+            assert!(args.costing(route_costing).is_ok(), "{route_costing:?}");
+        }
+        let args =
+            web::Query::<CompareRoutesRequest>::from_query("from=5602.EG.001&to=mi").unwrap();
+        assert!(!args.include_legs);
+    }
+
+    #[test]
+    fn test_failed_modes_are_reported() {
+        let response = CompareRoutesResponse {
+            departure_time: DateTime::parse_from_rfc3339("2025-02-20T14:05:00+01:00").unwrap(),
+            routes: BTreeMap::from([(
+                CostingRequest::PublicTransit.as_label().to_string(),
+                ComparisonResult::from(Err(transit_not_implemented())),
+            )]),
+        };
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            serde_json::json!({
+                "departure_time": "2025-02-20T14:05:00+01:00",
+                "routes": {
+                    "public_transit": {
+                        "error": {
+                            "error": "public transit routing is not yet implemented",
+                            "code": "not_implemented",
+                        }
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_bulk_requests_in_json() {
+        let args = serde_json::from_str::<Vec<RoutingRequest>>(
+            r#"[
+                {"from": "5602.EG.001", "to": {"lat": 48.1, "lon": 11.5}, "route_costing": "bicycle", "lang": "en"},
+                {"from_lat": 48.1, "from_lon": 11.5, "to": "mi", "route_costing": "pedestrian", "prefer_indoor": true},
+                {"from_lat": "48.1", "from_lon": "11.5", "to": "mi", "route_costing": "car"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(args.len(), 3);
+        assert!(args[0].lang.should_use_english());
+        assert_eq!(args[0].route_costing, CostingRequest::Bicycle);
+        let coordinate = RequestedLocation::Coordinate(RequestedCoordinate {
+            coordinate: Coordinate {
+                lat: 48.1,
+                lon: 11.5,
+            },
+            accuracy_m: None,
+        });
+        assert_eq!(args[0].destination().unwrap(), coordinate);
+        assert_eq!(args[1].origin().unwrap(), coordinate);
+        assert!(args[1].prefer_indoor);
+        assert_eq!(args[2].origin().unwrap(), coordinate);
+    }
+
+    #[test]
+    fn test_campus_time() {
+        let offset =
+            |utc: &str| campus_time(utc.parse().unwrap()).offset().local_minus_utc() / 3600;
+        assert_eq!(offset("2025-01-15T12:00:00Z"), 1);
+        assert_eq!(offset("2025-07-15T12:00:00Z"), 2);
+        // 2025-03-30 and 2025-10-26 are the last sundays of march and october
+        assert_eq!(offset("2025-03-30T00:59:59Z"), 1);
+        assert_eq!(offset("2025-03-30T01:00:00Z"), 2);
+        assert_eq!(offset("2025-10-26T00:59:59Z"), 2);
+        assert_eq!(offset("2025-10-26T01:00:00Z"), 1);
+        assert_eq!(
+            campus_time("2025-07-15T12:00:00Z".parse().unwrap()).to_rfc3339(),
+            "2025-07-15T14:00:00+02:00"
+        );
+    }
+
+    #[test]
+    fn test_arrival_time() {
+        let departure = DateTime::parse_from_rfc3339("2025-02-20T23:50:00+01:00").unwrap();
+        let arrival = arrival_time(&[], departure, 15.0 * 60.0 + 0.4);
+        assert_eq!(arrival.to_rfc3339(), "2025-02-21T00:05:00+01:00");
+        let lang = |lang| {
+            serde_json::from_value::<localisation::LangQueryArgs>(
+                serde_json::json!({ "lang": lang }),
+            )
+            .unwrap()
+        };
+        let (english, german) = (lang("en"), lang("de"));
+        assert_eq!(clock_time(arrival, english), "12:05 AM");
+        assert_eq!(clock_time(arrival, german), "00:05");
+        let afternoon = DateTime::parse_from_rfc3339("2025-02-20T14:32:00+01:00").unwrap();
+        assert_eq!(clock_time(afternoon, english), "2:32 PM");
+        assert_eq!(clock_time(afternoon, german), "14:32");
+    }
+
+    #[test]
+    fn test_bbox_center() {
+        assert_eq!(
+            bbox_center(48.0, 11.0, 48.5, 11.7),
+            Coordinate {
+                lat: 48.25,
+                lon: 11.35
+            }
+        );
+        // a single point is its own center
+        assert_eq!(
+            bbox_center(48.1, 11.5, 48.1, 11.5),
+            Coordinate {
+                lat: 48.1,
+                lon: 11.5
+            }
+        );
+    }
+
+    #[test]
+    fn test_coordinate_ranges() {
+        let valid = |lat, lon| Coordinate { lat, lon }.is_valid();
+        assert!(valid(48.1, 11.5));
+        assert!(valid(-90.0, -180.0));
+        assert!(valid(90.0, 180.0));
+        assert!(!valid(480.0, 11.5));
+        assert!(!valid(48.1, -180.5));
+        assert!(!valid(f64::NAN, 11.5));
+        assert!(!valid(48.1, f64::INFINITY));
+    }
+    #[test]
+    fn test_resolution_errors() {
+        let key = RequestedLocation::Location("garching".into());
+        let code = |resolution: Resolution<()>| {
+            let err = resolution.into_result(&key).unwrap_err();
+            serde_json::to_value(err).unwrap()["code"].clone()
+        };
+        assert_eq!(code(Resolution::NotRoutable), "not_routable");
+        assert_eq!(code(Resolution::Missing), "not_found");
+        assert_eq!(code(Resolution::InvalidCoordinate), "invalid_coordinate");
+        assert!(Resolution::Found(()).into_result(&key).is_ok());
+    }
+    #[test]
+    fn test_requested_location_alternatives() {
+        let key = RequestedLocation::Location("5602.EG.001".into());
+        let code = |res: Result<RequestedLocation, ApiError>| {
+            serde_json::to_value(res.unwrap_err()).unwrap()["code"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            requested_location("from", Some(&key), None, None).unwrap(),
+            key
+        );
+        assert_eq!(
+            code(requested_location("from", None, None, None)),
+            "missing_location"
+        );
+        assert_eq!(
+            code(requested_location("from", None, Some(48.1), None)),
+            "missing_location"
+        );
+        assert_eq!(
+            code(requested_location(
+                "from",
+                Some(&key),
+                Some(48.1),
+                Some(11.5)
+            )),
+            "ambiguous_location"
+        );
+        assert_eq!(
+            code(requested_location("from", Some(&key), None, Some(11.5))),
+            "ambiguous_location"
+        );
+        assert_eq!(
+            code(requested_location("from", None, Some(91.0), Some(11.5))),
+            "invalid_coordinate"
+        );
+        assert_eq!(
+            code(requested_location("from", None, Some(48.1), Some(f64::NAN))),
+            "invalid_coordinate"
+        );
+    }
+
+    #[test]
+    fn test_snapping_radius_is_sane() {
+        let coordinate = Coordinate {
+            lat: 48.1,
+            lon: 11.5,
+        };
+        let radius = |accuracy_m| {
+            RequestedCoordinate {
+                coordinate,
+                accuracy_m: Some(accuracy_m),
+            }
+            .snapping_radius()
+        };
+        assert_eq!(radius(0.0), None);
+        assert_eq!(radius(-5.0), None);
+        assert_eq!(radius(f32::NAN), None);
+        assert_eq!(radius(f32::INFINITY), None);
+        assert_eq!(radius(10_000.0), Some(MAX_SNAPPING_RADIUS_M as u32));
+    }
+
+    /// Compares json, allowing floats to differ by rounding errors (e.g. of decoding the shape)
+    fn assert_json_eq_approx(actual: &serde_json::Value, expected: &serde_json::Value, path: &str) {
+        use serde_json::Value;
+        match (actual, expected) {
+            (Value::Number(a), Value::Number(e)) => {
+                let (a, e) = (a.as_f64().unwrap(), e.as_f64().unwrap());
+                assert!((a - e).abs() < 1e-9, "{path}: {a} != {e}");
+            }
+            (Value::Array(a), Value::Array(e)) => {
+                assert_eq!(a.len(), e.len(), "{path}: lengths differ");
+                for (i, (a, e)) in a.iter().zip(e).enumerate() {
+                    assert_json_eq_approx(a, e, &format!("{path}[{i}]"));
+                }
+            }
+            (Value::Object(a), Value::Object(e)) => {
+                assert_eq!(
+                    a.keys().collect::<std::collections::BTreeSet<_>>(),
+                    e.keys().collect::<std::collections::BTreeSet<_>>(),
+                    "{path}: fields differ"
+                );
+                for (key, a) in a {
+                    assert_json_eq_approx(a, &e[key], &format!("{path}.{key}"));
+                }
+            }
+            (a, e) => assert_eq!(a, e, "{path}"),
+        }
+    }
+
+    /// Guards our mapping against changes of the fields `valhalla_client` deserializes
+    #[test]
+    fn test_valhalla_response_mapping() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/valhalla-pedestrian-route.json")).unwrap();
+        let trip = serde_json::from_value::<Trip>(fixture["trip"].clone())
+            .expect("the fixture is a response of valhalla");
+        let departure = DateTime::parse_from_rfc3339("2025-02-20T14:05:00+01:00").unwrap();
+        let lang = serde_json::from_value::<localisation::LangQueryArgs>(
+            serde_json::json!({ "lang": "en" }),
+        )
+        .unwrap();
+        let actual = serde_json::to_value(RoutingResponse::new(trip, departure, lang)).unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/pedestrian-routing-response.json"))
+                .unwrap();
+        assert_json_eq_approx(&actual, &expected, "$");
+    }
+
+    #[test]
+    fn test_english_instructions() {
+        let english: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/valhalla-pedestrian-route.json")).unwrap();
+        let trip = |fixture: &serde_json::Value| {
+            serde_json::from_value::<Trip>(fixture["trip"].clone()).unwrap()
+        };
+        let mut german = english.clone();
+        for maneuver in german["trip"]["legs"][0]["maneuvers"]
+            .as_array_mut()
+            .unwrap()
+        {
+            let instruction = format!("DE: {}", maneuver["instruction"].as_str().unwrap());
+            maneuver["instruction"] = instruction.into();
+        }
+        let departure = DateTime::parse_from_rfc3339("2025-02-20T14:05:00+01:00").unwrap();
+        let german_route = || {
+            RoutingResponse::new(
+                trip(&german),
+                departure,
+                localisation::LangQueryArgs::GERMAN,
+            )
+        };
+
+        let mut route = german_route();
+        assert!(route.add_english_instructions(trip(&english)));
+        assert!(!route.legs[0].maneuvers.is_empty());
+        for maneuver in &route.legs[0].maneuvers {
+            let english = maneuver.instruction_en.as_deref().unwrap();
+            assert_eq!(maneuver.instruction, format!("DE: {english}"));
+            assert_eq!(
+                maneuver.instruction_de.as_deref(),
+                Some(maneuver.instruction.as_str())
+            );
+        }
+
+        // the instructions of a different route cannot be combined
+        let mut other = english.clone();
+        other["trip"]["legs"][0]["maneuvers"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        let mut route = german_route();
+        assert!(!route.add_english_instructions(trip(&other)));
+        assert!(
+            route.legs[0]
+                .maneuvers
+                .iter()
+                .all(|m| m.instruction_de.is_none() && m.instruction_en.is_none())
+        );
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::setup::tests::PostgresTestContainer;
+
+    #[actix_web::test]
+    async fn test_resolve_all_preserves_order() {
+        let pg = PostgresTestContainer::new().await;
+        let cache = CoordinateCache::default();
+        for (key, r#type, lat, lon) in [
+            ("5602.EG.001", "room", 48.262, 11.668),
+            ("5121", "building", 48.268, 11.677),
+            ("garching", "campus", 48.265, 11.671),
+            ("broken", "room", 480.0, 11.671),
+        ] {
+            let data = serde_json::json!({
+                "id": key,
+                "name": key,
+                "type": r#type,
+                "type_common_name": "Hörsaal",
+                "coords": {"lat": lat, "lon": lon, "source": "inferred"},
+            });
+            sqlx::query("INSERT INTO de(key,data) VALUES ($1,$2)")
+                .bind(key)
+                .bind(data)
+                .execute(&pg.pool)
+                .await
+                .unwrap();
+        }
+        let user_location = Coordinate {
+            lat: 48.1,
+            lon: 11.5,
+        };
+        let requested = [
+            RequestedLocation::Location("5121".into()),
+            RequestedLocation::Coordinate(RequestedCoordinate {
+                coordinate: user_location,
+                accuracy_m: None,
+            }),
+            RequestedLocation::Location("does-not-exist".into()),
+            RequestedLocation::Location("5602.EG.001".into()),
+            RequestedLocation::Location("5121".into()),
+            RequestedLocation::Location("garching".into()),
+        ];
+        let resolved =
+            RequestedLocation::try_resolve_all_coordinates(&pg.pool, &cache, &requested, true)
+                .await
+                .unwrap();
+        assert_eq!(
+            resolved,
+            vec![
+                Resolution::Found(Coordinate {
+                    lat: 48.268,
+                    lon: 11.677
+                }),
+                Resolution::Found(user_location),
+                Resolution::Missing,
+                Resolution::Found(Coordinate {
+                    lat: 48.262,
+                    lon: 11.668
+                }),
+                Resolution::Found(Coordinate {
+                    lat: 48.268,
+                    lon: 11.677
+                }),
+                Resolution::NotRoutable,
+            ]
+        );
+        // without the constraint, any key with a coordinate resolves
+        let resolved = RequestedLocation::try_resolve_all_coordinates(
+            &pg.pool,
+            &cache,
+            &requested[5..],
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resolved,
+            vec![Resolution::Found(Coordinate {
+                lat: 48.265,
+                lon: 11.671
+            })]
+        );
+        // only user supplied coordinates => nothing to look up
+        let resolved = RequestedLocation::try_resolve_all_coordinates(
+            &pg.pool,
+            &cache,
+            &requested[1..2],
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resolved, vec![Resolution::Found(user_location)]);
+
+        // out of range coordinates are never routed to
+        let out_of_range = [
+            RequestedLocation::Location("broken".into()),
+            RequestedLocation::Coordinate(RequestedCoordinate {
+                coordinate: Coordinate {
+                    lat: 91.0,
+                    lon: 11.5,
+                },
+                accuracy_m: None,
+            }),
+        ];
+        let resolved =
+            RequestedLocation::try_resolve_all_coordinates(&pg.pool, &cache, &out_of_range, true)
+                .await
+                .unwrap();
+        assert_eq!(
+            resolved,
+            vec![Resolution::Missing, Resolution::InvalidCoordinate]
+        );
+
+        // cached keys are not looked up again until the data is re-imported
+        sqlx::query("DELETE FROM de")
+            .execute(&pg.pool)
+            .await
+            .unwrap();
+        let resolved =
+            RequestedLocation::try_resolve_all_coordinates(&pg.pool, &cache, &requested[..1], true)
+                .await
+                .unwrap();
+        assert_eq!(
+            resolved,
+            vec![Resolution::Found(Coordinate {
+                lat: 48.268,
+                lon: 11.677
+            })]
+        );
+        cache.invalidate_all();
+        let resolved =
+            RequestedLocation::try_resolve_all_coordinates(&pg.pool, &cache, &requested[..1], true)
+                .await
+                .unwrap();
+        assert_eq!(resolved, vec![Resolution::Missing]);
+    }
+}
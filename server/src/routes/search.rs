@@ -279,7 +279,9 @@ async fn cached_geoentry_search(
     let Ok(client) = Client::new(ms_url, std::env::var("MEILI_MASTER_KEY").ok()) else {
         error!("Failed to create a meilisearch client");
         return if search_addresses {
-            crate::search_executor::address_search(&q).await.0
+            crate::search_executor::address_search(&q)
+                .await
+                .into_inner()
         } else {
             vec![]
         };
@@ -289,10 +291,11 @@ async fn cached_geoentry_search(
     if search_addresses {
         let address_search = crate::search_executor::address_search(&q);
         let (address_search, mut geoentry_search) = join!(address_search, geoentry_search);
-        geoentry_search.0.extend(address_search.0);
-        geoentry_search.0
+        let mut sections = geoentry_search.into_inner();
+        sections.extend(address_search);
+        sections
     } else {
-        geoentry_search.await.0
+        geoentry_search.await.into_inner()
     }
 }
 
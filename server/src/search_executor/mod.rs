@@ -95,10 +95,10 @@ struct ResultEntry {
 #[tracing::instrument]
 pub async fn address_search(q: &str) -> LimitedVec<ResultsSection> {
     let results = match Nominatim::address_search(q).await {
-        Ok(r) => r.0,
+        Ok(r) => r.into_inner(),
         Err(e) => {
             error!(error = ?e, "Error searching for addresses");
-            return LimitedVec(vec![]);
+            return LimitedVec::new();
         }
     };
     let num_results = results.len();
@@ -155,7 +155,7 @@ pub async fn do_geoentry_search(
     let Ok(response) = query.execute().await else {
         // error should be serde_json::error
         error!("Error searching for results");
-        return LimitedVec(vec![]);
+        return LimitedVec::new();
     };
     let (section_buildings, mut section_rooms) = merger::merge_search_results(
         &limits,
@@ -170,8 +170,8 @@ pub async fn do_geoentry_search(
         .for_each(|r| visitor.visit(r));
 
     match section_buildings.n_visible {
-        0 => LimitedVec(vec![section_rooms, section_buildings]),
-        _ => LimitedVec(vec![section_buildings, section_rooms]),
+        0 => LimitedVec::from(vec![section_rooms, section_buildings]),
+        _ => LimitedVec::from(vec![section_buildings, section_rooms]),
     }
 }
 
@@ -210,7 +210,7 @@ mod test {
                 Limits::default(),
             )
             .await
-            .0
+            .into_inner()
         }
     }
     impl Display for TestQuery {
@@ -0,0 +1,245 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use reqwest::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
+use url::Url;
+
+/// Where the data is imported from, if `CDN_URL` is not set
+const DEFAULT_CDN_URL: &str = "https://nav.tum.de/cdn";
+
+/// Downloads the `file` from the CDN configured via `CDN_URL`
+///
+/// A `file://` url (e.g. `file:///data/cdn`) reads the file from disk instead, which allows importing without a network.
+/// Either way, the same bytes are parsed.
+pub async fn fetch(file: &str) -> anyhow::Result<Vec<u8>> {
+    fetch_from(&cdn_url(), file).await
+}
+
+fn cdn_url() -> String {
+    std::env::var("CDN_URL").unwrap_or_else(|_| DEFAULT_CDN_URL.to_string())
+}
+
+/// Whether a file on the CDN changed since it was imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Not modified since the last import => downloading it again is pointless
+    Unchanged,
+    /// Modified (or not imported before) at `last_modified`, if the CDN tells when
+    Changed {
+        last_modified: Option<DateTime<Utc>>,
+    },
+}
+
+/// Checks whether the `file` on the CDN was modified after `imported`, without downloading it
+///
+/// Issues a conditional `HEAD` request with `If-Modified-Since`.
+/// For a `file://` CDN, the modification time of the file is compared instead.
+pub async fn freshness(file: &str, imported: Option<DateTime<Utc>>) -> anyhow::Result<Freshness> {
+    freshness_at(&cdn_url(), file, imported).await
+}
+
+#[tracing::instrument]
+async fn freshness_at(
+    cdn_url: &str,
+    file: &str,
+    imported: Option<DateTime<Utc>>,
+) -> anyhow::Result<Freshness> {
+    let url = format!("{cdn_url}/{file}", cdn_url = cdn_url.trim_end_matches('/'));
+    let last_modified = if let Some(path) = local_path(&url)? {
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("could not read {path}", path = path.display()))?;
+        modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .and_then(|since_epoch| i64::try_from(since_epoch.as_secs()).ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+    } else {
+        let mut request = reqwest::Client::new().head(url);
+        if let Some(imported) = imported {
+            request = request.header(IF_MODIFIED_SINCE, http_date(imported));
+        }
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Freshness::Unchanged);
+        }
+        response
+            .error_for_status()?
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .map(|last_modified| last_modified.to_utc())
+    };
+    // not every server answers conditional requests
+    match (last_modified, imported) {
+        (Some(last_modified), Some(imported)) if last_modified <= imported => {
+            Ok(Freshness::Unchanged)
+        }
+        _ => Ok(Freshness::Changed { last_modified }),
+    }
+}
+
+/// Formats the time as required by HTTP headers, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[tracing::instrument]
+async fn fetch_from(cdn_url: &str, file: &str) -> anyhow::Result<Vec<u8>> {
+    let url = format!("{cdn_url}/{file}", cdn_url = cdn_url.trim_end_matches('/'));
+    if let Some(path) = local_path(&url)? {
+        return tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("could not read {path}", path = path.display()));
+    }
+    let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    Ok(body.to_vec())
+}
+
+/// The path `url` refers to, if it is a `file://` url
+fn local_path(url: &str) -> anyhow::Result<Option<std::path::PathBuf>> {
+    if !url.starts_with("file:") {
+        return Ok(None);
+    }
+    let path = Url::parse(url)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .with_context(|| format!("CDN_URL {url} is not a valid file url"))?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use actix_web::{HttpRequest, HttpResponse, web};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::setup::tests::mock_server;
+
+    #[test]
+    fn test_local_path() {
+        assert_eq!(
+            local_path("file:///data/cdn/api_data.parquet").unwrap(),
+            Some(PathBuf::from("/data/cdn/api_data.parquet"))
+        );
+        assert_eq!(
+            local_path("https://nav.tum.de/cdn/api_data.parquet").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_http_date() {
+        let time = DateTime::from_timestamp(784_111_777, 0).unwrap();
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        let parsed = DateTime::parse_from_rfc2822(&http_date(time)).unwrap();
+        assert_eq!(parsed.to_utc(), time);
+    }
+
+    #[actix_web::test]
+    async fn test_freshness_of_local_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("status_data.parquet"), b"parquet").unwrap();
+        let cdn_url = Url::from_directory_path(dir.path()).unwrap();
+        let cdn_url = cdn_url.as_str();
+        let Freshness::Changed {
+            last_modified: Some(last_modified),
+        } = freshness_at(cdn_url, "status_data.parquet", None)
+            .await
+            .unwrap()
+        else {
+            panic!("files which were never imported have changed");
+        };
+        let freshness = move |imported| freshness_at(cdn_url, "status_data.parquet", imported);
+        assert_eq!(
+            freshness(Some(last_modified)).await.unwrap(),
+            Freshness::Unchanged
+        );
+        let earlier = last_modified - chrono::Duration::seconds(1);
+        assert_eq!(
+            freshness(Some(earlier)).await.unwrap(),
+            Freshness::Changed {
+                last_modified: Some(last_modified)
+            }
+        );
+        assert!(
+            freshness_at(cdn_url, "api_data.parquet", None)
+                .await
+                .is_err()
+        );
+    }
+
+    /// A CDN, which last modified its files at the given unix timestamp
+    async fn mock_cdn(last_modified: i64) -> String {
+        async fn head(last_modified: web::Data<DateTime<Utc>>, req: HttpRequest) -> HttpResponse {
+            let since = req
+                .headers()
+                .get("If-Modified-Since")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+            match since {
+                Some(since) if since.to_utc() >= **last_modified => {
+                    HttpResponse::NotModified().finish()
+                }
+                _ => HttpResponse::Ok()
+                    .insert_header(("Last-Modified", http_date(**last_modified)))
+                    .finish(),
+            }
+        }
+        let last_modified = web::Data::new(DateTime::from_timestamp(last_modified, 0).unwrap());
+        let addr = mock_server(move |cfg| {
+            cfg.app_data(last_modified.clone())
+                .route("/cdn/status_data.parquet", web::head().to(head));
+        });
+        format!("http://{addr}/cdn")
+    }
+
+    #[actix_web::test]
+    async fn test_freshness_is_checked_conditionally() {
+        let cdn_url = mock_cdn(1_700_000_000).await;
+        let cdn_url = cdn_url.as_str();
+        let last_modified = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let freshness = move |imported| freshness_at(cdn_url, "status_data.parquet", imported);
+        assert_eq!(
+            freshness(None).await.unwrap(),
+            Freshness::Changed {
+                last_modified: Some(last_modified)
+            }
+        );
+        assert_eq!(
+            freshness(Some(last_modified)).await.unwrap(),
+            Freshness::Unchanged
+        );
+        let earlier = last_modified - chrono::Duration::hours(1);
+        assert_eq!(
+            freshness(Some(earlier)).await.unwrap(),
+            Freshness::Changed {
+                last_modified: Some(last_modified)
+            }
+        );
+        // files missing on the CDN are an error, like when downloading them
+        assert!(
+            freshness_at(cdn_url, "api_data.parquet", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_fetch_local_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("status_data.parquet"), b"parquet").unwrap();
+        let cdn_url = Url::from_directory_path(dir.path()).unwrap();
+        let fetched = fetch_from(cdn_url.as_str(), "status_data.parquet").await;
+        assert_eq!(fetched.unwrap(), b"parquet");
+        assert!(
+            fetch_from(cdn_url.as_str(), "api_data.parquet")
+                .await
+                .is_err()
+        );
+    }
+}
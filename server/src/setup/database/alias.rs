@@ -1,4 +1,5 @@
 use crate::limited::vec::LimitedVec;
+use crate::setup::cdn;
 use polars::prelude::*;
 use std::io::Write;
 use tempfile::tempfile;
@@ -37,12 +38,7 @@ impl Alias {
 }
 #[tracing::instrument]
 pub async fn download_updates() -> anyhow::Result<LimitedVec<Alias>> {
-    let cdn_url = std::env::var("CDN_URL").unwrap_or_else(|_| "https://nav.tum.de/cdn".to_string());
-    let body = reqwest::get(format!("{cdn_url}/api_data.parquet"))
-        .await?
-        .error_for_status()?
-        .bytes()
-        .await?;
+    let body = cdn::fetch("api_data.parquet").await?;
     let mut aliase = Vec::<Alias>::new();
     let mut file = tempfile()?;
     file.write_all(&body)?;
@@ -102,7 +98,7 @@ pub async fn download_updates() -> anyhow::Result<LimitedVec<Alias>> {
             visible_id,
         });
     }
-    Ok(LimitedVec(aliase))
+    Ok(LimitedVec::from(aliase))
 }
 #[tracing::instrument(skip(tx))]
 pub async fn load_all_to_db(
@@ -1,4 +1,5 @@
 use crate::limited::vec::LimitedVec;
+use crate::setup::cdn;
 use polars::prelude::ParquetReader;
 use polars::prelude::*;
 use serde_json::Value;
@@ -7,6 +8,7 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::io::Write;
 use tempfile::tempfile;
+use tracing::warn;
 
 #[derive(Clone)]
 pub(super) struct DelocalisedValues {
@@ -37,20 +39,23 @@ impl Hash for DelocalisedValues {
     }
 }
 
-impl From<HashMap<String, Value>> for DelocalisedValues {
-    fn from(value: HashMap<String, Value>) -> Self {
+impl TryFrom<HashMap<String, Value>> for DelocalisedValues {
+    /// Why the row is invalid
+    type Error = &'static str;
+
+    fn try_from(value: HashMap<String, Value>) -> Result<Self, Self::Error> {
         let key = value
             .get("id")
-            .expect("an ID should always exist")
+            .ok_or("the id is missing")?
             .as_str()
-            .expect("the id should be a valid string")
+            .ok_or("the id is not a string")?
             .to_string();
         let hash = value
             .get("hash")
-            .expect("a hash should always exist")
+            .ok_or("the hash is missing")?
             .as_i64()
-            .expect("a hash should be a valid i64");
-        Self {
+            .ok_or("the hash is not a valid i64")?;
+        Ok(Self {
             key,
             hash,
             de: value
@@ -63,7 +68,7 @@ impl From<HashMap<String, Value>> for DelocalisedValues {
                 .into_iter()
                 .map(|(k, v)| (k, Self::delocalise(v.clone(), "en")))
                 .collect(),
-        }
+        })
     }
 }
 impl DelocalisedValues {
@@ -124,20 +129,85 @@ impl DelocalisedValues {
         Ok(())
     }
 }
+
+/// How many invalid rows are reported individually, the others are only counted
+///
+/// A broken export would otherwise flood the logs with every single row.
+const MAX_REPORTED_INVALID_ROWS: usize = 100;
+
+/// A row of `api_data.json` which cannot be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct InvalidRow {
+    pub(super) index: usize,
+    /// `None` if the id itself is invalid
+    pub(super) key: Option<String>,
+    pub(super) reason: &'static str,
+}
+
+#[derive(Debug)]
+pub(super) struct Updates {
+    pub(super) values: LimitedVec<DelocalisedValues>,
+    /// The first invalid rows, see [`MAX_REPORTED_INVALID_ROWS`]
+    pub(super) invalid_rows: LimitedVec<InvalidRow, MAX_REPORTED_INVALID_ROWS>,
+    /// All invalid rows, including those which are not reported individually
+    pub(super) invalid_cnt: usize,
+}
+
+/// Downloads the data of `keys_which_need_updating`
+///
+/// Invalid rows are skipped and reported instead of aborting the whole import.
 #[tracing::instrument]
-pub async fn download_updates(
+pub(super) async fn download_updates(
     keys_which_need_updating: &LimitedVec<String>,
-) -> anyhow::Result<LimitedVec<DelocalisedValues>> {
-    let cdn_url = std::env::var("CDN_URL").unwrap_or_else(|_| "https://nav.tum.de/cdn".to_string());
-    let tasks = reqwest::get(format!("{cdn_url}/api_data.json"))
-        .await?
-        .json::<Vec<HashMap<String, Value>>>()
-        .await?
-        .into_iter()
-        .map(DelocalisedValues::from)
-        .filter(|d| keys_which_need_updating.0.contains(&d.key))
-        .collect::<LimitedVec<DelocalisedValues>>();
-    Ok(tasks)
+) -> anyhow::Result<Updates> {
+    let rows =
+        serde_json::from_slice::<Vec<HashMap<String, Value>>>(&cdn::fetch("api_data.json").await?)?;
+    let updates = delocalise_rows(rows, keys_which_need_updating);
+    for row in updates.invalid_rows.iter() {
+        warn!(
+            index = row.index,
+            key = row.key.as_deref(),
+            reason = row.reason,
+            "skipping invalid row of api_data"
+        );
+    }
+    if updates.invalid_cnt > 0 {
+        warn!(
+            skipped_cnt = updates.invalid_cnt,
+            reported_cnt = updates.invalid_rows.len(),
+            imported_cnt = updates.values.len(),
+            "skipped invalid rows of api_data"
+        );
+    }
+    Ok(updates)
+}
+
+fn delocalise_rows(
+    rows: Vec<HashMap<String, Value>>,
+    keys_which_need_updating: &LimitedVec<String>,
+) -> Updates {
+    let mut invalid_rows = LimitedVec::new();
+    let mut invalid_cnt = 0;
+    let mut values = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let key = row.get("id").and_then(Value::as_str).map(String::from);
+        match DelocalisedValues::try_from(row) {
+            Ok(value) if keys_which_need_updating.as_slice().contains(&value.key) => {
+                values.push(value);
+            }
+            Ok(_) => {}
+            Err(reason) => {
+                invalid_cnt += 1;
+                // beyond the capacity, rows are only counted
+                let _ = invalid_rows.push(InvalidRow { index, key, reason });
+            }
+        }
+    }
+    Updates {
+        values: LimitedVec::from(values),
+        invalid_rows,
+        invalid_cnt,
+    }
 }
 #[tracing::instrument(skip(tx))]
 pub(super) async fn load_all_to_db(
@@ -149,14 +219,12 @@ pub(super) async fn load_all_to_db(
     }
     Ok(())
 }
+/// The hashes of all entries, i.e. it changes whenever any of the data changes
+pub(super) const STATUS_FILE: &str = "status_data.parquet";
+
 #[tracing::instrument]
 pub async fn download_status() -> anyhow::Result<(LimitedVec<String>, LimitedVec<i64>)> {
-    let cdn_url = std::env::var("CDN_URL").unwrap_or_else(|_| "https://nav.tum.de/cdn".to_string());
-    let body = reqwest::get(format!("{cdn_url}/status_data.parquet"))
-        .await?
-        .error_for_status()?
-        .bytes()
-        .await?;
+    let body = cdn::fetch(STATUS_FILE).await?;
     let mut file = tempfile()?;
     file.write_all(&body)?;
     let df = ParquetReader::new(&mut file).finish().unwrap();
@@ -167,5 +235,85 @@ pub async fn download_status() -> anyhow::Result<(LimitedVec<String>, LimitedVec
         .collect();
     let hash_col = Vec::from(df.column("hash")?.i64()?);
     let hash_col = hash_col.into_iter().flatten().collect();
-    Ok((LimitedVec(id_col), LimitedVec(hash_col)))
+    Ok((LimitedVec::from(id_col), LimitedVec::from(hash_col)))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    fn row(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_missing_id_or_hash_is_an_error() {
+        let cases = [
+            (json!({"hash": 1}), "the id is missing"),
+            (json!({"id": 1, "hash": 1}), "the id is not a string"),
+            (json!({"id": "mi"}), "the hash is missing"),
+            (
+                json!({"id": "mi", "hash": 1.5}),
+                "the hash is not a valid i64",
+            ),
+        ];
+        for (value, expected) in cases {
+            let res = DelocalisedValues::try_from(row(value.clone()));
+            assert_eq!(res.err(), Some(expected), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_rows_are_reported() {
+        let rows = vec![
+            row(json!({"id": "mi", "hash": 1, "name": {"de": "Mathe", "en": "Maths"}})),
+            row(json!({"hash": 2})),
+            row(json!({"id": "mw", "hash": "not a number"})),
+            row(json!({"id": "garching", "hash": 3})),
+        ];
+        let keys = LimitedVec::from(vec!["mi".to_string()]);
+        let updates = delocalise_rows(rows, &keys);
+        assert_eq!(
+            updates
+                .values
+                .iter()
+                .map(|v| (v.key.as_str(), &v.de, &v.en))
+                .collect::<Vec<_>>(),
+            vec![(
+                "mi",
+                &json!({"id": "mi", "hash": 1, "name": "Mathe"}),
+                &json!({"id": "mi", "hash": 1, "name": "Maths"})
+            )]
+        );
+        assert_eq!(updates.invalid_cnt, 2);
+        assert_eq!(
+            updates.invalid_rows.into_inner(),
+            vec![
+                InvalidRow {
+                    index: 1,
+                    key: None,
+                    reason: "the id is missing"
+                },
+                InvalidRow {
+                    index: 2,
+                    key: Some("mw".to_string()),
+                    reason: "the hash is not a valid i64"
+                },
+            ]
+        );
+    }
+    #[test]
+    fn test_only_the_first_invalid_rows_are_reported() {
+        let rows = (0..MAX_REPORTED_INVALID_ROWS + 5)
+            .map(|hash| row(json!({"hash": hash})))
+            .collect();
+        let updates = delocalise_rows(rows, &LimitedVec::new());
+        assert_eq!(updates.invalid_cnt, MAX_REPORTED_INVALID_ROWS + 5);
+        assert_eq!(updates.invalid_rows.len(), MAX_REPORTED_INVALID_ROWS);
+        assert!(updates.invalid_rows.is_full());
+        assert_eq!(updates.invalid_rows.as_slice()[0].index, 0);
+    }
 }
@@ -1,6 +1,8 @@
-use tracing::{debug, debug_span, info, info_span};
+use chrono::{DateTime, Utc};
+use tracing::{debug, debug_span, info, info_span, warn};
 
 use crate::limited::vec::LimitedVec;
+use crate::setup::cdn::{self, Freshness};
 
 mod alias;
 mod data;
@@ -12,43 +14,177 @@ pub async fn setup(pool: &sqlx::PgPool) -> anyhow::Result<()> {
     info!("migrations complete");
     Ok(())
 }
+/// Serialises imports, so that concurrent imports don't race on the tables
+static IMPORT_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// What an import changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Entries available on the CDN
+    pub keys_cnt: usize,
+    /// Entries which were added, changed or removed
+    pub keys_which_need_updating_cnt: usize,
+    /// Entries which were (re-)loaded into the database
+    pub updated_cnt: usize,
+    /// Entries which were skipped as they could not be parsed
+    pub invalid_cnt: usize,
+    pub alias_cnt: usize,
+    /// Nothing changed on the CDN since the last import => nothing was downloaded
+    pub unchanged: bool,
+}
+
+/// Loads the data from the CDN into the database
+///
+/// All changes are applied in a single transaction and only one import runs at a time.
+/// In a `dry_run`, all changes are rolled back instead of being committed.
+/// This allows validating new data without touching the database.
+/// Otherwise, the import is skipped if the data on the CDN has not changed since the last import.
+#[tracing::instrument(skip(pool))]
+pub async fn load_data(pool: &sqlx::PgPool, dry_run: bool) -> anyhow::Result<ImportSummary> {
+    let guard = IMPORT_LOCK.lock().await;
+    import(pool, dry_run, guard).await
+}
+
+/// Like [`load_data`], but returns `None` instead of waiting if another import is running
 #[tracing::instrument(skip(pool))]
-pub async fn load_data(pool: &sqlx::PgPool) -> anyhow::Result<()> {
+pub async fn try_load_data(
+    pool: &sqlx::PgPool,
+    dry_run: bool,
+) -> Option<anyhow::Result<ImportSummary>> {
+    let guard = IMPORT_LOCK.try_lock().ok()?;
+    Some(import(pool, dry_run, guard).await)
+}
+
+/// Imports while holding the `_guard` of the [`IMPORT_LOCK`]
+async fn import(
+    pool: &sqlx::PgPool,
+    dry_run: bool,
+    _guard: tokio::sync::MutexGuard<'static, ()>,
+) -> anyhow::Result<ImportSummary> {
+    // dry-runs validate the data, even if it was imported before
+    let last_modified = if dry_run {
+        None
+    } else {
+        match freshness(pool).await {
+            Freshness::Unchanged => {
+                info!("the data on the CDN has not changed since the last import, skipping it");
+                return Ok(ImportSummary {
+                    unchanged: true,
+                    ..ImportSummary::default()
+                });
+            }
+            Freshness::Changed { last_modified } => last_modified,
+        }
+    };
     debug!("starting to download the status");
     let (new_keys, new_hashes) = data::download_status().await?;
     debug!("loaded new keys/hashes successfully");
+    let mut tx = pool.begin().await?;
     {
         let _ = info_span!("deleting old data").enter();
-        let mut tx = pool.begin().await?;
         cleanup_deleted(&new_keys, &mut tx).await?;
-        tx.commit().await?;
     }
     let keys_which_need_updating =
-        find_keys_which_need_updating(pool, &new_keys, &new_hashes).await?;
+        find_keys_which_need_updating(&mut tx, &new_keys, &new_hashes).await?;
+    let mut updated_cnt = 0;
+    let mut invalid_cnt = 0;
     if !keys_which_need_updating.is_empty() {
         let _ = info_span!("loading changed data").enter();
-        let data = data::download_updates(&keys_which_need_updating).await?;
-        let mut tx = pool.begin().await?;
-        data::load_all_to_db(data, &mut tx).await?;
-        tx.commit().await?;
+        let updates = data::download_updates(&keys_which_need_updating).await?;
+        updated_cnt = updates.values.len();
+        invalid_cnt = updates.invalid_cnt;
+        data::load_all_to_db(updates.values, &mut tx).await?;
     }
-    {
+    let alias_cnt = {
         let aliases = alias::download_updates().await?;
-        let mut tx = pool.begin().await?;
+        let alias_cnt = aliases.len();
         alias::load_all_to_db(aliases, &mut tx).await?;
-        tx.commit().await?;
+        alias_cnt
+    };
+    if let Some(last_modified) = last_modified {
+        record_import(&mut tx, last_modified).await?;
+    }
+    finish(tx, dry_run).await?;
+    let summary = ImportSummary {
+        keys_cnt: new_keys.len(),
+        keys_which_need_updating_cnt: keys_which_need_updating.len(),
+        updated_cnt,
+        invalid_cnt,
+        alias_cnt,
+        unchanged: false,
+    };
+    if dry_run {
+        info!(
+            ?summary,
+            "dry-run of the data import succeeded, all changes were rolled back"
+        );
+    }
+    Ok(summary)
+}
+
+/// Whether the data changed since the last import
+///
+/// If this cannot be determined, the data is assumed to have changed.
+async fn freshness(pool: &sqlx::PgPool) -> Freshness {
+    let imported = match last_import(pool).await {
+        Ok(imported) => imported,
+        Err(e) => {
+            warn!(error = ?e, "could not get when the data was last imported, importing it");
+            None
+        }
+    };
+    match cdn::freshness(data::STATUS_FILE, imported).await {
+        Ok(freshness) => freshness,
+        Err(e) => {
+            warn!(error = ?e, "could not check if the data on the CDN changed, importing it");
+            Freshness::Changed {
+                last_modified: None,
+            }
+        }
     }
+}
+
+/// The `Last-Modified` time of the data, which was imported last
+async fn last_import(pool: &sqlx::PgPool) -> sqlx::Result<Option<DateTime<Utc>>> {
+    sqlx::query_scalar!(
+        "SELECT last_modified FROM cdn_imports WHERE file = $1",
+        data::STATUS_FILE
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Remembers which version of the data was imported, as part of the import
+async fn record_import(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    last_modified: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "INSERT INTO cdn_imports (file, last_modified) VALUES ($1, $2) ON CONFLICT (file) DO UPDATE SET last_modified = EXCLUDED.last_modified, imported_at = NOW()",
+        data::STATUS_FILE,
+        last_modified
+    )
+    .execute(&mut **tx)
+    .await?;
     Ok(())
 }
 
-#[tracing::instrument(skip(pool))]
+async fn finish(tx: sqlx::Transaction<'_, sqlx::Postgres>, dry_run: bool) -> sqlx::Result<()> {
+    if dry_run {
+        tx.rollback().await
+    } else {
+        tx.commit().await
+    }
+}
+
+#[tracing::instrument(skip(tx))]
 async fn find_keys_which_need_updating(
-    pool: &sqlx::PgPool,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     keys: &LimitedVec<String>,
     hashes: &LimitedVec<i64>,
 ) -> anyhow::Result<LimitedVec<String>> {
     let number_of_keys = sqlx::query_scalar!("SELECT COUNT(*) FROM de")
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
     if number_of_keys == Some(0) {
         debug!(cnt = keys.len(), "all keys need updating",);
@@ -66,7 +202,7 @@ WHERE de.key = expected.key and de.hash != expected.hash
             keys.as_ref(),
             hashes.as_ref(),
         )
-        .fetch_all(pool)
+        .fetch_all(&mut **tx)
         .await?;
         debug!(cnt = keys_which_need_updating.len(), "updated items",);
         keys_which_need_updating
@@ -82,13 +218,13 @@ WHERE NOT EXISTS (SELECT * FROM UNNEST($1::text[]) as expected2(key) where de.ke
 "#,
             keys.as_ref()
         )
-        .fetch_all(pool)
+        .fetch_all(&mut **tx)
         .await?;
         debug!(cnt = keys_which_need_removing.len(), "deleted items",);
         keys_which_need_removing
     };
     keys_which_need_updating.append(&mut keys_which_need_removing);
-    Ok(LimitedVec(keys_which_need_updating))
+    Ok(LimitedVec::from(keys_which_need_updating))
 }
 
 #[tracing::instrument(skip(tx))]
@@ -96,7 +232,7 @@ async fn cleanup_deleted(
     keys: &LimitedVec<String>,
     tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 ) -> anyhow::Result<()> {
-    let keys = &keys.0;
+    let keys = keys.as_slice();
     sqlx::query!(
         "DELETE FROM aliases WHERE NOT EXISTS (SELECT * FROM UNNEST($1::text[]) AS expected(key) WHERE aliases.key = expected.key)",
         keys
@@ -123,3 +259,43 @@ async fn cleanup_deleted(
     .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_concurrent_imports_are_rejected() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/never_connected").unwrap();
+        let _running = IMPORT_LOCK.lock().await;
+        assert!(try_load_data(&pool, true).await.is_none());
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::setup::tests::PostgresTestContainer;
+
+    #[actix_web::test]
+    async fn test_imports_are_recorded() {
+        let pg = PostgresTestContainer::new().await;
+        assert_eq!(last_import(&pg.pool).await.unwrap(), None);
+
+        let first = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut tx = pg.pool.begin().await.unwrap();
+        record_import(&mut tx, first).await.unwrap();
+        // dry-runs are rolled back, together with their record
+        finish(tx, true).await.unwrap();
+        assert_eq!(last_import(&pg.pool).await.unwrap(), None);
+
+        for imported in [first, first + chrono::Duration::hours(1)] {
+            let mut tx = pg.pool.begin().await.unwrap();
+            record_import(&mut tx, imported).await.unwrap();
+            finish(tx, false).await.unwrap();
+            assert_eq!(last_import(&pg.pool).await.unwrap(), Some(imported));
+        }
+    }
+}
@@ -7,6 +7,8 @@ use meilisearch_sdk::tasks::Task;
 use serde_json::Value;
 use tracing::{debug, error, info};
 
+use crate::setup::cdn;
+
 const TIMEOUT: Option<Duration> = Some(Duration::from_secs(60));
 const POLLING_RATE: Option<Duration> = Some(Duration::from_millis(250));
 
@@ -107,12 +109,7 @@ pub async fn setup(client: &Client) -> anyhow::Result<()> {
 #[tracing::instrument(skip(client))]
 pub async fn load_data(client: &Client) -> anyhow::Result<()> {
     let entries = client.index("entries");
-    let cdn_url = std::env::var("CDN_URL").unwrap_or_else(|_| "https://nav.tum.de/cdn".to_string());
-    let documents = reqwest::get(format!("{cdn_url}/search_data.json"))
-        .await?
-        .error_for_status()?
-        .json::<Vec<Value>>()
-        .await?;
+    let documents = serde_json::from_slice::<Vec<Value>>(&cdn::fetch("search_data.json").await?)?;
     let res = entries
         .add_documents(&documents, Some("ms_id"))
         .await?
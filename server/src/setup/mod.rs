@@ -1,3 +1,4 @@
+pub mod cdn;
 pub mod database;
 
 pub mod meilisearch;
@@ -1,3 +1,6 @@
+use std::net::SocketAddr;
+
+use actix_web::{App, HttpServer, web};
 use meilisearch_sdk::client::Client;
 use testcontainers_modules::testcontainers::{ContainerAsync, ImageExt};
 use testcontainers_modules::{meilisearch, testcontainers::runners::AsyncRunner};
@@ -34,7 +37,7 @@ impl PostgresTestContainer {
     }
     pub async fn load_data_retrying(&self) {
         for i in 0..20 {
-            let res = crate::setup::database::load_data(&self.pool).await;
+            let res = crate::setup::database::load_data(&self.pool, false).await;
             if let Err(e) = res {
                 error!(error = ?e, "failed to load db. Retrying for 20s");
                 tokio::time::sleep(std::time::Duration::from_secs(1)).await;
@@ -91,3 +94,17 @@ async fn test_meilisearch_setup() {
         .await
         .unwrap();
 }
+
+/// Serve `configure` on a random local port in the background, e.g. to mock an external api
+pub fn mock_server(
+    configure: impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static,
+) -> SocketAddr {
+    let server = HttpServer::new(move || App::new().configure(configure.clone()))
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    addr
+}